@@ -0,0 +1,94 @@
+use crate::config::Config;
+use crate::verbosity::verbose_println;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// 名额用尽时重新尝试的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 分析并发名额（--max-concurrent-analyses）：数据目录下 analysis_slots/slot_<n>.lock 每个文件代表一个名额，
+/// 调用模型期间持有其中一个文件的独占锁。同一数据目录的独立服务、MCP 服务与 --once 等进程共享这些名额，
+/// 进程退出（包括崩溃）时由操作系统释放锁，不会留下失效的名额
+#[derive(Debug, Clone)]
+pub struct AnalysisSlots {
+    dir: PathBuf,
+    count: usize,
+}
+
+/// 持有期间占用一个名额，Drop 时关闭锁文件即释放
+#[derive(Debug)]
+pub struct AnalysisPermit {
+    _file: File,
+}
+
+impl AnalysisSlots {
+    /// 并发上限为 0 时不限制
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let count = config.analysis_concurrency_limit();
+        (count > 0).then(|| Self {
+            dir: config.get_data_dir().join("analysis_slots"),
+            count,
+        })
+    }
+
+    /// 尝试占用任一空闲名额，全部被占用时返回 None
+    fn try_acquire(&self) -> std::io::Result<Option<AnalysisPermit>> {
+        std::fs::create_dir_all(&self.dir)?;
+        for index in 0..self.count {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(self.dir.join(format!("slot_{}.lock", index)))?;
+            match file.try_lock() {
+                Ok(()) => return Ok(Some(AnalysisPermit { _file: file })),
+                Err(TryLockError::WouldBlock) => continue,
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// 占用一个名额，名额用尽时等待；锁文件不可用时打印警告并返回 None（本次分析不受限制，避免分析被卡住）
+    pub async fn acquire(&self) -> Option<AnalysisPermit> {
+        let mut waiting = false;
+        loop {
+            match self.try_acquire() {
+                Ok(Some(permit)) => return Some(permit),
+                Ok(None) => {
+                    if !waiting {
+                        verbose_println!(
+                            "⏳ 同时进行的分析已达上限 {}（可能有其他 OpenRecall 进程在分析），等待空闲名额...",
+                            self.count
+                        );
+                        waiting = true;
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Err(e) => {
+                    eprintln!("⚠️ 无法使用分析并发锁 {}: {}，本次分析不受并发限制", self.dir.display(), e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_exclusive_until_released() {
+        let dir = std::env::temp_dir().join(format!("openrecall_slots_{}", std::process::id()));
+        let slots = AnalysisSlots { dir: dir.clone(), count: 1 };
+
+        let permit = slots.try_acquire().unwrap();
+        assert!(permit.is_some());
+        assert!(slots.try_acquire().unwrap().is_none());
+        drop(permit);
+        assert!(slots.try_acquire().unwrap().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// 内置的跨平台应用名对照：(规范名称, 各系统上的原始名称)，匹配时忽略大小写
+const BUILTIN_ALIASES: &[(&str, &[&str])] = &[
+    ("Google Chrome", &["chrome", "chrome.exe"]),
+    ("Microsoft Edge", &["msedge", "msedge.exe"]),
+    ("Firefox", &["firefox.exe", "mozilla firefox"]),
+    ("Visual Studio Code", &["code", "code.exe"]),
+    ("Cursor", &["cursor.exe"]),
+    ("IntelliJ IDEA", &["idea64.exe", "idea.exe"]),
+    ("Windows Terminal", &["windowsterminal.exe"]),
+    ("File Explorer", &["explorer.exe"]),
+    ("Slack", &["slack.exe"]),
+    ("Discord", &["discord.exe"]),
+    ("Telegram", &["telegram.exe", "telegram desktop"]),
+    ("WeChat", &["wechat.exe", "weixin.exe", "微信"]),
+    ("Zoom", &["zoom.exe", "zoom.us"]),
+    ("Microsoft Teams", &["teams.exe", "ms-teams.exe", "microsoft teams (work or school)"]),
+    ("Microsoft Outlook", &["outlook.exe"]),
+    ("Microsoft Word", &["winword.exe"]),
+    ("Microsoft Excel", &["excel.exe"]),
+    ("Microsoft PowerPoint", &["powerpnt.exe"]),
+    ("Notion", &["notion.exe"]),
+    ("Spotify", &["spotify.exe"]),
+];
+
+/// 应用名称规范化表（--app-aliases）：原始名称（小写）→ 规范名称
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppAliases {
+    map: HashMap<String, String>,
+}
+
+impl AppAliases {
+    /// 只含内置对照表
+    pub fn builtin() -> Self {
+        let mut aliases = Self::default();
+        for (canonical, raw_names) in BUILTIN_ALIASES {
+            for raw in *raw_names {
+                aliases.insert(raw, canonical);
+            }
+        }
+        aliases
+    }
+
+    /// 内置对照表加上用户文件，用户文件中的同名条目优先
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut aliases = Self::builtin();
+        if let Some(path) = path {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("无法读取应用别名文件 {}: {}", path.display(), e))?;
+            let user = Self::parse(&content).map_err(|e| format!("应用别名文件 {} 格式错误: {}", path.display(), e))?;
+            aliases.map.extend(user.map);
+        }
+        Ok(aliases)
+    }
+
+    /// 解析别名文件：每行 "原始名称[, 原始名称...] = 规范名称"，# 开头的行为注释
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut aliases = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("第 {} 行应为 \"原始名称, ... = 规范名称\": {}", index + 1, line);
+            let (raw_names, canonical) = line.rsplit_once('=').ok_or_else(invalid)?;
+            let canonical = canonical.trim();
+            if canonical.is_empty() {
+                return Err(invalid());
+            }
+            let mut has_alias = false;
+            for raw in raw_names.split(',').map(str::trim).filter(|raw| !raw.is_empty()) {
+                aliases.insert(raw, canonical);
+                has_alias = true;
+            }
+            if !has_alias {
+                return Err(invalid());
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// 规范化应用名称，不在表中时原样返回
+    pub fn normalize(&self, name: &str) -> String {
+        self.map
+            .get(&name.trim().to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    fn insert(&mut self, raw: &str, canonical: &str) {
+        self.map.insert(raw.trim().to_lowercase(), canonical.to_string());
+    }
+}
+
+static APP_ALIASES: OnceLock<AppAliases> = OnceLock::new();
+
+/// 设置全局应用别名表，需在首次获取窗口信息之前调用；已设置时返回 false
+pub fn configure(aliases: AppAliases) -> bool {
+    APP_ALIASES.set(aliases).is_ok()
+}
+
+/// 按全局别名表规范化应用名称（未配置时使用内置对照表）
+pub fn normalize_app_name(name: Option<String>) -> Option<String> {
+    let aliases = APP_ALIASES.get_or_init(AppAliases::builtin);
+    name.map(|name| aliases.normalize(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_app_aliases() {
+        let user = AppAliases::parse(
+            "# 注释\n\
+             Chrome, Google Chrome Canary = Google Chrome\n\
+             chrome.exe = Chrome\n",
+        )
+        .unwrap();
+        assert_eq!(user.normalize("chrome"), "Google Chrome");
+        assert_eq!(user.normalize(" Google Chrome Canary "), "Google Chrome");
+        assert_eq!(user.normalize("Safari"), "Safari");
+
+        let builtin = AppAliases::builtin();
+        assert_eq!(builtin.normalize("CHROME.EXE"), "Google Chrome");
+        assert_eq!(builtin.normalize("Code"), "Visual Studio Code");
+
+        // 用户条目覆盖内置条目
+        let mut merged = builtin;
+        merged.map.extend(user.map);
+        assert_eq!(merged.normalize("chrome.exe"), "Chrome");
+        assert_eq!(merged.normalize("msedge.exe"), "Microsoft Edge");
+
+        assert!(AppAliases::parse("Chrome").is_err());
+        assert!(AppAliases::parse("Chrome =").is_err());
+        assert!(AppAliases::parse(" , = Chrome").is_err());
+    }
+}
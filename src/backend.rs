@@ -0,0 +1,76 @@
+use crate::config::{ApiBackend, Config};
+use crate::error::ScreenTimeError;
+use crate::ollama;
+use crate::siliconflow::{self, AnalysisResult, RequestOptions};
+use std::error::Error;
+
+/// 一次模型分析请求：接口（协议、地址、密钥、模型）与发送的内容
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisRequest<'a> {
+    pub backend: ApiBackend,
+    /// Ollama 原生接口不使用
+    pub api_key: &'a str,
+    pub api_url: &'a str,
+    pub model: &'a str,
+    /// 为空时只发送文本（如活动叙述）
+    pub image_paths: &'a [&'a str],
+    pub prompt: &'a str,
+    /// 系统上下文
+    pub extra_context: Option<&'a str>,
+    /// 用户活动历史
+    pub activity_history: Option<&'a str>,
+    pub timeout_secs: u64,
+}
+
+impl<'a> AnalysisRequest<'a> {
+    /// 使用配置中的主模型接口发送 prompt，不附带图片、上下文和历史
+    pub fn new(config: &'a Config, prompt: &'a str) -> Self {
+        Self {
+            backend: config.api_backend,
+            api_key: config.api_key(),
+            api_url: &config.api_url,
+            model: &config.model,
+            image_paths: &[],
+            prompt,
+            extra_context: None,
+            activity_history: None,
+            timeout_secs: config.api_timeout,
+        }
+    }
+}
+
+/// 按 --api-backend 把分析请求发给 OpenAI 兼容接口或 Ollama 原生接口
+pub async fn analyze_with_backend(
+    request: &AnalysisRequest<'_>,
+    options: &RequestOptions,
+) -> Result<AnalysisResult, ScreenTimeError> {
+    match request.backend {
+        ApiBackend::Siliconflow => siliconflow::analyze_screenshot_with_prompt(request, options).await,
+        ApiBackend::Ollama => ollama::analyze_screenshot_with_prompt(request, options).await,
+    }
+}
+
+/// 按 --api-backend 向配置的接口发送最小请求，验证地址、密钥和模型是否可用
+pub async fn ping_backend(
+    config: &Config,
+    timeout_secs: u64,
+    connect_timeout_secs: u64,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let proxy = config.api_proxy()?;
+    match config.api_backend {
+        ApiBackend::Siliconflow => {
+            siliconflow::ping(
+                config.api_key(),
+                &config.api_url,
+                &config.model,
+                timeout_secs,
+                connect_timeout_secs,
+                proxy.as_ref(),
+            )
+            .await
+        }
+        ApiBackend::Ollama => {
+            ollama::ping(&config.api_url, &config.model, timeout_secs, connect_timeout_secs, proxy.as_ref()).await
+        }
+    }
+}
@@ -1,243 +1,2084 @@
+use crate::backend::{analyze_with_backend, AnalysisRequest};
 use crate::config::Config;
 use crate::context;
+use crate::disk_space;
+use crate::error::ScreenTimeError;
+use crate::events;
+use crate::frame_buffer;
 use crate::logger;
-use crate::models::{ActivityLog, SystemContext, SystemInfo};
+use crate::media;
+use crate::metrics;
+use crate::offline_queue::{self, OfflineItem};
+use crate::permissions;
+use crate::models::{
+    ActivityClassification, ActivityLog, CaptureEvent, CapturePhaseTimings, SystemContext, SystemInfo, TokenUsage,
+};
 use crate::screenshot;
 use crate::service_state::ServiceStateManager;
 use crate::siliconflow;
-use chrono::Local;
+use crate::verbosity::{info_println, text_println, verbose_println};
+use crate::video;
+use crate::window_tracker::{self, WindowSwitchEvent, WINDOW_TRACKER};
+use chrono::{Local, Timelike};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::time::{interval, sleep};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tokio::time::sleep;
 
-/// 生成截图路径并确保目录存在
+/// --no-analysis 模式下写入日志的占位描述
+const ANALYSIS_SKIPPED_DESCRIPTION: &str = "[analysis skipped]";
+
+/// 未被 --analysis-sample-rate 抽中分析的截图写入日志的占位描述
+const ANALYSIS_SAMPLED_OUT_DESCRIPTION: &str = "[analysis sampled out]";
+
+/// 重试耗尽仍分析失败时写入日志的占位结果，保证时间线上不留空白
+fn analysis_failed_result(reason: &dyn std::fmt::Display) -> siliconflow::AnalysisResult {
+    siliconflow::AnalysisResult {
+        description: format!("[analysis failed: {}]", reason),
+        token_usage: None,
+        processing_time: Duration::ZERO,
+        model: None,
+        reasoning: None,
+    }
+}
+
+/// 截屏循环在多次截屏之间保留的状态
+#[derive(Default)]
+struct CaptureLoopState {
+    /// 上一张截图的平均哈希
+    last_image_hash: Option<u64>,
+    /// 上一次截图时的前台应用
+    last_app: Option<String>,
+    /// 上一次的分析描述（去重时复用）
+    last_description: Option<String>,
+    /// 生成上一次描述的模型（可能是备用模型）
+    last_model: Option<String>,
+    /// 连续截屏失败次数，截屏成功后清零
+    consecutive_capture_failures: u32,
+    /// 是否按 --batch-size 批量分析（仅截屏循环，--once 逐张分析）
+    batching: bool,
+    /// 已截屏、等待批量分析的截图
+    batch: Vec<PendingFrame>,
+    /// 后台分析队列（--analysis-workers 大于 0 时由截屏循环创建）
+    analysis_queue: Option<AnalysisQueue>,
+    /// 参与分析采样的截图数（--analysis-sample-rate）
+    sample_index: u64,
+    /// 无法连接模型接口时是否放入离线重试队列（仅截屏循环，--once 与手动截屏需要立即返回日志）
+    offline_queue: bool,
+    /// 是否按 --media-mode 处理前台媒体播放（仅截屏循环，--once 与手动截屏照常截屏）
+    media_aware: bool,
+    /// 本次媒体播放期间上一次截屏的时间（--media-mode sample），播放结束后清空
+    last_media_capture: Option<Instant>,
+}
+
+impl CaptureLoopState {
+    /// 按 --analysis-sample-rate 决定本张截图是否交给模型分析
+    fn take_analysis_sample(&mut self, rate: f64) -> bool {
+        self.sample_index += 1;
+        is_sampled(self.sample_index, rate)
+    }
+}
+
+/// 第 index 张（从 1 开始）截图是否被抽中：ceil(index × rate) 增加时抽中，
+/// 抽中的截图均匀分布且第一张总会分析（--once 与手动截屏因此不受采样影响）
+fn is_sampled(index: u64, rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate <= 0.0 || index == 0 {
+        return false;
+    }
+    (index as f64 * rate).ceil() > ((index - 1) as f64 * rate).ceil()
+}
+
+/// 已截屏、等待写入日志的一帧
+struct PendingFrame {
+    timestamp: chrono::DateTime<Local>,
+    screenshot_path: String,
+    thumbnail_task: Option<tokio::task::JoinHandle<Result<PathBuf, Box<dyn Error + Send + Sync>>>>,
+    /// 写入日志的系统上下文
+    context: context::SystemContext,
+    /// 各阶段耗时，写入日志后记录到运行指标
+    timings: CapturePhaseTimings,
+    /// 录屏片段（--capture-mode video），代表帧已用于分析
+    clip_path: Option<String>,
+}
+
+/// 自 start 起经过的毫秒数
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// 队列已满被丢弃的截图写入失败记录时的原因
+const QUEUE_DROPPED_REASON: &str = "分析队列已满，已丢弃最早排队的截图";
+
+/// 离线重试队列已满被挤出的截图写入失败记录时的原因
+const OFFLINE_DROPPED_REASON: &str = "离线重试队列已满，已放弃最早的截图";
+
+/// 离线重试队列中超时的截图写入失败记录时的原因
+const OFFLINE_EXPIRED_REASON: &str = "网络长时间未恢复，已放弃补分析";
+
+/// 后台分析队列（--analysis-workers）：截屏循环只负责截屏入队，由多个 worker 并发调用模型并写入日志，
+/// 模型较慢时截屏节奏不受影响。截屏循环退出前先排空队列；队列销毁时不中止 worker，
+/// 而是通知它们处理完已排队的任务后退出，排队的截图不会丢失
+struct AnalysisQueue {
+    shared: Arc<AnalysisQueueShared>,
+    capacity: usize,
+    drop_oldest: bool,
+}
+
+#[derive(Default)]
+struct AnalysisQueueShared {
+    /// 排队等待分析的任务，每个任务为单张截图或一批截图（--batch-size）
+    jobs: std::sync::Mutex<VecDeque<Vec<PendingFrame>>>,
+    notify: tokio::sync::Notify,
+    /// 排队中与分析中的任务数
+    in_flight: AtomicUsize,
+    /// 截屏时间最新的已完成日志，供画面去重复用描述、按分类调整间隔
+    latest: std::sync::Mutex<Option<ActivityLog>>,
+    /// 队列已销毁：worker 取完剩余任务后退出
+    closed: AtomicBool,
+}
+
+impl AnalysisQueue {
+    fn spawn(config: &Config, state_manager: Option<Arc<ServiceStateManager>>) -> Self {
+        let shared = Arc::new(AnalysisQueueShared::default());
+        for _ in 0..config.analysis_workers {
+            tokio::spawn(run_analysis_worker(config.clone(), state_manager.clone(), shared.clone()));
+        }
+        Self {
+            shared,
+            capacity: config.analysis_queue_size.max(1) as usize,
+            drop_oldest: config.queue_drops_oldest(),
+        }
+    }
+
+    /// 排队中（尚未开始分析）的任务数
+    fn pending(&self) -> usize {
+        self.shared.jobs.lock().unwrap().len()
+    }
+
+    fn is_full(&self) -> bool {
+        self.pending() >= self.capacity
+    }
+
+    /// 入队；drop-oldest 策略下队列已满时移出并返回最早排队的任务
+    fn push(&self, frames: Vec<PendingFrame>) -> Option<Vec<PendingFrame>> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let dropped = if self.drop_oldest && jobs.len() >= self.capacity {
+            jobs.pop_front()
+        } else {
+            None
+        };
+        // 被丢弃的任务让出计数，worker 取出任务前计数已包含新任务
+        if dropped.is_none() {
+            self.shared.in_flight.fetch_add(1, Ordering::SeqCst);
+        }
+        jobs.push_back(frames);
+        drop(jobs);
+        self.shared.notify.notify_one();
+        dropped
+    }
+
+    /// 没有排队或分析中的任务时返回最近一次成功分析的日志；
+    /// 仍有任务未完成时上一张截图尚无描述，返回 None
+    fn latest_if_idle(&self) -> Option<ActivityLog> {
+        if self.shared.in_flight.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        self.shared.latest.lock().unwrap().clone().filter(|log| !log.analysis_failed)
+    }
+
+    /// 最近完成的日志的【类型】
+    fn latest_category(&self) -> Option<String> {
+        self.shared
+            .latest
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|log| log.classification.as_ref()?.category.clone())
+    }
+
+    /// 等待排队与分析中的任务全部完成
+    async fn drain(&self) {
+        while self.shared.in_flight.load(Ordering::SeqCst) > 0 {
+            sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+impl Drop for AnalysisQueue {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.notify.notify_waiters();
+    }
+}
+
+/// 分析 worker：依次取出任务分析并写入日志，截屏计数由状态管理器加锁累加
+async fn run_analysis_worker(
+    config: Config,
+    state_manager: Option<Arc<ServiceStateManager>>,
+    shared: Arc<AnalysisQueueShared>,
+) {
+    loop {
+        // 先登记等待再检查队列，避免错过检查与等待之间的入队或关闭通知
+        let notified = shared.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        let job = shared.jobs.lock().unwrap().pop_front();
+        let Some(frames) = job else {
+            if shared.closed.load(Ordering::SeqCst) {
+                return;
+            }
+            notified.await;
+            continue;
+        };
+
+        let logs = analyze_frames(&config, state_manager.as_ref(), frames).await;
+        if let Some(log) = logs.into_iter().last() {
+            let mut latest = shared.latest.lock().unwrap();
+            if latest.as_ref().is_none_or(|current| current.timestamp <= log.timestamp) {
+                *latest = Some(log);
+            }
+        }
+        shared.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 连续截屏失败达到该次数后重新检查屏幕录制权限
+const CAPTURE_FAILURE_THRESHOLD: u32 = 3;
+
+/// 应用切换后等待界面稳定再截屏，避免截到切换动画
+const SWITCH_SETTLE_DELAY: Duration = Duration::from_secs(1);
+
+/// --capture-on-switch 时轮询前台窗口的间隔
+const SWITCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// drop 时中止的后台任务
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// 窗口追踪器只在查询前台窗口时检测切换，切换触发截屏需要后台持续轮询；
+/// 截屏循环退出（或被中止）时随之停止
+fn spawn_switch_poller() -> AbortOnDrop {
+    AbortOnDrop(tokio::spawn(async {
+        loop {
+            WINDOW_TRACKER.get_current_window_info().await;
+            sleep(SWITCH_POLL_INTERVAL).await;
+        }
+    }))
+}
+
+/// 离线重试（--offline-queue-size）：后台按 --offline-retry-interval-secs 检查模型接口，
+/// 恢复后补分析队列中的截图。截屏循环退出时通知停止，并等待正在补分析的截图写入日志；
+/// 截屏循环被中止时停止通知随发送端销毁送达，同样处理完当前截图后退出
+struct OfflineRetrier {
+    stop_tx: watch::Sender<bool>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl OfflineRetrier {
+    fn spawn(config: &Config, state_manager: Arc<ServiceStateManager>) -> Self {
+        let config = config.clone();
+        let (stop_tx, mut stop_rx) = watch::channel(false);
+        let handle = tokio::spawn(async move {
+            loop {
+                retry_offline_queue(&config, &state_manager, &stop_rx).await;
+                tokio::select! {
+                    _ = sleep(Duration::from_secs(config.offline_retry_interval_secs.max(1))) => {}
+                    _ = wait_for_stop(&mut stop_rx) => break,
+                }
+            }
+        });
+        Self { stop_tx, handle }
+    }
+
+    /// 通知停止并等待当前截图补分析完成
+    async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        if let Err(e) = self.handle.await {
+            eprintln!("离线重试任务异常结束: {}", e);
+        }
+    }
+}
+
+/// 按 --screenshot-name-template 生成截图路径并确保目录存在；同名文件已存在时追加序号，不覆盖已有截图
 fn generate_screenshot_path(
     config: &Config,
     timestamp: &chrono::DateTime<chrono::Local>,
 ) -> Result<PathBuf, std::io::Error> {
-    let screenshot_dir = config.get_screenshot_dir();
+    let name = timestamp.format(&config.screenshot_name_template).to_string();
+    let screenshot_path = config.get_screenshot_dir().join(format!("{}.png", name));
+
+    // 确保截图目录（含模板中的子目录）存在
+    if let Some(parent) = screenshot_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    reserve_unique_path(screenshot_path)
+}
+
+/// 以 create_new 原子地创建一个空文件占住路径，已存在时在文件名后追加 _1、_2 …；
+/// 并发截屏（如手动截屏与定时截屏同一毫秒）不会拿到同一个路径
+fn reserve_unique_path(path: PathBuf) -> Result<PathBuf, std::io::Error> {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut candidate = path.clone();
+    let mut counter = 0u32;
+    loop {
+        match fs::OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(_) => return Ok(candidate),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                counter += 1;
+                candidate = path.with_file_name(format!("{}_{}{}", stem, counter, extension));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 截屏未写入图片就提前返回（跳过、失败）时删除 reserve_unique_path 留下的空占位文件
+struct ReservedPath<'a>(&'a Path);
+
+impl Drop for ReservedPath<'_> {
+    fn drop(&mut self) {
+        if fs::metadata(self.0).is_ok_and(|metadata| metadata.len() == 0) {
+            let _ = fs::remove_file(self.0);
+        }
+    }
+}
+
+/// 录制与截图同名的 .mp4 片段，显示器与窗口裁剪和截图一致；
+/// 失败时给出警告（首次为 eprintln，之后仅 --verbose 显示）并返回 None，本次改用截图分析
+async fn record_clip(
+    config: &Config,
+    screenshot_path: &Path,
+    active_window: Option<&context::ActiveWindowInfo>,
+    display: screenshot::DisplaySelector<'_>,
+    crop_to_window: bool,
+) -> Option<video::VideoClip> {
+    static FALLBACK_WARNED: AtomicBool = AtomicBool::new(false);
+
+    let clip_path = screenshot_path.with_extension("mp4");
+    info_println!("🎬 录制 {} 秒屏幕片段...", config.clip_secs);
+    let recorded = match screenshot::recording_area(active_window, display, crop_to_window) {
+        Ok(area) => video::record_clip(config, &area, &clip_path).await,
+        Err(e) => Err(format!("无法确定录屏区域: {}", e)),
+    };
+    match recorded {
+        Ok(clip) => {
+            info_println!("🎞️ 已录制屏幕片段，抽取 {} 帧", clip.frame_paths.len());
+            Some(clip)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&clip_path);
+            if FALLBACK_WARNED.swap(true, Ordering::Relaxed) {
+                verbose_println!("⚠️ 录屏失败，本次改用截图分析: {}", e);
+            } else {
+                eprintln!("⚠️ 录屏失败，改用截图分析: {}", e);
+            }
+            None
+        }
+    }
+}
+
+/// 独立服务中运行的截屏循环：停止 / 暂停时通知循环退出，并等待它分析完剩余的批量截图、
+/// 排空后台分析队列后再返回；不中止任务，排队与分析中的截图都会写入日志
+pub struct CaptureTask {
+    stop_tx: watch::Sender<bool>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl CaptureTask {
+    pub fn spawn(config: Config, state_manager: Arc<ServiceStateManager>) -> Self {
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let handle = tokio::spawn(async move {
+            if let Err(e) = run_capture_loop_with_state(config, state_manager, stop_rx).await {
+                eprintln!("截屏循环出错: {}", e);
+            }
+        });
+        Self { stop_tx, handle }
+    }
+
+    /// 通知截屏循环退出并等待收尾完成
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        if let Err(e) = self.handle.await {
+            eprintln!("截屏循环异常结束: {}", e);
+        }
+    }
+}
+
+/// 等待 CaptureTask::stop 的停止通知；发送端已销毁同样视为停止
+async fn wait_for_stop(stop_rx: &mut watch::Receiver<bool>) {
+    let _ = stop_rx.wait_for(|stop| *stop).await;
+}
+
+/// 是否已收到停止通知（或发送端已销毁），供长时间任务在处理下一项前检查
+fn stop_requested(stop_rx: &watch::Receiver<bool>) -> bool {
+    *stop_rx.borrow() || stop_rx.has_changed().is_err()
+}
+
+/// 带状态管理的截屏循环
+async fn run_capture_loop_with_state(
+    config: Config,
+    state_manager: Arc<ServiceStateManager>,
+    mut stop_rx: watch::Receiver<bool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut config = config;
+    info_println!("🚀 启动带状态管理的截屏循环...");
 
     // 确保截图目录存在
-    if !screenshot_dir.exists() {
-        fs::create_dir_all(&screenshot_dir)?;
+    tokio::fs::create_dir_all(&config.get_screenshot_dir()).await?;
+
+    if config.capture_window_title().is_some() && !window_tracker::window_lookup_supported() {
+        eprintln!("⚠️ 当前平台不支持 --capture-window-title，每次截屏都会被跳过");
+    }
+
+    // 等待5秒后开始第一次截屏
+    info_println!("启动后5秒开始第一次截屏...");
+    tokio::select! {
+        _ = sleep(Duration::from_secs(5)) => {}
+        _ = wait_for_stop(&mut stop_rx) => {
+            info_println!("⏹️ 服务已停止，截屏循环退出");
+            return Ok(());
+        }
+    }
+
+    // 检查是否应该开始截屏
+    if !state_manager.should_capture().await {
+        info_println!("⏹️ 服务未启动，截屏循环退出");
+        return Ok(());
+    }
+
+    let mut loop_state = CaptureLoopState {
+        batching: true,
+        analysis_queue: (config.analysis_workers > 0 && !config.no_analysis)
+            .then(|| AnalysisQueue::spawn(&config, Some(state_manager.clone()))),
+        offline_queue: config.offline_queue_size > 0 && !config.no_analysis,
+        media_aware: true,
+        ..Default::default()
+    };
+    // 启动时先处理上次运行遗留的离线截图
+    let offline_retrier = loop_state
+        .offline_queue
+        .then(|| OfflineRetrier::spawn(&config, state_manager.clone()));
+    if config.batch_size > 1 && !config.no_analysis {
+        info_println!("🗂️ 批量分析：每 {} 张截图合并为一次请求", config.batch_size);
+    }
+    if loop_state.analysis_queue.is_some() {
+        info_println!(
+            "🧵 后台分析：{} 个 worker，最多排队 {} 个任务，队列满时{}",
+            config.analysis_workers,
+            config.analysis_queue_size,
+            if config.queue_drops_oldest() { "丢弃最早排队的截图" } else { "跳过截屏" }
+        );
+    }
+
+    // 执行第一次截屏
+    let mut last_category = None;
+    match perform_capture_exclusive(&config, Some(&state_manager), &mut loop_state).await {
+        Ok(Some(log)) => last_category = log.classification.and_then(|c| c.category),
+        Ok(None) => {
+            // 后台分析时按最近完成的分析结果确定分类
+            if let Some(category) = loop_state.analysis_queue.as_ref().and_then(AnalysisQueue::latest_category) {
+                last_category = Some(category);
+            }
+        }
+        Err(ScreenTimeError::Skipped(reason)) => info_println!("⏭️ {}", reason),
+        Err(e) => {
+            metrics::record_capture_error();
+            tracing::error!(event = "capture_failed", error = %e, "第一次截屏失败");
+            if !config.json_logs() {
+                eprintln!("第一次截屏失败: {}", e);
+            }
+        }
+    }
+
+    let interval_enabled = !config.switch_only;
+    let mut interval_overrides = load_interval_overrides(&config);
+    if interval_enabled {
+        info_println!("开始间隔循环，间隔: {} 秒", config.interval);
+        for (name, secs) in &interval_overrides {
+            info_println!("  - {}: {} 秒", name, secs);
+        }
+    }
+    if config.capture_on_switch {
+        info_println!(
+            "🔀 前台应用切换时触发截屏，最小间隔: {} 秒{}",
+            config.switch_min_interval_secs,
+            if interval_enabled { "" } else { "（已关闭定时截屏）" }
+        );
+    }
+
+    // 开始间隔循环：每次截屏后按当时的前台应用重新计算间隔（含抖动），
+    // 按截止时间调度，避免截屏耗时累积拉长平均间隔
+    let mut rng = match config.interval_jitter_seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut current_interval_secs = effective_interval(
+        config.interval,
+        &interval_overrides,
+        loop_state.last_app.as_deref(),
+        last_category.as_deref(),
+    );
+    let mut next_capture_at = tokio::time::Instant::now()
+        + jittered_interval(current_interval_secs, config.interval_jitter_secs, &mut rng);
+
+    // 切换触发：多次切换合并为一次，距上次截屏不足最小间隔时推迟到间隔结束
+    let mut switch_rx = config
+        .capture_on_switch
+        .then(|| WINDOW_TRACKER.subscribe_app_switches());
+    let _switch_poller = config.capture_on_switch.then(spawn_switch_poller);
+    let mut switch_capture_at: Option<tokio::time::Instant> = None;
+    let mut last_capture_at = tokio::time::Instant::now();
+
+    loop {
+        // 等待下一次触发：定时到点，或前台应用切换后的待执行截屏到点
+        let triggered_by_switch = tokio::select! {
+            _ = wait_for_stop(&mut stop_rx) => {
+                info_println!("⏹️ 服务已停止，截屏循环退出");
+                break;
+            }
+            _ = tokio::time::sleep_until(next_capture_at), if interval_enabled => false,
+            _ = tokio::time::sleep_until(switch_capture_at.unwrap_or_else(tokio::time::Instant::now)),
+                if switch_capture_at.is_some() => true,
+            event = next_app_switch(&mut switch_rx) => {
+                if switch_capture_at.is_none() {
+                    let earliest = last_capture_at + Duration::from_secs(config.switch_min_interval_secs);
+                    switch_capture_at = Some(earliest.max(tokio::time::Instant::now() + SWITCH_SETTLE_DELAY));
+                }
+                if !config.json_logs() {
+                    info_println!(
+                        "🔀 前台应用切换: {} → {}",
+                        event.from_app.as_deref().unwrap_or("未知"),
+                        event.to_app.as_deref().unwrap_or("未知")
+                    );
+                }
+                continue;
+            }
+        };
+
+        // 任一触发都会截屏：清除待执行的切换截屏；切换触发时下一次定时从现在起算
+        switch_capture_at = None;
+        last_capture_at = tokio::time::Instant::now();
+        let schedule_from = if triggered_by_switch { last_capture_at } else { next_capture_at };
+
+        // 运行时自动重载 .env 配置
+        let previous_interval = config.interval;
+        if let Ok(changed) = config.reload_from_dotenv_and_args() {
+            if changed {
+                interval_overrides = load_interval_overrides(&config);
+                if config.interval != previous_interval {
+                    info_println!("🔄 检测到 .env 变更，截屏间隔已更新为 {} 秒", config.interval.max(1));
+                }
+            }
+        }
+
+        // 检查服务状态
+        if !state_manager.should_capture().await {
+            info_println!("⏹️ 服务已停止，截屏循环退出");
+            break;
+        }
+
+        // 执行截屏
+        match perform_capture_exclusive(&config, Some(&state_manager), &mut loop_state).await {
+            Ok(Some(log)) => last_category = log.classification.and_then(|c| c.category),
+            Ok(None) => {
+                if let Some(category) = loop_state.analysis_queue.as_ref().and_then(AnalysisQueue::latest_category) {
+                    last_category = Some(category);
+                }
+            }
+            Err(ScreenTimeError::Skipped(reason)) => info_println!("⏭️ {}", reason),
+            Err(e) => {
+                metrics::record_capture_error();
+                tracing::error!(event = "capture_failed", error = %e, "截屏失败");
+                if !config.json_logs() {
+                    eprintln!("截屏失败: {}", e);
+                }
+                if check_permission_lost(&config, &state_manager, &loop_state).await {
+                    break;
+                }
+                // 截屏失败时短暂休眠再继续
+                sleep(Duration::from_secs(5)).await;
+            }
+        }
+
+        // 按本次截屏时的前台应用确定下一次间隔；截屏耗时超过间隔时从当前时间起算，避免连续补拍
+        let interval_secs = effective_interval(
+            config.interval,
+            &interval_overrides,
+            loop_state.last_app.as_deref(),
+            last_category.as_deref(),
+        );
+        if interval_secs != current_interval_secs {
+            current_interval_secs = interval_secs;
+            info_println!(
+                "⏱️ 前台应用 {}，截屏间隔调整为 {} 秒",
+                loop_state.last_app.as_deref().unwrap_or("未知"),
+                current_interval_secs
+            );
+        }
+        let delay = jittered_interval(current_interval_secs, config.interval_jitter_secs, &mut rng);
+        next_capture_at = (schedule_from + delay).max(last_capture_at + Duration::from_secs(1));
+    }
+
+    finish_loop(&config, Some(&state_manager), &mut loop_state).await;
+    if let Some(retrier) = offline_retrier {
+        retrier.stop().await;
+    }
+    info_println!("✅ 截屏循环正常退出");
+    Ok(())
+}
+
+/// 截屏循环退出前的收尾：分析批量队列中剩余的截图，并等待后台分析队列处理完
+async fn finish_loop(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    loop_state: &mut CaptureLoopState,
+) {
+    if !loop_state.batch.is_empty() {
+        info_println!("🗂️ 分析批量队列中剩余的 {} 张截图", loop_state.batch.len());
+        flush_batch(config, state_manager, loop_state).await;
+    }
+    if let Some(queue) = &loop_state.analysis_queue {
+        info_println!("🧵 等待分析队列中的任务完成...");
+        queue.drain().await;
+    }
+}
+
+/// 等待下一次前台应用切换；未开启 --capture-on-switch 时永不返回
+async fn next_app_switch(
+    switch_rx: &mut Option<broadcast::Receiver<WindowSwitchEvent>>,
+) -> WindowSwitchEvent {
+    if let Some(rx) = switch_rx {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return event,
+                // 处理过慢丢失了部分事件，继续取最新的
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+    std::future::pending().await
+}
+
+/// 连续截屏失败达到阈值时重新检查屏幕录制权限；权限已被撤销则将服务标记为
+/// PermissionLost 并返回 true，由调用方退出截屏循环，避免无休止地重试
+async fn check_permission_lost(
+    config: &Config,
+    state_manager: &Arc<ServiceStateManager>,
+    loop_state: &CaptureLoopState,
+) -> bool {
+    let failures = loop_state.consecutive_capture_failures;
+    if failures == 0 || !failures.is_multiple_of(CAPTURE_FAILURE_THRESHOLD) {
+        return false;
+    }
+
+    if permissions::check_screen_recording_permission() {
+        tracing::warn!(event = "capture_failing", failures, "连续截屏失败，但屏幕录制权限正常");
+        if !config.json_logs() {
+            eprintln!(
+                "⚠️ 已连续 {} 次截屏失败，屏幕录制权限正常，可能是显示器断开或系统休眠，将继续重试",
+                failures
+            );
+        }
+        return false;
+    }
+
+    if let Err(e) = state_manager.mark_permission_lost().await {
+        eprintln!("保存权限丢失状态失败: {}", e);
+    }
+    tracing::error!(event = "permission_lost", failures, "屏幕录制权限已丢失，截屏已停止");
+    if !config.json_logs() {
+        eprintln!("❌ 已连续 {} 次截屏失败，检测到屏幕录制权限已被撤销，截屏已停止", failures);
+        eprintln!("👉 macOS: 系统设置 → 隐私与安全性 → 屏幕录制，重新勾选终端或 OpenRecall");
+        eprintln!("👉 授权后重启独立服务，或通过 MCP monitor 工具 start 恢复截屏");
+    }
+    true
+}
+
+/// 截屏互斥锁：截屏循环与 CaptureNow 的临时截屏不会同时进行
+static CAPTURE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 持有截屏互斥锁执行单次截屏；在服务中运行时先检查磁盘可用空间（--min-free-mb）
+async fn perform_capture_exclusive(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    loop_state: &mut CaptureLoopState,
+) -> Result<Option<ActivityLog>, ScreenTimeError> {
+    let _guard = CAPTURE_LOCK.lock().await;
+    if let Some(state_manager) = state_manager {
+        disk_space::ensure_free_space(config, state_manager).await?;
+    }
+    perform_capture(config, state_manager, loop_state).await
+}
+
+/// 服务运行期间立即截屏并分析一次（CaptureNow）：与截屏循环互斥，不改变定时器；
+/// 使用独立的截屏状态，不参与去重与批量分析，截屏计数与定时截屏一样累加
+pub async fn capture_now(
+    config: &Config,
+    state_manager: &Arc<ServiceStateManager>,
+) -> Result<ActivityLog, ScreenTimeError> {
+    let mut loop_state = CaptureLoopState::default();
+    let result = perform_capture_exclusive(config, Some(state_manager), &mut loop_state).await;
+    if result.as_ref().is_err_and(|e| !matches!(e, ScreenTimeError::Skipped(_))) {
+        metrics::record_capture_error();
+    }
+    result?.ok_or_else(|| ScreenTimeError::other("截屏未生成日志"))
+}
+
+/// 单次截屏并分析（--once），不读写服务状态文件
+pub async fn capture_once(config: &Config) -> Result<ActivityLog, ScreenTimeError> {
+    let mut loop_state = CaptureLoopState::default();
+    perform_capture(config, None, &mut loop_state)
+        .await?
+        .ok_or_else(|| ScreenTimeError::other("截屏未生成日志"))
+}
+
+/// 分析已有图片文件（--analyze-image），跳过截屏；context 为调用方提供的上下文文本
+/// 图片按截屏流程做缩放/灰度/敏感信息遮盖后写入临时文件再上传，不写入活动日志
+pub async fn analyze_image_file(
+    config: &Config,
+    image_path: &str,
+    context: Option<&str>,
+) -> Result<siliconflow::AnalysisResult, Box<dyn Error + Send + Sync>> {
+    let timestamp = Local::now();
+    let image = image::open(image_path)
+        .map_err(|e| format!("无法读取图片 {}: {}", image_path, e))?;
+
+    let image_options = screenshot::ImageOptions::from_config(config);
+    let mut processed = screenshot::process_image_for_analysis(image, &image_options);
+    screenshot::redact_sensitive_text(&mut processed, &config.redact_keywords);
+
+    fs::create_dir_all(config.get_screenshot_dir())?;
+    let temp_path = reserve_unique_path(
+        config
+            .get_screenshot_dir()
+            .join(format!("analyze_{}.png", timestamp.format("%Y%m%d_%H%M%S_%3f"))),
+    )?;
+    let _reserved = ReservedPath(&temp_path);
+    processed.save_with_format(&temp_path, image::ImageFormat::Png)?;
+    let temp_path_str = temp_path.to_string_lossy().to_string();
+
+    // 外部图片与本机当前窗口、历史活动无关：占位符按 unknown 填充，不附带历史
+    let empty_ctx = context::SystemContext {
+        username: String::new(),
+        hostname: None,
+        os_name: None,
+        os_version: None,
+        processes_top: Vec::new(),
+        active_window: None,
+        installed_apps: Vec::new(),
+        input_activity: None,
+        clipboard_text: None,
+        since_last_capture: None,
+        window_info_disabled: false,
+    };
+    let prompt = render_prompt_template(&config.prompt, &empty_ctx, &timestamp);
+
+    let result = if config.no_analysis {
+        Ok(siliconflow::AnalysisResult {
+            description: ANALYSIS_SKIPPED_DESCRIPTION.to_string(),
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: None,
+            reasoning: None,
+        })
+    } else {
+        analyze_with_retry(config, &[temp_path_str.as_str()], &prompt, context, None, &timestamp).await
+    };
+
+    if let Err(e) = fs::remove_file(&temp_path) {
+        eprintln!("删除临时图片失败: {}", e);
+    }
+
+    result.map_err(Into::into)
+}
+
+/// 解析 --interval-overrides，无法解析的条目给出警告后忽略
+fn load_interval_overrides(config: &Config) -> Vec<(String, u64)> {
+    let (overrides, invalid) = config.interval_overrides();
+    for entry in invalid {
+        eprintln!("⚠️ 忽略无法解析的间隔覆盖 \"{}\"（格式: 名称=秒，秒数需大于 0）", entry);
+    }
+    overrides
+}
+
+/// 下一次截屏间隔（秒）：前台应用名或上次分析出的【类型】命中覆盖项时使用覆盖值（不区分大小写），
+/// 应用名优先；都未命中时使用 --interval
+fn effective_interval(
+    default_secs: u64,
+    overrides: &[(String, u64)],
+    app: Option<&str>,
+    category: Option<&str>,
+) -> u64 {
+    let lookup = |name: Option<&str>| {
+        let name = name?.trim();
+        overrides
+            .iter()
+            .find(|(key, _)| key.to_lowercase() == name.to_lowercase())
+            .map(|(_, secs)| *secs)
+    };
+    lookup(app).or_else(|| lookup(category)).unwrap_or(default_secs).max(1)
+}
+
+/// 计算带抖动的截屏间隔：interval ± rand(0..=jitter)
+/// jitter 限制在 interval - 1 以内，保证等待至少 1 秒且平均值等于 interval
+fn jittered_interval(interval_secs: u64, jitter_secs: u64, rng: &mut impl Rng) -> Duration {
+    let interval_secs = interval_secs.max(1);
+    let jitter = jitter_secs.min(interval_secs - 1) as i64;
+    if jitter == 0 {
+        return Duration::from_secs(interval_secs);
+    }
+    let offset = rng.gen_range(-jitter..=jitter);
+    Duration::from_secs((interval_secs as i64 + offset) as u64)
+}
+
+/// 应用切换后还需等待的时间：从最近一次窗口切换起算满 delay_ms，切换时间未知时等待完整时长
+fn post_switch_wait(delay_ms: u64, last_switch_ms: Option<u64>) -> Duration {
+    let delay = Duration::from_millis(delay_ms);
+    let Some(last_switch_ms) = last_switch_ms else {
+        return delay;
+    };
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    delay.saturating_sub(Duration::from_millis(now_ms.saturating_sub(last_switch_ms)))
+}
+
+/// 执行单次截屏操作；state_manager 为 None 时不更新截屏计数。
+/// 截图加入批量分析队列、尚未生成日志时返回 None，队列攒满时返回本批最后一条日志
+async fn perform_capture(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    loop_state: &mut CaptureLoopState,
+) -> Result<Option<ActivityLog>, ScreenTimeError> {
+    if loop_state.analysis_queue.as_ref().is_some_and(AnalysisQueue::is_full) && !config.queue_drops_oldest() {
+        return Err(ScreenTimeError::Skipped(
+            "分析队列已满（--queue-policy skip-capture），跳过本次截屏".to_string(),
+        ));
+    }
+
+    // 前台在播放媒体（--media-mode）：skip 跳过截屏，sample 按间隔截屏，summarize 在后面以本地描述代替模型分析
+    let media = if loop_state.media_aware && config.media_detection() {
+        media::detect_playback(config).await
+    } else {
+        None
+    };
+    match &media {
+        None => loop_state.last_media_capture = None,
+        Some(playback) if config.media_mode == "skip" => {
+            return Err(ScreenTimeError::Skipped(format!(
+                "{} 正在播放媒体（--media-mode skip），跳过本次截屏",
+                playback.app
+            )));
+        }
+        Some(playback) if config.media_mode == "sample" => {
+            let interval = Duration::from_secs(config.media_sample_minutes * 60);
+            if loop_state.last_media_capture.is_some_and(|last| last.elapsed() < interval) {
+                return Err(ScreenTimeError::Skipped(format!(
+                    "{} 正在播放媒体，距上次截屏不足 {} 分钟（--media-mode sample），跳过本次截屏",
+                    playback.app, config.media_sample_minutes
+                )));
+            }
+            loop_state.last_media_capture = Some(Instant::now());
+        }
+        Some(_) => {}
+    }
+    let media_summary = media.filter(|_| config.media_mode == "summarize");
+
+    let timestamp = Local::now();
+    context::mark_capture(timestamp.timestamp_millis() as u64);
+    let screenshot_path = match generate_screenshot_path(config, &timestamp) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("创建截图目录失败: {}", e);
+            return Err(e.into());
+        }
+    };
+    let _reserved = ReservedPath(&screenshot_path);
+    let screenshot_path_str = screenshot_path.to_str().unwrap_or("screenshot.png");
+
+    // 指定了目标窗口时按标题查找（不要求在前台），找不到则跳过本次截屏，不退回整屏
+    let target_window = match config.capture_window_title() {
+        Some(pattern) => match window_tracker::find_window_by_title(pattern).filter(|w| w.bounds.is_some()) {
+            Some(window) => {
+                verbose_println!("🪟 找到目标窗口: {}", window.window_title.as_deref().unwrap_or_default());
+                Some(context::ActiveWindowInfo {
+                    app_name: window.app_name,
+                    window_title: window.window_title,
+                    bounds: window.bounds,
+                    timestamp: Some(window.timestamp),
+                    process_id: window.process_id,
+                    switch_stats: None,
+                    recent_switches: None,
+                })
+            }
+            None => {
+                return Err(ScreenTimeError::Skipped(format!(
+                    "未找到标题包含 \"{}\" 的可见窗口，跳过本次截屏",
+                    pattern
+                )))
+            }
+        },
+        None => None,
+    };
+
+    let json_logs = config.json_logs();
+    tracing::info!(
+        event = "capture_start",
+        timestamp = %timestamp.to_rfc3339(),
+        screenshot_path = screenshot_path_str,
+        "开始截屏"
+    );
+    if !json_logs {
+        info_println!("────────── {} ──────────", timestamp.format("%H:%M:%S"));
+    }
+    events::publish(CaptureEvent::CaptureStarted {
+        timestamp,
+        screenshot_path: screenshot_path_str.to_string(),
+    });
+
+    // 确定图片处理参数（缩放约束、灰度转换）
+    let image_options = screenshot::ImageOptions::from_config(config);
+
+    // 获取当前活跃窗口信息，用于智能选择屏幕
+    let context_started = Instant::now();
+    let mut ctx_for_screenshot = context::collect_system_context(config).await;
+    let mut timings = CapturePhaseTimings {
+        context_ms: elapsed_ms(context_started),
+        ..CapturePhaseTimings::default()
+    };
+
+    // 前台应用与上次截屏时不同：等待界面稳定后重新获取窗口信息（--post-switch-delay-ms）
+    let foreground_app = ctx_for_screenshot.active_window.as_ref().and_then(|w| w.app_name.as_ref());
+    if config.post_switch_delay_ms > 0
+        && target_window.is_none()
+        && loop_state.last_app.is_some()
+        && foreground_app != loop_state.last_app.as_ref()
+    {
+        let settle = post_switch_wait(config.post_switch_delay_ms, WINDOW_TRACKER.get_stats().await.last_switch_time);
+        if !settle.is_zero() {
+            verbose_println!("⏳ 前台应用已切换，等待 {} 毫秒后截屏", settle.as_millis());
+            sleep(settle).await;
+            let context_started = Instant::now();
+            ctx_for_screenshot = context::collect_system_context(config).await;
+            timings.context_ms += elapsed_ms(context_started);
+        }
+    }
+
+    // 指定显示器时覆盖按前台窗口自动选屏；按标题截取窗口时选择窗口所在的显示器
+    let display = match (config.display_index, config.display_name.as_deref()) {
+        _ if target_window.is_some() => screenshot::DisplaySelector::Auto,
+        (Some(index), _) => screenshot::DisplaySelector::Index(index),
+        (None, Some(name)) => screenshot::DisplaySelector::Name(name),
+        (None, None) => screenshot::DisplaySelector::Auto,
+    };
+
+    // 截屏 - 使用智能截图功能
+    let screenshot_started = Instant::now();
+    let captured_image = match screenshot::capture_screenshot_auto(
+        screenshot_path_str,
+        &image_options,
+        target_window.as_ref().or(ctx_for_screenshot.active_window.as_ref()),
+        display,
+        target_window.is_some() || config.crop_to_active_window(),
+        &config.redact_keywords,
+    )
+    .await
+    {
+        Ok(image) => {
+            loop_state.consecutive_capture_failures = 0;
+            timings.screenshot_ms = elapsed_ms(screenshot_started);
+            image
+        }
+        Err(e) => {
+            loop_state.consecutive_capture_failures += 1;
+            // 保留文件读写等已分类的错误，其余归为截屏失败
+            return Err(match ScreenTimeError::from(e) {
+                ScreenTimeError::Other(e) => ScreenTimeError::Capture(e.to_string()),
+                e => e,
+            });
+        }
+    };
+    tracing::info!(event = "screenshot_saved", screenshot_path = screenshot_path_str, "截图已保存");
+    if !json_logs {
+        info_println!("📷 截图已保存: {}", screenshot_path_str);
+    }
+
+    // 后台生成缩略图：直接使用内存中的截图，不重新读取 PNG
+    let thumbnail_task = config.thumbnails.then(|| {
+        let image = captured_image.clone();
+        let path = screenshot::thumbnail_path(&screenshot_path);
+        let max_width = config.thumbnail_width.max(1);
+        tokio::task::spawn_blocking(move || {
+            screenshot::save_thumbnail(&image, &path, max_width).map(|()| path)
+        })
+    });
+
+    // 等待一段时间确保文件写入完成
+    sleep(Duration::from_millis(500)).await;
+
+    // 与上一张截图比较：画面无明显变化且前台应用未切换时复用上次描述；
+    // 上一张还在批量队列或后台分析队列中（尚无描述）时不复用
+    if let Some(queue) = &loop_state.analysis_queue {
+        let latest = queue.latest_if_idle();
+        loop_state.last_description = latest.as_ref().map(|log| log.description.clone());
+        loop_state.last_model = latest.and_then(|log| log.model);
+    }
+    let image_hash = screenshot::average_hash(&captured_image);
+    let current_app = ctx_for_screenshot
+        .active_window
+        .as_ref()
+        .and_then(|w| w.app_name.clone());
+    let similarity = loop_state
+        .last_image_hash
+        .map(|last| screenshot::hash_similarity(last, image_hash));
+    let reused_description = match (similarity, &loop_state.last_description) {
+        (Some(similarity), Some(description))
+            if screenshot::is_near_duplicate(similarity, config.dedup_threshold)
+                && loop_state.batch.is_empty()
+                && current_app == loop_state.last_app =>
+        {
+            Some((similarity, description.clone()))
+        }
+        _ => None,
+    };
+    loop_state.last_image_hash = Some(image_hash);
+    loop_state.last_app = current_app;
+
+    // 分析采样（--analysis-sample-rate）：未被抽中的截图不调用模型，以占位描述写入日志
+    let sampled_out = !config.no_analysis
+        && media_summary.is_none()
+        && reused_description.is_none()
+        && !loop_state.take_analysis_sample(config.analysis_sample_rate);
+
+    // 录屏模式（--capture-mode video）：需要调用模型时再录制一段屏幕，用代表帧代替截图分析；录制失败时退回截图
+    let clip = if config.video_capture()
+        && !config.no_analysis
+        && media_summary.is_none()
+        && !sampled_out
+        && reused_description.is_none()
+    {
+        let clip_started = Instant::now();
+        let clip = record_clip(
+            config,
+            &screenshot_path,
+            target_window.as_ref().or(ctx_for_screenshot.active_window.as_ref()),
+            display,
+            target_window.is_some() || config.crop_to_active_window(),
+        )
+        .await;
+        timings.screenshot_ms += elapsed_ms(clip_started);
+        clip
+    } else {
+        None
+    };
+
+    // 批量分析（--batch-size）：需要分析的截图先入队，攒够后一次请求；复用描述或跳过分析的截图照常逐张处理
+    if loop_state.batching
+        && config.batch_size > 1
+        && !config.no_analysis
+        && media_summary.is_none()
+        && !sampled_out
+        && reused_description.is_none()
+        && clip.is_none()
+    {
+        loop_state.batch.push(PendingFrame {
+            timestamp,
+            screenshot_path: screenshot_path_str.to_string(),
+            thumbnail_task,
+            context: ctx_for_screenshot,
+            timings,
+            clip_path: None,
+        });
+        if loop_state.batch.len() < config.batch_size as usize {
+            info_println!("🗂️ 已加入批量分析队列（{}/{}）", loop_state.batch.len(), config.batch_size);
+            return Ok(None);
+        }
+        return Ok(flush_batch(config, state_manager, loop_state).await);
+    }
+
+    // 后台分析（--analysis-workers）：截图入队后立即返回，由 worker 分析并写入日志；录屏片段在截屏循环中直接分析
+    if loop_state.analysis_queue.is_some()
+        && !config.no_analysis
+        && media_summary.is_none()
+        && !sampled_out
+        && reused_description.is_none()
+        && clip.is_none()
+    {
+        let frame = PendingFrame {
+            timestamp,
+            screenshot_path: screenshot_path_str.to_string(),
+            thumbnail_task,
+            context: ctx_for_screenshot,
+            timings,
+            clip_path: None,
+        };
+        enqueue_analysis(config, state_manager, loop_state, vec![frame]).await;
+        return Ok(None);
+    }
+
+    // 调用SiliconFlow API分析截图（带重试机制）；--no-analysis 时跳过
+    let mut shadow_description = None;
+    let mut analysis_failed = false;
+    let mut offline_error = None;
+    let analysis_result = if config.no_analysis {
+        info_println!("⏭️ 已跳过 AI 分析（--no-analysis）");
+        siliconflow::AnalysisResult {
+            description: ANALYSIS_SKIPPED_DESCRIPTION.to_string(),
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: None,
+            reasoning: None,
+        }
+    } else if let Some(playback) = &media_summary {
+        info_println!("🎬 {} 正在播放媒体，以本地描述代替 AI 分析（--media-mode summarize）", playback.app);
+        loop_state.last_description = None;
+        siliconflow::AnalysisResult {
+            description: playback.summary_description(config.lang),
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: None,
+            reasoning: None,
+        }
+    } else if sampled_out {
+        info_println!("🎲 本张截图未被抽中分析（--analysis-sample-rate {}）", config.analysis_sample_rate);
+        // 下一张截图与本张相似时不应复用更早的描述
+        loop_state.last_description = None;
+        siliconflow::AnalysisResult {
+            description: ANALYSIS_SAMPLED_OUT_DESCRIPTION.to_string(),
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: None,
+            reasoning: None,
+        }
+    } else if let Some((similarity, description)) = reused_description {
+        tracing::info!(
+            event = "no_significant_change",
+            screenshot_path = screenshot_path_str,
+            similarity,
+            "画面无明显变化，复用上次分析结果"
+        );
+        if !json_logs {
+            info_println!("♻️ 画面无明显变化（相似度 {:.2}），复用上次分析结果", similarity);
+        }
+        siliconflow::AnalysisResult {
+            description,
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: loop_state.last_model.clone(),
+            reasoning: None,
+        }
+    } else {
+        // 分析失败时不应复用更早的描述
+        loop_state.last_description = None;
+        events::publish(CaptureEvent::AnalysisStarted {
+            timestamp,
+            model: config.model.clone(),
+        });
+        let context_started = Instant::now();
+        let ctx = context::collect_system_context(config).await;
+        timings.context_ms += elapsed_ms(context_started);
+        let analysis_started = Instant::now();
+        let image_paths: Vec<&str> = match &clip {
+            Some(clip) => clip.frame_paths.iter().map(String::as_str).collect(),
+            None => vec![screenshot_path_str],
+        };
+        let analyzed = analyze_screenshot_with_retry(config, &image_paths, &ctx, &timestamp).await;
+        timings.analysis_ms = elapsed_ms(analysis_started);
+        match analyzed {
+            Ok((result, shadow)) => {
+                shadow_description = shadow;
+                record_analysis_metrics(&result);
+                loop_state.last_description = Some(result.description.clone());
+                loop_state.last_model = result.model.clone();
+                result
+            }
+            Err(e) => {
+                events::publish(CaptureEvent::AnalysisFailed {
+                    timestamp,
+                    error: e.to_string(),
+                });
+                dump_frames_on_error(config);
+                analysis_failed = true;
+                let result = analysis_failed_result(&e);
+                // 无法连接模型接口：截图放入离线重试队列，不写失败记录
+                if loop_state.offline_queue && e.is_offline() {
+                    offline_error = Some(e);
+                }
+                result
+            }
+        }
+    };
+    // 创建活动日志：上下文取分析完成后的状态
+    let context_started = Instant::now();
+    let ctx = context::collect_system_context(config).await;
+    timings.context_ms += elapsed_ms(context_started);
+    let frame = PendingFrame {
+        timestamp,
+        screenshot_path: screenshot_path_str.to_string(),
+        thumbnail_task,
+        context: ctx,
+        timings,
+        clip_path: clip.as_ref().map(|clip| clip.clip_path.to_string_lossy().into_owned()),
+    };
+    if let Some(e) = offline_error {
+        // 离线补分析只使用截图，不保留录屏片段
+        if let Some(clip_path) = &frame.clip_path {
+            let _ = fs::remove_file(clip_path);
+        }
+        queue_offline(config, state_manager, frame, &e).await;
+        return Ok(None);
+    }
+    Ok(Some(
+        finish_capture(config, state_manager, frame, analysis_result, shadow_description, analysis_failed, sampled_out)
+            .await,
+    ))
+}
+
+/// 分析完成后的收尾：发布完成事件、保存日志、按需删除截图并更新截屏计数；
+/// 分析失败时同样写入日志（analysis_failed），并保留截图以便人工查看。
+/// sampled_out 表示截图未被 --analysis-sample-rate 抽中、没有调用模型
+async fn finish_capture(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    frame: PendingFrame,
+    analysis_result: siliconflow::AnalysisResult,
+    shadow_description: Option<String>,
+    analysis_failed: bool,
+    sampled_out: bool,
+) -> ActivityLog {
+    let PendingFrame {
+        timestamp,
+        screenshot_path,
+        thumbnail_task,
+        context: mut ctx_original,
+        mut timings,
+        clip_path,
+    } = frame;
+    let screenshot_path_str = screenshot_path.as_str();
+    let json_logs = config.json_logs();
+
+    if !analysis_failed {
+        events::publish(CaptureEvent::AnalysisCompleted {
+            timestamp,
+            description: analysis_result.description.clone(),
+            model: analysis_result.model.clone(),
+            total_tokens: analysis_result.token_usage.as_ref().and_then(|t| t.total_tokens),
+        });
+    }
+
+    // 创建活动日志；剪贴板文本（--include-clipboard）只随请求发给模型，不写入日志
+    ctx_original.clipboard_text = None;
+    let ctx = convert_context_to_models(&ctx_original);
+
+    // 是否保留截图：显式开关、test_prompt 模式、分析失败或按 --keep-unsampled-screenshots 保留未抽中的截图
+    let should_keep = config.keep_screenshots
+        || config.test_prompt.is_some()
+        || analysis_failed
+        || (sampled_out && config.keep_unsampled_screenshots);
+
+    let screenshot_path_for_log = if should_keep {
+        Some(screenshot_path_str.to_string())
+    } else {
+        None
+    };
+
+    // 录屏片段仅在 --keep-clips 时保留；配置了 --redact-keywords 时片段无法遮盖，始终删除
+    let clip_path = match clip_path {
+        Some(path) if config.keep_clips && config.redact_keywords.is_empty() => Some(path),
+        Some(path) => {
+            if let Err(e) = std::fs::remove_file(&path) {
+                eprintln!("删除录屏片段失败: {}", e);
+            }
+            None
+        }
+        None => None,
+    };
+
+    let thumbnail_path = match thumbnail_task {
+        Some(task) => match task.await {
+            Ok(Ok(path)) => Some(path.to_string_lossy().into_owned()),
+            Ok(Err(e)) => {
+                eprintln!("⚠️ 生成缩略图失败: {}", e);
+                None
+            }
+            Err(e) => {
+                eprintln!("⚠️ 生成缩略图任务异常: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let log = ActivityLog {
+        timestamp,
+        classification: ActivityClassification::parse(&analysis_result.description),
+        description: analysis_result.description,
+        context: Some(ctx),
+        screenshot_path: screenshot_path_for_log,
+        model: analysis_result.model,
+        estimated_cost: config.estimate_cost(analysis_result.token_usage.as_ref()),
+        token_usage: analysis_result.token_usage,
+        shadow_description,
+        thumbnail_path,
+        clip_path,
+        span_end: None,
+        merged_count: None,
+        analysis_failed,
+        reasoning: analysis_result.reasoning.filter(|_| config.keep_reasoning),
+    };
+
+    // 保存日志
+    let log_write_started = Instant::now();
+    let saved = logger::save_activity_log(&log, config);
+    timings.log_write_ms = elapsed_ms(log_write_started);
+    match &saved {
+        Ok(_) => tracing::info!(
+            event = "log_saved",
+            timestamp = %log.timestamp.to_rfc3339(),
+            screenshot_path = log.screenshot_path.as_deref(),
+            total_tokens = log.token_usage.as_ref().and_then(|t| t.total_tokens),
+            "日志已保存"
+        ),
+        Err(e) => tracing::error!(event = "log_save_failed", error = %e, "保存日志时出错"),
+    }
+    if !json_logs {
+        match &saved {
+            Ok(_) if analysis_failed => eprintln!("⚠️ 分析失败，已记录占位日志并保留截图: {}", screenshot_path_str),
+            Ok(_) => info_println!("💾 日志已保存"),
+            Err(e) => eprintln!("保存日志时出错: {}", e),
+        }
+    }
+    if saved.is_ok() {
+        events::publish(CaptureEvent::LogSaved { log: Box::new(log.clone()) });
+    }
+    report_capture_phases(timings, json_logs);
+
+    // 非保留模式：删除截图文件
+    if !should_keep {
+        if let Err(e) = std::fs::remove_file(screenshot_path_str) {
+            eprintln!("删除截图失败: {}", e);
+        } else {
+            info_println!("🧹 已删除截图: {}", screenshot_path_str);
+        }
+    }
+
+    // 更新截屏计数
+    if let Some(state_manager) = state_manager {
+        if let Err(e) = state_manager.increment_capture_count(log.estimated_cost, sampled_out).await {
+            eprintln!("更新截屏计数时出错: {}", e);
+        }
     }
 
-    let screenshot_path = screenshot_dir.join(format!(
-        "screenshot_{}.png",
-        timestamp.format("%Y%m%d_%H%M%S")
-    ));
-    Ok(screenshot_path)
+    log
 }
 
-/// 带状态管理的截屏循环
-pub async fn run_capture_loop_with_state(
-    config: Config,
-    state_manager: Arc<ServiceStateManager>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut config = config;
-    println!("🚀 启动带状态管理的截屏循环...");
-
-    // 确保截图目录存在
-    tokio::fs::create_dir_all(&config.get_screenshot_dir()).await?;
-
-    // 等待5秒后开始第一次截屏
-    println!("启动后5秒开始第一次截屏...");
-    sleep(Duration::from_secs(5)).await;
+/// 输出本次截屏各阶段耗时并计入最近截屏的平均耗时
+fn report_capture_phases(timings: CapturePhaseTimings, json_logs: bool) {
+    tracing::info!(
+        event = "capture_timings",
+        context_ms = timings.context_ms,
+        screenshot_ms = timings.screenshot_ms,
+        analysis_ms = timings.analysis_ms,
+        log_write_ms = timings.log_write_ms,
+        "截屏各阶段耗时"
+    );
+    if !json_logs {
+        verbose_println!(
+            "⏱️ 耗时: 上下文 {} ms / 截屏 {} ms / 分析 {} ms / 写日志 {} ms",
+            timings.context_ms,
+            timings.screenshot_ms,
+            timings.analysis_ms,
+            timings.log_write_ms
+        );
+    }
+    metrics::record_capture_phases(timings);
+}
 
-    // 检查是否应该开始截屏
-    if !state_manager.should_capture().await {
-        println!("⏹️ 服务未启动，截屏循环退出");
-        return Ok(());
+/// 分析批量队列中的截图并逐张写入日志，返回最后一条日志；队列为空或交给后台分析队列时返回 None
+async fn flush_batch(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    loop_state: &mut CaptureLoopState,
+) -> Option<ActivityLog> {
+    let frames = std::mem::take(&mut loop_state.batch);
+    if frames.is_empty() {
+        return None;
+    }
+    if loop_state.analysis_queue.is_some() {
+        enqueue_analysis(config, state_manager, loop_state, frames).await;
+        return None;
     }
 
-    // 执行第一次截屏
-    if let Err(e) = perform_capture(&config, &state_manager).await {
-        eprintln!("第一次截屏失败: {}", e);
+    // 分析失败时不应复用更早的描述
+    loop_state.last_description = None;
+    let logs = analyze_frames(config, state_manager, frames).await;
+    for log in logs.iter().filter(|log| !log.analysis_failed) {
+        loop_state.last_description = Some(log.description.clone());
+        loop_state.last_model = log.model.clone();
     }
+    logs.into_iter().last()
+}
 
-    println!("开始间隔循环，间隔: {} 秒", config.interval);
+/// 把截图交给后台分析队列；drop-oldest 策略下队列已满时，最早排队的截图写入分析失败记录
+async fn enqueue_analysis(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    loop_state: &CaptureLoopState,
+    frames: Vec<PendingFrame>,
+) {
+    let Some(queue) = &loop_state.analysis_queue else {
+        return;
+    };
+    let dropped = queue.push(frames);
+    info_println!("🧵 已加入分析队列（排队 {}/{}）", queue.pending(), config.analysis_queue_size);
 
-    // 开始间隔循环
-    let mut current_interval_secs = config.interval.max(1);
-    let mut interval_timer = interval(Duration::from_secs(current_interval_secs));
+    for frame in dropped.into_iter().flatten() {
+        eprintln!("⚠️ {}: {}", QUEUE_DROPPED_REASON, frame.screenshot_path);
+        events::publish(CaptureEvent::AnalysisFailed {
+            timestamp: frame.timestamp,
+            error: QUEUE_DROPPED_REASON.to_string(),
+        });
+        finish_capture(config, state_manager, frame, analysis_failed_result(&QUEUE_DROPPED_REASON), None, true, false).await;
+    }
+}
 
-    loop {
-        // 等待下一个时间点
-        interval_timer.tick().await;
+/// 分析一张或一批截图并逐张写入日志（单张时附带影子后端对比），
+/// 使用截屏时采集的上下文；分析失败时每张截图都写入失败记录
+async fn analyze_frames(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    frames: Vec<PendingFrame>,
+) -> Vec<ActivityLog> {
+    for frame in &frames {
+        events::publish(CaptureEvent::AnalysisStarted {
+            timestamp: frame.timestamp,
+            model: config.model.clone(),
+        });
+    }
+    let analysis_started = Instant::now();
+    let analyzed = match frames.as_slice() {
+        [frame] => analyze_screenshot_with_retry(config, &[frame.screenshot_path.as_str()], &frame.context, &frame.timestamp)
+            .await
+            .map(|(result, shadow)| {
+                record_analysis_metrics(&result);
+                vec![(result, shadow)]
+            }),
+        _ => analyze_batch(config, &frames)
+            .await
+            .map(|results| results.into_iter().map(|result| (result, None)).collect()),
+    };
+    let analysis_ms = elapsed_ms(analysis_started);
 
-        // 运行时自动重载 .env 配置
-        if let Ok(changed) = config.reload_from_dotenv_and_args() {
-            if changed {
-                let new_interval_secs = config.interval.max(1);
-                if new_interval_secs != current_interval_secs {
-                    current_interval_secs = new_interval_secs;
-                    interval_timer = interval(Duration::from_secs(current_interval_secs));
-                    println!("🔄 检测到 .env 变更，截屏间隔已更新为 {} 秒", current_interval_secs);
-                }
+    // 无法连接模型接口：放入离线重试队列，网络恢复后再写入日志
+    if let Err(e) = &analyzed {
+        if config.offline_queue_size > 0 && e.is_offline() {
+            for frame in frames {
+                events::publish(CaptureEvent::AnalysisFailed {
+                    timestamp: frame.timestamp,
+                    error: e.to_string(),
+                });
+                queue_offline(config, state_manager, frame, e).await;
             }
+            return Vec::new();
         }
+    }
 
-        // 检查服务状态
-        if !state_manager.should_capture().await {
-            println!("⏹️ 服务已停止，截屏循环退出");
-            break;
+    let results: Vec<(siliconflow::AnalysisResult, Option<String>, bool)> = match analyzed {
+        Ok(results) => results
+            .into_iter()
+            .map(|(result, shadow)| (result, shadow, false))
+            .collect(),
+        Err(e) => {
+            dump_frames_on_error(config);
+            frames
+                .iter()
+                .map(|frame| {
+                    events::publish(CaptureEvent::AnalysisFailed {
+                        timestamp: frame.timestamp,
+                        error: e.to_string(),
+                    });
+                    (analysis_failed_result(&e), None, true)
+                })
+                .collect()
         }
+    };
 
-        // 执行截屏
-        if let Err(e) = perform_capture(&config, &state_manager).await {
-            eprintln!("截屏失败: {}", e);
-            // 截屏失败时短暂休眠再继续
-            sleep(Duration::from_secs(5)).await;
+    let mut logs = Vec::with_capacity(frames.len());
+    for (mut frame, (result, shadow, analysis_failed)) in frames.into_iter().zip(results) {
+        frame.timings.analysis_ms = analysis_ms;
+        logs.push(finish_capture(config, state_manager, frame, result, shadow, analysis_failed, false).await);
+    }
+    logs
+}
+
+/// 无法连接模型接口：截图放入离线重试队列，暂不写日志；队列已满被挤出的截图与入队失败的截图写入失败记录
+async fn queue_offline(
+    config: &Config,
+    state_manager: Option<&Arc<ServiceStateManager>>,
+    frame: PendingFrame,
+    error: &ScreenTimeError,
+) {
+    let PendingFrame {
+        timestamp,
+        screenshot_path,
+        thumbnail_task,
+        context,
+        ..
+    } = frame;
+    let thumbnail_path = match thumbnail_task {
+        Some(task) => match task.await {
+            Ok(Ok(path)) => Some(path.to_string_lossy().into_owned()),
+            _ => None,
+        },
+        None => None,
+    };
+    let item = OfflineItem {
+        timestamp,
+        screenshot_path,
+        thumbnail_path,
+        context,
+    };
+
+    match offline_queue::enqueue(config, item.clone()) {
+        Ok(dropped) => {
+            info_println!("📥 无法连接模型接口，截图已加入离线重试队列，网络恢复后补分析: {}", item.screenshot_path);
+            for item in dropped {
+                eprintln!("⚠️ {}: {}", OFFLINE_DROPPED_REASON, item.screenshot_path);
+                let frame = offline_frame(item);
+                finish_capture(config, state_manager, frame, analysis_failed_result(&OFFLINE_DROPPED_REASON), None, true, false)
+                    .await;
+            }
+        }
+        Err(e) => {
+            eprintln!("⚠️ 加入离线重试队列失败: {}", e);
+            finish_capture(config, state_manager, offline_frame(item), analysis_failed_result(error), None, true, false)
+                .await;
         }
     }
+}
 
-    println!("✅ 截屏循环正常退出");
-    Ok(())
+/// 把离线队列中的截图还原为待写入日志的一帧
+fn offline_frame(item: OfflineItem) -> PendingFrame {
+    PendingFrame {
+        timestamp: item.timestamp,
+        screenshot_path: item.screenshot_path,
+        thumbnail_task: item
+            .thumbnail_path
+            .map(|path| tokio::spawn(async move { Ok(PathBuf::from(path)) })),
+        context: item.context,
+        timings: CapturePhaseTimings::default(),
+        clip_path: None,
+    }
 }
 
-/// 执行单次截屏操作
-async fn perform_capture(
+/// 处理一轮离线重试：超时的截图写入失败记录；模型接口可连接时按截屏时间先后补分析，
+/// 使用截屏时的上下文并沿用原截屏时间写入日志，再次无法连接时留到下一轮
+async fn retry_offline_queue(
     config: &Config,
     state_manager: &Arc<ServiceStateManager>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let timestamp = Local::now();
-    let screenshot_path = match generate_screenshot_path(config, &timestamp) {
-        Ok(path) => path,
-        Err(e) => {
-            eprintln!("创建截图目录失败: {}", e);
-            return Err(Box::new(e));
+    stop_rx: &watch::Receiver<bool>,
+) {
+    match offline_queue::take_expired(config) {
+        Ok(expired) => {
+            for item in expired {
+                eprintln!("⚠️ {}: {}", OFFLINE_EXPIRED_REASON, item.screenshot_path);
+                let frame = offline_frame(item);
+                finish_capture(config, Some(state_manager), frame, analysis_failed_result(&OFFLINE_EXPIRED_REASON), None, true, false)
+                    .await;
+            }
         }
-    };
-    let screenshot_path_str = screenshot_path.to_str().unwrap_or("screenshot.png");
+        Err(e) => eprintln!("⚠️ 清理离线重试队列失败: {}", e),
+    }
 
-    println!("────────── {} ──────────", timestamp.format("%H:%M:%S"));
+    if offline_queue::peek(config).is_none() {
+        return;
+    }
+    if !offline_queue::api_reachable(config).await {
+        verbose_println!("📴 模型接口仍不可用，离线队列中有 {} 张截图等待补分析", offline_queue::len(config));
+        return;
+    }
+    info_println!("🌐 模型接口已恢复，开始补分析离线队列中的 {} 张截图", offline_queue::len(config));
 
-    // 确定图片处理参数
-    let target_width = if config.image_target_width > 0 {
-        Some(config.image_target_width)
-    } else {
+    while let Some(item) = offline_queue::peek(config) {
+        // 停止时不再取下一张，留在队列中等下次启动补分析
+        if stop_requested(stop_rx) {
+            return;
+        }
+        events::publish(CaptureEvent::AnalysisStarted {
+            timestamp: item.timestamp,
+            model: config.model.clone(),
+        });
+        let analysis_started = Instant::now();
+        let analyzed =
+            analyze_screenshot_with_retry(config, &[item.screenshot_path.as_str()], &item.context, &item.timestamp).await;
+        let (result, shadow, analysis_failed) = match analyzed {
+            Ok((result, shadow)) => {
+                record_analysis_metrics(&result);
+                (result, shadow, false)
+            }
+            Err(e) if e.is_offline() => {
+                eprintln!("⚠️ 补分析时再次无法连接模型接口，稍后重试: {}", e);
+                return;
+            }
+            Err(e) => {
+                events::publish(CaptureEvent::AnalysisFailed {
+                    timestamp: item.timestamp,
+                    error: e.to_string(),
+                });
+                (analysis_failed_result(&e), None, true)
+            }
+        };
+        // 先移出队列再写日志：中途停止时最多丢失这一条记录，不会在下次补分析时重复写入；
+        // 移除失败时停止本轮，避免反复分析同一张截图
+        if let Err(e) = offline_queue::remove(config, &item) {
+            eprintln!("⚠️ 更新离线重试队列失败: {}", e);
+            return;
+        }
+        let mut frame = offline_frame(item);
+        frame.timings.analysis_ms = elapsed_ms(analysis_started);
+        finish_capture(config, Some(state_manager), frame, result, shadow, analysis_failed, false).await;
+    }
+    info_println!("✅ 离线队列已全部补分析");
+}
+
+/// 按 --max-images-per-request 分组发送多图请求，返回与 frames 一一对应的分析结果。
+/// 请求被接口拒绝时对半拆分重试；模型漏掉的截图单独补分析；
+/// 回复中没有任何分段标记时视为整批的综合描述，每张截图使用同一描述
+async fn analyze_batch(
+    config: &Config,
+    frames: &[PendingFrame],
+) -> Result<Vec<siliconflow::AnalysisResult>, ScreenTimeError> {
+    let activity_history = if config.history_count == 0 {
         None
+    } else {
+        logger::get_recent_activity_context(config, config.history_count, config.history_days).ok()
     };
 
-    // 确定是否启用灰度转换
-    let grayscale = config.image_grayscale && !config.no_image_grayscale;
+    let chunk_size = match config.max_images_per_request {
+        0 => frames.len(),
+        max => max,
+    };
+    let mut pending: VecDeque<std::ops::Range<usize>> = (0..frames.len())
+        .step_by(chunk_size)
+        .map(|start| start..(start + chunk_size).min(frames.len()))
+        .collect();
+    let mut results: Vec<Option<siliconflow::AnalysisResult>> = frames.iter().map(|_| None).collect();
 
-    // 获取当前活跃窗口信息，用于智能选择屏幕
-    let ctx_for_screenshot = context::collect_system_context(config).await;
+    while let Some(range) = pending.pop_front() {
+        let chunk = &frames[range.clone()];
+        let last = &chunk[chunk.len() - 1];
+        let image_paths: Vec<&str> = chunk.iter().map(|f| f.screenshot_path.as_str()).collect();
+        let ctx_text = format_context_for_model(config, &last.context);
+        let prompt = render_prompt_template(&config.prompt, &last.context, &last.timestamp);
 
-    // 截屏 - 使用智能截图功能
-    screenshot::capture_screenshot_smart(
-        screenshot_path_str,
-        target_width,
-        grayscale,
-        ctx_for_screenshot.active_window.as_ref(),
-    )?;
-    println!("📷 截图已保存: {}", screenshot_path_str);
+        if chunk.len() == 1 {
+            let result = analyze_with_retry(
+                config,
+                &image_paths,
+                &prompt,
+                Some(&ctx_text),
+                activity_history.as_deref(),
+                &last.timestamp,
+            )
+            .await?;
+            record_analysis_metrics(&result);
+            results[range.start] = Some(result);
+            continue;
+        }
 
-    // 等待一段时间确保文件写入完成
-    sleep(Duration::from_millis(500)).await;
+        info_println!("🗂️ 批量分析 {} 张截图", chunk.len());
+        let result = match analyze_with_retry(
+            config,
+            &image_paths,
+            &batch_prompt(&prompt, chunk),
+            Some(&ctx_text),
+            activity_history.as_deref(),
+            &last.timestamp,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(e) if e.is_request_rejected() => {
+                eprintln!("⚠️ 多图请求被拒绝（{}），可能超过接口的单次图片数上限，拆分为两组重试", e);
+                let middle = range.start + chunk.len() / 2;
+                pending.push_front(middle..range.end);
+                pending.push_front(range.start..middle);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        record_analysis_metrics(&result);
 
-    // 调用SiliconFlow API分析截图（带重试机制）
-    let analysis_result =
-        analyze_screenshot_with_retry(config, screenshot_path_str, &timestamp).await?;
+        let mut sections = split_batch_response(&result.description, chunk.len());
+        if sections.iter().all(Option::is_none) {
+            eprintln!("⚠️ 批量分析结果未按截图分段，{} 张截图使用同一综合描述", chunk.len());
+            sections = vec![Some(result.description.trim().to_string()); chunk.len()];
+        }
+        for (index, section) in sections.into_iter().enumerate() {
+            match section {
+                Some(description) => {
+                    results[range.start + index] = Some(siliconflow::AnalysisResult {
+                        description,
+                        token_usage: split_token_usage(result.token_usage.as_ref(), chunk.len(), index),
+                        processing_time: result.processing_time,
+                        model: result.model.clone(),
+                        reasoning: result.reasoning.clone(),
+                    });
+                }
+                None => {
+                    eprintln!("⚠️ 批量分析结果缺少第 {} 张截图，单独补充分析", index + 1);
+                    pending.push_back(range.start + index..range.start + index + 1);
+                }
+            }
+        }
+    }
 
-    // 创建活动日志
-    let ctx_original = context::collect_system_context(config).await;
-    let ctx = convert_context_to_models(&ctx_original);
+    results
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| ScreenTimeError::other("批量分析结果不完整"))
+}
 
-    // 是否保留截图：显式开关或 test_prompt 模式强制保留
-    let should_keep = config.keep_screenshots || config.test_prompt.is_some();
+/// 分析失败时导出最近上传的图片（--keep-last-frames），便于核对模型实际看到的画面
+fn dump_frames_on_error(config: &Config) {
+    if config.keep_last_frames == 0 {
+        return;
+    }
+    match frame_buffer::dump(config, "analysis_error") {
+        Ok(Some((dir, count))) => eprintln!("🧾 已导出最近 {} 帧上传图片到 {}", count, dir.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!("导出最近上传图片失败: {}", e),
+    }
+}
 
-    let screenshot_path_for_log = if should_keep {
-        Some(screenshot_path_str.to_string())
-    } else {
-        None
-    };
+fn record_analysis_metrics(result: &siliconflow::AnalysisResult) {
+    metrics::observe_analysis_duration(result.processing_time);
+    if let Some(tokens) = result.token_usage.as_ref().and_then(|t| t.total_tokens) {
+        metrics::record_tokens(tokens as u64);
+    }
+}
 
-    let log = ActivityLog {
-        timestamp,
-        description: analysis_result.description,
-        context: Some(ctx),
-        screenshot_path: screenshot_path_for_log,
-        model: Some(config.model.clone()),
-        token_usage: analysis_result.token_usage,
-    };
+/// 在提示词后追加录屏说明：图片是同一段录屏按时间先后抽取的帧，要求给出整段的合并描述
+fn clip_prompt(prompt: &str, frame_count: usize, clip_secs: u64) -> String {
+    format!(
+        "{}\n\n本次请求附带的 {} 张图片是同一段 {} 秒屏幕录像中按时间先后均匀抽取的画面。请把它们视为连续的操作过程，结合画面变化判断用户在这段时间内做了什么，按上述格式只输出一条整体结果，不要逐张描述。",
+        prompt, frame_count, clip_secs
+    )
+}
 
-    // 保存日志
-    match logger::save_activity_log(&log, config) {
-        Ok(_) => println!("💾 日志已保存"),
-        Err(e) => eprintln!("保存日志时出错: {}", e),
+/// 在单张截图的提示词后追加批量说明：截图数量、各张的截屏时间与前台窗口，以及分段输出格式
+fn batch_prompt(prompt: &str, frames: &[PendingFrame]) -> String {
+    let mut text = format!(
+        "{}\n\n本次请求按截屏时间先后附带 {} 张截图：\n",
+        prompt,
+        frames.len()
+    );
+    for (index, frame) in frames.iter().enumerate() {
+        let window = frame.context.active_window.as_ref();
+        text.push_str(&format!(
+            "- 截图 {}：{}，前台窗口 {} - {}\n",
+            index + 1,
+            frame.timestamp.format("%H:%M:%S"),
+            window.and_then(|w| w.app_name.as_deref()).unwrap_or("未知"),
+            window.and_then(|w| w.window_title.as_deref()).unwrap_or("未知")
+        ));
     }
+    text.push_str(
+        "请按上述要求逐张分析，每张截图的结果单独成段，段首单独一行写“### 截图 序号”（序号从 1 开始），不要合并多张截图的结果。",
+    );
+    text
+}
 
-    // 非保留模式：删除截图文件（无论分析成功或失败都执行到此）
-    if !should_keep {
-        if let Err(e) = std::fs::remove_file(screenshot_path_str) {
-            eprintln!("删除截图失败: {}", e);
-        } else {
-            println!("🧹 已删除截图: {}", screenshot_path_str);
+/// 按“### 截图 N”分段标记拆分批量回复，返回与截图一一对应的描述（缺失的为 None）
+fn split_batch_response(response: &str, count: usize) -> Vec<Option<String>> {
+    let mut sections: Vec<Vec<&str>> = vec![Vec::new(); count];
+    let mut current = None;
+    for line in response.lines() {
+        match batch_section_marker(line) {
+            Some((index, rest)) if (1..=count).contains(&index) => {
+                current = Some(index - 1);
+                sections[index - 1].push(rest);
+            }
+            // 超出截图数量的序号：丢弃该段
+            Some(_) => current = None,
+            None => {
+                if let Some(index) = current {
+                    sections[index].push(line);
+                }
+            }
         }
     }
+    sections
+        .into_iter()
+        .map(|lines| Some(lines.join("\n").trim().to_string()).filter(|text| !text.is_empty()))
+        .collect()
+}
 
-    // 更新截屏计数
-    if let Err(e) = state_manager.increment_capture_count().await {
-        eprintln!("更新截屏计数时出错: {}", e);
-    }
+/// 识别分段标记行，如 "### 截图 2"、"**截图2：**【工作】…"、"Screenshot 2:"，
+/// 返回序号和冒号后同一行的内容
+fn batch_section_marker(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim().trim_start_matches(['#', '*', ' ']);
+    let rest = line
+        .strip_prefix("截图")
+        .or_else(|| line.strip_prefix("Screenshot"))
+        .or_else(|| line.strip_prefix("screenshot"))?
+        .trim_start();
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let index = rest[..digits].parse().ok()?;
+    let rest = rest[digits..].trim_start_matches(['*', ' ', ')', '）']);
+    let rest = match rest.strip_prefix(':').or_else(|| rest.strip_prefix('：')) {
+        Some(content) => content,
+        None if rest.is_empty() => rest,
+        None => return None,
+    };
+    Some((index, rest.trim_matches(['*', ' '])))
+}
 
-    Ok(())
+/// 批量请求的 token 用量按截图数平均分摊（余数计入第一张），保证逐条累计的费用与实际一致
+fn split_token_usage(usage: Option<&TokenUsage>, count: usize, index: usize) -> Option<TokenUsage> {
+    let share = |value: Option<u32>| {
+        value.map(|v| {
+            let count = count.max(1) as u32;
+            v / count + if index == 0 { v % count } else { 0 }
+        })
+    };
+    usage.map(|usage| TokenUsage {
+        prompt_tokens: share(usage.prompt_tokens),
+        completion_tokens: share(usage.completion_tokens),
+        total_tokens: share(usage.total_tokens),
+    })
+}
+
+/// 按 --context-format 组装随截图发送给模型的系统上下文
+pub fn format_context_for_model(config: &Config, ctx: &context::SystemContext) -> String {
+    if config.json_context() {
+        context::format_context_as_json(ctx)
+    } else {
+        context::format_context_as_text(ctx, config.lang)
+    }
 }
 
-/// 带重试机制的截图分析
+/// 带重试机制的截图分析，ctx_original 为随截图发送的系统上下文；image_paths 为多张时是同一段录屏的代表帧，
+/// 合并描述为一条结果。配置了影子后端时并行分析同样的图片，返回主结果和影子描述
 async fn analyze_screenshot_with_retry(
     config: &Config,
-    screenshot_path_str: &str,
+    image_paths: &[&str],
+    ctx_original: &context::SystemContext,
     timestamp: &chrono::DateTime<chrono::Local>,
-) -> Result<siliconflow::AnalysisResult, Box<dyn Error + Send + Sync>> {
-    const MAX_RETRIES: u32 = 5;
-    const RETRY_DELAYS: [u64; 5] = [5, 15, 30, 45, 60]; // 重试延迟：5秒、15秒、30秒
+) -> Result<(siliconflow::AnalysisResult, Option<String>), ScreenTimeError> {
+    let ctx_text = format_context_for_model(config, ctx_original);
+
+    // 获取历史活动记录；--history-count 0 时不向模型发送历史
+    let activity_history = if config.history_count == 0 {
+        None
+    } else {
+        match logger::get_recent_activity_context(config, config.history_count, config.history_days) {
+            Ok(history) => Some(history),
+            Err(e) => {
+                eprintln!("获取历史活动记录时出错: {}", e);
+                None
+            }
+        }
+    };
+
+    // 根据当前上下文填充提示词模板中的占位符
+    let mut prompt = render_prompt_template(&config.prompt, ctx_original, timestamp);
+    if image_paths.len() > 1 {
+        prompt = clip_prompt(&prompt, image_paths.len(), config.clip_secs);
+    }
+
+    let (result, shadow_description) = tokio::join!(
+        analyze_with_retry(
+            config,
+            image_paths,
+            &prompt,
+            Some(&ctx_text),
+            activity_history.as_deref(),
+            timestamp,
+        ),
+        analyze_with_shadow(
+            config,
+            image_paths,
+            &prompt,
+            Some(&ctx_text),
+            activity_history.as_deref(),
+        )
+    );
+    result.map(|result| (result, shadow_description))
+}
+
+/// 用影子后端分析同样的图片：只尝试一次、不参与限速与并发名额，失败时仅打印警告，不影响主流程
+async fn analyze_with_shadow(
+    config: &Config,
+    image_paths: &[&str],
+    prompt: &str,
+    ctx_text: Option<&str>,
+    activity_history: Option<&str>,
+) -> Option<String> {
+    let (backend, api_url, model, api_key) = config.shadow_backend()?;
+    let request_options = siliconflow::RequestOptions {
+        min_interval: Duration::ZERO,
+        analysis_slots: None,
+        ..siliconflow::RequestOptions::from_config(config)
+    };
 
-    // 获取系统上下文和历史记录
-    let ctx_original = context::collect_system_context(config).await;
-    let ctx_text = context::format_context_as_text(&ctx_original);
+    let request = AnalysisRequest {
+        backend,
+        api_key,
+        api_url,
+        model,
+        image_paths,
+        prompt,
+        extra_context: ctx_text,
+        activity_history,
+        timeout_secs: config.api_timeout,
+    };
+    let result = analyze_with_backend(&request, &request_options).await;
 
-    // 获取历史活动记录（最近5条）
-    let activity_history = match logger::get_recent_activity_context(config, 5) {
-        Ok(history) => Some(history),
+    match result {
+        Ok(analysis_result) => {
+            tracing::info!(
+                event = "shadow_analysis_success",
+                model,
+                duration_ms = analysis_result.processing_time.as_millis() as u64,
+                description = analysis_result.description.as_str(),
+                "影子后端分析成功"
+            );
+            if !config.json_logs() {
+                verbose_println!(
+                    "👥 影子后端（{}）耗时 {:.2}s: {}",
+                    model,
+                    analysis_result.processing_time.as_secs_f64(),
+                    analysis_result.description.lines().next().unwrap_or("")
+                );
+            }
+            Some(analysis_result.description)
+        }
         Err(e) => {
-            eprintln!("获取历史活动记录时出错: {}", e);
+            tracing::warn!(event = "shadow_analysis_failure", model, error = %e, "影子后端分析失败");
+            if !config.json_logs() {
+                eprintln!("⚠️ 影子后端（{}）分析失败: {}", model, e);
+            }
             None
         }
-    };
+    }
+}
 
+/// 调用模型分析图片（批量分析时为多张），失败时按 RETRY_DELAYS 重试，限流时遵循 Retry-After；
+/// 多图请求被接口拒绝时不重试，直接返回 RequestRejectedError 由调用方拆分批次
+async fn analyze_with_retry(
+    config: &Config,
+    image_paths: &[&str],
+    prompt: &str,
+    ctx_text: Option<&str>,
+    activity_history: Option<&str>,
+    timestamp: &chrono::DateTime<chrono::Local>,
+) -> Result<siliconflow::AnalysisResult, ScreenTimeError> {
+    const MAX_RETRIES: u32 = 5;
+    const RETRY_DELAYS: [u64; 5] = [5, 15, 30, 45, 60]; // 重试延迟：5秒、15秒、30秒
+    const MAX_RATE_LIMIT_DELAY_SECS: u64 = 300; // Retry-After 上限，避免截屏循环被长时间阻塞
+
+    // 缺少密钥时每次请求必然失败，不必重试
+    if let Err(e) = config.ensure_api_key() {
+        return Err(ScreenTimeError::Config(e));
+    }
+
+    let request_options = siliconflow::RequestOptions::from_config(config);
     let mut last_error = None;
+    let screenshot_path_str = image_paths.join(", ");
+    let screenshot_path_str = screenshot_path_str.as_str();
 
-    for attempt in 1..=MAX_RETRIES {
-        println!("🔍 尝试分析截图 (第 {}/{} 次)", attempt, MAX_RETRIES);
+    let json_logs = config.json_logs();
 
-        match siliconflow::analyze_screenshot_with_prompt(
-            &config.api_key,
-            &config.api_url,
-            &config.model,
-            screenshot_path_str,
-            &config.prompt,
-            Some(&ctx_text),
-            activity_history.as_deref(),
-            config.api_timeout,
-        )
-        .await
-        {
+    // 尝试计划：主模型重试 MAX_RETRIES 次，仍失败时用备用模型再尝试一次
+    let mut models = vec![config.model.as_str(); MAX_RETRIES as usize];
+    if let Some(fallback_model) = config.fallback_model() {
+        models.push(fallback_model);
+    }
+    let max_attempts = models.len() as u32;
+
+    for (attempt, model) in (1..=max_attempts).zip(models) {
+        if !json_logs {
+            // 首次请求属于常规进度，之后的重试只在 --verbose 时显示
+            if attempt == 1 {
+                info_println!("🔍 分析截图（模型 {}）", model);
+            } else {
+                verbose_println!("🔍 尝试分析截图 (第 {}/{} 次，模型 {})", attempt, max_attempts, model);
+            }
+            verbose_println!(
+                "   请求 {:?} {} · 超时 {} 秒 · 图片 {} KB · 提示词 {} 字 · 上下文 {} 字 · 历史 {} 字",
+                config.api_backend,
+                config.api_url,
+                config.api_timeout,
+                image_paths
+                    .iter()
+                    .map(|path| fs::metadata(path).map(|m| m.len() / 1024).unwrap_or(0))
+                    .sum::<u64>(),
+                prompt.chars().count(),
+                ctx_text.map_or(0, |t| t.chars().count()),
+                activity_history.map_or(0, |t| t.chars().count())
+            );
+        }
+        let attempt_started = std::time::Instant::now();
+
+        let request = AnalysisRequest {
+            model,
+            image_paths,
+            extra_context: ctx_text,
+            activity_history,
+            ..AnalysisRequest::new(config, prompt)
+        };
+        let result = analyze_with_backend(&request, &request_options).await;
+
+        match result {
             Ok(analysis_result) => {
-                println!("✅ 分析成功:");
+                let usage = analysis_result.token_usage.as_ref();
+                tracing::info!(
+                    event = "analysis_success",
+                    timestamp = %timestamp.to_rfc3339(),
+                    screenshot_path = screenshot_path_str,
+                    attempt,
+                    model,
+                    prompt_tokens = usage.and_then(|t| t.prompt_tokens),
+                    completion_tokens = usage.and_then(|t| t.completion_tokens),
+                    total_tokens = usage.and_then(|t| t.total_tokens),
+                    duration_ms = analysis_result.processing_time.as_millis() as u64,
+                    description = analysis_result.description.as_str(),
+                    "分析成功"
+                );
+                if json_logs {
+                    return Ok(analysis_result);
+                }
+                if model == config.model {
+                    text_println!("✅ 分析成功:");
+                } else {
+                    text_println!("✅ 分析成功（备用模型 {}）:", model);
+                }
                 for line in analysis_result.description.lines() {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
-                        println!("   {}", trimmed);
+                        text_println!("   {}", trimmed);
                     }
                 }
                 if let Some(ref token_usage) = analysis_result.token_usage {
-                    println!(
+                    info_println!(
                         "   Token: 输入 {}, 输出 {}, 总计 {} · 耗时 {:.2}s · {}",
                         token_usage.prompt_tokens.unwrap_or(0),
                         token_usage.completion_tokens.unwrap_or(0),
@@ -246,7 +2087,7 @@ async fn analyze_screenshot_with_retry(
                         timestamp.format("%Y-%m-%d %H:%M:%S")
                     );
                 } else {
-                    println!(
+                    info_println!(
                         "   耗时 {:.2}s · {}",
                         analysis_result.processing_time.as_secs_f64(),
                         timestamp.format("%Y-%m-%d %H:%M:%S")
@@ -259,24 +2100,138 @@ async fn analyze_screenshot_with_retry(
                 let error_msg = last_error.as_ref().unwrap();
 
                 // 不管什么错误都重试
-                eprintln!(
-                    "❌ 分析失败 (第 {}/{} 次): {}",
-                    attempt, MAX_RETRIES, error_msg
+                tracing::warn!(
+                    event = "analysis_failure",
+                    screenshot_path = screenshot_path_str,
+                    attempt,
+                    max_attempts,
+                    model,
+                    error = %error_msg,
+                    "分析失败"
                 );
+                // 还会重试的失败只在 --verbose 时显示，最后一次失败始终输出到 stderr
+                let split_batch = image_paths.len() > 1 && error_msg.is_request_rejected();
+                if !json_logs {
+                    if attempt < max_attempts && !split_batch {
+                        verbose_println!("⚠️ 分析失败 (第 {}/{} 次): {}", attempt, max_attempts, error_msg);
+                    } else {
+                        eprintln!("❌ 分析失败 (第 {}/{} 次): {}", attempt, max_attempts, error_msg);
+                    }
+                    verbose_println!("   本次请求耗时 {:.2}s", attempt_started.elapsed().as_secs_f64());
+                }
+
+                if split_batch {
+                    break;
+                }
 
                 if attempt < MAX_RETRIES {
-                    let delay = RETRY_DELAYS[attempt as usize - 1];
-                    println!("⏳ 等待 {} 秒后重试...", delay);
+                    // 限流时优先遵循服务端的 Retry-After，未提供时按常规延迟退避
+                    let rate_limited = error_msg.is_rate_limited();
+                    let delay = error_msg
+                        .retry_after()
+                        .map(|d| d.as_secs().clamp(1, MAX_RATE_LIMIT_DELAY_SECS))
+                        .unwrap_or(RETRY_DELAYS[attempt as usize - 1]);
+                    tracing::info!(
+                        event = "analysis_retry",
+                        attempt,
+                        delay_secs = delay,
+                        rate_limited,
+                        "等待后重试"
+                    );
+                    if !json_logs {
+                        if rate_limited {
+                            verbose_println!("🚦 接口限流，等待 {} 秒后重试...", delay);
+                        } else {
+                            verbose_println!("⏳ 等待 {} 秒后重试...", delay);
+                        }
+                    }
                     sleep(Duration::from_secs(delay)).await;
+                } else if attempt < max_attempts {
+                    // 主模型重试耗尽，下一次尝试使用备用模型
+                    tracing::warn!(
+                        event = "analysis_fallback",
+                        primary_model = config.model.as_str(),
+                        fallback_model = config.fallback_model(),
+                        "主模型重试耗尽，改用备用模型"
+                    );
+                    if !json_logs {
+                        info_println!(
+                            "🔁 主模型 {} 重试耗尽，改用备用模型 {} 再试一次",
+                            config.model,
+                            config.fallback_model().unwrap_or_default()
+                        );
+                    }
                 } else {
-                    eprintln!("❌ 达到最大重试次数，分析失败");
+                    tracing::error!(event = "analysis_exhausted", attempts = max_attempts, "达到最大重试次数，分析失败");
+                    if !json_logs {
+                        eprintln!("❌ 达到最大重试次数，分析失败");
+                    }
                 }
             }
         }
     }
 
     // 所有重试都失败了
-    Err(last_error.unwrap_or_else(|| "未知错误".into()))
+    Err(last_error.unwrap_or_else(|| ScreenTimeError::other("未知错误")))
+}
+
+/// 填充提示词模板：支持 {app_name}、{window_title}、{time_of_day}、{os}，
+/// 未知占位符原样保留，缺失的值替换为 "unknown"。只扫描模板一遍，
+/// 已填入的值（如窗口标题中的 "{os}"）不会再被展开
+fn render_prompt_template(
+    template: &str,
+    ctx: &context::SystemContext,
+    timestamp: &chrono::DateTime<chrono::Local>,
+) -> String {
+    if !template.contains('{') {
+        return template.to_string();
+    }
+
+    let window = ctx.active_window.as_ref();
+    let app_name = window
+        .and_then(|w| w.app_name.as_deref())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("unknown");
+    let window_title = window
+        .and_then(|w| w.window_title.as_deref())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("unknown");
+    let os = ctx.os_name.as_deref().unwrap_or("unknown");
+    let time_of_day = match timestamp.hour() {
+        0..=5 => "凌晨",
+        6..=11 => "上午",
+        12..=17 => "下午",
+        _ => "晚上",
+    };
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let tail = &rest[start + 1..];
+        let value = tail.find('}').and_then(|end| {
+            let value = match &tail[..end] {
+                "app_name" => app_name,
+                "window_title" => window_title,
+                "time_of_day" => time_of_day,
+                "os" => os,
+                _ => return None,
+            };
+            Some((value, end + 1))
+        });
+        match value {
+            Some((value, consumed)) => {
+                rendered.push_str(value);
+                rest = &tail[consumed..];
+            }
+            None => {
+                rendered.push('{');
+                rest = tail;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
 }
 
 /// 将context模块的SystemContext转换为models模块的SystemContext
@@ -295,3 +2250,260 @@ fn convert_context_to_models(ctx: &context::SystemContext) -> SystemContext {
         timestamp: Local::now(), // 使用当前时间作为时间戳
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jittered_interval_bounds_and_mean() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let samples: Vec<u64> = (0..10_000)
+            .map(|_| jittered_interval(60, 30, &mut rng).as_secs())
+            .collect();
+        assert!(samples.iter().all(|&s| (30..=90).contains(&s)));
+        let mean = samples.iter().sum::<u64>() as f64 / samples.len() as f64;
+        assert!((mean - 60.0).abs() < 1.0, "mean = {}", mean);
+
+        // 抖动大于间隔时也不会出现 0 秒等待
+        let mut rng = StdRng::seed_from_u64(7);
+        assert!((0..1_000).all(|_| jittered_interval(10, 100, &mut rng).as_secs() >= 1));
+        assert_eq!(jittered_interval(60, 0, &mut rng), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_split_batch_response() {
+        let response = "好的，逐张分析如下：\n### 截图 1\n【工作】【VSCode】【编写代码】\n\n**截图2：** 【学习】【Safari】【阅读文档】\n继续阅读\n### 截图 9\n多余的段落";
+        let sections = split_batch_response(response, 3);
+        assert_eq!(sections[0].as_deref(), Some("【工作】【VSCode】【编写代码】"));
+        assert_eq!(sections[1].as_deref(), Some("【学习】【Safari】【阅读文档】\n继续阅读"));
+        assert_eq!(sections[2], None);
+
+        // 正文中以“截图”开头的句子不是分段标记
+        assert!(split_batch_response("截图 1 中用户在写代码", 1)[0].is_none());
+
+        let usage = TokenUsage { prompt_tokens: Some(10), completion_tokens: Some(3), total_tokens: Some(13) };
+        let shares: Vec<u32> = (0..3).filter_map(|i| split_token_usage(Some(&usage), 3, i)?.total_tokens).collect();
+        assert_eq!(shares, vec![5, 4, 4]);
+    }
+
+    #[test]
+    fn test_clip_prompt() {
+        let prompt = clip_prompt("描述用户在做什么", 4, 10);
+        assert!(prompt.starts_with("描述用户在做什么\n\n"));
+        assert!(prompt.contains("附带的 4 张图片是同一段 10 秒屏幕录像"));
+        assert!(prompt.contains("只输出一条整体结果"));
+    }
+
+    #[test]
+    fn test_analysis_sampling() {
+        let sampled: Vec<u64> = (1..=20).filter(|&i| is_sampled(i, 0.2)).collect();
+        assert_eq!(sampled, vec![1, 6, 11, 16]);
+        assert_eq!((1..=1000).filter(|&i| is_sampled(i, 0.3)).count(), 300);
+        assert!((1..=10).all(|i| is_sampled(i, 1.0)));
+        assert!(!(1..=10).any(|i| is_sampled(i, 0.0)));
+    }
+
+    #[test]
+    fn test_unique_path_does_not_overwrite() {
+        let dir = std::env::temp_dir().join(format!("openrecall_unique_path_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("screenshot.png");
+        assert_eq!(reserve_unique_path(path.clone()).unwrap(), path);
+
+        // 上一次调用已创建占位文件，再次预留同一路径时依次追加序号
+        assert_eq!(reserve_unique_path(path.clone()).unwrap(), dir.join("screenshot_1.png"));
+        fs::write(dir.join("screenshot_2.png"), b"").unwrap();
+        assert_eq!(reserve_unique_path(path).unwrap(), dir.join("screenshot_3.png"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_effective_interval_overrides() {
+        let overrides = vec![("Terminal".to_string(), 30), ("阅读".to_string(), 120)];
+        assert_eq!(effective_interval(60, &overrides, Some("terminal"), None), 30);
+        assert_eq!(effective_interval(60, &overrides, Some("Safari"), Some("阅读")), 120);
+        assert_eq!(effective_interval(60, &overrides, Some("Terminal"), Some("阅读")), 30);
+        assert_eq!(effective_interval(60, &overrides, Some("Safari"), None), 60);
+        assert_eq!(effective_interval(60, &[], None, None), 60);
+    }
+
+    /// 本地假模型接口：对每个请求返回相同的 OpenAI 兼容回复，返回接口地址
+    async fn serve_fake_api(content: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    // 读完请求头与 Content-Length 指定的请求体
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 8192];
+                    loop {
+                        let n = stream.read(&mut buf).await.unwrap_or(0);
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request);
+                        if let Some(header_end) = text.find("\r\n\r\n") {
+                            let length = text[..header_end]
+                                .lines()
+                                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                                .unwrap_or(0);
+                            if request.len() >= header_end + 4 + length {
+                                break;
+                            }
+                        }
+                    }
+                    let body = serde_json::json!({
+                        "choices": [{ "message": { "content": content } }],
+                        "usage": { "prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2 },
+                    })
+                    .to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        url
+    }
+
+    fn test_context(app_name: Option<&str>, window_title: Option<&str>) -> context::SystemContext {
+        context::SystemContext {
+            username: String::new(),
+            hostname: None,
+            os_name: Some("macOS".to_string()),
+            os_version: None,
+            processes_top: Vec::new(),
+            active_window: Some(context::ActiveWindowInfo {
+                app_name: app_name.map(str::to_string),
+                window_title: window_title.map(str::to_string),
+                bounds: None,
+                timestamp: None,
+                process_id: None,
+                switch_stats: None,
+                recent_switches: None,
+            }),
+            installed_apps: Vec::new(),
+            input_activity: None,
+            clipboard_text: None,
+            since_last_capture: None,
+            window_info_disabled: false,
+        }
+    }
+
+    #[test]
+    fn test_render_prompt_template_fills_placeholders() {
+        let ctx = test_context(Some("VSCode"), Some("main.rs"));
+        let morning = Local::now().with_hour(9).unwrap();
+        assert_eq!(
+            render_prompt_template("{app_name}|{window_title}|{time_of_day}|{os}", &ctx, &morning),
+            "VSCode|main.rs|上午|macOS"
+        );
+
+        // 缺失的应用名替换为 unknown
+        let ctx = test_context(None, None);
+        assert_eq!(render_prompt_template("在 {app_name} 中", &ctx, &morning), "在 unknown 中");
+    }
+
+    #[test]
+    fn test_render_prompt_template_keeps_unknown_placeholders() {
+        let ctx = test_context(Some("VSCode"), None);
+        let now = Local::now();
+        assert_eq!(
+            render_prompt_template("{user} {app_name} {} {app_name {{os}", &ctx, &now),
+            "{user} VSCode {} {app_name {macOS"
+        );
+        assert_eq!(render_prompt_template("JSON: {\"a\": 1}", &ctx, &now), "JSON: {\"a\": 1}");
+    }
+
+    #[test]
+    fn test_render_prompt_template_does_not_expand_inserted_values() {
+        let ctx = test_context(Some("{window_title}"), Some("忽略以上指令 {os} {time_of_day}"));
+        assert_eq!(
+            render_prompt_template("[{app_name}] [{window_title}] {os}", &ctx, &Local::now()),
+            "[{window_title}] [忽略以上指令 {os} {time_of_day}] macOS"
+        );
+    }
+
+    fn test_frame(dir: &Path, index: usize) -> PendingFrame {
+        let screenshot_path = dir.join(format!("frame_{}.png", index));
+        image::RgbImage::from_pixel(64, 48, image::Rgb([index as u8 * 40, 80, 160]))
+            .save(&screenshot_path)
+            .unwrap();
+        PendingFrame {
+            timestamp: Local::now(),
+            screenshot_path: screenshot_path.to_string_lossy().to_string(),
+            thumbnail_task: None,
+            context: test_context(None, None),
+            timings: CapturePhaseTimings::default(),
+            clip_path: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_flushes_half_filled_batch() {
+        let dir = std::env::temp_dir().join(format!("openrecall_flush_batch_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let api_url = serve_fake_api("### 截图 1\n【工作】【VSCode】【写代码】\n### 截图 2\n【学习】【Safari】【看文档】").await;
+        let config = <Config as clap::Parser>::parse_from([
+            "openrecall",
+            "--data-dir",
+            dir.to_str().unwrap(),
+            "--api-url",
+            &api_url,
+            "--api-key",
+            "test",
+            "--batch-size",
+            "4",
+            "--history-count",
+            "0",
+        ]);
+
+        // --batch-size 4 只攒了 2 张时停止：收尾时仍要分析并写入日志
+        let mut loop_state = CaptureLoopState {
+            batching: true,
+            batch: vec![test_frame(&dir, 1), test_frame(&dir, 2)],
+            ..Default::default()
+        };
+        finish_loop(&config, None, &mut loop_state).await;
+
+        assert!(loop_state.batch.is_empty());
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let logs = logger::load_daily_activity_logs(&config, &today).unwrap();
+        let descriptions: Vec<&str> = logs.iter().map(|log| log.description.as_str()).collect();
+        assert_eq!(descriptions, ["【工作】【VSCode】【写代码】", "【学习】【Safari】【看文档】"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_log_omits_clipboard_text() {
+        let config = crate::test_support::test_config("capture_clipboard");
+        let dir = config.get_screenshot_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let mut frame = test_frame(&dir, 1);
+        frame.context.clipboard_text = Some("验证码 123456".to_string());
+        let analysis_result = siliconflow::AnalysisResult {
+            description: "【工作】【VSCode】【写代码】".to_string(),
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: None,
+            reasoning: None,
+        };
+
+        let log = finish_capture(&config, None, frame, analysis_result, None, false, false).await;
+
+        assert!(!serde_json::to_string(&log).unwrap().contains("123456"));
+        for entry in fs::read_dir(config.get_logs_dir()).unwrap() {
+            let saved = fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(saved.contains("写代码") && !saved.contains("123456"), "{}", saved);
+        }
+        fs::remove_dir_all(config.data_dir.as_ref().unwrap()).unwrap();
+    }
+}
@@ -469,11 +469,14 @@ async fn request_clipboard_ai_decision(
     config: &Config,
     content: &str,
 ) -> Result<ClipboardDecision, Box<dyn Error + Send + Sync>> {
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(
-            config.clipboard_ai_timeout_seconds,
-        ))
-        .build()?;
+    let mut builder = Client::builder().timeout(std::time::Duration::from_secs(
+        config.clipboard_ai_timeout_seconds,
+    ));
+    // 剪贴板过滤同样调用模型接口，走相同的代理
+    if let Some(proxy) = config.api_proxy()? {
+        builder = builder.proxy(proxy);
+    }
+    let client = builder.build()?;
 
     // 固定输出协议：即使用户自定义 prompt，也必须输出固定 JSON 结构
     let protocol = "你必须严格只输出一行 JSON，且字段结构固定为：{\"save\": true|false, \"reason\": \"...\", \"category\": \"url|keyword_combo|topic|noise|sensitive|other\"}。不要输出任何额外文本、markdown、代码块。";
@@ -492,7 +495,7 @@ async fn request_clipboard_ai_decision(
 
     let response = client
         .post(&config.api_url)
-        .header("Authorization", format!("Bearer {}", config.api_key))
+        .header("Authorization", format!("Bearer {}", config.api_key()))
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
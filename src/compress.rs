@@ -0,0 +1,52 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// 文件压缩后的路径：在原文件名后追加 .gz（如 2024-05-06.json.gz）
+pub fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// 原文件或其 .gz 压缩版本是否存在
+pub fn exists(path: &Path) -> bool {
+    path.exists() || gz_path(path).exists()
+}
+
+/// 读取并解压 .gz 文件（支持多个 gzip 成员拼接）
+pub fn read_gz(path: &Path) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    MultiGzDecoder::new(File::open(path)?).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// 原子写入 .gz 文件：先写临时文件再重命名，返回压缩后的字节数
+pub fn write_gz(path: &Path, content: &[u8]) -> io::Result<u64> {
+    let tmp_path = path.with_extension("gz.tmp");
+    {
+        let mut encoder = GzEncoder::new(BufWriter::new(File::create(&tmp_path)?), Compression::default());
+        encoder.write_all(content)?;
+        let mut writer = encoder.finish()?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(fs::metadata(path)?.len())
+}
+
+/// 把文件压缩为同目录的 .gz 并删除原文件，保留原修改时间（按修改时间清理过期文件时不受影响）；
+/// 返回节省的字节数
+pub fn gzip_file(path: &Path) -> io::Result<u64> {
+    let metadata = fs::metadata(path)?;
+    let gz = gz_path(path);
+    let compressed = write_gz(&gz, &fs::read(path)?)?;
+    if let Ok(modified) = metadata.modified() {
+        let _ = File::options().write(true).open(&gz).and_then(|file| file.set_modified(modified));
+    }
+    fs::remove_file(path)?;
+    Ok(metadata.len().saturating_sub(compressed))
+}
@@ -0,0 +1,76 @@
+use crate::config::Config;
+use crate::verbosity::{self, text_println};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+
+/// 使用条款版本，条款内容有实质变化时递增，已同意旧版本的用户需重新确认
+pub const CONSENT_VERSION: u32 = 1;
+
+/// 首次运行时展示的条款
+const TERMS: &str = "\
+OpenRecall 会在后台持续截取屏幕，并把截图发送给配置的模型接口进行分析：
+  - 截图可能包含聊天、邮件、密码管理器等敏感内容，请在需要时暂停（--pause-hotkey）或设置工作时间（--schedule）
+  - 使用云端模型时，截图会上传到对应服务商，受其隐私政策约束
+  - 活动日志与保留的截图保存在本机数据目录，可随时用 --delete-logs 删除
+  - 请仅在你有权记录的设备和账号上使用";
+
+/// consent.json：记录用户同意的时间与条款版本
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Consent {
+    pub accepted_at: DateTime<Local>,
+    pub version: u32,
+    /// interactive（交互确认）或 accept-terms（命令行参数）
+    pub method: String,
+}
+
+/// 启动截屏前确认用户已同意条款：已记录当前版本的同意时直接通过；
+/// 传入 --accept-terms 时直接记录；否则在交互终端中询问一次，非交互环境拒绝启动
+pub fn ensure_consent(config: &Config, interactive: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = config.get_consent_path();
+    let recorded = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Consent>(&content).ok());
+    if recorded.is_some_and(|consent| consent.version >= CONSENT_VERSION) {
+        return Ok(());
+    }
+
+    let method = if config.accept_terms {
+        "accept-terms"
+    } else if interactive && io::stdin().is_terminal() {
+        if !prompt_consent()? {
+            return Err("未同意使用条款，已退出（可使用 --accept-terms 跳过确认）".into());
+        }
+        "interactive"
+    } else {
+        return Err(format!(
+            "尚未同意使用条款（{} 不存在或版本过旧）。请在终端中运行一次以确认，或在非交互环境中传入 --accept-terms",
+            path.display()
+        )
+        .into());
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let consent = Consent {
+        accepted_at: Local::now(),
+        version: CONSENT_VERSION,
+        method: method.to_string(),
+    };
+    fs::write(&path, serde_json::to_string_pretty(&consent)?)?;
+    text_println!("✅ 已记录同意（版本 {}）: {}", CONSENT_VERSION, path.display());
+    Ok(())
+}
+
+/// 展示条款并读取回答，输入 yes / y 视为同意
+fn prompt_consent() -> Result<bool, Box<dyn Error + Send + Sync>> {
+    text_println!("📜 首次运行需要确认使用条款：\n{}\n", TERMS);
+    verbosity::print_prompt("输入 yes 同意并继续，其他任意输入退出: ")?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "yes" | "y"))
+}
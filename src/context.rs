@@ -1,5 +1,6 @@
 use crate::config::Config;
 use crate::input_tracker;
+use crate::lang::Lang;
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
 use std::path::PathBuf;
@@ -65,6 +66,54 @@ pub struct SystemContext {
     pub active_window: Option<ActiveWindowInfo>,
     pub installed_apps: Vec<String>,
     pub input_activity: Option<input_tracker::InputActivity>,
+    /// 剪贴板中的文本片段（--include-clipboard），已截断
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clipboard_text: Option<String>,
+    /// 上一次截屏以来的窗口活动（--since-last-capture-apps）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since_last_capture: Option<CaptureDelta>,
+    /// 按配置跳过了前台窗口采集（--no-window-info），格式化时不输出窗口相关段落
+    #[serde(skip, default)]
+    pub window_info_disabled: bool,
+}
+
+/// 上一次截屏到本次采集上下文之间的窗口活动
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CaptureDelta {
+    /// 距上一次截屏的毫秒数
+    pub elapsed_ms: u64,
+    pub switch_count: usize,
+    /// 期间各应用的使用时长（毫秒），按时长降序
+    pub apps: Vec<(String, u64)>,
+}
+
+/// (上一次截屏, 本次截屏) 的开始时间（毫秒时间戳），截屏流程在每次截屏开始时调用 mark_capture 更新
+static CAPTURE_TIMES: Mutex<(Option<u64>, Option<u64>)> = Mutex::new((None, None));
+
+/// 记录一次截屏开始；同一次截屏中多次采集上下文都以上一次截屏为起点
+pub fn mark_capture(timestamp_ms: u64) {
+    let mut times = CAPTURE_TIMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    *times = (times.1, Some(timestamp_ms));
+}
+
+/// 上一次截屏以来的窗口切换次数与用过的应用（最多 limit 个）；还没有上一次截屏或期间没有切换时为 None
+fn capture_delta(limit: usize) -> Option<CaptureDelta> {
+    use crate::window_tracker::WINDOW_TRACKER;
+
+    let since = CAPTURE_TIMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).0?;
+    let now = chrono::Local::now().timestamp_millis() as u64;
+    let snapshot = WINDOW_TRACKER.snapshot();
+    let switch_count = snapshot.switches_between(since, now, usize::MAX).len();
+    if switch_count == 0 {
+        return None;
+    }
+    let mut apps = snapshot.app_usage_between(since, now);
+    apps.truncate(limit);
+    Some(CaptureDelta {
+        elapsed_ms: now.saturating_sub(since),
+        switch_count,
+        apps,
+    })
 }
 
 #[derive(Default)]
@@ -80,33 +129,21 @@ lazy_static::lazy_static! {
 pub async fn collect_system_context(config: &Config) -> SystemContext {
     let username = whoami::username();
 
-    let mut sys = System::new_all();
-    sys.refresh_all();
-
-    // 为 CPU 使用率做第二次刷新（需要两次采样）
-    sys.refresh_processes();
-    sleep(Duration::from_millis(200)).await;
-    sys.refresh_processes();
-
     let hostname = System::host_name();
     let os_name = System::name();
     let os_version = System::os_version();
 
-    // Top N 进程（按CPU使用率），并带上当前可得的 CPU 百分比
-    let mut procs: Vec<ProcessInfo> = sys
-        .processes()
-        .values()
-        .map(|p| ProcessInfo {
-            name: p.name().to_string(),
-            cpu_percent: p.cpu_usage(),
-        })
-        .collect();
-    procs.sort_by_key(|p| std::cmp::Reverse(p.cpu_percent as u64));
-    procs.truncate(10);
-
-
+    let procs = if config.no_process_info {
+        Vec::new()
+    } else {
+        collect_top_processes().await
+    };
 
-    let active_window = get_enhanced_active_window_info().await;
+    let active_window = if config.no_window_info {
+        None
+    } else {
+        get_enhanced_active_window_info().await
+    };
     let installed_apps = collect_installed_apps(config);
     let input_activity = if config.input_context_enabled {
         input_tracker::ensure_started();
@@ -118,6 +155,16 @@ pub async fn collect_system_context(config: &Config) -> SystemContext {
     } else {
         None
     };
+    let since_last_capture = if config.no_window_info || config.since_last_capture_apps == 0 {
+        None
+    } else {
+        capture_delta(config.since_last_capture_apps)
+    };
+    let clipboard_text = if config.include_clipboard {
+        read_clipboard_snippet(config.clipboard_context_max_chars, &config.redact_keywords).await
+    } else {
+        None
+    };
 
     SystemContext {
         username,
@@ -128,9 +175,62 @@ pub async fn collect_system_context(config: &Config) -> SystemContext {
         active_window,
         installed_apps,
         input_activity,
+        clipboard_text,
+        since_last_capture,
+        window_info_disabled: config.no_window_info,
     }
 }
 
+/// 读取剪贴板文本并截断到 max_chars 个字符；剪贴板为空、不是文本（如图片）、
+/// 含控制字符或命中敏感关键词时返回 None
+async fn read_clipboard_snippet(max_chars: usize, redact_keywords: &[String]) -> Option<String> {
+    let text = tokio::task::spawn_blocking(|| arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()))
+        .await
+        .ok()?
+        .ok()?;
+    let text = text.trim();
+    if text.is_empty() || max_chars == 0 || text.contains('\0') {
+        return None;
+    }
+    let lowercase = text.to_lowercase();
+    if redact_keywords
+        .iter()
+        .map(|keyword| keyword.trim().to_lowercase())
+        .any(|keyword| !keyword.is_empty() && lowercase.contains(&keyword))
+    {
+        return None;
+    }
+
+    let mut snippet: String = text.chars().take(max_chars).collect();
+    if text.chars().nth(max_chars).is_some() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+/// Top N 进程（按CPU使用率），并带上当前可得的 CPU 百分比
+async fn collect_top_processes() -> Vec<ProcessInfo> {
+    let mut sys = System::new_all();
+    sys.refresh_all();
+
+    // 为 CPU 使用率做第二次刷新（需要两次采样）
+    sys.refresh_processes();
+    sleep(Duration::from_millis(200)).await;
+    sys.refresh_processes();
+
+    let mut procs: Vec<ProcessInfo> = sys
+        .processes()
+        .values()
+        .map(|p| ProcessInfo {
+            name: p.name().to_string(),
+            cpu_percent: p.cpu_usage(),
+        })
+        .collect();
+    procs.sort_by_key(|p| std::cmp::Reverse(p.cpu_percent as u64));
+    procs.truncate(10);
+    procs
+}
+
 fn collect_installed_apps(config: &Config) -> Vec<String> {
     if !config.installed_apps_enabled {
         return Vec::new();
@@ -226,81 +326,38 @@ async fn get_enhanced_active_window_info() -> Option<ActiveWindowInfo> {
 async fn get_active_window_info() -> Option<ActiveWindowInfo> {
     #[cfg(target_os = "macos")]
     {
-        use std::process::Command;
-        let app_name = Command::new("/usr/bin/osascript")
-            .args([
-                "-e",
-                r#"tell application "System Events" to get name of first process whose frontmost is true"#,
-            ])
-            .output()
-            .ok()
-            .and_then(|o| {
-                if o.status.success() {
-                    String::from_utf8(o.stdout).ok()
-                } else {
-                    None
-                }
-            })
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty());
+        use crate::window_tracker::{run_window_query, window_query_timeout};
+
+        // 每个 osascript 调用都有超时，System Events 卡住时放弃本次查询
+        let timeout = window_query_timeout();
+        let osascript = |script: &str| {
+            let mut command = tokio::process::Command::new("/usr/bin/osascript");
+            command.args(["-e", script]);
+            async move {
+                run_window_query(command, timeout)
+                    .await
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+            }
+        };
+
+        let app_name =
+            osascript(r#"tell application "System Events" to get name of first process whose frontmost is true"#).await;
 
         let window_title = if app_name.is_some() {
-            Command::new("/usr/bin/osascript")
-                .args([
-                    "-e",
-                    r#"tell application "System Events" to tell (first process whose frontmost is true) to get title of front window"#,
-                ])
-                .output()
-                .ok()
-                .and_then(|o| {
-                    if o.status.success() {
-                        String::from_utf8(o.stdout).ok()
-                    } else {
-                        None
-                    }
-                })
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
+            osascript(r#"tell application "System Events" to tell (first process whose frontmost is true) to get title of front window"#).await
         } else {
             None
         };
 
         // 获取窗口位置和大小
         let bounds = if app_name.is_some() {
-            Command::new("/usr/bin/osascript")
-                .args([
-                    "-e",
-                    r#"tell application "System Events" to tell (first process whose frontmost is true) to get position of front window"#,
-                ])
-                .output()
-                .ok()
-                .and_then(|o| {
-                    if o.status.success() {
-                        String::from_utf8(o.stdout).ok()
-                    } else {
-                        None
-                    }
-                })
-                .and_then(|pos_str| {
-                    // 获取窗口大小
-                    Command::new("/usr/bin/osascript")
-                        .args([
-                            "-e",
-                            r#"tell application "System Events" to tell (first process whose frontmost is true) to get size of front window"#,
-                        ])
-                        .output()
-                        .ok()
-                        .and_then(|o| {
-                            if o.status.success() {
-                                String::from_utf8(o.stdout).ok()
-                            } else {
-                                None
-                            }
-                        })
-                        .and_then(|size_str| {
-                            parse_window_bounds(&pos_str.trim(), &size_str.trim())
-                        })
-                })
+            match osascript(r#"tell application "System Events" to tell (first process whose frontmost is true) to get position of front window"#).await {
+                Some(pos_str) => osascript(r#"tell application "System Events" to tell (first process whose frontmost is true) to get size of front window"#)
+                    .await
+                    .and_then(|size_str| parse_window_bounds(&pos_str, &size_str)),
+                None => None,
+            }
         } else {
             None
         };
@@ -409,11 +466,15 @@ async fn get_active_window_info() -> Option<ActiveWindowInfo> {
     }
 }
 
-pub fn format_context_as_text(ctx: &SystemContext) -> String {
+/// 发送给模型的上下文文本，固定文案按 --lang 选择语言
+pub fn format_context_as_text(ctx: &SystemContext, lang: Lang) -> String {
+    let l = lang.context_labels();
     let mut s = String::new();
     s.push_str(&format!(
-        "用户: {}\n主机: {}\nOS: {} {}\n",
+        "{}: {}\n{}: {}\nOS: {} {}\n",
+        l.user,
         ctx.username,
+        l.host,
         ctx.hostname.clone().unwrap_or_default(),
         ctx.os_name.clone().unwrap_or_default(),
         ctx.os_version.clone().unwrap_or_default()
@@ -421,26 +482,33 @@ pub fn format_context_as_text(ctx: &SystemContext) -> String {
 
     if let Some(w) = &ctx.active_window {
         s.push_str(&format!(
-            "前台应用: {}\n窗口标题: {}\n",
-            w.app_name.clone().unwrap_or("未知".to_string()),
-            w.window_title.clone().unwrap_or("未知".to_string())
+            "{}: {}\n{}: {}\n",
+            l.foreground_app,
+            w.app_name.as_deref().unwrap_or(l.unknown),
+            l.window_title,
+            w.window_title.as_deref().unwrap_or(l.unknown)
         ));
         
         // 添加窗口切换统计信息
         if let Some(stats) = &w.switch_stats {
             s.push_str(&format!(
-                "窗口切换统计:\n  - 总切换次数: {}\n  - 当前会话时长: {:.1}分钟\n",
+                "{}:\n  - {}: {}\n  - {}: {:.1}{}\n",
+                l.switch_stats,
+                l.total_switches,
                 stats.total_switches,
-                stats.current_session_duration_ms as f64 / 60000.0
+                l.current_session,
+                stats.current_session_duration_ms as f64 / 60000.0,
+                l.minutes
             ));
             
             if !stats.most_used_apps.is_empty() {
-                s.push_str("  - 最常用应用:\n");
+                s.push_str(&format!("  - {}:\n", l.most_used_apps));
                 for (app, duration) in stats.most_used_apps.iter().take(3) {
                     s.push_str(&format!(
-                        "    * {}: {:.1}分钟\n",
+                        "    * {}: {:.1}{}\n",
                         app,
-                        *duration as f64 / 60000.0
+                        *duration as f64 / 60000.0,
+                        l.minutes
                     ));
                 }
             }
@@ -449,27 +517,49 @@ pub fn format_context_as_text(ctx: &SystemContext) -> String {
         // 添加最近的窗口切换记录
         if let Some(switches) = &w.recent_switches {
             if !switches.is_empty() {
-                s.push_str("最近窗口切换:\n");
+                s.push_str(&format!("{}:\n", l.recent_switches));
                 for switch in switches.iter().take(3) {
-                    let from_app = switch.from_app.as_deref().unwrap_or("未知");
-                    let to_app = switch.to_app.as_deref().unwrap_or("未知");
+                    let from_app = switch.from_app.as_deref().unwrap_or(l.unknown);
+                    let to_app = switch.to_app.as_deref().unwrap_or(l.unknown);
                     s.push_str(&format!(
-                        "  - {} -> {} (停留{:.1}秒)\n",
+                        "  - {} -> {} ({}{:.1}{})\n",
                         from_app,
                         to_app,
-                        switch.duration_ms as f64 / 1000.0
+                        l.stayed,
+                        switch.duration_ms as f64 / 1000.0,
+                        l.seconds
                     ));
                 }
             }
         }
-    } else {
-        s.push_str("前台应用: [需要辅助功能权限]\n窗口标题: [需要辅助功能权限]\n");
+    } else if !ctx.window_info_disabled {
+        s.push_str(&format!(
+            "{}: {}\n{}: {}\n",
+            l.foreground_app, l.needs_accessibility, l.window_title, l.needs_accessibility
+        ));
+    }
+
+    if let Some(delta) = &ctx.since_last_capture {
+        s.push_str(&format!(
+            "{} ({:.1}{}):\n  - {}: {}\n",
+            l.since_last_capture,
+            delta.elapsed_ms as f64 / 60000.0,
+            l.minutes,
+            l.total_switches,
+            delta.switch_count
+        ));
+        if !delta.apps.is_empty() {
+            s.push_str(&format!("  - {}:\n", l.apps_used));
+            for (app, duration) in &delta.apps {
+                s.push_str(&format!("    * {}: {:.1}{}\n", app, *duration as f64 / 60000.0, l.minutes));
+            }
+        }
     }
 
 
 
     if !ctx.processes_top.is_empty() {
-        s.push_str("Top 进程:\n");
+        s.push_str(&format!("{}:\n", l.top_processes));
         for p in &ctx.processes_top {
             s.push_str(&format!(
                 "  - {} | cpu: {:.1}%\n",
@@ -479,32 +569,55 @@ pub fn format_context_as_text(ctx: &SystemContext) -> String {
     }
 
     if !ctx.installed_apps.is_empty() {
-        s.push_str("已安装软件清单(部分):\n");
+        s.push_str(&format!("{}:\n", l.installed_apps));
         let shown = ctx.installed_apps.iter().take(80).cloned().collect::<Vec<_>>();
         s.push_str(&format!("  - {}\n", shown.join(", ")));
-        s.push_str("软件识别规则：优先匹配此清单；若无明确证据请输出“未知软件”。\n");
+        s.push_str(l.installed_apps_rule);
+        s.push('\n');
     }
 
     if let Some(input) = &ctx.input_activity {
-        s.push_str("最近输入活动:\n");
+        s.push_str(&format!("{}:\n", l.input_activity));
         s.push_str(&format!(
-            "  - 键盘事件: {}\n  - 鼠标点击: {}\n  - 鼠标移动: {}\n",
-            input.keyboard_events, input.mouse_clicks, input.mouse_moves
+            "  - {}: {}\n  - {}: {}\n  - {}: {}\n",
+            l.keyboard_events, input.keyboard_events, l.mouse_clicks, input.mouse_clicks, l.mouse_moves, input.mouse_moves
         ));
         if let Some(secs) = input.last_input_secs_ago {
-            s.push_str(&format!("  - 最近输入距今: {} 秒\n", secs));
+            s.push_str(&format!("  - {}: {} {}\n", l.last_input_ago, secs, l.seconds_ago));
         }
         if !input.inferred_text.is_empty() {
-            s.push_str(&format!("  - 推断输入文本: {}\n", input.inferred_text));
+            s.push_str(&format!("  - {}: {}\n", l.inferred_text, input.inferred_text));
         }
         if !input.recent_keys.is_empty() {
-            s.push_str(&format!("  - 最近按键: {}\n", input.recent_keys.join(", ")));
+            s.push_str(&format!("  - {}: {}\n", l.recent_keys, input.recent_keys.join(", ")));
+        }
+    }
+
+    if let Some(text) = &ctx.clipboard_text {
+        s.push_str(&format!("{}:\n", l.clipboard_text));
+        for line in text.lines() {
+            s.push_str(&format!("  {}\n", line));
         }
     }
 
     s
 }
 
+/// --context-format json：紧凑的 SystemContext JSON，列表按文本格式的条数截断以控制 token
+pub fn format_context_as_json(ctx: &SystemContext) -> String {
+    let mut ctx = ctx.clone();
+    ctx.installed_apps.truncate(80);
+    if let Some(window) = ctx.active_window.as_mut() {
+        if let Some(stats) = window.switch_stats.as_mut() {
+            stats.most_used_apps.truncate(3);
+        }
+        if let Some(switches) = window.recent_switches.as_mut() {
+            switches.truncate(3);
+        }
+    }
+    serde_json::to_string(&ctx).unwrap_or_default()
+}
+
 /// 解析macOS AppleScript返回的窗口位置和大小字符串
 fn parse_window_bounds(position_str: &str, size_str: &str) -> Option<WindowBounds> {
     // AppleScript返回的格式通常是 "x, y" 和 "width, height"
@@ -0,0 +1,141 @@
+use crate::config::Config;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// 诊断日志文件名，位于数据目录的 diagnostics/ 下
+const LOG_FILE_NAME: &str = "openrecall.log";
+
+/// 轮转后最多保留的历史文件数（openrecall.log.1 ~ openrecall.log.3）
+const MAX_BACKUPS: usize = 3;
+
+/// 诊断日志路径（--service-log-level）
+pub fn log_path(config: &Config) -> PathBuf {
+    config.get_data_dir().join("diagnostics").join(LOG_FILE_NAME)
+}
+
+/// 按大小轮转的日志文件：写入后超过上限时依次改名为 .1、.2……，最旧的一份被覆盖
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, max_bytes, file, size })
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for index in (1..MAX_BACKUPS).rev() {
+            let from = self.backup_path(index);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.backup_path(1))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // fmt 层每条事件只写入一次，因此按事件整体轮转，不会把一行拆到两个文件
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            if let Err(e) = self.rotate() {
+                eprintln!("⚠️ 诊断日志轮转失败: {}", e);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// 写入诊断日志文件的 tracing 层：本程序的事件按 --service-log-level 记录，依赖库只记录 warn 及以上；
+/// 格式跟随 --log-format。级别为 off 或无法打开文件时返回 None
+pub fn layer<S>(config: &Config) -> Option<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let level: LevelFilter = config.service_log_level.parse().ok()?;
+    if level == LevelFilter::OFF {
+        return None;
+    }
+    let path = log_path(config);
+    let file = match RotatingFile::open(path.clone(), config.service_log_max_mb * 1024 * 1024) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("⚠️ 无法打开诊断日志 {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let filter = Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_default(level.min(LevelFilter::WARN));
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(Mutex::new(file));
+    Some(if config.json_logs() {
+        layer.json().with_filter(filter).boxed()
+    } else {
+        layer.with_filter(filter).boxed()
+    })
+}
+
+/// 把 panic 信息也写入诊断日志，后台运行时崩溃原因不会随终端输出丢失
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!(event = "panic", panic = %info, "程序崩溃");
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotating_file_keeps_limited_backups() {
+        let dir = std::env::temp_dir().join(format!("openrecall_diagnostics_test_{}", std::process::id()));
+        let path = dir.join(LOG_FILE_NAME);
+        let mut file = RotatingFile::open(path.clone(), 100).unwrap();
+        for i in 0..20 {
+            file.write_all(format!("{:039}\n", i).as_bytes()).unwrap();
+        }
+
+        assert!(fs::metadata(&path).unwrap().len() <= 100);
+        assert!(file.backup_path(MAX_BACKUPS).exists());
+        assert!(!file.backup_path(MAX_BACKUPS + 1).exists());
+        let newest_backup = fs::read_to_string(file.backup_path(1)).unwrap();
+        assert!(newest_backup.ends_with(&format!("{:039}\n", 17)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
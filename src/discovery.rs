@@ -0,0 +1,81 @@
+use crate::mcp_service::PROTOCOL_VERSION;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// mDNS 服务类型
+pub const SERVICE_TYPE: &str = "_screentime._tcp.local.";
+
+/// 退出时等待 goodbye 报文发出的最长时间
+const UNREGISTER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 已注册的 mDNS 广播，drop 时注销并关闭守护线程
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+/// 在局域网广播 MCP SSE 服务（--advertise）：监听 0.0.0.0 / :: 时广播本机所有网卡地址，
+/// 否则只广播监听的地址。TXT 记录包含 MCP 协议版本、程序版本与 SSE / POST 路径
+pub fn advertise(
+    bind: SocketAddr,
+    sse_path: &str,
+    post_path: &str,
+) -> Result<Advertisement, Box<dyn Error + Send + Sync>> {
+    if bind.ip().is_loopback() {
+        return Err(format!(
+            "MCP 服务只监听 {}，局域网内无法连接，已跳过广播（可设置 --mcp-host 0.0.0.0 --mcp-allow-remote）",
+            bind.ip()
+        )
+        .into());
+    }
+
+    let hostname: String = sysinfo::System::host_name()
+        .unwrap_or_else(|| "openrecall".to_string())
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect();
+    let protocol_version = PROTOCOL_VERSION.to_string();
+    let properties = [
+        ("protocol_version", protocol_version.as_str()),
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("sse_path", sse_path),
+        ("post_path", post_path),
+    ];
+
+    let ip = if bind.ip().is_unspecified() { String::new() } else { bind.ip().to_string() };
+    let mut info = ServiceInfo::new(
+        SERVICE_TYPE,
+        &format!("OpenRecall on {}", hostname),
+        &format!("{}.local.", hostname),
+        ip,
+        bind.port(),
+        &properties[..],
+    )?;
+    if bind.ip().is_unspecified() {
+        info = info.enable_addr_auto();
+    }
+
+    let fullname = info.get_fullname().to_string();
+    let daemon = ServiceDaemon::new()?;
+    daemon.register(info)?;
+    Ok(Advertisement { daemon, fullname })
+}
+
+impl Advertisement {
+    pub fn fullname(&self) -> &str {
+        &self.fullname
+    }
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        if let Ok(receiver) = self.daemon.unregister(&self.fullname) {
+            let _ = receiver.recv_timeout(UNREGISTER_TIMEOUT);
+        }
+        if let Ok(receiver) = self.daemon.shutdown() {
+            let _ = receiver.recv_timeout(UNREGISTER_TIMEOUT);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::config::Config;
+use crate::error::ScreenTimeError;
+use crate::service_state::ServiceStateManager;
+use crate::standalone_service;
+use crate::verbosity::info_println;
+use std::path::Path;
+use sysinfo::Disks;
+
+/// path 所在磁盘的可用空间（MB）：取挂载点是 path 最长前缀的磁盘；无法确定时返回 None
+pub fn available_mb(path: &Path) -> Option<u64> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let disks = Disks::new_with_refreshed_list();
+    let mounts = disks.list().iter().map(|disk| (disk.mount_point(), disk.available_space()));
+    longest_mount_match(&path, mounts).map(|bytes| bytes / 1024 / 1024)
+}
+
+/// 在 (挂载点, 可用字节数) 中找出挂载点是 path 最长前缀（按路径组件比较）的一项，返回其可用字节数
+fn longest_mount_match<'a>(path: &Path, mounts: impl IntoIterator<Item = (&'a Path, u64)>) -> Option<u64> {
+    mounts
+        .into_iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+        .map(|(_, available)| available)
+}
+
+/// 截屏前检查数据目录的可用空间（--min-free-mb）：不足时记录低空间状态并返回 Skipped，
+/// 刚进入低空间状态且开启 --low-disk-cleanup 时先清理一次过期文件；空间恢复后清除状态，截屏自动继续
+pub async fn ensure_free_space(config: &Config, state_manager: &ServiceStateManager) -> Result<(), ScreenTimeError> {
+    if config.min_free_mb == 0 {
+        // 关闭检查后清除之前记录的低空间状态
+        set_low_disk(state_manager, None).await;
+        return Ok(());
+    }
+    let data_dir = config.get_data_dir();
+    // 查询不到磁盘信息时不阻止截屏
+    let Some(mut free_mb) = available_mb(&data_dir) else {
+        return Ok(());
+    };
+    let was_low = state_manager.get_state().await.low_disk_free_mb.is_some();
+
+    if free_mb < config.min_free_mb && !was_low {
+        eprintln!(
+            "⚠️ 数据目录 {} 所在磁盘仅剩 {} MB，低于 --min-free-mb {} MB，暂停截屏直到空间释放",
+            data_dir.display(),
+            free_mb,
+            config.min_free_mb
+        );
+        if config.low_disk_cleanup && config.screenshot_retention_days > 0 {
+            let cleanup_config = config.clone();
+            match tokio::task::spawn_blocking(move || standalone_service::cleanup_expired_files(&cleanup_config)).await {
+                Ok((files, bytes)) => info_println!(
+                    "🧹 磁盘空间不足，已清理过期文件：删除 {} 个文件，释放 {:.2} MB",
+                    files,
+                    bytes as f64 / 1024.0 / 1024.0
+                ),
+                Err(e) => eprintln!("⚠️ 过期文件清理失败: {}", e),
+            }
+            free_mb = available_mb(&data_dir).unwrap_or(free_mb);
+        }
+    }
+
+    if free_mb < config.min_free_mb {
+        set_low_disk(state_manager, Some(free_mb)).await;
+        return Err(ScreenTimeError::Skipped(format!(
+            "磁盘可用空间 {} MB 低于 --min-free-mb {} MB，跳过截屏",
+            free_mb, config.min_free_mb
+        )));
+    }
+    if was_low {
+        set_low_disk(state_manager, None).await;
+        info_println!("💾 磁盘可用空间已恢复到 {} MB，继续截屏", free_mb);
+    }
+    Ok(())
+}
+
+async fn set_low_disk(state_manager: &ServiceStateManager, free_mb: Option<u64>) {
+    if let Err(e) = state_manager.set_low_disk(free_mb).await {
+        eprintln!("保存服务状态失败: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_longest_mount_match() {
+        let mounts = [(Path::new("/"), 1), (Path::new("/home"), 2), (Path::new("/home/user/data"), 3)];
+        assert_eq!(longest_mount_match(Path::new("/home/user/data/screenshots"), mounts), Some(3));
+        assert_eq!(longest_mount_match(Path::new("/home/user"), mounts), Some(2));
+        // 按路径组件匹配：/homework 不属于 /home
+        assert_eq!(longest_mount_match(Path::new("/homework"), mounts), Some(1));
+        assert_eq!(longest_mount_match(Path::new("relative/dir"), mounts), None);
+        assert_eq!(longest_mount_match(Path::new("/var"), []), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+use thiserror::Error;
+
+/// 截屏、分析与服务状态等核心流程的错误类型，调用方（如 MCP 工具）据此区分失败原因
+#[derive(Debug, Error)]
+pub enum ScreenTimeError {
+    /// 缺少屏幕录制等系统权限
+    #[error("权限不足: {0}")]
+    Permission(String),
+    /// 接口返回失败状态码；限流时 retry_after 为服务端通过 Retry-After 要求的等待时间
+    #[error("{}", api_message(*.status, .retry_after, .body))]
+    Api {
+        status: u16,
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    /// 无法连接接口、请求超时或响应读取失败
+    #[error("API请求发送失败: {0}")]
+    Network(#[from] reqwest::Error),
+    /// 文件读写失败（含磁盘已满）
+    #[error("文件读写失败: {0}")]
+    Io(#[from] io::Error),
+    /// 截屏失败
+    #[error("截屏失败: {0}")]
+    Capture(String),
+    /// 按规则跳过本次截屏（如目标窗口不存在、分析队列已满），不计为失败
+    #[error("{0}")]
+    Skipped(String),
+    /// 配置无效
+    #[error("配置错误: {0}")]
+    Config(String),
+    /// 其他错误
+    #[error(transparent)]
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+fn api_message(status: u16, retry_after: &Option<Duration>, body: &str) -> String {
+    match retry_after {
+        Some(delay) => format!("API请求被限流: {} - {}（Retry-After: {} 秒）", status, body, delay.as_secs()),
+        None if status == 429 => format!("API请求被限流: {} - {}", status, body),
+        None => format!("API请求失败: {} - {}", status, body),
+    }
+}
+
+impl ScreenTimeError {
+    /// 包装一条没有更具体分类的错误信息
+    pub fn other(message: impl Into<String>) -> Self {
+        Self::Other(message.into().into())
+    }
+
+    /// 接口限流（HTTP 429，或失败响应携带 Retry-After）
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, Self::Api { status, retry_after, .. } if *status == 429 || retry_after.is_some())
+    }
+
+    /// 服务端要求的重试等待时间
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Api { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// 请求被接口拒绝（HTTP 400 / 413 / 422），如请求体过大或图片数量超过接口上限
+    pub fn is_request_rejected(&self) -> bool {
+        matches!(self, Self::Api { status: 400 | 413 | 422, .. }) && !self.is_rate_limited()
+    }
+
+    /// 无法连接模型接口（网络断开、DNS 解析失败、连接被拒绝），网络恢复后可重新分析；
+    /// 超时说明接口可达但响应慢，不视为离线
+    pub fn is_offline(&self) -> bool {
+        matches!(self, Self::Network(e) if e.is_connect() && !e.is_timeout())
+    }
+
+    /// 面向用户的说明：按错误类型给出原因与处理建议
+    pub fn user_message(&self) -> String {
+        match self {
+            Self::Permission(_) => format!(
+                "{}\n👉 请在 系统设置 → 隐私与安全性 → 屏幕录制 中授权后执行 start",
+                self
+            ),
+            Self::Api { status: 401 | 403, .. } => format!("{}\n👉 接口拒绝访问，请检查 API 密钥是否有效", self),
+            Self::Api { .. } if self.is_rate_limited() => format!("{}\n👉 接口限流，请稍后再试或调大 --api-min-interval-ms", self),
+            Self::Api { status: 500..=599, .. } => format!("{}\n👉 模型服务暂时不可用，请稍后再试", self),
+            Self::Network(e) if e.is_timeout() => format!("{}\n👉 请求超时，可调大 --api-timeout", self),
+            Self::Network(_) => format!("{}\n👉 无法连接模型接口，请检查网络与 --api-url", self),
+            Self::Io(e) => io_message(self, e),
+            _ => self.to_string(),
+        }
+    }
+}
+
+fn io_message(error: &ScreenTimeError, io_error: &io::Error) -> String {
+    match io_error.kind() {
+        io::ErrorKind::StorageFull => format!("{}\n👉 磁盘空间不足，可用 --screenshot-retention-days 或 --compress 释放空间", error),
+        io::ErrorKind::PermissionDenied => format!("{}\n👉 没有数据目录的读写权限，请检查 --data-dir", error),
+        _ => error.to_string(),
+    }
+}
+
+/// 任意错误的用户说明：ScreenTimeError 按类型给出建议，其他错误原样显示
+pub fn user_message(error: &(dyn Error + 'static)) -> String {
+    if let Some(e) = error.downcast_ref::<ScreenTimeError>() {
+        return e.user_message();
+    }
+    if let Some(e) = error.downcast_ref::<io::Error>() {
+        return io_message(&ScreenTimeError::Io(io::Error::new(e.kind(), e.to_string())), e);
+    }
+    error.to_string()
+}
+
+impl From<Box<dyn Error + Send + Sync>> for ScreenTimeError {
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        match error.downcast::<ScreenTimeError>() {
+            Ok(e) => *e,
+            Err(error) => match error.downcast::<io::Error>() {
+                Ok(e) => Self::Io(*e),
+                Err(error) => Self::Other(error),
+            },
+        }
+    }
+}
+
+impl From<serde_json::Error> for ScreenTimeError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Other(Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_classification() {
+        let api = |status, retry_after| ScreenTimeError::Api { status, retry_after, body: "busy".to_string() };
+
+        let limited = api(429, None);
+        assert!(limited.is_rate_limited());
+        assert!(!limited.is_request_rejected());
+        assert_eq!(limited.to_string(), "API请求被限流: 429 - busy");
+
+        let retry = api(503, Some(Duration::from_secs(7)));
+        assert!(retry.is_rate_limited());
+        assert_eq!(retry.retry_after(), Some(Duration::from_secs(7)));
+
+        let rejected = api(413, None);
+        assert!(rejected.is_request_rejected());
+        assert_eq!(rejected.to_string(), "API请求失败: 413 - busy");
+
+        assert!(api(401, None).user_message().contains("API 密钥"));
+
+        // 装箱后再转换回来时保留原有分类
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(api(500, None));
+        assert!(matches!(ScreenTimeError::from(boxed), ScreenTimeError::Api { status: 500, .. }));
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(io::Error::from(io::ErrorKind::StorageFull));
+        let disk_full = ScreenTimeError::from(boxed);
+        assert!(matches!(disk_full, ScreenTimeError::Io(_)));
+        assert!(disk_full.user_message().contains("磁盘空间不足"));
+    }
+}
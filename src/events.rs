@@ -0,0 +1,20 @@
+use crate::models::CaptureEvent;
+use tokio::sync::broadcast;
+
+/// 事件缓冲区大小，订阅方处理过慢时丢弃最旧的事件
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref CAPTURE_EVENTS: broadcast::Sender<CaptureEvent> =
+        broadcast::channel(EVENT_CHANNEL_CAPACITY).0;
+}
+
+/// 发布截屏进度事件；没有订阅者时直接丢弃
+pub fn publish(event: CaptureEvent) {
+    let _ = CAPTURE_EVENTS.send(event);
+}
+
+/// 订阅截屏进度事件
+pub fn subscribe() -> broadcast::Receiver<CaptureEvent> {
+    CAPTURE_EVENTS.subscribe()
+}
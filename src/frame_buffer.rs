@@ -0,0 +1,71 @@
+use crate::config::Config;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// 一帧实际上传给模型的图片
+struct RecentFrame {
+    /// 上传时间
+    timestamp: DateTime<Local>,
+    /// 截图路径（同一张截图重试或影子分析时只保留最新一份）
+    source: String,
+    /// 编码后的图片字节（PNG，超过上传上限时为压缩后的 JPEG）
+    bytes: Vec<u8>,
+    extension: &'static str,
+}
+
+/// 保留的帧数（--keep-last-frames），0 为关闭
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+static FRAMES: Mutex<VecDeque<RecentFrame>> = Mutex::new(VecDeque::new());
+
+/// 设置保留的帧数；内存占用约为 N 张按 --max-width 等缩放后的截图
+pub fn configure(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::Relaxed);
+}
+
+/// 记录一帧上传给模型的图片，超出容量时丢弃最早的一帧
+pub fn record(source: &str, bytes: &[u8], mime_type: &str) {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let mut frames = FRAMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    frames.retain(|frame| frame.source != source);
+    frames.push_back(RecentFrame {
+        timestamp: Local::now(),
+        source: source.to_string(),
+        bytes: bytes.to_vec(),
+        extension: if mime_type == "image/jpeg" { "jpg" } else { "png" },
+    });
+    while frames.len() > capacity {
+        frames.pop_front();
+    }
+}
+
+/// 把缓冲中的帧写入 data_dir/debug_frames/<时间>_<原因>/，文件名为上传时间与原截图名；
+/// 未开启或缓冲为空时返回 None，否则返回目录与帧数
+pub fn dump(config: &Config, reason: &str) -> Result<Option<(PathBuf, usize)>, Box<dyn Error + Send + Sync>> {
+    let frames = FRAMES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if frames.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = config
+        .get_data_dir()
+        .join("debug_frames")
+        .join(format!("{}_{}", Local::now().format("%Y%m%d_%H%M%S"), reason));
+    std::fs::create_dir_all(&dir)?;
+    for frame in frames.iter() {
+        let stem = Path::new(&frame.source)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "frame".to_string());
+        let name = format!("{}_{}.{}", frame.timestamp.format("%H%M%S%.3f"), stem, frame.extension);
+        std::fs::write(dir.join(name), &frame.bytes)?;
+    }
+    Ok(Some((dir, frames.len())))
+}
@@ -0,0 +1,266 @@
+use crate::config::Config;
+use crate::models::{CaptureServiceStatus, ServiceCommand};
+use crate::standalone_service::ServiceController;
+use crate::verbosity::info_println;
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::{GlobalHotKeyEvent, HotKeyState};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+
+/// 快捷键每个进程只注册一次（MCP 模式下独立服务可能被再次启动）
+static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// 解析 "Cmd+Shift+P"、"Ctrl+Alt+F9" 形式的快捷键，大小写不敏感；
+/// 至少需要一个修饰键，避免普通输入误触发
+pub fn parse_hotkey(spec: &str) -> Result<HotKey, String> {
+    let mut modifiers = Modifiers::empty();
+    let mut key = None;
+
+    for part in spec.split('+').map(str::trim) {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= Modifiers::CONTROL,
+            "shift" => modifiers |= Modifiers::SHIFT,
+            "alt" | "option" | "opt" => modifiers |= Modifiers::ALT,
+            // macOS 的 Cmd / Windows 的 Win 键
+            "cmd" | "command" | "super" | "meta" | "win" => modifiers |= Modifiers::SUPER,
+            name => {
+                if key.is_some() {
+                    return Err(format!("快捷键只能包含一个主键: {}", spec));
+                }
+                key = Some(parse_key(name).ok_or_else(|| format!("无法识别的按键 \"{}\"", part))?);
+            }
+        }
+    }
+
+    let key = key.ok_or_else(|| format!("快捷键缺少主键: {}", spec))?;
+    if modifiers.is_empty() {
+        return Err(format!("快捷键至少需要一个修饰键（Ctrl / Shift / Alt / Cmd）: {}", spec));
+    }
+    Ok(HotKey::new(Some(modifiers), key))
+}
+
+/// 主键：字母、数字、F1-F12
+fn parse_key(name: &str) -> Option<Code> {
+    const LETTERS: [Code; 26] = [
+        Code::KeyA, Code::KeyB, Code::KeyC, Code::KeyD, Code::KeyE, Code::KeyF, Code::KeyG,
+        Code::KeyH, Code::KeyI, Code::KeyJ, Code::KeyK, Code::KeyL, Code::KeyM, Code::KeyN,
+        Code::KeyO, Code::KeyP, Code::KeyQ, Code::KeyR, Code::KeyS, Code::KeyT, Code::KeyU,
+        Code::KeyV, Code::KeyW, Code::KeyX, Code::KeyY, Code::KeyZ,
+    ];
+    const DIGITS: [Code; 10] = [
+        Code::Digit0, Code::Digit1, Code::Digit2, Code::Digit3, Code::Digit4,
+        Code::Digit5, Code::Digit6, Code::Digit7, Code::Digit8, Code::Digit9,
+    ];
+    const FUNCTION_KEYS: [Code; 12] = [
+        Code::F1, Code::F2, Code::F3, Code::F4, Code::F5, Code::F6,
+        Code::F7, Code::F8, Code::F9, Code::F10, Code::F11, Code::F12,
+    ];
+
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'..='z'), None) => return Some(LETTERS[(c as u8 - b'a') as usize]),
+        (Some(c @ '0'..='9'), None) => return Some(DIGITS[(c as u8 - b'0') as usize]),
+        _ => {}
+    }
+    let index: usize = name.strip_prefix('f')?.parse().ok()?;
+    FUNCTION_KEYS.get(index.checked_sub(1)?).copied()
+}
+
+/// 在独立服务中注册暂停快捷键（--pause-hotkey）：通过 global-hotkey 向系统注册单个组合键，
+/// 只接收该组合键的事件，不监听其他按键；按下时通过控制 socket 在运行与停止之间切换截屏。
+/// 快捷键无法解析或注册失败（如被其他程序占用、Linux 非 X11 会话）时只输出警告，服务照常运行
+pub fn spawn_pause_hotkey(config: &Config) {
+    let Some(spec) = config.pause_hotkey.as_deref().map(str::trim).filter(|s| !s.is_empty()) else {
+        return;
+    };
+    let hotkey = match parse_hotkey(spec) {
+        Ok(hotkey) => hotkey,
+        Err(e) => {
+            eprintln!("⚠️ 暂停快捷键未启用: {}", e);
+            return;
+        }
+    };
+    if REGISTERED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    // macOS 要求在主线程注册，由 drive_main_run_loop 完成并输出结果
+    #[cfg(target_os = "macos")]
+    {
+        *PENDING_REGISTRATION.lock().unwrap_or_else(|p| p.into_inner()) = Some((hotkey, spec.to_string()));
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        if let Err(e) = register(hotkey) {
+            eprintln!("⚠️ 暂停快捷键注册失败: {}，快捷键不可用", e);
+            REGISTERED.store(false, Ordering::SeqCst);
+            return;
+        }
+        info_println!("⌨️ 暂停快捷键已启用: {}", spec);
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+    let id = hotkey.id();
+    std::thread::spawn(move || {
+        // 按住时部分平台会重复发送按下事件，松开前只触发一次
+        let mut key_down = false;
+        while let Ok(event) = GlobalHotKeyEvent::receiver().recv() {
+            if event.id() != id {
+                continue;
+            }
+            match event.state() {
+                HotKeyState::Pressed => {
+                    if !key_down && tx.send(()).is_err() {
+                        break;
+                    }
+                    key_down = true;
+                }
+                HotKeyState::Released => key_down = false,
+            }
+        }
+    });
+
+    let controller = ServiceController::new(config);
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            if let Err(e) = toggle_capture(&controller).await {
+                eprintln!("⚠️ 快捷键切换截屏失败: {}", e);
+            }
+        }
+    });
+}
+
+/// X11：管理器自带事件线程，保持存活即可
+#[cfg(not(any(target_os = "macos", windows)))]
+fn register(hotkey: HotKey) -> Result<(), String> {
+    // 没有 X11 连接时管理器的事件线程会直接退出，注册却不报错
+    if std::env::var_os("DISPLAY").is_none() {
+        return Err("全局快捷键仅支持 X11 会话（未设置 DISPLAY）".to_string());
+    }
+    let manager = global_hotkey::GlobalHotKeyManager::new().map_err(|e| e.to_string())?;
+    manager.register(hotkey).map_err(|e| e.to_string())?;
+    std::mem::forget(manager);
+    Ok(())
+}
+
+/// Windows：快捷键消息投递到创建管理器的线程，需在同一线程上运行消息循环
+#[cfg(windows)]
+fn register(hotkey: HotKey) -> Result<(), String> {
+    let (result_tx, result_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let manager = match global_hotkey::GlobalHotKeyManager::new()
+            .and_then(|manager| manager.register(hotkey).map(|_| manager))
+        {
+            Ok(manager) => manager,
+            Err(e) => {
+                let _ = result_tx.send(Err(e.to_string()));
+                return;
+            }
+        };
+        let _ = result_tx.send(Ok(()));
+
+        use winapi::um::winuser::{DispatchMessageW, GetMessageW, TranslateMessage, MSG};
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) > 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+        drop(manager);
+    });
+    result_rx.recv().map_err(|_| "快捷键线程意外退出".to_string())?
+}
+
+/// macOS：等待主线程注册的快捷键及其原始写法
+#[cfg(target_os = "macos")]
+static PENDING_REGISTRATION: std::sync::Mutex<Option<(HotKey, String)>> = std::sync::Mutex::new(None);
+
+/// macOS 主线程驱动 CFRunLoop 的间隔
+#[cfg(target_os = "macos")]
+const MAIN_RUN_LOOP_TICK: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// 在主线程上运行服务：macOS 的全局快捷键必须在主线程注册，并依赖主线程的事件循环分发，
+/// 因此由 main 的顶层 future（始终在主线程上轮询）一边等待服务一边注册快捷键、驱动 CFRunLoop；
+/// 其他平台直接等待服务
+pub async fn drive_main_run_loop<F: std::future::Future>(service: F) -> F::Output {
+    #[cfg(target_os = "macos")]
+    {
+        use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop};
+
+        let mut manager = None;
+        let mut tick = tokio::time::interval(MAIN_RUN_LOOP_TICK);
+        tokio::pin!(service);
+        loop {
+            tokio::select! {
+                output = &mut service => return output,
+                _ = tick.tick() => {
+                    let pending = PENDING_REGISTRATION.lock().unwrap_or_else(|p| p.into_inner()).take();
+                    if let Some((hotkey, spec)) = pending {
+                        match global_hotkey::GlobalHotKeyManager::new()
+                            .and_then(|registered| registered.register(hotkey).map(|_| registered))
+                        {
+                            Ok(registered) => {
+                                manager = Some(registered);
+                                info_println!("⌨️ 暂停快捷键已启用: {}", spec);
+                            }
+                            Err(e) => eprintln!("⚠️ 暂停快捷键注册失败: {}，快捷键不可用", e),
+                        }
+                    }
+                    if manager.is_some() {
+                        CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, std::time::Duration::ZERO, false);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        service.await
+    }
+}
+
+/// 运行中则停止，否则（停止 / 暂停 / 权限丢失）启动
+async fn toggle_capture(
+    controller: &ServiceController,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let status = controller.send_command(ServiceCommand::Status).await?;
+    let running = status
+        .state
+        .is_some_and(|state| matches!(state.status, CaptureServiceStatus::Running));
+    let command = if running { ServiceCommand::Stop } else { ServiceCommand::Start };
+    let response = controller.send_command(command).await?;
+    if !response.success {
+        return Err(response.message.into());
+    }
+    info_println!(
+        "⌨️ 快捷键：{}",
+        if running { "截屏已停止" } else { "截屏已开始" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hotkey() {
+        assert_eq!(
+            parse_hotkey("Cmd+Shift+P").unwrap(),
+            HotKey::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::KeyP)
+        );
+        assert_eq!(
+            parse_hotkey("ctrl + alt + f9").unwrap(),
+            HotKey::new(Some(Modifiers::CONTROL | Modifiers::ALT), Code::F9)
+        );
+        assert_eq!(parse_hotkey("Option+Win+0").unwrap().key, Code::Digit0);
+
+        assert!(parse_hotkey("P").is_err());
+        assert!(parse_hotkey("Ctrl+Shift").is_err());
+        assert!(parse_hotkey("Ctrl+P+Q").is_err());
+        assert!(parse_hotkey("Ctrl+F13").is_err());
+        assert!(parse_hotkey("Ctrl+Enter").is_err());
+    }
+}
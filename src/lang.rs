@@ -0,0 +1,130 @@
+use clap::ValueEnum;
+
+/// 中文默认提示词（--prompt 的默认值）
+pub const DEFAULT_PROMPT_ZH: &str =
+    "请描述这张截图中用户正在使用什么软件，在做什么，并进行分类，严格按照格式输出结果：【类型】【软件】【主要工作摘要】。";
+
+/// 英文默认提示词，保留【】分段格式以便解析分类
+const DEFAULT_PROMPT_EN: &str = "Describe which software the user is using in this screenshot and what they are doing, then classify the activity. Output strictly in this format: 【Category】【Software】【Summary of the main work】.";
+
+/// 默认提示词与发送给模型的上下文文本所用语言（--lang）
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Lang {
+    /// 中文
+    #[default]
+    Zh,
+    /// 英文
+    En,
+}
+
+/// 上下文文本（format_context_as_text）中的固定文案
+pub struct ContextLabels {
+    pub user: &'static str,
+    pub host: &'static str,
+    pub foreground_app: &'static str,
+    pub window_title: &'static str,
+    pub unknown: &'static str,
+    pub needs_accessibility: &'static str,
+    pub switch_stats: &'static str,
+    pub total_switches: &'static str,
+    pub current_session: &'static str,
+    pub most_used_apps: &'static str,
+    pub recent_switches: &'static str,
+    pub stayed: &'static str,
+    pub minutes: &'static str,
+    pub seconds: &'static str,
+    pub top_processes: &'static str,
+    pub installed_apps: &'static str,
+    pub installed_apps_rule: &'static str,
+    pub input_activity: &'static str,
+    pub keyboard_events: &'static str,
+    pub mouse_clicks: &'static str,
+    pub mouse_moves: &'static str,
+    pub last_input_ago: &'static str,
+    pub seconds_ago: &'static str,
+    pub inferred_text: &'static str,
+    pub recent_keys: &'static str,
+    pub clipboard_text: &'static str,
+    pub since_last_capture: &'static str,
+    pub apps_used: &'static str,
+}
+
+const ZH_LABELS: ContextLabels = ContextLabels {
+    user: "用户",
+    host: "主机",
+    foreground_app: "前台应用",
+    window_title: "窗口标题",
+    unknown: "未知",
+    needs_accessibility: "[需要辅助功能权限]",
+    switch_stats: "窗口切换统计",
+    total_switches: "总切换次数",
+    current_session: "当前会话时长",
+    most_used_apps: "最常用应用",
+    recent_switches: "最近窗口切换",
+    stayed: "停留",
+    minutes: "分钟",
+    seconds: "秒",
+    top_processes: "Top 进程",
+    installed_apps: "已安装软件清单(部分)",
+    installed_apps_rule: "软件识别规则：优先匹配此清单；若无明确证据请输出“未知软件”。",
+    input_activity: "最近输入活动",
+    keyboard_events: "键盘事件",
+    mouse_clicks: "鼠标点击",
+    mouse_moves: "鼠标移动",
+    last_input_ago: "最近输入距今",
+    seconds_ago: "秒",
+    inferred_text: "推断输入文本",
+    recent_keys: "最近按键",
+    clipboard_text: "剪贴板文本",
+    since_last_capture: "自上次截屏以来",
+    apps_used: "期间使用的应用",
+};
+
+const EN_LABELS: ContextLabels = ContextLabels {
+    user: "User",
+    host: "Host",
+    foreground_app: "Foreground app",
+    window_title: "Window title",
+    unknown: "unknown",
+    needs_accessibility: "[accessibility permission required]",
+    switch_stats: "Window switch stats",
+    total_switches: "Total switches",
+    current_session: "Current session",
+    most_used_apps: "Most used apps",
+    recent_switches: "Recent window switches",
+    stayed: "stayed ",
+    minutes: " min",
+    seconds: "s",
+    top_processes: "Top processes",
+    installed_apps: "Installed software (partial)",
+    installed_apps_rule: "Software identification rule: prefer names from this list; if there is no clear evidence, output \"Unknown software\".",
+    input_activity: "Recent input activity",
+    keyboard_events: "Keyboard events",
+    mouse_clicks: "Mouse clicks",
+    mouse_moves: "Mouse moves",
+    last_input_ago: "Last input",
+    seconds_ago: "s ago",
+    inferred_text: "Inferred typed text",
+    recent_keys: "Recent keys",
+    clipboard_text: "Clipboard text",
+    since_last_capture: "Since last capture",
+    apps_used: "Apps used",
+};
+
+impl Lang {
+    /// 该语言的默认提示词
+    pub fn default_prompt(self) -> &'static str {
+        match self {
+            Lang::Zh => DEFAULT_PROMPT_ZH,
+            Lang::En => DEFAULT_PROMPT_EN,
+        }
+    }
+
+    /// 该语言的上下文文案
+    pub fn context_labels(self) -> &'static ContextLabels {
+        match self {
+            Lang::Zh => &ZH_LABELS,
+            Lang::En => &EN_LABELS,
+        }
+    }
+}
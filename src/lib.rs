@@ -3,4 +3,11 @@ pub mod window_tracker;
 pub mod config;
 pub mod models; 
 pub mod clipboard;
-pub mod input_tracker;
\ No newline at end of file
+pub mod input_tracker;
+pub mod timezone;
+pub mod schedule;
+pub mod app_aliases;
+pub mod lang;
+// 库中的模块只用到部分输出宏
+#[allow(unused_macros, unused_imports)]
+pub mod verbosity;
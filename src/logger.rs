@@ -1,14 +1,39 @@
-use crate::models::ActivityLog;
+use crate::compress;
+use crate::models::{ActivityClassification, ActivityLog, TokenUsage};
 use crate::config::Config;
-use chrono::Local;
+use crate::verbosity::{info_println, verbose_println};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// 日志压缩时相邻两条记录的最大间隔（秒），超过视为中断（如离开电脑），不再合并
+const COMPACT_MAX_GAP_SECS: i64 = 10 * 60;
+
+/// 单次删除允许的最长时间范围（天），避免误填的日期把多年的记录一次删光；更长的范围需分批删除
+const MAX_DELETE_RANGE_DAYS: i64 = 31;
+
+/// 串行化日志写入：后台分析 worker（--analysis-workers）会并发保存，JSON 格式需整文件读改写
+static SAVE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
 /// 保存活动日志（按日期分类存储）
 pub fn save_activity_log(log: &ActivityLog, config: &Config) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // 获取当前日期（YYYY-MM-DD格式）
-    let date = log.timestamp.format("%Y-%m-%d").to_string();
+    let daily_log_path = store_activity_log(log, config)?;
+    if !config.json_logs() {
+        info_println!("📝 日志已保存到: {}", daily_log_path.display());
+    }
+    Ok(())
+}
+
+/// 写入当日 JSON / JSONL 日志与 Markdown 日志，返回 JSON / JSONL 日志路径
+fn store_activity_log(log: &ActivityLog, config: &Config) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let _guard = SAVE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    // 按 --timezone 确定所属日期（YYYY-MM-DD格式）
+    let date = config.timezone.date_of(&log.timestamp).format("%Y-%m-%d").to_string();
     
     // 确保日志目录存在
     let logs_dir = config.get_logs_dir();
@@ -16,31 +41,32 @@ pub fn save_activity_log(log: &ActivityLog, config: &Config) -> Result<(), Box<d
         fs::create_dir_all(&logs_dir)?;
     }
     
-    // 获取当日日志文件路径
-    let daily_log_path = config.get_daily_log_path(&date);
-    
-    // 读取当日已有日志
-    let mut logs: Vec<ActivityLog> = if daily_log_path.exists() {
-        let file = File::open(&daily_log_path)?;
-        serde_json::from_reader(file)?
+    let daily_log_path = if config.jsonl_activity_logs() {
+        // JSONL：只追加一行，不读取也不重写已有内容
+        let daily_log_path = config.get_daily_jsonl_path(&date);
+        append_daily_jsonl(&daily_log_path, log)?;
+        daily_log_path
     } else {
-        Vec::new()
+        // 获取当日日志文件路径
+        let daily_log_path = config.get_daily_log_path(&date);
+
+        // 读取当日已有日志（损坏时尽量恢复）
+        let mut logs = load_daily_json(&daily_log_path)?;
+
+        // 按时间插入：并发分析时完成顺序可能与截屏顺序不同
+        let position = logs.partition_point(|existing| existing.timestamp <= log.timestamp);
+        logs.insert(position, log.clone());
+
+        // 保存日志：已压缩的内容已合并写入明文文件
+        write_daily_logs(&daily_log_path, &logs)?;
+        retire_gz(&daily_log_path, "json.bak")?;
+        daily_log_path
     };
-    
-    // 添加新日志
-    logs.push(log.clone());
-    
-    // 保存日志
-    let file = File::create(&daily_log_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &logs)?;
 
     // 同步保存可读 Markdown 日志
     save_activity_log_markdown(log, config)?;
-    
-    println!("📝 日志已保存到: {}", daily_log_path.display());
-    
-    Ok(())
+
+    Ok(daily_log_path)
 }
 
 /// 保存可读的 Markdown 活动日志（按日期追加）
@@ -48,18 +74,31 @@ fn save_activity_log_markdown(
     log: &ActivityLog,
     config: &Config,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let date = log.timestamp.format("%Y-%m-%d").to_string();
-    let logs_md_dir = config.get_data_dir().join("logs_md");
-    if !logs_md_dir.exists() {
-        fs::create_dir_all(&logs_md_dir)?;
-    }
-
-    let daily_md_path = logs_md_dir.join(format!("{}.md", date));
+    let date = config.timezone.date_of(&log.timestamp).format("%Y-%m-%d").to_string();
+    let daily_md_path = get_daily_markdown_path(config, &date)?;
     let mut file = fs::OpenOptions::new()
         .create(true)
         .append(true)
         .open(&daily_md_path)?;
 
+    file.write_all(format_activity_log_markdown(log).as_bytes())?;
+    Ok(())
+}
+
+/// 获取当日 Markdown 日志路径，并确保目录存在
+fn get_daily_markdown_path(
+    config: &Config,
+    date: &str,
+) -> Result<std::path::PathBuf, Box<dyn Error + Send + Sync>> {
+    let logs_md_dir = config.get_data_dir().join("logs_md");
+    if !logs_md_dir.exists() {
+        fs::create_dir_all(&logs_md_dir)?;
+    }
+    Ok(logs_md_dir.join(format!("{}.md", date)))
+}
+
+/// 将单条活动日志格式化为 Markdown 片段
+fn format_activity_log_markdown(log: &ActivityLog) -> String {
     let status_line = if log.description.trim().is_empty() {
         "失败/空结果".to_string()
     } else {
@@ -78,7 +117,7 @@ fn save_activity_log_markdown(
         .and_then(|ctx| ctx.window_title.clone())
         .unwrap_or_else(|| "-".to_string());
 
-    let token_line = match &log.token_usage {
+    let mut token_line = match &log.token_usage {
         Some(token) => format!(
             "输入 {} / 输出 {} / 总计 {}",
             token.prompt_tokens.unwrap_or(0),
@@ -87,13 +126,16 @@ fn save_activity_log_markdown(
         ),
         None => "-".to_string(),
     };
+    if let Some(cost) = log.estimated_cost {
+        token_line.push_str(&format!("（估算费用 {:.4}）", cost));
+    }
 
     let screenshot_line = match &log.screenshot_path {
         Some(path) => path.clone(),
         None => "已删除".to_string(),
     };
 
-    let md = format!(
+    format!(
         "## {}\n\n- 状态: {}\n- 软件: {}\n- 窗口: {}\n- 模型: {}\n- Token: {}\n- 截图: {}\n\n### AI 输出\n> {}\n\n---\n\n",
         log.timestamp.format("%H:%M:%S"),
         status_line,
@@ -103,33 +145,628 @@ fn save_activity_log_markdown(
         token_line,
         screenshot_line,
         log.description.replace('\n', "\n> ")
-    );
+    )
+}
+
+/// 删除结果统计
+#[derive(Debug, Default, Serialize)]
+pub struct DeletedLogs {
+    /// 删除的日志条数
+    pub entries: usize,
+    /// 删除的截图文件数（含缩略图与 --keep-clips 保留的录屏片段）
+    pub screenshots: usize,
+}
+
+/// 解析删除范围：只给出时间点时匹配该秒内的记录，不带偏移的时间按 --timezone 解释
+pub fn resolve_delete_range(
+    config: &Config,
+    start: &str,
+    end: Option<&str>,
+) -> Result<(chrono::DateTime<Local>, chrono::DateTime<Local>), Box<dyn Error + Send + Sync>> {
+    let start_time = config.timezone.parse_datetime(start)?;
+    let end_time = match end {
+        Some(end) => config.timezone.parse_datetime(end)?,
+        None => start_time + chrono::Duration::seconds(1) - chrono::Duration::nanoseconds(1),
+    };
+
+    if start_time > end_time {
+        return Err("开始时间晚于结束时间".into());
+    }
+    if end_time - start_time > chrono::Duration::days(MAX_DELETE_RANGE_DAYS) {
+        return Err(format!("删除范围超过 {} 天，请分批删除", MAX_DELETE_RANGE_DAYS).into());
+    }
+    Ok((start_time, end_time))
+}
+
+/// 删除时间范围 [start, end] 内的活动日志及其截图、录屏片段，并重写对应日期的 Markdown 日志
+pub fn delete_activity_logs(
+    config: &Config,
+    start: chrono::DateTime<Local>,
+    end: chrono::DateTime<Local>,
+) -> Result<DeletedLogs, Box<dyn Error + Send + Sync>> {
+    if start > end {
+        return Err("开始时间晚于结束时间".into());
+    }
+
+    let mut deleted = DeletedLogs::default();
+    // 前后各多查一天：更改 --timezone 之前写入的记录可能落在相邻日期的文件里
+    let first = config.timezone.date_of(&start).pred_opt().unwrap_or(chrono::NaiveDate::MIN);
+    let last = config.timezone.date_of(&end).succ_opt().unwrap_or(chrono::NaiveDate::MAX);
+    for date in daily_log_dates(config).range(first..=last) {
+        let date_str = date.format("%Y-%m-%d").to_string();
+        let (removed, kept): (Vec<ActivityLog>, Vec<ActivityLog>) =
+            load_daily_activity_logs(config, &date_str)?
+                .into_iter()
+                .partition(|log| log.timestamp >= start && log.timestamp <= end);
+
+        if !removed.is_empty() {
+            rewrite_daily_logs(config, &date_str, &kept)?;
+            purge_daily_backups(config, &date_str, &kept)?;
 
-    file.write_all(md.as_bytes())?;
+            let markdown: String = kept.iter().map(format_activity_log_markdown).collect();
+            fs::write(get_daily_markdown_path(config, &date_str)?, markdown)?;
+
+            for log in &removed {
+                let files = log.screenshot_path.iter().chain(log.thumbnail_path.iter()).chain(log.clip_path.iter());
+                for path in files {
+                    let path = Path::new(path);
+                    if fs::remove_file(path).is_ok() || fs::remove_file(compress::gz_path(path)).is_ok() {
+                        deleted.screenshots += 1;
+                    }
+                }
+            }
+            deleted.entries += removed.len();
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// 日志目录中存在每日日志文件（含压缩、备份等变体）的日期
+fn daily_log_dates(config: &Config) -> BTreeSet<chrono::NaiveDate> {
+    let Ok(entries) = fs::read_dir(config.get_logs_dir()) else {
+        return BTreeSet::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let (date, _) = name.to_str()?.split_once('.')?;
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+        })
+        .collect()
+}
+
+/// 删除记录后清理该日的备份（.bak、.bak.gz、.corrupt）：其内容已合并进重写后的日志，
+/// 保留会让已删除的记录留在磁盘上，恢复损坏日志时还可能被读回；已生成的 .compact.json 按剩余记录重新压缩
+fn purge_daily_backups(
+    config: &Config,
+    date: &str,
+    kept: &[ActivityLog],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let json_path = config.get_daily_log_path(date);
+    let jsonl_path = config.get_daily_jsonl_path(date);
+    let compact_path = json_path.with_extension("compact.json");
+    if compact_path.exists() {
+        if kept.is_empty() {
+            fs::remove_file(&compact_path)?;
+        } else {
+            let mut kept = kept.to_vec();
+            kept.sort_by_key(|log| log.timestamp);
+            write_daily_logs(&compact_path, &compact_logs(&kept))?;
+        }
+    }
+
+    let backups = [
+        json_path.with_extension("json.bak"),
+        json_path.with_extension("json.bak.gz"),
+        json_path.with_extension("json.corrupt"),
+        jsonl_path.with_extension("jsonl.bak"),
+        jsonl_path.with_extension("jsonl.bak.gz"),
+        compact_path.with_extension("json.bak"),
+    ];
+    for path in backups {
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("无法删除日志备份 {}: {}", path.display(), e).into()),
+        }
+    }
     Ok(())
 }
 
+/// 单日压缩结果
+#[derive(Debug, Serialize)]
+pub struct CompactedDay {
+    pub date: String,
+    /// 压缩前条数
+    pub before: usize,
+    /// 压缩后条数
+    pub after: usize,
+    /// 写入的文件
+    pub path: PathBuf,
+}
+
+/// 压缩最近 days 天的日志：默认写入同目录的 YYYY-MM-DD.compact.json，原日志不变；
+/// inplace 时替换原日志（上一版本保留为 .bak）并重写 Markdown 日志，跳过今天以免与运行中的服务同时写入
+pub fn compact_daily_logs(
+    config: &Config,
+    days: u32,
+    inplace: bool,
+) -> Result<Vec<CompactedDay>, Box<dyn Error + Send + Sync>> {
+    let today = config.timezone.today();
+    let mut results = Vec::new();
+
+    for offset in 0..days {
+        if inplace && offset == 0 {
+            continue;
+        }
+        let date = (today - chrono::Duration::days(offset as i64)).format("%Y-%m-%d").to_string();
+        let mut logs = load_daily_activity_logs(config, &date)?;
+        if logs.is_empty() {
+            continue;
+        }
+        logs.sort_by_key(|log| log.timestamp);
+        let compacted = compact_logs(&logs);
+
+        let path = if inplace {
+            let path = rewrite_daily_logs(config, &date, &compacted)?;
+            let markdown: String = compacted.iter().map(format_activity_log_markdown).collect();
+            fs::write(get_daily_markdown_path(config, &date)?, markdown)?;
+            path
+        } else {
+            let compact_path = config.get_daily_log_path(&date).with_extension("compact.json");
+            write_daily_logs(&compact_path, &compacted)?;
+            compact_path
+        };
+
+        results.push(CompactedDay {
+            date,
+            before: logs.len(),
+            after: compacted.len(),
+            path,
+        });
+    }
+
+    Ok(results)
+}
+
+/// 旧版日志迁移结果
+#[derive(Debug, Default, Serialize)]
+pub struct MigratedLogs {
+    /// 写入按日日志的条数
+    pub migrated: usize,
+    /// 与已有日志（或旧文件中更早的记录）时间戳相同而跳过的条数
+    pub skipped: usize,
+    /// 无法解析的条数
+    pub invalid: usize,
+}
+
+/// 迁移旧版单文件日志（--migrate-logs）：读取 JSON 数组或 JSONL 格式的 activity_log.json，
+/// 按 --timezone 拆分到各日期后写入当前的按日日志（含 Markdown），时间戳已存在的记录跳过
+pub fn migrate_legacy_logs(config: &Config, path: &Path) -> Result<MigratedLogs, Box<dyn Error + Send + Sync>> {
+    let content = if path.extension().is_some_and(|ext| ext == "gz") {
+        String::from_utf8_lossy(&compress::read_gz(path)?).into_owned()
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("无法读取旧日志 {}: {}", path.display(), e))?
+    };
+
+    let mut result = MigratedLogs::default();
+    let entries: Vec<serde_json::Value> = if content.trim_start().starts_with('[') {
+        serde_json::from_str(&content).map_err(|e| format!("旧日志不是有效的 JSON 数组 {}: {}", path.display(), e))?
+    } else {
+        let mut entries = Vec::new();
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => result.invalid += 1,
+            }
+        }
+        entries
+    };
+
+    let mut logs = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match parse_legacy_entry(entry) {
+            Some(log) => logs.push(log),
+            None => result.invalid += 1,
+        }
+    }
+    logs.sort_by_key(|log| log.timestamp);
+
+    // 按日期分组，每天只读取、写入一次
+    let mut by_date: BTreeMap<String, Vec<ActivityLog>> = BTreeMap::new();
+    for log in logs {
+        let date = config.timezone.date_of(&log.timestamp).format("%Y-%m-%d").to_string();
+        by_date.entry(date).or_default().push(log);
+    }
+
+    let _guard = SAVE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (date, logs) in by_date {
+        let mut merged = load_daily_activity_logs(config, &date)?;
+        let mut timestamps: HashSet<DateTime<Local>> = merged.iter().map(|log| log.timestamp).collect();
+        let mut markdown = String::new();
+        for log in logs {
+            if !timestamps.insert(log.timestamp) {
+                result.skipped += 1;
+                continue;
+            }
+            markdown.push_str(&format_activity_log_markdown(&log));
+            merged.push(log);
+            result.migrated += 1;
+        }
+        if markdown.is_empty() {
+            continue;
+        }
+
+        merged.sort_by_key(|log| log.timestamp);
+        fs::create_dir_all(config.get_logs_dir())?;
+        rewrite_daily_logs(config, &date, &merged)?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(get_daily_markdown_path(config, &date)?)?
+            .write_all(markdown.as_bytes())?;
+    }
+
+    Ok(result)
+}
+
+/// 解析旧版日志条目：时间戳与描述必须存在，结构与当前版本不兼容的 context 会被丢弃
+fn parse_legacy_entry(mut entry: serde_json::Value) -> Option<ActivityLog> {
+    if let Ok(log) = serde_json::from_value::<ActivityLog>(entry.clone()) {
+        return Some(log);
+    }
+    entry.as_object_mut()?.remove("context");
+    serde_json::from_value(entry).ok()
+}
+
+/// 合并连续的同【类型】同【软件】记录（需按时间排序），无法解析分类的记录保持原样；
+/// 已压缩过的记录可再次参与合并
+pub fn compact_logs(logs: &[ActivityLog]) -> Vec<ActivityLog> {
+    let mut compacted = Vec::new();
+    let mut i = 0;
+
+    while i < logs.len() {
+        let key = compact_key(&logs[i]);
+        let mut j = i + 1;
+        if key.is_some() {
+            while j < logs.len()
+                && compact_key(&logs[j]) == key
+                && (logs[j].timestamp - logs[j - 1].end_timestamp()).num_seconds() <= COMPACT_MAX_GAP_SECS
+            {
+                j += 1;
+            }
+        }
+        compacted.push(merge_span(&logs[i..j]));
+        i = j;
+    }
+
+    compacted
+}
+
+/// 合并键：【类型】与【软件】都能解析时才参与合并
+fn compact_key(log: &ActivityLog) -> Option<(String, String)> {
+    let classification = log
+        .classification
+        .clone()
+        .or_else(|| ActivityClassification::parse(&log.description))?;
+    Some((classification.category?, classification.software?))
+}
+
+/// 把一段记录合并为一条：以中间一条作为代表描述，时间取整段起止，token 与费用累加
+fn merge_span(span: &[ActivityLog]) -> ActivityLog {
+    if span.len() == 1 {
+        return span[0].clone();
+    }
+
+    let mut merged = span[span.len() / 2].clone();
+    merged.timestamp = span[0].timestamp;
+    merged.span_end = span.iter().map(|log| log.end_timestamp()).max();
+    merged.merged_count = Some(span.iter().map(|log| log.entry_count()).sum());
+
+    let usages: Vec<&TokenUsage> = span.iter().filter_map(|log| log.token_usage.as_ref()).collect();
+    merged.token_usage = (!usages.is_empty()).then(|| {
+        let sum = |field: fn(&TokenUsage) -> Option<u32>| {
+            let values: Vec<u32> = usages.iter().filter_map(|usage| field(usage)).collect();
+            (!values.is_empty()).then(|| values.iter().sum())
+        };
+        TokenUsage {
+            prompt_tokens: sum(|usage| usage.prompt_tokens),
+            completion_tokens: sum(|usage| usage.completion_tokens),
+            total_tokens: sum(|usage| usage.total_tokens),
+        }
+    });
+    let costs: Vec<f64> = span.iter().filter_map(|log| log.estimated_cost).collect();
+    merged.estimated_cost = (!costs.is_empty()).then(|| costs.iter().sum());
 
+    merged
+}
 
-/// 读取指定日期的活动日志
+/// 读取指定日期的活动日志：JSON 数组与 JSONL 两种格式的同日文件都会读取（切换格式的当天两者并存），按时间合并
 pub fn load_daily_activity_logs(config: &Config, date: &str) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
-    let daily_log_path = config.get_daily_log_path(date);
-    
-    if !daily_log_path.exists() {
-        return Ok(Vec::new());
+    let mut logs = load_daily_json(&config.get_daily_log_path(date))?;
+
+    let jsonl_path = config.get_daily_jsonl_path(date);
+    if compress::exists(&jsonl_path) {
+        // JSONL 按完成顺序追加，并发分析时可能乱序；与 JSON 同日文件合并时也需排序
+        logs.extend(load_daily_jsonl(&jsonl_path)?);
+        logs.sort_by_key(|log| log.timestamp);
     }
-    
-    let file = File::open(daily_log_path)?;
-    let logs: Vec<ActivityLog> = serde_json::from_reader(file)?;
     Ok(logs)
 }
 
+/// 读取 JSON 数组格式的日志文件（损坏时尽量恢复），文件不存在时为空；
+/// 同时读取 --compress 压缩后的 .json.gz（压缩后又写入的记录在明文文件中），按时间合并
+fn load_daily_json(path: &Path) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
+    let gz = compress::gz_path(path);
+    let mut logs = if gz.exists() {
+        let content = String::from_utf8_lossy(&compress::read_gz(&gz)?).into_owned();
+        serde_json::from_str::<Vec<ActivityLog>>(&content).unwrap_or_else(|e| {
+            eprintln!("⚠️ 压缩日志损坏: {} ({})，只读取可解析的部分", gz.display(), e);
+            salvage_log_entries(&content)
+        })
+    } else {
+        Vec::new()
+    };
+    if !path.exists() {
+        return Ok(logs);
+    }
+
+    let content = fs::read_to_string(path)?;
+    let plain = match serde_json::from_str::<Vec<ActivityLog>>(&content) {
+        Ok(plain) => plain,
+        Err(e) => recover_daily_logs(path, &content, &e)?,
+    };
+    if logs.is_empty() {
+        return Ok(plain);
+    }
+    logs.extend(plain);
+    logs.sort_by_key(|log| log.timestamp);
+    Ok(logs)
+}
+
+/// 逐行读取 JSONL 日志（含压缩后的 .jsonl.gz），跳过无法解析的行（如写入中断留下的截断行）
+fn load_daily_jsonl(path: &Path) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
+    let gz = compress::gz_path(path);
+    let mut content = if gz.exists() { compress::read_gz(&gz)? } else { Vec::new() };
+    if path.exists() {
+        content.push(b'\n');
+        content.extend(fs::read(path)?);
+    }
+    let mut logs = Vec::new();
+    let mut skipped = 0;
+    for line in String::from_utf8_lossy(&content).lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ActivityLog>(line) {
+            Ok(log) => logs.push(log),
+            Err(_) => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        verbose_println!("⚠️ {} 中有 {} 行无法解析，已跳过", path.display(), skipped);
+    }
+    Ok(logs)
+}
+
+/// 向 JSONL 日志追加一条：整行一次 write_all，不重写已有内容；
+/// 上次写入中断使文件未以换行结尾时先补换行，避免新记录与截断行粘连
+fn append_daily_jsonl(path: &Path, log: &ActivityLog) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut line = serde_json::to_string(log)?;
+    line.push('\n');
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    if file.metadata()?.len() > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.insert(0, '\n');
+        }
+    }
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// 重写某天的日志（删除记录、原地压缩）：按当前 --activity-log-format 写入，
+/// 另一种格式的同日文件内容已合并在 logs 中，移为 .bak；返回写入的文件
+fn rewrite_daily_logs(
+    config: &Config,
+    date: &str,
+    logs: &[ActivityLog],
+) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let json_path = config.get_daily_log_path(date);
+    let jsonl_path = config.get_daily_jsonl_path(date);
+    let (path, backup, other, other_backup) = if config.jsonl_activity_logs() {
+        write_daily_jsonl(&jsonl_path, logs)?;
+        (jsonl_path, "jsonl.bak", json_path, "json.bak")
+    } else {
+        write_daily_logs(&json_path, logs)?;
+        (json_path, "json.bak", jsonl_path, "jsonl.bak")
+    };
+    retire_gz(&path, backup)?;
+    if other.exists() {
+        fs::rename(&other, other.with_extension(other_backup))?;
+    }
+    retire_gz(&other, other_backup)?;
+    Ok(path)
+}
+
+/// 明文日志已包含压缩文件的全部内容并重写后，把 .gz 移为备份（如 .json.bak.gz），避免重复读取
+fn retire_gz(path: &Path, backup: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let gz = compress::gz_path(path);
+    if gz.exists() {
+        fs::rename(&gz, path.with_extension(format!("{}.gz", backup)))?;
+    }
+    Ok(())
+}
+
+/// 压缩已结束日期的每日日志（--compress）：YYYY-MM-DD.json / .jsonl 压缩为同名 .gz 并删除明文文件；
+/// 压缩后又有写入（如更改 --timezone 或迟到的分析结果）时与已压缩内容合并后重新压缩。
+/// 返回 (压缩的文件数, 节省的字节数)
+pub fn compress_daily_logs(config: &Config) -> Result<(u64, u64), Box<dyn Error + Send + Sync>> {
+    let _guard = SAVE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let entries = match fs::read_dir(config.get_logs_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return Ok((0, 0)),
+    };
+    let today = config.timezone.today();
+    let mut files = 0u64;
+    let mut saved = 0u64;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some((date, extension)) = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.split_once('.')) else {
+            continue;
+        };
+        let jsonl = match extension {
+            "json" => false,
+            "jsonl" => true,
+            _ => continue,
+        };
+        match chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(day) if day < today => {}
+            _ => continue,
+        }
+
+        let gz = compress::gz_path(&path);
+        let result = if gz.exists() {
+            let before = fs::metadata(&path)?.len() + fs::metadata(&gz)?.len();
+            let content = if jsonl {
+                let mut content = Vec::new();
+                for log in load_daily_jsonl(&path)? {
+                    serde_json::to_writer(&mut content, &log)?;
+                    content.push(b'\n');
+                }
+                content
+            } else {
+                serde_json::to_vec_pretty(&load_daily_json(&path)?)?
+            };
+            compress::write_gz(&gz, &content)
+                .and_then(|after| fs::remove_file(&path).map(|_| before.saturating_sub(after)))
+        } else {
+            compress::gzip_file(&path)
+        };
+
+        match result {
+            Ok(bytes) => {
+                files += 1;
+                saved += bytes;
+            }
+            Err(e) => eprintln!("⚠️ 压缩日志失败: {} ({})", path.display(), e),
+        }
+    }
+
+    Ok((files, saved))
+}
+
+/// 原子重写 JSONL 日志：先写临时文件再重命名，并保留上一版本为 .jsonl.bak
+fn write_daily_jsonl(path: &Path, logs: &[ActivityLog]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tmp_path = path.with_extension("jsonl.tmp");
+    {
+        let file = File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        for log in logs {
+            serde_json::to_writer(&mut writer, log)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+
+    if path.exists() {
+        let _ = fs::copy(path, path.with_extension("jsonl.bak"));
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// 原子写入当日日志：先写临时文件再重命名，并保留上一版本为 .bak
+fn write_daily_logs(path: &Path, logs: &[ActivityLog]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tmp_path = path.with_extension("json.tmp");
+    {
+        let file = File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, logs)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+
+    if path.exists() {
+        let _ = fs::copy(path, path.with_extension("json.bak"));
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// 日志文件解析失败时的恢复：从损坏文件中取出可解析的条目，或使用 .bak 备份，
+/// 取条目较多者；损坏文件移到 .corrupt 后写回恢复结果
+fn recover_daily_logs(
+    path: &Path,
+    content: &str,
+    parse_error: &serde_json::Error,
+) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
+    eprintln!("⚠️ 日志文件损坏: {} ({})，尝试恢复", path.display(), parse_error);
+
+    let salvaged = salvage_log_entries(content);
+    let from_backup = fs::read_to_string(path.with_extension("json.bak"))
+        .ok()
+        .and_then(|backup| serde_json::from_str::<Vec<ActivityLog>>(&backup).ok())
+        .unwrap_or_default();
+    let recovered = if from_backup.len() > salvaged.len() { from_backup } else { salvaged };
+
+    let corrupt_path = path.with_extension("json.corrupt");
+    fs::rename(path, &corrupt_path)?;
+    write_daily_logs(path, &recovered)?;
+
+    eprintln!(
+        "⚠️ 已恢复 {} 条日志，损坏文件已移至: {}",
+        recovered.len(),
+        corrupt_path.display()
+    );
+    Ok(recovered)
+}
+
+/// 逐个解析 JSON 数组中的日志对象，遇到第一处无法解析的位置即停止（常见于写入中断导致的截断）
+fn salvage_log_entries(content: &str) -> Vec<ActivityLog> {
+    let mut logs = Vec::new();
+    let mut rest = match content.trim_start().strip_prefix('[') {
+        Some(rest) => rest,
+        None => return logs,
+    };
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() || rest.starts_with(']') {
+            break;
+        }
+
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<ActivityLog>();
+        match stream.next() {
+            Some(Ok(log)) => {
+                logs.push(log);
+                rest = &rest[stream.byte_offset()..];
+            }
+            _ => break,
+        }
+    }
+
+    logs
+}
+
 /// 读取最近N天的日志
 pub fn load_recent_daily_logs(config: &Config, days: u32) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
-    use chrono::{Local, Duration};
+    use chrono::Duration;
     
     let mut all_logs = Vec::new();
-    let today = Local::now().date_naive();
+    let today = config.timezone.today();
     
     for i in 0..days {
         let date = today - Duration::days(i as i64);
@@ -152,13 +789,23 @@ pub fn load_activity_logs_since(
     config: &Config,
     since: chrono::DateTime<Local>,
 ) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
-    let today = Local::now().date_naive();
-    let date_str = today.format("%Y-%m-%d").to_string();
-    let logs = load_daily_activity_logs(config, &date_str)?;
-    Ok(logs
-        .into_iter()
-        .filter(|log| log.timestamp >= since)
-        .collect())
+    // since 可能在前一天（如跨零点的上报周期）
+    let today = config.timezone.today();
+    let mut date = config.timezone.date_of(&since).min(today);
+    let mut logs = Vec::new();
+    while date <= today {
+        logs.extend(
+            load_daily_activity_logs(config, &date.format("%Y-%m-%d").to_string())?
+                .into_iter()
+                .filter(|log| log.timestamp >= since),
+        );
+        date = match date.succ_opt() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    logs.sort_by_key(|log| log.timestamp);
+    Ok(logs)
 }
 
 /// 将活动日志格式化为 OpenClaw /hooks/agent 的 message 内容
@@ -182,16 +829,16 @@ pub fn format_logs_for_openclaw(logs: &[ActivityLog], interval_minutes: u64) ->
 }
 
 /// 获取最近N条活动日志的timestamp和description，用于AI分析的上下文
-pub fn get_recent_activity_context(config: &Config, count: usize) -> Result<String, Box<dyn Error + Send + Sync>> {
-    // 读取最近3天的日志
-    let logs = load_recent_daily_logs(config, 3)?;
+pub fn get_recent_activity_context(config: &Config, count: usize, days: u32) -> Result<String, Box<dyn Error + Send + Sync>> {
+    // 读取最近N天的日志
+    let logs = load_recent_daily_logs(config, days)?;
     
     if logs.is_empty() {
         return Ok("暂无历史活动记录".to_string());
     }
     
-    // 获取最后N条记录（最新的记录在最后）
-    let recent_logs: Vec<&ActivityLog> = logs.iter().rev().take(count).collect();
+    // 获取最后N条记录（最新的记录在最后），分析失败的占位记录不作为历史
+    let recent_logs: Vec<&ActivityLog> = logs.iter().rev().filter(|log| !log.analysis_failed).take(count).collect();
     
     let mut context = String::new();
     context.push_str("【用户最近的活动历史】\n");
@@ -206,4 +853,165 @@ pub fn get_recent_activity_context(config: &Config, count: usize) -> Result<Stri
     }
     
     Ok(context)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+
+    fn legacy_entry(time: &str, description: &str) -> serde_json::Value {
+        serde_json::json!({ "timestamp": format!("2024-03-0{}+08:00", time), "description": description })
+    }
+
+    fn activity(time: &str, description: &str, cost: f64) -> ActivityLog {
+        let mut entry = legacy_entry(time, description);
+        entry["estimated_cost"] = serde_json::json!(cost);
+        entry["token_usage"] = serde_json::json!({ "prompt_tokens": 100, "completion_tokens": 20, "total_tokens": 120 });
+        serde_json::from_value(entry).unwrap()
+    }
+
+    #[test]
+    fn test_compact_logs_merges_consecutive_same_activity() {
+        let logs = [
+            activity("1T09:00:00", "【工作】【VSCode】【写解析器】", 0.1),
+            activity("1T09:05:00", "【工作】【VSCode】【调试解析器】", 0.2),
+            activity("1T09:10:00", "【工作】【VSCode】【提交代码】", 0.3),
+        ];
+        let compacted = compact_logs(&logs);
+        assert_eq!(compacted.len(), 1);
+        let merged = &compacted[0];
+        assert_eq!(merged.timestamp, logs[0].timestamp);
+        assert_eq!(merged.span_end, Some(logs[2].timestamp));
+        assert_eq!(merged.merged_count, Some(3));
+        assert_eq!(merged.description, "【工作】【VSCode】【调试解析器】");
+        assert_eq!(merged.token_usage.as_ref().and_then(|usage| usage.total_tokens), Some(360));
+        assert!((merged.estimated_cost.unwrap() - 0.6).abs() < 1e-9);
+
+        // 已压缩的记录与后续同类记录可以再次合并
+        let mut again = compacted.clone();
+        again.push(activity("1T09:15:00", "【工作】【VSCode】【写测试】", 0.1));
+        let recompacted = compact_logs(&again);
+        assert_eq!(recompacted.len(), 1);
+        assert_eq!(recompacted[0].merged_count, Some(4));
+    }
+
+    #[test]
+    fn test_compact_logs_keeps_distinct_entries() {
+        let logs = [
+            activity("1T09:00:00", "【工作】【VSCode】【写代码】", 0.1),
+            // 软件不同
+            activity("1T09:05:00", "【工作】【Slack】【回复消息】", 0.1),
+            // 与上一条间隔超过 10 分钟
+            activity("1T09:30:00", "【工作】【Slack】【回复消息】", 0.1),
+            // 无法解析分类的记录不参与合并
+            activity("1T09:31:00", "用户正在浏览网页", 0.1),
+            activity("1T09:32:00", "用户正在浏览网页", 0.1),
+        ];
+        let compacted = compact_logs(&logs);
+        assert_eq!(compacted.len(), logs.len());
+        assert!(compacted.iter().all(|log| log.merged_count.is_none() && log.span_end.is_none()));
+    }
+
+    #[test]
+    fn test_migrate_legacy_logs_array_and_jsonl() {
+        let config = test_config("logger_migrate");
+        fs::create_dir_all(config.get_data_dir()).unwrap();
+        let entries = [
+            legacy_entry("1T09:00:00", "【工作】【VSCode】【写代码】"),
+            legacy_entry("1T10:00:00", "【学习】【Safari】【看文档】"),
+            // 同一文件内重复的记录只迁移一次
+            legacy_entry("1T10:00:00", "【学习】【Safari】【看文档】"),
+            legacy_entry("2T09:00:00", "【工作】【Slack】【回复消息】"),
+        ];
+
+        let array_path = config.get_data_dir().join("activity_log.json");
+        fs::write(&array_path, serde_json::to_string(&entries).unwrap()).unwrap();
+        let migrated = migrate_legacy_logs(&config, &array_path).unwrap();
+        assert_eq!((migrated.migrated, migrated.skipped, migrated.invalid), (3, 1, 0));
+
+        // JSONL 格式再迁移一次：已存在的时间戳全部跳过，无法解析的行单独计数
+        let jsonl_path = config.get_data_dir().join("activity_log.jsonl");
+        let mut lines: Vec<String> = entries.iter().map(|entry| entry.to_string()).collect();
+        lines.push("{not json".to_string());
+        lines.push(legacy_entry("2T11:00:00", "【娱乐】【Bilibili】【看视频】").to_string());
+        fs::write(&jsonl_path, lines.join("\n")).unwrap();
+        let migrated = migrate_legacy_logs(&config, &jsonl_path).unwrap();
+        assert_eq!((migrated.migrated, migrated.skipped, migrated.invalid), (1, 4, 1));
+
+        let day_of = |time: &str| {
+            let timestamp: DateTime<Local> = format!("2024-03-0{}+08:00", time).parse().unwrap();
+            config.timezone.date_of(&timestamp).format("%Y-%m-%d").to_string()
+        };
+        let dates: std::collections::BTreeSet<String> =
+            ["1T09:00:00", "1T10:00:00", "2T09:00:00", "2T11:00:00"].into_iter().map(day_of).collect();
+        let total: usize = dates
+            .iter()
+            .map(|date| load_daily_activity_logs(&config, date).unwrap().len())
+            .sum();
+        assert_eq!(total, 4);
+        fs::remove_dir_all(config.get_data_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_delete_range_is_bounded() {
+        let config = test_config("logger_delete_range");
+        assert!(resolve_delete_range(&config, "2025-01-01 00:00:00", Some("2025-01-31 23:59:59")).is_ok());
+        let err = resolve_delete_range(&config, "2025-01-01 00:00:00", Some("2025-03-01 00:00:00")).unwrap_err();
+        assert!(err.to_string().contains("分批删除"), "{}", err);
+        assert!(resolve_delete_range(&config, "2025-01-02 00:00:00", Some("2025-01-01 00:00:00")).is_err());
+    }
+
+    #[test]
+    fn test_delete_logs_leaves_no_copy_on_disk() {
+        let config = test_config("logger_delete");
+        let secret = "【工作】【Chrome】【查看银行账户】";
+        let mut deleted = activity("1T09:05:00", secret, 0.1);
+        // --keep-clips 保留的录屏片段随记录一起删除
+        let clip_path = config.get_screenshot_dir().join("clip.mp4");
+        fs::create_dir_all(config.get_screenshot_dir()).unwrap();
+        fs::write(&clip_path, b"mp4").unwrap();
+        deleted.clip_path = Some(clip_path.to_string_lossy().into_owned());
+        for log in [
+            activity("1T09:00:00", "【工作】【VSCode】【写代码】", 0.1),
+            deleted.clone(),
+            activity("1T09:10:00", "【工作】【VSCode】【写测试】", 0.1),
+        ] {
+            store_activity_log(&log, &config).unwrap();
+        }
+
+        // 各种备份里都留有待删除的记录：上次写入的 .bak、压缩后退役的 .bak.gz、另一种格式的 .jsonl.bak、.compact.json
+        let date = config.timezone.date_of(&deleted.timestamp).format("%Y-%m-%d").to_string();
+        let json_path = config.get_daily_log_path(&date);
+        let content = fs::read(&json_path).unwrap();
+        compress::write_gz(&json_path.with_extension("json.bak.gz"), &content).unwrap();
+        let mut line = serde_json::to_vec(&deleted).unwrap();
+        line.push(b'\n');
+        fs::write(config.get_daily_jsonl_path(&date).with_extension("jsonl.bak"), &line).unwrap();
+        compress::write_gz(&config.get_daily_jsonl_path(&date).with_extension("jsonl.bak.gz"), &line).unwrap();
+        write_daily_logs(&json_path.with_extension("compact.json"), std::slice::from_ref(&deleted)).unwrap();
+        assert!(json_path.with_extension("json.bak").exists());
+
+        let result = delete_activity_logs(&config, deleted.timestamp, deleted.timestamp).unwrap();
+        assert_eq!(result.entries, 1);
+        assert_eq!(result.screenshots, 1);
+        assert!(!clip_path.exists());
+        assert_eq!(load_daily_activity_logs(&config, &date).unwrap().len(), 2);
+
+        for dir in [config.get_logs_dir(), config.get_data_dir().join("logs_md")] {
+            for entry in fs::read_dir(&dir).unwrap().flatten() {
+                let path = entry.path();
+                let content = if path.extension().is_some_and(|ext| ext == "gz") {
+                    compress::read_gz(&path).unwrap()
+                } else {
+                    fs::read(&path).unwrap()
+                };
+                assert!(
+                    !String::from_utf8_lossy(&content).contains(secret),
+                    "{} 仍包含已删除的记录",
+                    path.display()
+                );
+            }
+        }
+        fs::remove_dir_all(config.get_data_dir()).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 mod screenshot;
 mod siliconflow;
+mod ollama; // Ollama 原生接口
+mod backend; // 按 --api-backend 分发模型请求
 mod logger;
 mod models;
 mod capture;
@@ -14,35 +16,179 @@ mod window_tracker; // 窗口追踪模块
 mod openclaw; // OpenClaw webhook 上报
 mod clipboard; // 剪贴板监听
 mod input_tracker; // 输入追踪
+mod hotkey; // 暂停快捷键
+mod report; // 活动报表
+mod thumbnails; // 截图缩略图索引
+mod events; // 截屏进度事件
+mod metrics; // Prometheus 指标
+mod doctor; // 环境自检
+mod autostart; // 登录自启安装
+mod verbosity; // 输出详细程度（-v / -q）
+mod timezone; // 日志日期边界时区
+mod schedule; // 工作时间表
+mod narrate; // 活动叙述（MCP narrate）
+mod compress; // 日志与截图的 gzip 压缩
+mod consent; // 首次运行的使用条款确认
+mod discovery; // MCP 服务的 mDNS 广播
+mod app_aliases; // 应用名称规范化
+mod error; // 核心流程的结构化错误类型
+mod frame_buffer; // 最近上传截图的内存缓冲
+mod lang; // 提示词与上下文文本的语言
+mod offline_queue; // 网络不可用时待补分析的截图队列
+mod response_cache; // 按截图与提示词缓存模型回复
+mod analysis_slots; // 跨进程限制同时进行的分析
+mod video; // 录屏片段与代表帧（--capture-mode video）
+mod disk_space; // 数据目录可用空间检查（--min-free-mb）
+mod search; // 按关键词检索活动日志（search_logs / --search-logs）
+mod media; // 前台媒体播放检测（--media-mode）
+mod selftest; // 合成截图端到端自检（--selftest）
+mod diagnostics; // 程序自身的轮转诊断日志（--service-log-level）
+#[cfg(test)]
+mod test_support; // 测试共用的配置夹具
 
 use std::error::Error;
+use std::sync::Arc;
 
 use mcp_service::OpenRecallService;
 use standalone_service::{StandaloneService, ServiceController};
 use rmcp::transport::sse_server::{SseServer, SseServerConfig};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-    println!("🚀 OpenRecall 启动中...\n");
-    
     let config = config::Config::from_args();
+    verbosity::set(verbosity::Verbosity::from_config(&config));
+    // --once 的结果本身就是 JSON：与 --json 相同，stdout 只留给结果，提示文字与 tracing 输出改写到 stderr
+    verbosity::set_json_output(config.json_output || config.once);
+    window_tracker::configure_window_tracker(window_tracker::WindowTrackerOptions::from_config(&config));
+    app_aliases::configure(app_aliases::AppAliases::load(config.app_aliases.as_deref())?);
+    frame_buffer::configure(config.keep_last_frames);
+    // 代理地址写错时所有分析都会连接失败并反复重试，启动时直接报错
+    config.api_proxy()?;
+
+    if config.store_api_key {
+        return store_api_key(&config);
+    }
+    // 报表输出到 stdout，放在启动提示之前避免混入其他输出
+    if config.report {
+        return report::run_report(&config);
+    }
+    if config.export_switches {
+        return export_switches(&config).await;
+    }
+    if let Some(query) = &config.search_logs {
+        return search_logs(&config, query);
+    }
+    if config.contact_sheet {
+        return thumbnails::run_contact_sheet(&config);
+    }
+
+    verbosity::info_println!("🚀 OpenRecall 启动中...\n");
+
+    if config.install_service {
+        // 自启进程运行在非交互环境，安装时先完成确认
+        consent::ensure_consent(&config, true)?;
+        autostart::install_service(&config)?;
+        return print_command_result("install-service", serde_json::json!({}));
+    }
+
+    if config.uninstall_service {
+        autostart::uninstall_service(&config)?;
+        return print_command_result("uninstall-service", serde_json::json!({}));
+    }
+
+    // 自检未通过时以错误返回，进程退出码为 1
+    if config.doctor {
+        if !doctor::run_doctor(&config).await {
+            return Err("环境自检未通过".into());
+        }
+        return Ok(());
+    }
+
+    if config.selftest {
+        if !selftest::run_selftest(&config).await {
+            return Err("端到端自检未通过".into());
+        }
+        return Ok(());
+    }
     
     // 检查是否为测试prompt模式
     if let Some(_) = &config.test_prompt {
-        println!("🧪 启动测试prompt模式");
+        verbosity::info_println!("🧪 启动测试prompt模式");
         return test_prompt::run_test_prompt(config).await;
     }
     
+    if let Some(start) = &config.delete_logs {
+        let (start, end) = logger::resolve_delete_range(&config, start, config.delete_logs_end.as_deref())?;
+        verbosity::text_println!(
+            "🗑️ 删除 {} ~ {} 的活动日志...",
+            start.format("%Y-%m-%d %H:%M:%S"),
+            end.format("%Y-%m-%d %H:%M:%S")
+        );
+        let deleted = logger::delete_activity_logs(&config, start, end)?;
+        verbosity::text_println!("✅ 已删除 {} 条日志，{} 个截图文件", deleted.entries, deleted.screenshots);
+        return print_command_result(
+            "delete-logs",
+            serde_json::json!({
+                "start": start.to_rfc3339(),
+                "end": end.to_rfc3339(),
+                "entries": deleted.entries,
+                "screenshots": deleted.screenshots,
+            }),
+        );
+    }
+
+    if config.compact {
+        verbosity::text_println!("🗜️ 压缩最近 {} 天的活动日志...", config.compact_days);
+        if config.compact_inplace {
+            verbosity::text_println!("ℹ️ 原地压缩跳过今天（独立服务可能仍在写入）");
+        }
+        let days = logger::compact_daily_logs(&config, config.compact_days, config.compact_inplace)?;
+        if days.is_empty() {
+            verbosity::text_println!("ℹ️ 没有需要压缩的日志");
+        }
+        for day in &days {
+            verbosity::text_println!("  - {}: {} 条 → {} 条，已写入 {}", day.date, day.before, day.after, day.path.display());
+        }
+        return print_command_result("compact", serde_json::json!({ "days": days }));
+    }
+
+    if let Some(old_path) = &config.migrate_logs {
+        verbosity::text_println!("📦 迁移旧版日志: {} → {}", old_path.display(), config.get_logs_dir().display());
+        let migrated = logger::migrate_legacy_logs(&config, old_path)?;
+        verbosity::text_println!(
+            "✅ 已迁移 {} 条，跳过重复 {} 条，无法解析 {} 条",
+            migrated.migrated, migrated.skipped, migrated.invalid
+        );
+        return print_command_result("migrate-logs", &migrated);
+    }
+
+    // 以下模式都会请求模型接口（MCP 模式会自动启动独立服务），分发前统一检查 API 密钥
+    if let Err(e) = config.ensure_api_key() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
+
+    if let Some(image_path) = &config.analyze_image {
+        verbosity::info_println!("🖼️ 分析图片文件: {}", image_path.display());
+        return run_analyze_image(&config, image_path).await;
+    }
+
+    if config.once {
+        // 单次模式：不启动 socket 服务，也不读写服务状态文件
+        verbosity::info_println!("📸 启动单次截屏模式");
+        return run_once(config).await;
+    }
+
     if config.mcp {
         // MCP 服务器模式
-        println!("🔗 启动 MCP 服务器模式");
-        return run_mcp_server(config).await;
+        verbosity::info_println!("🔗 启动 MCP 服务器模式");
+        return hotkey::drive_main_run_loop(run_mcp_server(config)).await;
     }
     
     // 默认启动独立截屏服务模式
-    println!("🚀 启动独立截屏服务模式");
-    run_standalone_service(config).await?;
+    verbosity::info_println!("🚀 启动独立截屏服务模式");
+    hotkey::drive_main_run_loop(run_standalone_service(config)).await?;
     
     Ok(())
 }
@@ -56,33 +202,165 @@ mod tests {
     async fn test_windows_compatibility() {
         // 创建一个测试配置，避免解析命令行参数
         let config = config::Config {
-            api_key: "test_key".to_string(),
+            api_key: Some("test_key".to_string()),
+            api_key_from_keyring: None,
+            store_api_key: false,
             api_url: "http://127.0.0.1:1234/v1/chat/completions".to_string(),
+            api_backend: crate::config::ApiBackend::Siliconflow,
             model: "default".to_string(),
+            fallback_model: None,
+            shadow_backend: None,
+            shadow_api_url: None,
+            shadow_model: None,
+            shadow_api_key: None,
             prompt: "测试提示".to_string(),
+            lang: crate::lang::Lang::Zh,
+            max_tokens: None,
+            temperature: None,
+            price_per_1k_prompt: None,
+            price_per_1k_completion: None,
+            image_detail: None,
             interval: 60,
+            interval_jitter_secs: 0,
+            interval_jitter_seed: None,
+            interval_overrides: Vec::new(),
+            capture_on_switch: false,
+            switch_only: false,
+            switch_min_interval_secs: 10,
+            post_switch_delay_ms: 0,
             start_capture_on_launch: false,
+            accept_terms: false,
             data_dir: None,
+            profile: None,
+            screenshot_dir: None,
+            screenshot_name_template: config::DEFAULT_SCREENSHOT_NAME_TEMPLATE.to_string(),
+            logs_dir: None,
             installed_apps_enabled: true,
             installed_apps_refresh_minutes: 30,
             installed_apps_max_items: 300,
             installed_apps_include_user_dir: true,
+            no_process_info: false,
+            no_window_info: false,
             input_context_enabled: false,
             input_context_window_seconds: 60,
             input_context_max_keystrokes: 120,
             input_context_include_raw_keys: true,
+            include_clipboard: false,
+            clipboard_context_max_chars: 500,
+            window_cache_ms: 500,
+            window_query_timeout_ms: 2000,
+            switch_history_size: 100,
+            since_last_capture_apps: 5,
+            session_history_size: 50,
+            min_session_ms: 0,
+            sleep_gap_secs: 600,
+            app_aliases: None,
+            focus_gap_minutes: 5,
+            focus_min_minutes: 10,
             state_path: None,
             image_target_width: 1440,
+            image_max_pixels: 0,
+            max_upload_bytes: 0,
+            capture_region: "full".to_string(),
+            capture_mode: "screenshot".to_string(),
+            clip_secs: 10,
+            clip_frames: 4,
+            keep_clips: false,
+            ffmpeg_path: "ffmpeg".to_string(),
+            media_mode: "off".to_string(),
+            media_apps: Vec::new(),
+            media_min_minutes: 5,
+            media_sample_minutes: 10,
+            thumbnails: false,
+            thumbnail_width: 320,
+            display_index: None,
+            display_name: None,
+            capture_window_title: None,
+            dedup_threshold: 0.0,
+            analysis_sample_rate: 1.0,
+            keep_unsampled_screenshots: false,
+            offline_queue_size: 100,
+            offline_queue_max_age_hours: 24,
+            offline_retry_interval_secs: 60,
+            redact_keywords: Vec::new(),
+            history_count: 5,
+            batch_size: 1,
+            max_images_per_request: 0,
+            analysis_workers: 0,
+            analysis_queue_size: 8,
+            queue_policy: "drop-oldest".to_string(),
+            history_days: 3,
+            context_format: "text".to_string(),
             image_grayscale: true,
             no_image_grayscale: false,
             mcp: false,
+            once: false,
+            doctor: false,
+            selftest: false,
+            install_service: false,
+            uninstall_service: false,
+            analyze_image: None,
+            analyze_context: None,
+            report: false,
+            report_period: "day".to_string(),
+            report_out: None,
+            contact_sheet: false,
+            contact_sheet_date: None,
+            contact_sheet_format: "html".to_string(),
+            contact_sheet_out: None,
+            delete_logs: None,
+            delete_logs_end: None,
+            compact: false,
+            compact_days: 7,
+            compact_inplace: false,
+            migrate_logs: None,
+            export_switches: false,
+            switches_start: None,
+            switches_end: None,
+            switches_limit: None,
+            search_logs: None,
+            search_app: None,
+            search_days: 30,
+            search_limit: 50,
             mcp_port: 6672,
+            mcp_host: std::net::Ipv4Addr::LOCALHOST.into(),
+            mcp_allow_remote: false,
+            advertise: false,
             test_prompt: None,
             test_log_path: PathBuf::from("test_log.json"),
+            test_diff: false,
+            test_diff_out: None,
+            resume: false,
             socket_path: None,
             control_port: 5830,
+            control_token: None,
+            pause_hotkey: None,
+            schedule: None,
             keep_screenshots: false,
+            keep_last_frames: 0,
+            screenshot_retention_days: 0,
+            retention_delete_logs: false,
+            min_free_mb: 0,
+            low_disk_cleanup: false,
+            keep_reasoning: false,
+            compress: false,
+            compress_screenshots_after_days: 0,
+            no_analysis: false,
+            log_format: "text".to_string(),
+            service_log_level: "info".to_string(),
+            service_log_max_mb: 10,
+            activity_log_format: "json".to_string(),
+            timezone: timezone::LogTimezone::Local,
+            verbose: false,
+            quiet: false,
+            json_output: false,
             api_timeout: 120,
+            api_connect_timeout: 10,
+            proxy: None,
+            api_min_interval_ms: 0,
+            max_concurrent_analyses: None,
+            cache_dir: None,
+            cache_ttl_hours: 24,
             openclaw_url: None,
             openclaw_token: None,
             openclaw_report_interval_minutes: 30,
@@ -116,18 +394,29 @@ mod tests {
 }
 
 async fn run_mcp_server(config: config::Config) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let bind_address = format!("127.0.0.1:{}", config.mcp_port);
+    let bind_address = std::net::SocketAddr::new(config.mcp_host, config.mcp_port);
+
+    init_tracing(&config);
 
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_from_default_env()
-            .unwrap_or_else(|_| "info".to_string().into()))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    if let Err(e) = config.ensure_mcp_host() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
+    let remote_access = !config.mcp_host.is_loopback();
+    if remote_access {
+        eprintln!(
+            "⚠️ MCP 服务监听 {}（--mcp-allow-remote）：接口不鉴权，同一网络中的设备都能读取活动日志并控制截屏",
+            bind_address
+        );
+    }
 
-    println!("🌐 启动 MCP SSE 服务器，地址: {}", bind_address);
+    verbosity::info_println!("🌐 启动 MCP SSE 服务器，地址: {}", bind_address);
 
-    // 确保必要的目录存在
-    tokio::fs::create_dir_all(&config.get_screenshot_dir()).await?;
+    // 确保必要的目录存在且可写
+    if let Err(e) = config.ensure_writable_dirs() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
     
     // 检查独立服务是否已启动，如果没有则自动启动并确保运行
     let service_controller = ServiceController::new(&config);
@@ -142,7 +431,7 @@ async fn run_mcp_server(config: config::Config) -> Result<(), Box<dyn Error + Se
                 process_service_status_response(response, &service_controller).await;
         }
         Err(_) => {
-            println!("🚀 独立截屏服务未运行，正在自动启动...");
+            verbosity::info_println!("🚀 独立截屏服务未运行，正在自动启动...");
             let config_clone = config.clone();
             tokio::spawn(async move {
                 if let Err(e) = start_standalone_service_background(config_clone).await {
@@ -174,13 +463,13 @@ async fn run_mcp_server(config: config::Config) -> Result<(), Box<dyn Error + Se
     }
 
     if !capture_running {
-        println!(
+        verbosity::text_println!(
             "⚠️ 独立截屏服务未能自动启动，截屏功能暂不可用（可使用 monitor.start 手动启动）"
         );
     }
 
     let server_config = SseServerConfig {
-        bind: bind_address.parse()?,
+        bind: bind_address,
         sse_path: "/sse".to_string(),
         post_path: "/message".to_string(),
         ct: tokio_util::sync::CancellationToken::new(),
@@ -189,11 +478,17 @@ async fn run_mcp_server(config: config::Config) -> Result<(), Box<dyn Error + Se
 
     let (sse_server, router) = SseServer::new(server_config);
     
-    // 添加 CORS 中间件
-    use tower_http::cors::{Any, CorsLayer};
+    // 添加 CORS 中间件：只监听本机时允许任意来源；对局域网开放时只允许本机页面跨域访问，
+    // 避免局域网内其他网页借浏览器调用接口
+    use tower_http::cors::{AllowOrigin, Any, CorsLayer};
     use axum::http::HeaderName;
+    let allow_origin = if remote_access {
+        AllowOrigin::predicate(|origin, _| origin.to_str().is_ok_and(config::is_loopback_url))
+    } else {
+        AllowOrigin::any()
+    };
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(allow_origin)
         .allow_methods(Any)
         .allow_headers(vec![
             HeaderName::from_static("content-type"),
@@ -201,6 +496,32 @@ async fn run_mcp_server(config: config::Config) -> Result<(), Box<dyn Error + Se
         ])
         .allow_credentials(false);
     
+    // 健康检查：供 Prometheus/Kubernetes 等探针使用
+    let health_controller = Arc::new(ServiceController::new(&config));
+    let server_started_at = chrono::Local::now();
+    let router = router.route(
+        "/health",
+        axum::routing::get(move || health_check(health_controller.clone(), server_started_at)),
+    );
+
+    // 新日志推送：独立 SSE 流，连接时可按 ?backfill=N 补发最近的日志
+    let logs_controller = Arc::new(ServiceController::new(&config));
+    let logs_config = Arc::new(config.clone());
+    let router = router.route(
+        "/logs/stream",
+        axum::routing::get(move |axum::extract::Query(query): axum::extract::Query<LogStreamQuery>| {
+            log_stream_sse(logs_config.clone(), logs_controller.clone(), query)
+        }),
+    );
+
+    // Prometheus 指标：抓取时才向独立服务查询，短时间内重复抓取复用缓存
+    let metrics_controller = Arc::new(ServiceController::new(&config));
+    let metrics_cache: MetricsCache = Arc::new(tokio::sync::Mutex::new(None));
+    let router = router.route(
+        "/metrics",
+        axum::routing::get(move || prometheus_metrics(metrics_controller.clone(), metrics_cache.clone())),
+    );
+
     let router_with_cors = router.layer(cors);
     
     let listener = tokio::net::TcpListener::bind(sse_server.config.bind).await?;
@@ -216,51 +537,319 @@ async fn run_mcp_server(config: config::Config) -> Result<(), Box<dyn Error + Se
         }
     });
 
+    // 局域网服务发现：退出时 drop 注销
+    let advertisement = if config.advertise {
+        match discovery::advertise(sse_server.config.bind, &sse_server.config.sse_path, &sse_server.config.post_path) {
+            Ok(advertisement) => {
+                verbosity::info_println!("📡 已通过 mDNS 广播: {}", advertisement.fullname());
+                Some(advertisement)
+            }
+            Err(e) => {
+                eprintln!("⚠️ mDNS 广播失败: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    print_startup_info(
+        &config,
+        "mcp",
+        serde_json::json!({
+            "address": bind_address.to_string(),
+            "sse_path": sse_server.config.sse_path,
+            "post_path": sse_server.config.post_path,
+            "capture_running": capture_running,
+        }),
+    )?;
+
     let cfg = config.clone();
     let cancel_token = sse_server.with_service(move || OpenRecallService::new(cfg.clone()));
 
-    println!("✅ MCP 服务器启动成功！ SSE: /sse, POST: /message");
-    println!("🌐 CORS 已启用，支持跨域访问");
-    println!("按 Ctrl+C 停止服务器...");
+    verbosity::info_println!("✅ MCP 服务器启动成功！ SSE: /sse, POST: /message, 健康检查: GET /health, 指标: GET /metrics");
+    if remote_access {
+        verbosity::info_println!("🌐 CORS 已启用，仅允许本机页面跨域访问");
+    } else {
+        verbosity::info_println!("🌐 CORS 已启用，支持跨域访问");
+    }
+    verbosity::info_println!("按 Ctrl+C 停止服务器...");
 
     tokio::signal::ctrl_c().await?;
     cancel_token.cancel();
+    drop(advertisement);
     Ok(())
 }
 
+/// GET /health：返回独立截屏服务状态，服务不可达时返回 503
+async fn health_check(
+    controller: Arc<ServiceController>,
+    server_started_at: chrono::DateTime<chrono::Local>,
+) -> (axum::http::StatusCode, axum::Json<serde_json::Value>) {
+    use axum::http::StatusCode;
+
+    let now = chrono::Local::now();
+    let server_uptime_seconds = (now - server_started_at).num_seconds().max(0);
+
+    match controller
+        .send_command(crate::models::ServiceCommand::Status)
+        .await
+    {
+        Ok(response) => {
+            let state = response.state.unwrap_or_default();
+            let running = matches!(state.status, crate::models::CaptureServiceStatus::Running);
+            let capture_service = match state.status {
+                crate::models::CaptureServiceStatus::Running => "running",
+                crate::models::CaptureServiceStatus::Paused => "paused",
+                crate::models::CaptureServiceStatus::Stopped => "stopped",
+                crate::models::CaptureServiceStatus::PermissionLost => "permission_lost",
+            };
+            let capture_uptime_seconds = if running {
+                state
+                    .last_start_time
+                    .map(|t| (now - t).num_seconds().max(0))
+                    .unwrap_or(0)
+            } else {
+                0
+            };
+            (
+                StatusCode::OK,
+                axum::Json(serde_json::json!({
+                    "status": "ok",
+                    "capture_service": capture_service,
+                    "total_captures": state.total_captures,
+                    "last_capture_time": state.last_capture_time.map(|t| t.to_rfc3339()),
+                    "low_disk_free_mb": state.low_disk_free_mb,
+                    "uptime_seconds": capture_uptime_seconds,
+                    "server_uptime_seconds": server_uptime_seconds,
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            axum::Json(serde_json::json!({
+                "status": "unavailable",
+                "capture_service": "unreachable",
+                "error": e.to_string(),
+                "server_uptime_seconds": server_uptime_seconds,
+            })),
+        ),
+    }
+}
+
+/// /metrics 缓存有效期，抓取间隔内的重复请求不再查询独立服务
+const METRICS_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 最近一次渲染的指标文本及生成时间
+type MetricsCache = Arc<tokio::sync::Mutex<Option<(std::time::Instant, String)>>>;
+
+/// GET /metrics：Prometheus 文本格式指标，服务不可达时返回 503
+async fn prometheus_metrics(
+    controller: Arc<ServiceController>,
+    cache: MetricsCache,
+) -> (axum::http::StatusCode, [(axum::http::header::HeaderName, &'static str); 1], String) {
+    use axum::http::{header, StatusCode};
+
+    const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+    let mut cached = cache.lock().await;
+    if let Some((rendered_at, body)) = cached.as_ref() {
+        if rendered_at.elapsed() < METRICS_CACHE_TTL {
+            return (StatusCode::OK, [(header::CONTENT_TYPE, CONTENT_TYPE)], body.clone());
+        }
+    }
+
+    match controller
+        .send_command(crate::models::ServiceCommand::Metrics)
+        .await
+    {
+        Ok(response) => {
+            let state = response.state.unwrap_or_default();
+            let service_metrics = response.metrics.unwrap_or_default();
+            let body = metrics::render_prometheus(&state, &service_metrics);
+            *cached = Some((std::time::Instant::now(), body.clone()));
+            (StatusCode::OK, [(header::CONTENT_TYPE, CONTENT_TYPE)], body)
+        }
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            format!("# 独立截屏服务不可达: {}\n", e),
+        ),
+    }
+}
+
+/// /logs/stream 补发条数上限
+const LOG_STREAM_MAX_BACKFILL: usize = 500;
+
+/// /logs/stream 补发时读取的天数
+const LOG_STREAM_BACKFILL_DAYS: u32 = 7;
+
+#[derive(serde::Deserialize)]
+struct LogStreamQuery {
+    /// 连接时先补发最近 N 条日志（0 表示不补发）
+    #[serde(default)]
+    backfill: usize,
+}
+
+/// 新日志 SSE：先补发最近 backfill 条，再转发独立截屏服务每次保存的 ActivityLog（事件名 log）
+async fn log_stream_sse(
+    config: Arc<config::Config>,
+    controller: Arc<ServiceController>,
+    query: LogStreamQuery,
+) -> Result<
+    axum::response::sse::Sse<impl futures::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>>,
+    (axum::http::StatusCode, String),
+> {
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use futures::StreamExt;
+
+    // 先订阅再读取补发日志，避免两者之间保存的日志丢失
+    let subscription = controller
+        .subscribe_events()
+        .await
+        .map_err(|e| (axum::http::StatusCode::SERVICE_UNAVAILABLE, format!("独立截屏服务不可达: {}", e)))?;
+
+    let backfill = query.backfill.min(LOG_STREAM_MAX_BACKFILL);
+    let mut recent = if backfill > 0 {
+        logger::load_recent_daily_logs(&config, LOG_STREAM_BACKFILL_DAYS)
+            .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("读取日志失败: {}", e)))?
+    } else {
+        Vec::new()
+    };
+    recent.drain(..recent.len().saturating_sub(backfill));
+    let last_backfilled = recent.last().map(|log| log.timestamp);
+
+    let backfill_stream = futures::stream::iter(
+        recent
+            .into_iter()
+            .filter_map(|log| Event::default().event("log").json_data(&log).ok())
+            .map(Ok),
+    );
+    let live_stream = futures::stream::unfold(subscription, move |mut subscription| async move {
+        loop {
+            if let crate::models::CaptureEvent::LogSaved { log } = subscription.next_event().await.ok()? {
+                // 补发时已包含的日志不再重复推送
+                if last_backfilled.is_some_and(|last| log.timestamp <= last) {
+                    continue;
+                }
+                let event = Event::default().event("log").json_data(&log).ok()?;
+                return Some((Ok(event), subscription));
+            }
+        }
+    });
+
+    Ok(Sse::new(backfill_stream.chain(live_stream)).keep_alive(KeepAlive::default()))
+}
+
+/// 初始化 tracing 日志输出，--log-format json 时输出结构化 JSON；
+/// 同时按 --service-log-level 把事件写入数据目录下轮转的诊断日志
+fn init_tracing(config: &config::Config) {
+    let json = config.json_logs();
+    let writer = || {
+        if verbosity::json_output() {
+            tracing_subscriber::fmt::writer::BoxMakeWriter::new(std::io::stderr)
+        } else {
+            tracing_subscriber::fmt::writer::BoxMakeWriter::new(std::io::stdout)
+        }
+    };
+    // 提示文字（console 字段）已直接打印，终端不再重复输出，只写入诊断日志；
+    // text 格式下带 event 字段的截屏/分析事件也已有对应的可读输出
+    let stdout_layer = if json {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(writer())
+            .with_filter(tracing_subscriber::filter::filter_fn(|meta| meta.fields().field("console").is_none()))
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .with_writer(writer())
+            .with_filter(tracing_subscriber::filter::filter_fn(|meta| {
+                meta.fields().field("event").is_none() && meta.fields().field("console").is_none()
+            }))
+            .boxed()
+    };
+    let stdout_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| verbosity::get().tracing_filter().to_string().into());
+    let initialized = tracing_subscriber::registry()
+        .with(stdout_layer.with_filter(stdout_filter))
+        .with(diagnostics::layer(config))
+        .try_init();
+    if initialized.is_ok() {
+        diagnostics::install_panic_hook();
+    }
+}
+
 async fn run_standalone_service(config: config::Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    init_tracing(&config);
+    tracing::info!(
+        event = "service_start",
+        version = env!("CARGO_PKG_VERSION"),
+        interval = config.interval,
+        model = config.model.as_str(),
+        "独立截屏服务启动"
+    );
+
+    // 首次运行确认使用条款，未同意时不启动截屏
+    if let Err(e) = consent::ensure_consent(&config, true) {
+        eprintln!("❌ {}", e);
+        return Err(e);
+    }
+
     // 首先检查并请求必要权限
-    println!("第一步：权限检查");
-    let _permission_status = permissions::ensure_permissions().await?;
-    println!("✅ 权限检查通过！\n");
+    verbosity::info_println!("第一步：权限检查");
+    let _permission_status = permissions::ensure_permissions(true).await?;
+    verbosity::info_println!("✅ 权限检查通过！\n");
     
-    println!("📋 配置信息:");
-    println!("  - 监控间隔: {} 秒", config.interval);
-    println!(
+    verbosity::info_println!("📋 配置信息:");
+    verbosity::info_println!("  - 监控间隔: {} 秒", config.interval);
+    verbosity::info_println!(
         "  - 启动强制截屏: {}",
         if config.start_capture_on_launch { "是" } else { "否" }
     );
-    println!("  - API URL: {}", config.api_url);
-    println!("  - 使用模型: {}", config.model);
-    println!("  - 截图目录: {:?}", config.get_screenshot_dir());
-    println!("  - 日志目录: {:?}", config.get_logs_dir());
-    println!("  - 状态文件: {:?}", config.get_state_path());
+    if config.no_analysis {
+        verbosity::info_println!("  - AI 分析: 已跳过（--no-analysis）");
+    }
+    verbosity::info_println!("  - API URL: {}", config.api_url);
+    verbosity::info_println!("  - API 协议: {:?}", config.api_backend);
+    if let Some(proxy) = config.proxy_display() {
+        verbosity::info_println!("  - 代理: {}", proxy);
+    }
+    verbosity::info_println!("  - 使用模型: {}", config.model);
+    verbosity::info_println!("  - 截图目录: {:?}", config.get_screenshot_dir());
+    verbosity::info_println!("  - 日志目录: {:?}", config.get_logs_dir());
+    verbosity::info_println!("  - 状态文件: {:?}", config.get_state_path());
+    if config.service_log_level != "off" {
+        verbosity::info_println!("  - 诊断日志: {:?}（级别 {}）", diagnostics::log_path(&config), config.service_log_level);
+    }
     #[cfg(unix)]
     {
-        println!("  - Socket路径: {:?}", config.get_socket_path());
+        verbosity::info_println!("  - Socket路径: {:?}", config.get_socket_path());
     }
     #[cfg(windows)]
     {
-        println!("  - 控制端口: {}", config.get_control_port());
+        verbosity::info_println!("  - 控制端口: {}", config.get_control_port());
+    }
+    verbosity::info_println!("  - 图片处理:");
+    verbosity::info_println!("    * 目标宽度: {}", if config.image_target_width > 0 { config.image_target_width.to_string() } else { "保持原图".to_string() });
+    verbosity::info_println!("    * 最大像素: {}", if config.image_max_pixels > 0 { config.image_max_pixels.to_string() } else { "不限制".to_string() });
+    verbosity::info_println!("    * 上传上限: {}", if config.max_upload_bytes > 0 { format!("{} 字节", config.max_upload_bytes) } else { "不限制".to_string() });
+    verbosity::info_println!("    * 截图区域: {}", if config.crop_to_active_window() { "前台窗口" } else { "整屏" });
+    match (config.display_index, config.display_name.as_deref()) {
+        (Some(index), _) => verbosity::info_println!("    * 显示器: 序号 {}", index),
+        (None, Some(name)) => verbosity::info_println!("    * 显示器: {}", name),
+        (None, None) => verbosity::info_println!("    * 显示器: 自动（跟随前台窗口）"),
     }
-    println!("  - 图片处理:");
-    println!("    * 目标宽度: {}", if config.image_target_width > 0 { config.image_target_width.to_string() } else { "保持原图".to_string() });
-    println!("    * 灰度转换: {}", if config.image_grayscale && !config.no_image_grayscale { "启用" } else { "禁用" });
-    println!();
+    verbosity::info_println!("    * 灰度转换: {}", if config.image_grayscale && !config.no_image_grayscale { "启用" } else { "禁用" });
+    verbosity::info_println!();
     
-    // 确保必要的目录存在
-    tokio::fs::create_dir_all(&config.get_screenshot_dir()).await?;
+    // 确保必要的目录存在且可写
+    if let Err(e) = config.ensure_writable_dirs() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
     
+    print_startup_info(&config, "standalone", serde_json::json!({}))?;
+
     // 创建并启动独立服务
     let service = StandaloneService::new(config).await?;
     
@@ -270,13 +859,210 @@ async fn run_standalone_service(config: config::Config) -> Result<(), Box<dyn Er
     Ok(())
 }
 
+/// 单次截屏：完成一次截屏与分析后将活动日志以 JSON 输出到 stdout
+async fn run_once(config: config::Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    init_tracing(&config);
+
+    if let Err(e) = consent::ensure_consent(&config, true) {
+        eprintln!("❌ {}", e);
+        return Err(e);
+    }
+    let _permission_status = permissions::ensure_permissions(true).await?;
+
+    if let Err(e) = config.ensure_writable_dirs() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
+
+    let log = capture::capture_once(&config).await?;
+    println!("{}", serde_json::to_string(&log)?);
+    // 失败记录已写入日志，仍以非零退出码告知调用方
+    if log.analysis_failed {
+        return Err(log.description.into());
+    }
+
+    Ok(())
+}
+
+/// --json 模式下输出启动信息：运行模式、模型接口、数据路径与控制入口，供外部程序定位服务
+fn print_startup_info(
+    config: &config::Config,
+    mode: &str,
+    extra: serde_json::Value,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !verbosity::json_output() {
+        return Ok(());
+    }
+    #[cfg(unix)]
+    let control = serde_json::json!({ "socket_path": config.get_socket_path() });
+    #[cfg(windows)]
+    let control = serde_json::json!({ "port": config.get_control_port() });
+
+    let mut output = serde_json::json!({
+        "event": "startup",
+        "mode": mode,
+        "pid": std::process::id(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "interval_secs": config.interval,
+        "analysis": !config.no_analysis,
+        "api_url": config.api_url,
+        "api_backend": format!("{:?}", config.api_backend).to_lowercase(),
+        "model": config.model,
+        "data_dir": config.get_data_dir(),
+        "screenshot_dir": config.get_screenshot_dir(),
+        "logs_dir": config.get_logs_dir(),
+        "state_path": config.get_state_path(),
+        "control": control,
+    });
+    if let (Some(output), serde_json::Value::Object(fields)) = (output.as_object_mut(), extra) {
+        output.extend(fields);
+    }
+    verbosity::print_json(&output)?;
+    Ok(())
+}
+
+/// --json 模式下把一次性命令的结果输出到 stdout：{"command": 命令名, ...结果字段}
+fn print_command_result(command: &str, result: impl serde::Serialize) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !verbosity::json_output() {
+        return Ok(());
+    }
+    let mut output = serde_json::Map::new();
+    output.insert("command".to_string(), command.into());
+    if let serde_json::Value::Object(fields) = serde_json::to_value(result)? {
+        output.extend(fields);
+    }
+    verbosity::print_json(&output)?;
+    Ok(())
+}
+
+/// 从标准输入读取 API 密钥并保存到系统钥匙串（--store-api-key）
+fn store_api_key(config: &config::Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    use std::io::{BufRead, IsTerminal};
+
+    let service = config.keyring_service();
+    if std::io::stdin().is_terminal() {
+        verbosity::print_prompt(&format!("🔑 请输入要保存的 API 密钥（服务名 {}）: ", service))?;
+    }
+    // 终端输入时不回显密钥；通过管道传入时按行读取
+    let key = if std::io::stdin().is_terminal() {
+        rpassword::read_password()?
+    } else {
+        let mut key = String::new();
+        std::io::stdin().lock().read_line(&mut key)?;
+        key
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("API 密钥为空，未保存".into());
+    }
+
+    config.store_api_key_in_keyring(key)?;
+    verbosity::text_println!("✅ API 密钥已保存到系统钥匙串（服务名 {}）", service);
+    verbosity::text_println!("💡 之后使用 --api-key-from-keyring {} 启动即可，无需在 .env 中保存明文密钥", service);
+    print_command_result("store-api-key", serde_json::json!({ "keyring_service": service }))
+}
+
+/// 导出窗口切换事件（--export-switches），JSON 输出到 stdout
+async fn export_switches(config: &config::Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let start = match config.switches_start.as_deref() {
+        Some(s) => config.timezone.parse_datetime(s)?,
+        None => chrono::Local::now() - chrono::Duration::days(1),
+    };
+    let end = match config.switches_end.as_deref() {
+        Some(s) => config.timezone.parse_datetime(s)?,
+        None => chrono::Local::now(),
+    };
+
+    let snapshot = window_tracker::WindowTrackerSnapshot::read_from(&config.get_window_tracker_path())
+        .await
+        .unwrap_or_default();
+    let switches = snapshot.switches_between(
+        start.timestamp_millis().max(0) as u64,
+        end.timestamp_millis().max(0) as u64,
+        config.switches_limit.unwrap_or(usize::MAX),
+    );
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&serde_json::json!({
+            "range": { "start": start.to_rfc3339(), "end": end.to_rfc3339() },
+            "count": switches.len(),
+            "switches": switches,
+        }))?
+    );
+    Ok(())
+}
+
+/// 按关键词检索活动日志（--search-logs），匹配行输出到 stdout；--json 时输出匹配的日志条目
+fn search_logs(config: &config::Config, query: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let logs = logger::load_recent_daily_logs(config, config.search_days.max(1))?;
+    let matches = search::search_logs(&logs, query, config.search_app.as_deref());
+    let shown = &matches[matches.len().saturating_sub(config.search_limit)..];
+
+    if verbosity::json_output() {
+        return print_command_result(
+            "search-logs",
+            serde_json::json!({
+                "query": query,
+                "app": config.search_app,
+                "total": matches.len(),
+                "logs": shown,
+            }),
+        );
+    }
+    if shown.is_empty() {
+        eprintln!("ℹ️ 最近 {} 天没有匹配“{}”的记录", config.search_days.max(1), query);
+        return Ok(());
+    }
+    print!("{}", search::format_matches(shown, query, config.timezone));
+    if shown.len() < matches.len() {
+        eprintln!("ℹ️ 共 {} 条匹配，仅显示最近 {} 条（--search-limit）", matches.len(), shown.len());
+    }
+    Ok(())
+}
+
+/// 分析已有图片（--analyze-image），stdout 最后一行输出 JSON 结果
+async fn run_analyze_image(
+    config: &config::Config,
+    image_path: &std::path::Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    init_tracing(config);
+
+    if let Err(e) = config.ensure_writable_dirs() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
+
+    let image_path = image_path.to_string_lossy();
+    let result = capture::analyze_image_file(config, &image_path, config.analyze_context.as_deref()).await?;
+    println!(
+        "{}",
+        serde_json::json!({
+            "image_path": image_path,
+            "classification": crate::models::ActivityClassification::parse(&result.description),
+            "description": result.description,
+            "model": result.model,
+            "token_usage": result.token_usage,
+            "processing_time_ms": result.processing_time.as_millis() as u64,
+        })
+    );
+
+    Ok(())
+}
+
 /// 在后台启动独立服务
 async fn start_standalone_service_background(config: config::Config) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // 首先检查并请求必要权限
-    let _permission_status = permissions::ensure_permissions().await?;
+    // 后台启动无法交互确认：需已记录同意或传入 --accept-terms
+    consent::ensure_consent(&config, false)?;
+
+    // 后台启动无法交互授权：缺少权限时直接返回错误，不阻塞在读取标准输入上
+    let _permission_status = permissions::ensure_permissions(false).await?;
     
-    // 确保必要的目录存在
-    tokio::fs::create_dir_all(&config.get_screenshot_dir()).await?;
+    // 确保必要的目录存在且可写
+    if let Err(e) = config.ensure_writable_dirs() {
+        eprintln!("❌ {}", e);
+        return Err(e.into());
+    }
     
     // 创建并启动独立服务
     let service = StandaloneService::new(config).await?;
@@ -293,13 +1079,26 @@ async fn process_service_status_response(
 ) -> bool {
     if let Some(state) = response.state {
         if matches!(state.status, crate::models::CaptureServiceStatus::Running) {
-            println!("✅ 检测到独立截屏服务已运行");
+            verbosity::info_println!("✅ 检测到独立截屏服务已运行");
+            return true;
+        }
+
+        if matches!(state.status, crate::models::CaptureServiceStatus::Paused) {
+            // 用户主动暂停时不自动启动，等待定时恢复或手动 start
+            verbosity::text_println!("⏸️ 独立截屏服务处于暂停状态，保持暂停");
+            return true;
+        }
+
+        if matches!(state.status, crate::models::CaptureServiceStatus::PermissionLost) {
+            // 权限未恢复时自动启动只会再次失败，等待用户授权后手动 start
+            verbosity::text_println!("❌ 独立截屏服务因屏幕录制权限丢失已停止截屏");
+            verbosity::text_println!("👉 重新授予屏幕录制权限后，通过 monitor 工具 start 恢复截屏");
             return true;
         }
 
-        println!("ℹ️ 独立截屏服务当前为停止状态，正在自动启动...");
+        verbosity::info_println!("ℹ️ 独立截屏服务当前为停止状态，正在自动启动...");
     } else {
-        println!("⚠️ 未获取到独立截屏服务状态，尝试自动启动...");
+        verbosity::text_println!("⚠️ 未获取到独立截屏服务状态，尝试自动启动...");
     }
 
     start_capture_service(controller).await
@@ -312,7 +1111,7 @@ async fn start_capture_service(controller: &ServiceController) -> bool {
     {
         Ok(response) => {
             if response.success {
-                println!("✅ 已自动启动截屏服务");
+                verbosity::info_println!("✅ 已自动启动截屏服务");
                 true
             } else {
                 eprintln!("⚠️ 自动启动截屏服务失败: {}", response.message);
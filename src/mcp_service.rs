@@ -1,19 +1,29 @@
 use rmcp::{
-    ErrorData as McpError, ServerHandler,
+    ErrorData as McpError, RoleServer, ServerHandler,
     handler::server::tool::Parameters,
     handler::server::router::tool::ToolRouter,
     model::*,
+    service::{NotificationContext, Peer, RequestContext},
     tool, tool_handler, tool_router, schemars,
 };
 use std::future::Future;
 use serde::Deserialize;
 
-use chrono::{DateTime, Local, NaiveDateTime};
+use chrono::{DateTime, Local};
 use std::sync::Arc;
+use crate::error;
 use crate::logger;
-use crate::models::{ActivityLog, ServiceCommand, CaptureServiceStatus};
+use crate::narrate;
+use crate::report;
+use crate::search;
+use crate::models::{ActivityLog, CaptureEvent, ServiceCommand, CaptureServiceStatus};
 use crate::standalone_service::ServiceController;
+use crate::thumbnails;
 use crate::config::Config;
+use crate::window_tracker::{WindowTrackerSnapshot, WINDOW_TRACKER};
+
+/// 服务端声明的 MCP 协议版本（get_info 与 mDNS TXT 记录共用）
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::V_2024_11_05;
 
 #[derive(Clone)]
 pub struct OpenRecallService {
@@ -26,21 +36,101 @@ pub struct OpenRecallService {
 pub struct MonitorArgs {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub action: Option<String>,
+    /// pause 时的暂停时长（秒），不填则一直暂停直到手动 start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ReadLogsArgs {
+    /// 开始时间 YYYY-MM-DD HH:MM:SS（按 --timezone 解释）或带偏移的 RFC 3339
     #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    /// 结束时间，格式同 start_time
     #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")] pub limit: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")] pub detailed: Option<bool>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SearchLogsArgs {
+    /// 关键词，匹配描述或窗口标题（不区分大小写）
+    pub query: String,
+    /// 只检索前台应用名包含该名称的记录（不区分大小写）
+    #[serde(skip_serializing_if = "Option::is_none")] pub app: Option<String>,
+    /// 开始时间 YYYY-MM-DD HH:MM:SS（按 --timezone 解释）或带偏移的 RFC 3339，默认 30 天前；早于日志保留期限时报错
+    #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    /// 结束时间，格式同 start_time
+    #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
+    /// 最多返回的匹配条数（取最近的记录），默认 50
+    #[serde(skip_serializing_if = "Option::is_none")] pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct AppUsageArgs {
+    /// 日期 YYYY-MM-DD，默认今天；设置 start_time/end_time 时忽略
+    #[serde(skip_serializing_if = "Option::is_none")] pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetSwitchesArgs {
+    /// 开始时间 YYYY-MM-DD HH:MM:SS（按 --timezone 解释）或带偏移的 RFC 3339，默认 24 小时前
+    #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    /// 结束时间，格式同 start_time，默认现在
+    #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
+    /// 最多返回的事件数（取范围内最近的事件），默认 20
+    #[serde(skip_serializing_if = "Option::is_none")] pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FocusSessionsArgs {
+    /// 开始时间 YYYY-MM-DD HH:MM:SS（按 --timezone 解释）或带偏移的 RFC 3339，默认今天 0 点
+    #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    /// 结束时间，格式同 start_time，默认现在
+    #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
+    /// 离开相关应用或未追踪多少分钟视为中断，默认取 --focus-gap-minutes
+    #[serde(skip_serializing_if = "Option::is_none")] pub gap_minutes: Option<u64>,
+    /// 最短专注时长（分钟），默认取 --focus-min-minutes
+    #[serde(skip_serializing_if = "Option::is_none")] pub min_minutes: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct NarrateArgs {
+    /// 回顾最近多少分钟，默认 60；设置 start_time 时忽略
+    #[serde(skip_serializing_if = "Option::is_none")] pub minutes: Option<u64>,
+    /// 开始时间 YYYY-MM-DD HH:MM:SS（按 --timezone 解释）或带偏移的 RFC 3339
+    #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    /// 结束时间，默认现在
+    #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
+    /// 最多使用范围内最近的多少条记录，默认 120
+    #[serde(skip_serializing_if = "Option::is_none")] pub limit: Option<usize>,
+    /// 为 true 时调用配置的模型写成一段叙述（消耗 token），默认 false 在本地合并去重描述
+    #[serde(skip_serializing_if = "Option::is_none")] pub use_llm: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ThumbnailsArgs {
+    /// 日期 YYYY-MM-DD，默认今天
+    #[serde(skip_serializing_if = "Option::is_none")] pub date: Option<String>,
+    /// 返回格式：json（默认，时间戳到缩略图路径的列表）或 html（缩略图网格页面）
+    #[serde(skip_serializing_if = "Option::is_none")] pub format: Option<String>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ClipboardListArgs {
     #[serde(skip_serializing_if = "Option::is_none")] pub limit: Option<usize>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DeleteLogsArgs {
+    /// 要删除的单条记录时间（YYYY-MM-DD HH:MM:SS）
+    #[serde(skip_serializing_if = "Option::is_none")] pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub start_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub end_time: Option<String>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ClipboardSaveArgs {
     pub id: String,
@@ -63,29 +153,118 @@ impl OpenRecallService {
         }
     }
 
-    #[tool(description = "监控控制工具 - action参数: start(开始), stop(停止), status(查询状态)")]
+    /// 解析查询时间：带偏移的按偏移解释，不带偏移的按 --timezone 解释
+    fn parse_datetime(&self, value: &str) -> Result<DateTime<Local>, String> {
+        self.config.timezone.parse_datetime(value)
+    }
+
+    /// 覆盖 start 所在日期到今天需要读取的日志天数（含今天）；
+    /// start 早于日志保留期限（--retention-delete-logs）时返回错误，更早的日志已被清理
+    fn days_since(&self, start: &DateTime<Local>) -> Result<u32, String> {
+        let tz = self.config.timezone;
+        let days = ((tz.today() - tz.date_of(start)).num_days() + 1).max(1);
+        // 保留 N 天时，今天之前第 N 天的日志仍在
+        if let Some(retention) = self.config.log_retention_days() {
+            if days as u64 > retention + 1 {
+                return Err(format!(
+                    "start_time 早于日志保留期限：已开启 --retention-delete-logs，只保留最近 {} 天的日志",
+                    retention
+                ));
+            }
+        }
+        Ok(u32::try_from(days).unwrap_or(u32::MAX))
+    }
+
+    /// 窗口追踪历史：优先使用本进程内的追踪器，本进程没有追踪数据时读取独立服务持久化的快照
+    async fn tracker_snapshot(&self) -> WindowTrackerSnapshot {
+        let snapshot = WINDOW_TRACKER.snapshot();
+        if !snapshot.session_history.is_empty() {
+            return snapshot;
+        }
+        WindowTrackerSnapshot::read_from(&self.config.get_window_tracker_path())
+            .await
+            .unwrap_or_default()
+    }
+
+    #[tool(description = "监控控制工具 - action参数: start(开始), stop(停止), pause(暂停，可选 duration_secs 秒后自动恢复), status(查询状态), capture(立即截屏并返回分析描述)")]
     async fn monitor(&self, Parameters(args): Parameters<MonitorArgs>) -> Result<CallToolResult, McpError> {
         let action = args.action.as_deref().unwrap_or("status");
         
         let command = match action {
             "start" => ServiceCommand::Start,
             "stop" => ServiceCommand::Stop,
+            "pause" => ServiceCommand::Pause { resume_after_secs: args.duration_secs },
             "status" => ServiceCommand::Status,
-            _ => return Ok(CallToolResult::success(vec![Content::text("invalid action, use: start, stop, status")])),
+            "capture" => ServiceCommand::CaptureNow,
+            _ => return Ok(CallToolResult::success(vec![Content::text("invalid action, use: start, stop, pause, status, capture")])),
         };
         
-        match self.service_controller.send_command(command).await {
+        let response = match command {
+            ServiceCommand::CaptureNow => self.service_controller.capture_now().await,
+            command => self.service_controller.send_command(command).await,
+        };
+        match response {
             Ok(response) => {
+                // --json：附带完整的服务响应（含 CaptureServiceState），便于脚本直接解析
+                let data = if self.config.json_output {
+                    serde_json::to_string(&response).ok()
+                } else {
+                    None
+                };
                 let mut message = response.message;
                 
                 if let Some(state) = response.state {
                     let status_str = match state.status {
+                        CaptureServiceStatus::Running if state.low_disk_free_mb.is_some() => "running (low disk)",
                         CaptureServiceStatus::Running => "running",
+                        CaptureServiceStatus::Paused => "paused",
+                        CaptureServiceStatus::Stopped if state.outside_schedule => "stopped (outside schedule)",
                         CaptureServiceStatus::Stopped => "stopped",
+                        CaptureServiceStatus::PermissionLost => "permission_lost",
                     };
                     
                     message = format!("{}\n状态: {}\n总截屏数: {}", 
                         message, status_str, state.total_captures);
+
+                    if state.sampled_out_captures > 0 {
+                        let analyzed = state.total_captures.saturating_sub(state.sampled_out_captures);
+                        message = format!(
+                            "{}\n实际分析: {}/{}（{:.0}%），未抽中 {} 张",
+                            message,
+                            analyzed,
+                            state.total_captures,
+                            analyzed as f64 * 100.0 / state.total_captures.max(1) as f64,
+                            state.sampled_out_captures
+                        );
+                    }
+
+                    if matches!(state.status, CaptureServiceStatus::PermissionLost) {
+                        message = format!(
+                            "{}\n⚠️ 屏幕录制权限已丢失，截屏已停止。请在 系统设置 → 隐私与安全性 → 屏幕录制 中重新授权后执行 start",
+                            message
+                        );
+                    }
+
+                    if let Some(free_mb) = state.low_disk_free_mb {
+                        message = format!(
+                            "{}\n⚠️ 磁盘空间不足：数据目录仅剩 {} MB，低于 --min-free-mb {} MB，截屏已暂停，释放空间后自动恢复",
+                            message, free_mb, self.config.min_free_mb
+                        );
+                    }
+
+                    if state.total_estimated_cost > 0.0 {
+                        message = format!("{}\n累计估算费用: {:.4}", message, state.total_estimated_cost);
+                    }
+
+                    if matches!(state.status, CaptureServiceStatus::Paused) {
+                        message = match state.paused_until {
+                            Some(until) => {
+                                let remaining = (until - Local::now()).num_seconds().max(0);
+                                format!("{}\n暂停至: {}（剩余 {} 秒）", message, until.format("%Y-%m-%d %H:%M:%S"), remaining)
+                            }
+                            None => format!("{}\n暂停至: 手动恢复", message),
+                        };
+                    }
                     
                     if let Some(last_start) = state.last_start_time {
                         message = format!("{}\n最后启动: {}", message, last_start.format("%Y-%m-%d %H:%M:%S"));
@@ -96,6 +275,18 @@ impl OpenRecallService {
                     }
                 }
 
+                if let Some(avg) = response.metrics.as_ref().and_then(|m| m.capture_phase_avg_ms) {
+                    message = format!(
+                        "{}\n最近 {} 次截屏平均耗时: 上下文 {} ms / 截屏 {} ms / 分析 {} ms / 写日志 {} ms",
+                        message,
+                        response.metrics.as_ref().map_or(0, |m| m.capture_phase_samples),
+                        avg.context_ms,
+                        avg.screenshot_ms,
+                        avg.analysis_ms,
+                        avg.log_write_ms
+                    );
+                }
+
                 if let Some(clipboard) = response.clipboard_status {
                     message = format!(
                         "{}\n剪贴板监听: {}\n剪贴板自动保存: {}\n剪贴板记录数: {}",
@@ -105,15 +296,20 @@ impl OpenRecallService {
                         clipboard.total_items
                     );
                 }
-                
-                Ok(CallToolResult::success(vec![Content::text(message)]))
+
+                let mut contents = vec![Content::text(message)];
+                contents.extend(data.map(Content::text));
+                Ok(CallToolResult::success(contents))
             }
             Err(e) => {
-                let error_msg = if e.to_string().contains("No such file or directory") || 
-                                   e.to_string().contains("Connection refused") {
-                    "截屏服务未运行，请先启动独立服务模式"
+                // 控制 socket 不存在或拒绝连接说明独立服务没有运行
+                let not_running = e
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused));
+                let error_msg = if not_running {
+                    "截屏服务未运行，请先启动独立服务模式".to_string()
                 } else {
-                    &format!("服务通信错误: {}", e)
+                    format!("服务通信错误: {}", error::user_message(&*e))
                 };
                 Ok(CallToolResult::success(vec![Content::text(error_msg)]))
             }
@@ -125,15 +321,22 @@ impl OpenRecallService {
         let limit = args.limit.unwrap_or(50).max(0) as usize;
         let detailed = args.detailed.unwrap_or(false);
 
+        let start = match args.start_time.as_deref().map(|s| self.parse_datetime(s)).transpose() {
+            Ok(t) => t,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid start_time: {}", e))])),
+        };
+        let end = match args.end_time.as_deref().map(|s| self.parse_datetime(s)).transpose() {
+            Ok(t) => t,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid end_time: {}", e))])),
+        };
+
         let logs = match logger::load_recent_daily_logs(&self.config, 30) {
             Ok(v) => v,
-            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("read logs error: {}", e))]))
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("read logs error: {}", error::user_message(&*e)))]))
         };
 
         let filtered: Vec<&ActivityLog> = logs.iter().filter(|log| {
-            if let Some(ref s) = args.start_time { if let Ok(st) = parse_datetime(s) { if log.timestamp < st { return false; } } }
-            if let Some(ref e) = args.end_time { if let Ok(et) = parse_datetime(e) { if log.timestamp > et { return false; } } }
-            true
+            start.is_none_or(|st| log.timestamp >= st) && end.is_none_or(|et| log.timestamp <= et)
         }).collect();
 
         let result_logs: Vec<&ActivityLog> = filtered.into_iter().rev().take(limit).collect();
@@ -141,15 +344,330 @@ impl OpenRecallService {
         for l in result_logs.into_iter().rev() {
             let line = if detailed {
                 let ctx = l.context.as_ref().and_then(|c| serde_json::to_value(c).ok()).unwrap_or(serde_json::Value::Null);
-                format!("{} | {} | ctx={}\n", l.timestamp.format("%Y-%m-%d %H:%M:%S"), l.description, ctx)
+                format!("{} | {} | ctx={}\n", self.config.timezone.format(&l.timestamp, "%Y-%m-%d %H:%M:%S"), l.description, ctx)
             } else {
-                format!("{} | {}\n", l.timestamp.format("%Y-%m-%d %H:%M:%S"), l.description)
+                format!("{} | {}\n", self.config.timezone.format(&l.timestamp, "%Y-%m-%d %H:%M:%S"), l.description)
             };
             out.push_str(&line);
         }
         Ok(CallToolResult::success(vec![Content::text(out)]))
     }
 
+    #[tool(description = "按关键词检索活动日志：返回描述或窗口标题包含 query（不区分大小写）的记录，可用 app 限定前台应用、start_time/end_time 限定时间（默认最近 30 天）；输出格式同 read_logs，匹配处用 ** 标出")]
+    async fn search_logs(&self, Parameters(args): Parameters<SearchLogsArgs>) -> Result<CallToolResult, McpError> {
+        if args.query.trim().is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text("query 不能为空".to_string())]));
+        }
+        let start = match args.start_time.as_deref().map(|s| self.parse_datetime(s)).transpose() {
+            Ok(t) => t,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid start_time: {}", e))])),
+        };
+        let end = match args.end_time.as_deref().map(|s| self.parse_datetime(s)).transpose() {
+            Ok(t) => t,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid end_time: {}", e))])),
+        };
+        let limit = args.limit.unwrap_or(50);
+
+        // 按起始日期决定读取天数，未指定时读取最近 30 天（不超过日志保留天数）
+        let tz = self.config.timezone;
+        let days = match start.as_ref().map(|st| self.days_since(st)) {
+            Some(Ok(days)) => days,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(e)])),
+            None => self.config.log_retention_days().map_or(30, |retention| (retention + 1).min(30) as u32),
+        };
+        let logs = match logger::load_recent_daily_logs(&self.config, days) {
+            Ok(v) => v,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("read logs error: {}", error::user_message(&*e)))])),
+        };
+        let in_range: Vec<ActivityLog> = logs
+            .into_iter()
+            .filter(|log| start.is_none_or(|st| log.timestamp >= st) && end.is_none_or(|et| log.timestamp <= et))
+            .collect();
+
+        let matches = search::search_logs(&in_range, &args.query, args.app.as_deref());
+        let shown = &matches[matches.len().saturating_sub(limit)..];
+        let mut out = search::format_matches(shown, &args.query, tz);
+        if shown.is_empty() {
+            out = format!("没有匹配“{}”的记录", args.query);
+        } else if shown.len() < matches.len() {
+            out.push_str(&format!("（共 {} 条匹配，仅显示最近 {} 条）\n", matches.len(), shown.len()));
+        }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "用一段话描述某段时间在做什么（默认最近 60 分钟，可用 minutes 或 start_time/end_time 指定）。默认在本地按应用合并、去重日志描述；use_llm=true 时调用配置的模型生成连贯叙述，失败时退回本地结果")]
+    async fn narrate(&self, Parameters(args): Parameters<NarrateArgs>) -> Result<CallToolResult, McpError> {
+        let end = match args.end_time.as_deref().map(|s| self.parse_datetime(s)) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid end_time: {}", e))])),
+            None => Local::now(),
+        };
+        let start = match args.start_time.as_deref().map(|s| self.parse_datetime(s)) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid start_time: {}", e))])),
+            None => end - chrono::Duration::minutes(args.minutes.unwrap_or(60).max(1) as i64),
+        };
+        let limit = args.limit.unwrap_or(120).max(1);
+
+        // 按起始日期决定读取天数
+        let tz = self.config.timezone;
+        let days = match self.days_since(&start) {
+            Ok(days) => days,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(e)])),
+        };
+        let logs = match logger::load_recent_daily_logs(&self.config, days) {
+            Ok(v) => v,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("read logs error: {}", error::user_message(&*e)))])),
+        };
+        let in_range: Vec<&ActivityLog> = logs
+            .iter()
+            .filter(|log| log.end_timestamp() >= start && log.timestamp <= end)
+            .collect();
+        let selected = &in_range[in_range.len().saturating_sub(limit)..];
+
+        let local = narrate::local_narrative(selected, tz, self.config.interval);
+        let text = if args.use_llm.unwrap_or(false) && !selected.is_empty() {
+            match narrate::llm_narrative(&self.config, selected).await {
+                Ok(narrative) => narrative,
+                Err(e) => format!("{}\n\n（模型生成叙述失败，以上为本地汇总: {}）", local, e),
+            }
+        } else {
+            local
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(description = "应用使用时长统计（按日期 date 或时间范围 start_time/end_time，默认今天），返回摘要和 JSON 数据")]
+    async fn get_app_usage(&self, Parameters(args): Parameters<AppUsageArgs>) -> Result<CallToolResult, McpError> {
+        let limit = args.limit.unwrap_or(10);
+
+        // 确定统计范围
+        let (range_start, range_end) = if args.start_time.is_some() || args.end_time.is_some() {
+            let start = match args.start_time.as_deref().map(|s| self.parse_datetime(s)) {
+                Some(Ok(t)) => t,
+                Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid start_time: {}", e))])),
+                None => Local::now() - chrono::Duration::days(1),
+            };
+            let end = match args.end_time.as_deref().map(|s| self.parse_datetime(s)) {
+                Some(Ok(t)) => t,
+                Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid end_time: {}", e))])),
+                None => Local::now(),
+            };
+            (start, end)
+        } else {
+            let date = match args.date.as_deref() {
+                Some(d) => match chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d") {
+                    Ok(date) => date,
+                    Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid date: {}", e))])),
+                },
+                None => self.config.timezone.today(),
+            };
+            let start = self.config.timezone.start_of_day(date);
+            (start, start + chrono::Duration::days(1))
+        };
+
+        let snapshot = self.tracker_snapshot().await;
+
+        let start_ms = range_start.timestamp_millis().max(0) as u64;
+        let end_ms = range_end.timestamp_millis().max(0) as u64;
+        let (mut usage, source) = if snapshot.covers_since(start_ms) {
+            (snapshot.app_usage_between(start_ms, end_ms), "window_tracker")
+        } else {
+            // 窗口追踪历史只保留最近的会话，覆盖不到的范围按每日活动日志估算
+            let days = match self.days_since(&range_start) {
+                Ok(days) => days,
+                Err(e) => return Ok(CallToolResult::success(vec![Content::text(e)])),
+            };
+            let logs = match logger::load_recent_daily_logs(&self.config, days) {
+                Ok(v) => v,
+                Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("read logs error: {}", error::user_message(&*e)))])),
+            };
+            let in_range = logs.iter().filter(|log| log.timestamp >= range_start && log.timestamp < range_end);
+            (report::app_usage_from_logs(in_range, self.config.interval), "activity_logs")
+        };
+        usage.truncate(limit);
+
+        let total_ms: u64 = usage.iter().map(|(_, d)| *d).sum();
+        let range_label = format!(
+            "{} ~ {}",
+            self.config.timezone.format(&range_start, "%Y-%m-%d %H:%M:%S"),
+            self.config.timezone.format(&range_end, "%Y-%m-%d %H:%M:%S")
+        );
+        let mut summary = if usage.is_empty() {
+            format!("{} 期间没有窗口使用记录。", range_label)
+        } else {
+            let top = &usage[0];
+            format!(
+                "{} 期间共记录 {:.1} 分钟，使用最多的是 {}（{:.1} 分钟，占 {:.0}%）。",
+                range_label,
+                total_ms as f64 / 60000.0,
+                top.0,
+                top.1 as f64 / 60000.0,
+                top.1 as f64 / total_ms.max(1) as f64 * 100.0
+            )
+        };
+        for (app, duration) in usage.iter() {
+            summary.push_str(&format!("\n- {}: {:.1} 分钟", app, *duration as f64 / 60000.0));
+        }
+        if source == "activity_logs" && !usage.is_empty() {
+            summary.push_str(&format!(
+                "\n（窗口追踪历史未覆盖该时段，按活动日志估算：每条记录计 {} 秒）",
+                self.config.interval
+            ));
+        }
+
+        let data = serde_json::json!({
+            "range": { "start": range_start.to_rfc3339(), "end": range_end.to_rfc3339() },
+            "total_ms": total_ms,
+            "source": source,
+            "apps": usage.iter().map(|(app, ms)| serde_json::json!({ "app": app, "duration_ms": ms })).collect::<Vec<_>>(),
+            "most_used_apps_all_time": snapshot.most_used_apps(5).iter().map(|(app, ms)| serde_json::json!({ "app": app, "duration_ms": ms })).collect::<Vec<_>>(),
+        });
+
+        Ok(CallToolResult::success(vec![
+            Content::text(summary),
+            Content::text(data.to_string()),
+        ]))
+    }
+
+    #[tool(description = "窗口切换事件（from/to 应用与标题、时间戳、上一窗口停留时长），按时间范围 start_time/end_time 查询（默认最近 24 小时），limit 默认 20，按时间先后返回 JSON")]
+    async fn get_switches(&self, Parameters(args): Parameters<GetSwitchesArgs>) -> Result<CallToolResult, McpError> {
+        let start = match args.start_time.as_deref().map(|s| self.parse_datetime(s)) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid start_time: {}", e))])),
+            None => Local::now() - chrono::Duration::days(1),
+        };
+        let end = match args.end_time.as_deref().map(|s| self.parse_datetime(s)) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid end_time: {}", e))])),
+            None => Local::now(),
+        };
+        let limit = args.limit.unwrap_or(20);
+
+        let snapshot = self.tracker_snapshot().await;
+
+        let start_ms = start.timestamp_millis().max(0) as u64;
+        let end_ms = end.timestamp_millis().max(0) as u64;
+        let switches = snapshot.switches_between(start_ms, end_ms, limit);
+
+        let data = serde_json::json!({
+            "range": { "start": start.to_rfc3339(), "end": end.to_rfc3339() },
+            "count": switches.len(),
+            "switches": switches,
+        });
+        Ok(CallToolResult::success(vec![
+            Content::text(format!(
+                "{} ~ {} 期间返回 {} 条窗口切换事件",
+                self.config.timezone.format(&start, "%Y-%m-%d %H:%M:%S"),
+                self.config.timezone.format(&end, "%Y-%m-%d %H:%M:%S"),
+                switches.len()
+            )),
+            Content::text(data.to_string()),
+        ]))
+    }
+
+    #[tool(description = "专注时段：把窗口会话归并为连续停留在相关应用、没有长时间中断的时段，返回开始/结束时间、时长和主要应用。按 start_time/end_time 查询（默认今天），gap_minutes 为中断阈值，min_minutes 为最短时长")]
+    async fn get_focus_sessions(&self, Parameters(args): Parameters<FocusSessionsArgs>) -> Result<CallToolResult, McpError> {
+        let start = match args.start_time.as_deref().map(|s| self.parse_datetime(s)) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid start_time: {}", e))])),
+            None => self.config.timezone.start_of_day(self.config.timezone.today()),
+        };
+        let end = match args.end_time.as_deref().map(|s| self.parse_datetime(s)) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid end_time: {}", e))])),
+            None => Local::now(),
+        };
+        let gap_ms = args.gap_minutes.unwrap_or(self.config.focus_gap_minutes).max(1) * 60_000;
+        let min_ms = args.min_minutes.unwrap_or(self.config.focus_min_minutes) * 60_000;
+
+        let start_ms = start.timestamp_millis().max(0) as u64;
+        let end_ms = end.timestamp_millis().max(0) as u64;
+
+        let sessions = self.tracker_snapshot().await.focus_sessions_between(start_ms, end_ms, gap_ms, min_ms);
+
+        let total_ms: u64 = sessions.iter().map(|s| s.duration_ms).sum();
+        let format_ms = |ms: u64| {
+            DateTime::from_timestamp_millis(ms as i64)
+                .map(|t| self.config.timezone.format(&t.with_timezone(&Local), "%H:%M"))
+                .unwrap_or_default()
+        };
+        let mut summary = format!(
+            "{} ~ {} 期间共 {} 个专注时段，合计 {:.1} 分钟",
+            self.config.timezone.format(&start, "%Y-%m-%d %H:%M:%S"),
+            self.config.timezone.format(&end, "%Y-%m-%d %H:%M:%S"),
+            sessions.len(),
+            total_ms as f64 / 60000.0
+        );
+        for session in &sessions {
+            summary.push_str(&format!(
+                "\n- {} ~ {}（{:.1} 分钟）主要使用 {}，切换 {} 次",
+                format_ms(session.start_time),
+                format_ms(session.end_time),
+                session.duration_ms as f64 / 60000.0,
+                session.dominant_app,
+                session.switch_count
+            ));
+        }
+
+        let data = serde_json::json!({
+            "range": { "start": start.to_rfc3339(), "end": end.to_rfc3339() },
+            "gap_minutes": gap_ms / 60_000,
+            "min_minutes": min_ms / 60_000,
+            "total_ms": total_ms,
+            "sessions": sessions,
+        });
+        Ok(CallToolResult::success(vec![
+            Content::text(summary),
+            Content::text(data.to_string()),
+        ]))
+    }
+
+    #[tool(description = "某天的截图缩略图索引（需独立服务开启 --thumbnails），format 为 json（默认，时间戳、缩略图路径、类型/软件、描述首行）或 html（缩略图网格页面）")]
+    async fn get_thumbnails(&self, Parameters(args): Parameters<ThumbnailsArgs>) -> Result<CallToolResult, McpError> {
+        let date = match thumbnails::resolve_date(&self.config, args.date.as_deref()) {
+            Ok(date) => date,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid date: {}", e))])),
+        };
+        let entries = match thumbnails::list_thumbnails(&self.config, date) {
+            Ok(entries) => entries,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("读取日志失败: {}", e))])),
+        };
+        let summary = format!("{} 共有 {} 张缩略图", date.format("%Y-%m-%d"), entries.len());
+        let body = match args.format.as_deref() {
+            Some("html") => thumbnails::render_html(date, &entries),
+            _ => match thumbnails::render_json(date, &entries) {
+                Ok(json) => json,
+                Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("序列化失败: {}", e))])),
+            },
+        };
+        Ok(CallToolResult::success(vec![Content::text(summary), Content::text(body)]))
+    }
+
+    #[tool(description = "删除活动日志及其截图（隐私清理）：需提供 timestamp，或同时提供 start_time 和 end_time（最长 31 天），返回删除条数")]
+    async fn delete_logs(&self, Parameters(args): Parameters<DeleteLogsArgs>) -> Result<CallToolResult, McpError> {
+        // 必须给出明确范围，避免误删全部日志
+        let range = match (&args.timestamp, &args.start_time, &args.end_time) {
+            (Some(ts), None, None) => logger::resolve_delete_range(&self.config, ts, None),
+            (None, Some(start), Some(end)) => logger::resolve_delete_range(&self.config, start, Some(end)),
+            _ => return Ok(CallToolResult::success(vec![Content::text(
+                "refused: provide either timestamp, or both start_time and end_time",
+            )])),
+        };
+        let (start, end) = match range {
+            Ok(v) => v,
+            Err(e) => return Ok(CallToolResult::success(vec![Content::text(format!("invalid range: {}", e))])),
+        };
+
+        match logger::delete_activity_logs(&self.config, start, end) {
+            Ok(deleted) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "已删除 {} 条日志，{} 个截图文件",
+                deleted.entries, deleted.screenshots
+            ))])),
+            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!("delete logs error: {}", e))])),
+        }
+    }
+
     #[tool(description = "查询剪贴板监听状态")]
     async fn clipboard_status(&self) -> Result<CallToolResult, McpError> {
         match self
@@ -222,6 +740,17 @@ impl OpenRecallService {
         }
     }
 
+    #[tool(description = "导出最近上传给模型的截图（需以 --keep-last-frames 启动服务），用于核对模型实际看到的画面")]
+    async fn dump_frames(&self) -> Result<CallToolResult, McpError> {
+        match self.service_controller.send_command(ServiceCommand::DumpFrames).await {
+            Ok(response) => Ok(CallToolResult::success(vec![Content::text(response.message)])),
+            Err(e) => Ok(CallToolResult::success(vec![Content::text(format!(
+                "服务通信错误: {}",
+                error::user_message(&*e)
+            ))])),
+        }
+    }
+
     #[tool(description = "开启/关闭剪贴板自动保存")]
     async fn clipboard_auto_save(
         &self,
@@ -243,19 +772,277 @@ impl OpenRecallService {
     }
 }
 
+/// 今日活动时间线资源（Markdown）
+const TODAY_RESOURCE_URI: &str = "screentime://today";
+/// 今日活动时间线资源（JSON，完整的活动日志数组）
+const TODAY_JSON_RESOURCE_URI: &str = "screentime://today.json";
+
+impl OpenRecallService {
+    /// 今日活动时间线：每条日志一行，时间、应用与描述
+    fn today_timeline_markdown(&self, date: &str, logs: &[ActivityLog]) -> String {
+        let mut out = format!("# 今日活动时间线（{}）\n\n", date);
+        if logs.is_empty() {
+            out.push_str("今天还没有活动记录。\n");
+            return out;
+        }
+        for log in logs {
+            let app = log
+                .context
+                .as_ref()
+                .and_then(|ctx| ctx.active_app.as_deref())
+                .unwrap_or("未知软件");
+            out.push_str(&format!(
+                "- {} 【{}】 {}\n",
+                self.config.timezone.format(&log.timestamp, "%H:%M:%S"),
+                app,
+                log.description.trim().replace('\n', " ")
+            ));
+        }
+        out
+    }
+
+    /// 读取今日活动资源，uri 为 TODAY_RESOURCE_URI 或 TODAY_JSON_RESOURCE_URI
+    fn read_today_resource(&self, uri: &str) -> Result<ReadResourceResult, McpError> {
+        if uri != TODAY_RESOURCE_URI && uri != TODAY_JSON_RESOURCE_URI {
+            return Err(McpError::resource_not_found(format!("unknown resource: {}", uri), None));
+        }
+
+        let date = self.config.timezone.today().format("%Y-%m-%d").to_string();
+        let logs = logger::load_daily_activity_logs(&self.config, &date)
+            .map_err(|e| McpError::internal_error(format!("read logs error: {}", error::user_message(&*e)), None))?;
+        let text = if uri == TODAY_JSON_RESOURCE_URI {
+            serde_json::to_string_pretty(&logs).map_err(|e| McpError::internal_error(e.to_string(), None))?
+        } else {
+            self.today_timeline_markdown(&date, &logs)
+        };
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::text(text, uri)],
+        })
+    }
+}
+
+/// resources/list 返回的资源列表
+fn today_resources() -> Vec<Resource> {
+    vec![
+        timeline_resource(TODAY_RESOURCE_URI, "today", "今日活动时间线（Markdown）", "text/markdown"),
+        timeline_resource(TODAY_JSON_RESOURCE_URI, "today.json", "今日活动日志（JSON）", "application/json"),
+    ]
+}
+
+fn timeline_resource(uri: &str, name: &str, description: &str, mime_type: &str) -> Resource {
+    let mut resource = RawResource::new(uri, name);
+    resource.description = Some(description.to_string());
+    resource.mime_type = Some(mime_type.to_string());
+    resource.no_annotation()
+}
+
+/// 进度事件通知的 logger 名
+const CAPTURE_EVENT_LOGGER: &str = "screentime.capture";
+
+/// 独立截屏服务不可达时重新订阅进度事件的间隔
+const CAPTURE_EVENT_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 等待进度事件时检查客户端是否已断开的间隔
+const CAPTURE_EVENT_PEER_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// 把截屏进度事件转为 notifications/message 通知，data 即事件 JSON（event 字段为事件名）；
+/// log_saved 携带完整日志，由 /logs/stream 推送，不通过 MCP 转发
+fn capture_event_notification(event: &CaptureEvent) -> Option<LoggingMessageNotificationParam> {
+    let level = match event {
+        CaptureEvent::LogSaved { .. } => return None,
+        CaptureEvent::AnalysisFailed { .. } => LoggingLevel::Error,
+        _ => LoggingLevel::Info,
+    };
+    Some(LoggingMessageNotificationParam {
+        level,
+        logger: Some(CAPTURE_EVENT_LOGGER.to_string()),
+        data: serde_json::to_value(event).ok()?,
+    })
+}
+
+/// 向已初始化的客户端推送进度事件：经由该客户端的 /sse 流发送；
+/// 独立截屏服务不可达（如尚未通过 monitor.start 启动）或重启时定期重新订阅。
+/// 等待事件期间定期检查客户端是否已断开，断开后立即退出并关闭订阅，不必等到下一条事件
+async fn forward_capture_events(controller: Arc<ServiceController>, peer: Peer<RoleServer>) {
+    let mut peer_check = tokio::time::interval(CAPTURE_EVENT_PEER_CHECK_INTERVAL);
+    while !peer.is_transport_closed() {
+        let mut subscription = match controller.subscribe_events().await {
+            Ok(subscription) => subscription,
+            Err(_) => {
+                tokio::time::sleep(CAPTURE_EVENT_RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+        loop {
+            // 读取中的事件不能被取消（会读丢半帧），检查断开期间保留同一个 future
+            let next_event = subscription.next_event();
+            tokio::pin!(next_event);
+            let event = loop {
+                tokio::select! {
+                    event = &mut next_event => break event,
+                    _ = peer_check.tick() => {
+                        if peer.is_transport_closed() {
+                            return;
+                        }
+                    }
+                }
+            };
+            let Ok(event) = event else {
+                break;
+            };
+            let Some(notification) = capture_event_notification(&event) else {
+                continue;
+            };
+            if peer.notify_logging_message(notification).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
 #[tool_handler]
 impl ServerHandler for OpenRecallService {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: ServerCapabilities::builder().enable_tools().enable_resources().enable_logging().build(),
             server_info: Implementation::from_build_env(),
-            instructions: Some("OpenRecall MCP server: tools=monitor, read_logs, clipboard_status, clipboard_list, clipboard_save, clipboard_auto_save".to_string()),
+            instructions: Some("OpenRecall MCP server: tools=monitor, read_logs, search_logs, get_app_usage, narrate, delete_logs, clipboard_status, clipboard_list, clipboard_save, clipboard_auto_save, dump_frames; resources=screentime://today, screentime://today.json; capture progress is pushed as notifications/message (logger screentime.capture)".to_string()),
         }
     }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        tokio::spawn(forward_capture_events(self.service_controller.clone(), context.peer));
+    }
+
+    /// 进度事件不分级别过滤，接受设置以兼容会先调用 logging/setLevel 的客户端
+    async fn set_level(
+        &self,
+        _request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        Ok(())
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        Ok(ListResourcesResult {
+            resources: today_resources(),
+            next_cursor: None,
+        })
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.read_today_resource(request.uri.as_str())
+    }
 }
 
-fn parse_datetime(datetime_str: &str) -> Result<DateTime<Local>, chrono::ParseError> {
-    let naive = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S")?;
-    Ok(naive.and_local_timezone(Local).single().unwrap_or_else(|| naive.and_utc().with_timezone(&Local)))
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+
+    fn test_service(name: &str) -> (OpenRecallService, std::path::PathBuf) {
+        let config = test_config(&format!("mcp_{}", name));
+        let dir = config.get_data_dir();
+        (OpenRecallService::new(config), dir)
+    }
+
+    fn resource_text(result: &ReadResourceResult) -> &str {
+        match &result.contents[..] {
+            [ResourceContents::TextResourceContents { text, .. }] => text,
+            other => panic!("unexpected resource contents: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_list_resources() {
+        let resources = today_resources();
+        let listed: Vec<_> = resources
+            .iter()
+            .map(|resource| (resource.uri.as_str(), resource.mime_type.as_deref()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (TODAY_RESOURCE_URI, Some("text/markdown")),
+                (TODAY_JSON_RESOURCE_URI, Some("application/json")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_capture_events_become_logging_notifications() {
+        let timestamp = Local::now();
+        let started = capture_event_notification(&CaptureEvent::AnalysisStarted {
+            timestamp,
+            model: "Qwen/Qwen2.5-VL-72B-Instruct".to_string(),
+        })
+        .unwrap();
+        assert_eq!(started.level, LoggingLevel::Info);
+        assert_eq!(started.logger.as_deref(), Some(CAPTURE_EVENT_LOGGER));
+        assert_eq!(started.data["event"], "analysis_started");
+        assert_eq!(started.data["model"], "Qwen/Qwen2.5-VL-72B-Instruct");
+
+        let completed = capture_event_notification(&CaptureEvent::AnalysisCompleted {
+            timestamp,
+            description: "【工作】【VSCode】【写代码】".to_string(),
+            model: None,
+            total_tokens: Some(120),
+        })
+        .unwrap();
+        assert_eq!(completed.data["event"], "analysis_completed");
+        assert_eq!(completed.data["description"], "【工作】【VSCode】【写代码】");
+
+        let failed = capture_event_notification(&CaptureEvent::AnalysisFailed {
+            timestamp,
+            error: "timeout".to_string(),
+        })
+        .unwrap();
+        assert_eq!(failed.level, LoggingLevel::Error);
+
+        let log: ActivityLog =
+            serde_json::from_value(serde_json::json!({ "timestamp": timestamp, "description": "" })).unwrap();
+        assert!(capture_event_notification(&CaptureEvent::LogSaved { log: Box::new(log) }).is_none());
+
+        let (service, _) = test_service("capabilities");
+        assert!(service.get_info().capabilities.logging.is_some());
+    }
+
+    #[test]
+    fn test_read_today_resources() {
+        let (service, dir) = test_service("resources");
+
+        // 没有日志时两种格式都可读
+        let empty = service.read_today_resource(TODAY_RESOURCE_URI).unwrap();
+        assert!(resource_text(&empty).contains("今天还没有活动记录"));
+        let empty_json = service.read_today_resource(TODAY_JSON_RESOURCE_URI).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<ActivityLog>>(resource_text(&empty_json)).unwrap().len(), 0);
+
+        let now = Local::now();
+        let log: ActivityLog = serde_json::from_value(serde_json::json!({
+            "timestamp": now,
+            "description": "【工作】【VSCode】【编写资源测试】",
+            "context": { "active_app": "VSCode", "timestamp": now },
+        }))
+        .unwrap();
+        logger::save_activity_log(&log, &service.config).unwrap();
+
+        let markdown = service.read_today_resource(TODAY_RESOURCE_URI).unwrap();
+        assert!(resource_text(&markdown).contains("【VSCode】 【工作】【VSCode】【编写资源测试】"));
+        let json = service.read_today_resource(TODAY_JSON_RESOURCE_URI).unwrap();
+        let logs: Vec<ActivityLog> = serde_json::from_str(resource_text(&json)).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].description, log.description);
+
+        assert!(service.read_today_resource("screentime://yesterday").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
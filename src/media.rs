@@ -0,0 +1,79 @@
+use crate::config::Config;
+use crate::lang::Lang;
+use crate::screenshot;
+use crate::window_tracker::WINDOW_TRACKER;
+
+/// 前台正在播放的媒体（--media-mode）
+#[derive(Debug, Clone)]
+pub struct MediaPlayback {
+    pub app: String,
+    pub title: Option<String>,
+}
+
+impl MediaPlayback {
+    /// summarize 模式写入日志的本地描述，保持【类型】【软件】【主要工作摘要】格式以便解析分类
+    pub fn summary_description(&self, lang: Lang) -> String {
+        let title = self.title.as_deref().filter(|title| !title.trim().is_empty());
+        match lang {
+            Lang::Zh => format!("【娱乐】【{}】观看视频/媒体：{}", self.app, title.unwrap_or("未知内容")),
+            Lang::En => format!("【Entertainment】【{}】Watching video/media: {}", self.app, title.unwrap_or("unknown")),
+        }
+    }
+}
+
+/// 检测前台是否在播放媒体：前台应用名与 --media-apps 中某项相同或窗口标题中以完整单词出现某项（不区分大小写），
+/// 且窗口铺满显示器，或已连续停留在前台 --media-min-minutes 分钟（期间没有切换应用）
+pub async fn detect_playback(config: &Config) -> Option<MediaPlayback> {
+    let window = WINDOW_TRACKER.get_current_window_info().await?;
+    let app = window.app_name.filter(|app| !app.is_empty())?;
+    if !is_media_window(&config.media_apps, &app, window.window_title.as_deref().unwrap_or_default()) {
+        return None;
+    }
+
+    let full_screen = window.bounds.as_ref().is_some_and(screenshot::window_fills_display);
+    let settled = WINDOW_TRACKER.get_stats().await.current_session_duration_ms
+        >= config.media_min_minutes * 60 * 1000;
+    (full_screen || settled).then_some(MediaPlayback {
+        app,
+        title: window.window_title,
+    })
+}
+
+/// 应用名与某项完全相同，或窗口标题中出现某项且前后不紧挨英文字母或数字
+/// （"Plex" 不会匹配 "Perplexity"，中文名称不受影响），均不区分大小写
+fn is_media_window(media_apps: &[String], app: &str, title: &str) -> bool {
+    let app = app.to_lowercase();
+    let title = title.to_lowercase();
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    media_apps
+        .iter()
+        .map(|keyword| keyword.trim().to_lowercase())
+        .filter(|keyword| !keyword.is_empty())
+        .any(|keyword| {
+            app == keyword
+                || title.match_indices(&keyword).any(|(start, _)| {
+                    !is_word_char(title[..start].chars().next_back())
+                        && !is_word_char(title[start + keyword.len()..].chars().next())
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_media_window() {
+        let apps: Vec<String> = ["Plex", "YouTube", "IINA", "爱奇艺"].iter().map(|s| s.to_string()).collect();
+        assert!(is_media_window(&apps, "iina", ""));
+        assert!(is_media_window(&apps, "Google Chrome", "(3) YouTube - Google Chrome"));
+        assert!(is_media_window(&apps, "Safari", "爱奇艺视频 - 庆余年"));
+        assert!(is_media_window(&apps, "Plex Media Player", "plex"));
+
+        // 应用名需完全相同，标题中的关键词不能是其他单词的一部分
+        assert!(!is_media_window(&apps, "Perplexity", "Perplexity - Ask anything"));
+        assert!(!is_media_window(&apps, "Plex Media Player", "Library"));
+        assert!(!is_media_window(&apps, "Notes", "YouTubers I follow"));
+        assert!(!is_media_window(&[" ".to_string()], "Notes", "Notes"));
+    }
+}
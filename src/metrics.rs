@@ -0,0 +1,123 @@
+use crate::models::{CapturePhaseTimings, CaptureServiceState, ServiceMetrics};
+use crate::window_tracker::WINDOW_TRACKER;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 截屏阶段耗时取最近多少次截屏求平均
+pub const CAPTURE_PHASE_WINDOW: usize = 20;
+
+static RECENT_CAPTURE_PHASES: Mutex<VecDeque<CapturePhaseTimings>> = Mutex::new(VecDeque::new());
+
+/// 分析耗时直方图的桶上界（秒），+Inf 桶由 count 表示
+pub const ANALYSIS_DURATION_BUCKETS: [f64; 9] = [0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0];
+
+lazy_static::lazy_static! {
+    static ref METRICS: Mutex<ServiceMetrics> = Mutex::new(ServiceMetrics {
+        analysis_duration_buckets: vec![0; ANALYSIS_DURATION_BUCKETS.len()],
+        ..ServiceMetrics::default()
+    });
+}
+
+/// 累加模型返回的 token 用量
+pub fn record_tokens(tokens: u64) {
+    METRICS.lock().unwrap().tokens_total += tokens;
+}
+
+/// 记录一次截屏失败
+pub fn record_capture_error() {
+    METRICS.lock().unwrap().capture_errors_total += 1;
+}
+
+/// 记录一次模型分析耗时
+pub fn observe_analysis_duration(duration: Duration) {
+    let secs = duration.as_secs_f64();
+    let mut metrics = METRICS.lock().unwrap();
+    for (bucket, upper) in metrics
+        .analysis_duration_buckets
+        .iter_mut()
+        .zip(ANALYSIS_DURATION_BUCKETS)
+    {
+        if secs <= upper {
+            *bucket += 1;
+        }
+    }
+    metrics.analysis_duration_sum_seconds += secs;
+    metrics.analysis_duration_count += 1;
+}
+
+/// 记录一次截屏的各阶段耗时，只保留最近 CAPTURE_PHASE_WINDOW 次
+pub fn record_capture_phases(timings: CapturePhaseTimings) {
+    let mut recent = RECENT_CAPTURE_PHASES.lock().unwrap();
+    if recent.len() == CAPTURE_PHASE_WINDOW {
+        recent.pop_front();
+    }
+    recent.push_back(timings);
+}
+
+/// 最近若干次截屏各阶段的平均耗时
+fn capture_phase_average(recent: &VecDeque<CapturePhaseTimings>) -> Option<CapturePhaseTimings> {
+    let count = recent.len() as u64;
+    if count == 0 {
+        return None;
+    }
+    let average = |phase: fn(&CapturePhaseTimings) -> u64| recent.iter().map(phase).sum::<u64>() / count;
+    Some(CapturePhaseTimings {
+        context_ms: average(|t| t.context_ms),
+        screenshot_ms: average(|t| t.screenshot_ms),
+        analysis_ms: average(|t| t.analysis_ms),
+        log_write_ms: average(|t| t.log_write_ms),
+    })
+}
+
+/// 当前指标快照（窗口切换次数取自窗口追踪器）
+pub fn snapshot() -> ServiceMetrics {
+    let mut metrics = METRICS.lock().unwrap().clone();
+    metrics.window_switches_total = WINDOW_TRACKER.total_switch_count();
+    let recent = RECENT_CAPTURE_PHASES.lock().unwrap();
+    metrics.capture_phase_avg_ms = capture_phase_average(&recent);
+    metrics.capture_phase_samples = recent.len();
+    metrics
+}
+
+/// 渲染为 Prometheus 文本格式
+pub fn render_prometheus(state: &CaptureServiceState, metrics: &ServiceMetrics) -> String {
+    let mut out = String::new();
+
+    write_counter(&mut out, "screentime_captures_total", "累计截屏次数", state.total_captures);
+    write_counter(&mut out, "screentime_tokens_total", "累计消耗的模型 token 数", metrics.tokens_total);
+    write_counter(
+        &mut out,
+        "screentime_capture_errors_total",
+        "截屏或分析失败次数",
+        metrics.capture_errors_total,
+    );
+    write_counter(
+        &mut out,
+        "screentime_window_switches_total",
+        "前台窗口切换次数",
+        metrics.window_switches_total,
+    );
+
+    let name = "screentime_analysis_duration_seconds";
+    let _ = writeln!(out, "# HELP {} 模型分析耗时（秒）", name);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (upper, count) in ANALYSIS_DURATION_BUCKETS
+        .iter()
+        .zip(&metrics.analysis_duration_buckets)
+    {
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, upper, count);
+    }
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, metrics.analysis_duration_count);
+    let _ = writeln!(out, "{}_sum {}", name, metrics.analysis_duration_sum_seconds);
+    let _ = writeln!(out, "{}_count {}", name, metrics.analysis_duration_count);
+
+    out
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
@@ -12,6 +12,124 @@ pub struct ActivityLog {
     pub model: Option<String>,
     /// 消耗的token数量
     pub token_usage: Option<TokenUsage>,
+    /// 按配置单价估算的本次分析费用（未配置单价时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_cost: Option<f64>,
+    /// 从描述中解析出的【类型】【软件】【主要工作摘要】（描述不符合格式时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<ActivityClassification>,
+    /// 影子后端（--shadow-backend）对同一张截图的描述，仅用于对比，未启用或影子分析失败时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_description: Option<String>,
+    /// 缩略图路径（--thumbnails），不受 --keep-screenshots 影响
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_path: Option<String>,
+    /// 录屏片段路径（--capture-mode video），与截图一样仅在保留时记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clip_path: Option<String>,
+    /// 日志压缩（--compact）合并时，时间段内最后一条原始记录的时间；未合并时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span_end: Option<DateTime<Local>>,
+    /// 日志压缩合并的原始记录条数；未合并时为空（即 1 条）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_count: Option<u32>,
+    /// 重试耗尽仍分析失败：描述为 "[analysis failed: 原因]"，截图会被保留以便人工查看
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub analysis_failed: bool,
+    /// 思考模型的推理过程（--keep-reasoning），已从 description 中移除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+}
+
+impl ActivityLog {
+    /// 本条代表的原始记录数
+    pub fn entry_count(&self) -> u32 {
+        self.merged_count.unwrap_or(1)
+    }
+
+    /// 本条覆盖的最后一条原始记录时间
+    pub fn end_timestamp(&self) -> DateTime<Local> {
+        self.span_end.unwrap_or(self.timestamp)
+    }
+}
+
+/// 描述中【类型】【软件】【主要工作摘要】的结构化结果，模型未按格式输出的字段为 None
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ActivityClassification {
+    pub category: Option<String>,
+    pub software: Option<String>,
+    pub summary: Option<String>,
+}
+
+/// 带标签写法中各字段的标签，英文标签（--lang en 的提示词）不区分大小写
+const CATEGORY_LABELS: &[&str] = &["类型", "category"];
+const SOFTWARE_LABELS: &[&str] = &["软件", "software"];
+const SUMMARY_LABELS: &[&str] = &["主要工作摘要", "摘要", "summary", "summary of the main work", "main work summary"];
+
+impl ActivityClassification {
+    /// 解析描述，支持按位置的「【编程】【VS Code】【编写代码】」
+    /// 和带标签的「【类型】编程【软件】VS Code【主要工作摘要】编写代码」（或英文标签
+    /// 「【Category】Coding【Software】VS Code【Summary】…」）两种写法；
+    /// 三个字段都解析不到时返回 None
+    pub fn parse(description: &str) -> Option<Self> {
+        // (括号内文本, 括号后到下一个【之前的文本)
+        let mut segments: Vec<(String, String)> = Vec::new();
+        let mut rest = description.trim();
+        while let Some(start) = rest.find('【') {
+            let after = &rest[start + '【'.len_utf8()..];
+            let Some(end) = after.find('】') else { break };
+            let tag = after[..end].trim().to_string();
+            rest = &after[end + '】'.len_utf8()..];
+            let trailing = rest.split('【').next().unwrap_or("").trim().to_string();
+            segments.push((tag, trailing));
+        }
+
+        let non_empty = |text: &str| Some(text.trim().to_string()).filter(|t| !t.is_empty());
+        let is_label = |labels: &[&str], tag: &str| labels.contains(&tag.to_lowercase().as_str());
+        let labeled = |labels: &[&str], first_line: bool| {
+            segments
+                .iter()
+                .find(|(tag, _)| is_label(labels, tag))
+                .and_then(|(_, text)| {
+                    if first_line {
+                        non_empty(text.lines().next().unwrap_or(""))
+                    } else {
+                        non_empty(text)
+                    }
+                })
+        };
+
+        let classification = if segments.iter().any(|(tag, _)| {
+            [CATEGORY_LABELS, SOFTWARE_LABELS, SUMMARY_LABELS]
+                .iter()
+                .any(|labels| is_label(labels, tag))
+        }) {
+            Self {
+                category: labeled(CATEGORY_LABELS, true),
+                software: labeled(SOFTWARE_LABELS, true),
+                summary: labeled(SUMMARY_LABELS, false),
+            }
+        } else {
+            let positional = |index: usize| segments.get(index).and_then(|(tag, _)| non_empty(tag));
+            // 摘要可能写在第三个括号内，也可能直接跟在【软件】之后
+            let summary = positional(2).or_else(|| {
+                segments
+                    .get(1)
+                    .and_then(|(_, trailing)| non_empty(trailing))
+            });
+            Self {
+                category: positional(0),
+                software: positional(1),
+                summary,
+            }
+        };
+
+        if classification == Self::default() {
+            None
+        } else {
+            Some(classification)
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -44,6 +162,9 @@ pub struct SystemInfo {
 pub enum CaptureServiceStatus {
     Running,
     Stopped,
+    Paused,
+    /// 截屏连续失败且检测到屏幕录制权限被撤销，需用户重新授权后手动启动
+    PermissionLost,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -54,6 +175,24 @@ pub struct CaptureServiceState {
     pub total_captures: u64,
     pub last_capture_time: Option<DateTime<Local>>,
     pub config_hash: String, // 用于检测配置变更
+    /// 暂停状态下的自动恢复时间（None 表示需手动恢复）
+    #[serde(default)]
+    pub paused_until: Option<DateTime<Local>>,
+    /// 累计估算费用（按 --price-per-1k-* 单价计算）
+    #[serde(default)]
+    pub total_estimated_cost: f64,
+    /// 因不在工作时间（--schedule）而停止
+    #[serde(default)]
+    pub outside_schedule: bool,
+    /// 未被 --analysis-sample-rate 抽中、未经模型分析的截屏数（计入 total_captures）
+    #[serde(default)]
+    pub sampled_out_captures: u64,
+    /// 数据目录可用空间低于 --min-free-mb 而暂停截屏时检测到的剩余空间（MB），None 表示空间充足
+    #[serde(default)]
+    pub low_disk_free_mb: Option<u64>,
+    /// 工作时间内手动停止后，到下一个工作时间边界之前重启服务也不按 --schedule 自动开始截屏
+    #[serde(default)]
+    pub manual_stop_until: Option<DateTime<Local>>,
 }
 
 impl Default for CaptureServiceState {
@@ -65,6 +204,12 @@ impl Default for CaptureServiceState {
             total_captures: 0,
             last_capture_time: None,
             config_hash: String::new(),
+            paused_until: None,
+            total_estimated_cost: 0.0,
+            outside_schedule: false,
+            sampled_out_captures: 0,
+            low_disk_free_mb: None,
+            manual_stop_until: None,
         }
     }
 }
@@ -74,11 +219,60 @@ impl Default for CaptureServiceState {
 pub enum ServiceCommand {
     Start,
     Stop,
+    Pause { resume_after_secs: Option<u64> },
     Status,
     ClipboardStatus,
     ClipboardList { limit: Option<usize> },
     ClipboardSave { id: String, target_dir: Option<String> },
     ClipboardAutoSave { enabled: bool },
+    /// 订阅截屏进度事件：连接保持打开，服务端持续推送 CaptureEvent 帧
+    Subscribe,
+    /// 获取运行指标（供 /metrics 导出）
+    Metrics,
+    /// 立即执行一次截屏并分析（不影响定时截屏），响应消息中返回分析描述
+    CaptureNow,
+    /// 把内存中最近上传给模型的截图（--keep-last-frames）写入调试目录
+    DumpFrames,
+}
+
+/// 控制请求：命令及控制令牌（服务端设置了 --control-token 时必须一致）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceRequest {
+    pub command: ServiceCommand,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// 截屏进度事件，MCP 服务器以 notifications/message 通知经由客户端的 /sse 流推送
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CaptureEvent {
+    /// 开始截屏
+    CaptureStarted {
+        timestamp: DateTime<Local>,
+        screenshot_path: String,
+    },
+    /// 开始调用模型分析
+    AnalysisStarted {
+        timestamp: DateTime<Local>,
+        model: String,
+    },
+    /// 分析完成（包括去重复用和 --no-analysis 跳过）
+    AnalysisCompleted {
+        timestamp: DateTime<Local>,
+        description: String,
+        model: Option<String>,
+        total_tokens: Option<u32>,
+    },
+    /// 分析最终失败（重试耗尽）
+    AnalysisFailed {
+        timestamp: DateTime<Local>,
+        error: String,
+    },
+    /// 活动日志已保存，携带完整日志（供 /logs/stream 实时推送）
+    LogSaved {
+        log: Box<ActivityLog>,
+    },
 }
 
 // 新增：服务响应
@@ -88,6 +282,39 @@ pub struct ServiceResponse {
     pub message: String,
     pub state: Option<CaptureServiceState>,
     pub clipboard_status: Option<ClipboardStatus>,
+    #[serde(default)]
+    pub metrics: Option<ServiceMetrics>,
+}
+
+/// 独立截屏服务的运行指标（进程内累计，服务重启后清零）
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ServiceMetrics {
+    pub tokens_total: u64,
+    pub capture_errors_total: u64,
+    pub window_switches_total: u64,
+    /// 与 metrics::ANALYSIS_DURATION_BUCKETS 一一对应的累计计数
+    pub analysis_duration_buckets: Vec<u64>,
+    pub analysis_duration_sum_seconds: f64,
+    pub analysis_duration_count: u64,
+    /// 最近若干次截屏各阶段的平均耗时（见 metrics::CAPTURE_PHASE_WINDOW），尚无截屏时为 None
+    #[serde(default)]
+    pub capture_phase_avg_ms: Option<CapturePhaseTimings>,
+    /// 参与平均的截屏次数
+    #[serde(default)]
+    pub capture_phase_samples: usize,
+}
+
+/// 一次截屏各阶段的耗时（毫秒），用于判断慢在截屏、上下文采集还是模型分析
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CapturePhaseTimings {
+    /// 采集系统上下文（前台窗口、进程信息等）
+    pub context_ms: u64,
+    /// 截屏与图片处理
+    pub screenshot_ms: u64,
+    /// 模型分析（含重试；批量分析时为整批耗时，复用描述或跳过分析时为 0）
+    pub analysis_ms: u64,
+    /// 写入活动日志
+    pub log_write_ms: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -118,4 +345,75 @@ pub struct ClipboardStatus {
     pub auto_save: bool,
     pub total_items: usize,
     pub last_capture_time: Option<DateTime<Local>>,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classification(category: &str, software: &str, summary: &str) -> Option<ActivityClassification> {
+        Some(ActivityClassification {
+            category: Some(category.to_string()),
+            software: Some(software.to_string()),
+            summary: Some(summary.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_parse_english_labels() {
+        assert_eq!(
+            ActivityClassification::parse("【Category】Coding\n【Software】VS Code\n【Summary】Writing a parser"),
+            classification("Coding", "VS Code", "Writing a parser")
+        );
+        assert_eq!(
+            ActivityClassification::parse("【category】Reading【SOFTWARE】Safari【Summary of the main work】Docs"),
+            classification("Reading", "Safari", "Docs")
+        );
+    }
+
+    #[test]
+    fn test_parse_full_output() {
+        assert_eq!(
+            ActivityClassification::parse("【类型】编程\n【软件】VS Code\n【主要工作摘要】编写日志解析器"),
+            classification("编程", "VS Code", "编写日志解析器")
+        );
+        assert_eq!(
+            ActivityClassification::parse("【编程】【VS Code】【编写代码】"),
+            classification("编程", "VS Code", "编写代码")
+        );
+        // 摘要直接跟在第二个括号之后
+        assert_eq!(
+            ActivityClassification::parse("【编程】【VS Code】编写代码"),
+            classification("编程", "VS Code", "编写代码")
+        );
+    }
+
+    #[test]
+    fn test_parse_partial_output() {
+        assert_eq!(
+            ActivityClassification::parse("【类型】编程\n模型没有给出其余字段"),
+            Some(ActivityClassification {
+                category: Some("编程".to_string()),
+                software: None,
+                summary: None,
+            })
+        );
+        assert_eq!(
+            ActivityClassification::parse("【软件】Safari【摘要】"),
+            Some(ActivityClassification {
+                category: None,
+                software: Some("Safari".to_string()),
+                summary: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_garbled_output() {
+        assert_eq!(ActivityClassification::parse("用户正在浏览网页"), None);
+        assert_eq!(ActivityClassification::parse("【类型 编程 软件 Safari"), None);
+        assert_eq!(ActivityClassification::parse("【】【 】"), None);
+        assert_eq!(ActivityClassification::parse("【类型】\n【软件】\n【主要工作摘要】"), None);
+        assert_eq!(ActivityClassification::parse(""), None);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::backend::{analyze_with_backend, AnalysisRequest};
+use crate::config::Config;
+use crate::models::{ActivityClassification, ActivityLog};
+use crate::siliconflow::RequestOptions;
+use crate::timezone::LogTimezone;
+use chrono::Duration;
+use std::error::Error;
+
+/// 每段最多列出的不同活动摘要数
+const MAX_SUMMARIES_PER_SEGMENT: usize = 3;
+
+/// 单条摘要的最大字符数
+const SUMMARY_MAX_CHARS: usize = 40;
+
+/// 送给模型的单条描述最大字符数
+const LLM_LINE_MAX_CHARS: usize = 120;
+
+/// 连续停留在同一应用的一段时间
+struct Segment<'a> {
+    app: &'a str,
+    first: &'a ActivityLog,
+    last: &'a ActivityLog,
+    /// 去重后的活动摘要（按出现顺序）
+    summaries: Vec<String>,
+}
+
+/// 本地拼接叙述（不调用模型）：把连续的同应用记录合并为一段，段内摘要去重，
+/// 开头给出时间范围和用时最多的应用。logs 需按时间排序，每条按一个截屏间隔估算时长
+pub fn local_narrative(logs: &[&ActivityLog], tz: LogTimezone, interval_secs: u64) -> String {
+    let (Some(first), Some(last)) = (logs.first(), logs.last()) else {
+        return "这段时间没有活动记录。".to_string();
+    };
+
+    let segments = segments(logs, interval_secs);
+    let mut app_secs: Vec<(&str, i64)> = Vec::new();
+    for segment in &segments {
+        let secs = segment_secs(segment, interval_secs);
+        match app_secs.iter_mut().find(|(app, _)| *app == segment.app) {
+            Some((_, total)) => *total += secs,
+            None => app_secs.push((segment.app, secs)),
+        }
+    }
+    app_secs.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+
+    let mut text = format!(
+        "{} – {} 共 {} 条记录",
+        tz.format(&first.timestamp, "%H:%M"),
+        tz.format(&segment_end(last, interval_secs), "%H:%M"),
+        logs.iter().map(|log| log.entry_count()).sum::<u32>()
+    );
+    let top: Vec<String> = app_secs
+        .iter()
+        .take(3)
+        .map(|(app, secs)| format!("{}（约 {} 分钟）", app, (secs + 59) / 60))
+        .collect();
+    text.push_str(&format!("，主要在 {}。", top.join("、")));
+
+    let parts: Vec<String> = segments
+        .iter()
+        .map(|segment| {
+            let mut part = format!(
+                "{} – {} 在 {}",
+                tz.format(&segment.first.timestamp, "%H:%M"),
+                tz.format(&segment_end(segment.last, interval_secs), "%H:%M"),
+                segment.app
+            );
+            if !segment.summaries.is_empty() {
+                part.push('：');
+                part.push_str(&segment.summaries.join("、"));
+            }
+            part
+        })
+        .collect();
+    text.push_str(&parts.join("；"));
+    text.push('。');
+    text
+}
+
+/// 调用配置的模型（纯文本请求，只尝试一次）把记录写成一段连贯的叙述
+pub async fn llm_narrative(
+    config: &Config,
+    logs: &[&ActivityLog],
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if logs.is_empty() {
+        return Ok("这段时间没有活动记录。".to_string());
+    }
+    config.ensure_api_key()?;
+
+    let tz = config.timezone;
+    // (时间, 应用, 描述, 条数)：连续相同的描述只发送一次，附带重复次数
+    let mut lines: Vec<(String, &str, String, u32)> = Vec::new();
+    for log in logs.iter().filter(|log| is_analyzed(log)) {
+        let app = app_name(log);
+        let description = truncate(first_line(&log.description), LLM_LINE_MAX_CHARS);
+        match lines.last_mut() {
+            Some((_, last_app, last_description, count)) if *last_app == app && *last_description == description => {
+                *count += log.entry_count();
+            }
+            _ => lines.push((tz.format(&log.timestamp, "%H:%M"), app, description, log.entry_count())),
+        }
+    }
+    if lines.is_empty() {
+        return Err("范围内没有可用于叙述的分析结果".into());
+    }
+    let timeline: Vec<String> = lines
+        .into_iter()
+        .map(|(time, app, description, count)| match count {
+            1 => format!("{} [{}] {}", time, app, description),
+            _ => format!("{} [{}] {}（×{}）", time, app, description, count),
+        })
+        .collect();
+
+    let prompt = format!(
+        "以下是用户在 {} 到 {} 之间的屏幕活动记录，按时间先后排列，每行格式为“时间 [应用] 截图描述”。\n\
+         请用第二人称（“你”）写一段连贯的中文叙述（150~300 字），概括这段时间主要在做什么、经历了哪几个阶段以及之间的切换；\
+         不要逐条罗列，不要输出标题或列表，不要编造记录中没有的内容。\n\n{}",
+        tz.format(&logs[0].timestamp, "%Y-%m-%d %H:%M"),
+        tz.format(&logs[logs.len() - 1].end_timestamp(), "%Y-%m-%d %H:%M"),
+        timeline.join("\n")
+    );
+
+    let options = RequestOptions::from_config(config);
+    let result = analyze_with_backend(&AnalysisRequest::new(config, &prompt), &options).await?;
+    Ok(result.description.trim().to_string())
+}
+
+/// 合并连续的同应用记录，间隔超过两个截屏周期视为中断
+fn segments<'a>(logs: &[&'a ActivityLog], interval_secs: u64) -> Vec<Segment<'a>> {
+    let max_gap = (interval_secs * 2).max(1) as i64;
+    let mut segments: Vec<Segment<'a>> = Vec::new();
+
+    for &log in logs {
+        let app = app_name(log);
+        let continues = segments.last().is_some_and(|segment| {
+            segment.app == app && (log.timestamp - segment.last.end_timestamp()).num_seconds() <= max_gap
+        });
+        if !continues {
+            segments.push(Segment { app, first: log, last: log, summaries: Vec::new() });
+        }
+        let Some(segment) = segments.last_mut() else { continue };
+        segment.last = log;
+        if let Some(summary) = activity_summary(log) {
+            if !segment.summaries.contains(&summary) && segment.summaries.len() < MAX_SUMMARIES_PER_SEGMENT {
+                segment.summaries.push(summary);
+            }
+        }
+    }
+
+    segments
+}
+
+fn segment_end(log: &ActivityLog, interval_secs: u64) -> chrono::DateTime<chrono::Local> {
+    log.end_timestamp() + Duration::seconds(interval_secs as i64)
+}
+
+fn segment_secs(segment: &Segment, interval_secs: u64) -> i64 {
+    (segment_end(segment.last, interval_secs) - segment.first.timestamp).num_seconds().max(0)
+}
+
+/// 优先取结构化的【主要工作摘要】，否则取描述首行；跳过分析失败和未分析的记录
+fn activity_summary(log: &ActivityLog) -> Option<String> {
+    if !is_analyzed(log) {
+        return None;
+    }
+    let summary = log
+        .classification
+        .clone()
+        .or_else(|| ActivityClassification::parse(&log.description))
+        .and_then(|c| c.summary)
+        .unwrap_or_else(|| first_line(&log.description).to_string());
+    let summary = summary.trim();
+    (!summary.is_empty()).then(|| truncate(summary, SUMMARY_MAX_CHARS))
+}
+
+/// 描述来自模型分析（而非分析失败或 --no-analysis 的占位）
+fn is_analyzed(log: &ActivityLog) -> bool {
+    !log.analysis_failed && !log.description.trim_start().starts_with("[analysis ")
+}
+
+fn app_name(log: &ActivityLog) -> &str {
+    log.context
+        .as_ref()
+        .and_then(|ctx| ctx.active_app.as_deref())
+        .filter(|app| !app.is_empty())
+        .unwrap_or("未知软件")
+}
+
+fn first_line(text: &str) -> &str {
+    text.trim().lines().next().unwrap_or("")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() > max_chars {
+        format!("{}…", text.chars().take(max_chars).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(time: &str, app: &str, description: &str) -> ActivityLog {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": format!("2024-05-06T{}:00+08:00", time),
+            "description": description,
+            "context": { "active_app": app, "timestamp": format!("2024-05-06T{}:00+08:00", time) },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_local_narrative() {
+        let tz = LogTimezone::parse("+08:00").unwrap();
+        let logs = [
+            log("10:00", "Code", "【编程】【Code】【编写解析器】"),
+            log("10:01", "Code", "【编程】【Code】【编写解析器】"),
+            log("10:02", "Code", "【编程】【Code】【运行测试】"),
+            log("10:03", "Chrome", "[analysis failed: timeout]"),
+            log("10:04", "Chrome", "【学习】【Chrome】【查阅 tokio 文档】"),
+        ];
+        let refs: Vec<&ActivityLog> = logs.iter().collect();
+        let text = local_narrative(&refs, tz, 60);
+
+        assert_eq!(
+            text,
+            "10:00 – 10:05 共 5 条记录，主要在 Code（约 3 分钟）、Chrome（约 2 分钟）。\
+             10:00 – 10:03 在 Code：编写解析器、运行测试；10:03 – 10:05 在 Chrome：查阅 tokio 文档。"
+        );
+        assert_eq!(local_narrative(&[], tz, 60), "这段时间没有活动记录。");
+    }
+}
@@ -0,0 +1,201 @@
+use crate::config::Config;
+use crate::context::SystemContext;
+use crate::siliconflow;
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 检查模型接口是否可连接的超时时间
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 无法连接模型接口、等待网络恢复后补分析的截图
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfflineItem {
+    /// 截屏时间，补写日志时沿用
+    pub timestamp: DateTime<Local>,
+    pub screenshot_path: String,
+    #[serde(default)]
+    pub thumbnail_path: Option<String>,
+    /// 截屏时采集的系统上下文
+    pub context: SystemContext,
+}
+
+impl OfflineItem {
+    fn same_as(&self, other: &OfflineItem) -> bool {
+        self.timestamp == other.timestamp && self.screenshot_path == other.screenshot_path
+    }
+}
+
+/// 截屏循环入队与后台重试会同时改写队列文件，读写时加锁
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 读取队列（按截屏时间先后）；文件不存在时为空，损坏时给出警告并视为空
+fn load(config: &Config) -> Vec<OfflineItem> {
+    let path = config.get_offline_queue_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("⚠️ 离线重试队列文件损坏，已忽略: {} ({})", path.display(), e);
+        Vec::new()
+    })
+}
+
+/// 写回队列；队列为空时删除文件
+fn save(config: &Config, items: &[OfflineItem]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = config.get_offline_queue_path();
+    if items.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(items)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// 加入队列；超出 --offline-queue-size 时移出最早的截图并返回，由调用方写入分析失败记录。
+/// 剪贴板文本可能含密码、验证码，不随队列落盘，补分析时不再发送
+pub fn enqueue(config: &Config, mut item: OfflineItem) -> Result<Vec<OfflineItem>, Box<dyn Error + Send + Sync>> {
+    item.context.clipboard_text = None;
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut items = load(config);
+    items.push(item);
+    items.sort_by_key(|item| item.timestamp);
+    let overflow = items.len().saturating_sub(config.offline_queue_size);
+    let dropped = items.drain(..overflow).collect();
+    save(config, &items)?;
+    Ok(dropped)
+}
+
+/// 移出超过 --offline-queue-max-age-hours 的截图并返回
+pub fn take_expired(config: &Config) -> Result<Vec<OfflineItem>, Box<dyn Error + Send + Sync>> {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let items = load(config);
+    let cutoff = Local::now() - ChronoDuration::hours(config.offline_queue_max_age_hours as i64);
+    let (expired, kept): (Vec<_>, Vec<_>) = items.into_iter().partition(|item| item.timestamp < cutoff);
+    if !expired.is_empty() {
+        save(config, &kept)?;
+    }
+    Ok(expired)
+}
+
+/// 队列中最早的截图
+pub fn peek(config: &Config) -> Option<OfflineItem> {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    load(config).into_iter().next()
+}
+
+/// 补分析完成（或放弃）后从队列移除
+pub fn remove(config: &Config, item: &OfflineItem) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut items = load(config);
+    items.retain(|queued| !queued.same_as(item));
+    save(config, &items)
+}
+
+/// 队列中等待补分析的截图数
+pub fn len(config: &Config) -> usize {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    load(config).len()
+}
+
+/// 模型接口是否可连接：收到任何 HTTP 响应（包括 404 / 405）都说明网络已恢复
+pub async fn api_reachable(config: &Config) -> bool {
+    let client = match siliconflow::http_client(
+        REACHABILITY_TIMEOUT,
+        REACHABILITY_TIMEOUT.min(Duration::from_secs(config.api_connect_timeout.max(1))),
+        config.api_proxy().ok().flatten().as_ref(),
+    ) {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+    client.head(&config.api_url).send().await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+
+    fn test_item(timestamp: DateTime<Local>, name: &str) -> OfflineItem {
+        OfflineItem {
+            timestamp,
+            screenshot_path: format!("/tmp/{}.png", name),
+            thumbnail_path: None,
+            context: SystemContext {
+                username: String::new(),
+                hostname: None,
+                os_name: None,
+                os_version: None,
+                processes_top: Vec::new(),
+                active_window: None,
+                installed_apps: Vec::new(),
+                input_activity: None,
+                clipboard_text: None,
+                since_last_capture: None,
+                window_info_disabled: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_enqueue_drops_clipboard_text() {
+        let config = test_config("offline_clipboard");
+        let mut item = test_item(Local::now(), "a");
+        item.context.clipboard_text = Some("验证码 123456".to_string());
+        enqueue(&config, item).unwrap();
+
+        let queued = fs::read_to_string(config.get_offline_queue_path()).unwrap();
+        assert!(!queued.contains("123456"));
+        assert!(!queued.contains("clipboard_text"));
+        fs::remove_dir_all(config.get_data_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_enqueue_overflow_drops_oldest() {
+        let mut config = test_config("offline_overflow");
+        config.offline_queue_size = 2;
+        let now = Local::now();
+        assert!(enqueue(&config, test_item(now - ChronoDuration::minutes(1), "b")).unwrap().is_empty());
+        assert!(enqueue(&config, test_item(now, "c")).unwrap().is_empty());
+
+        let dropped = enqueue(&config, test_item(now - ChronoDuration::minutes(2), "a")).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].screenshot_path, "/tmp/a.png");
+        assert_eq!(len(&config), 2);
+        assert_eq!(peek(&config).unwrap().screenshot_path, "/tmp/b.png");
+        fs::remove_dir_all(config.get_data_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_take_expired_and_remove() {
+        let config = test_config("offline_expiry");
+        let now = Local::now();
+        let stale = test_item(now - ChronoDuration::hours(config.offline_queue_max_age_hours as i64 + 1), "stale");
+        let fresh = test_item(now, "fresh");
+        enqueue(&config, stale).unwrap();
+        enqueue(&config, fresh.clone()).unwrap();
+
+        let expired = take_expired(&config).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].screenshot_path, "/tmp/stale.png");
+        assert!(take_expired(&config).unwrap().is_empty());
+        assert_eq!(len(&config), 1);
+
+        remove(&config, &fresh).unwrap();
+        assert!(peek(&config).is_none());
+        // 队列清空后删除文件，再次移除不报错
+        assert!(!config.get_offline_queue_path().exists());
+        remove(&config, &fresh).unwrap();
+        fs::remove_dir_all(config.get_data_dir()).unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::Duration;
+use crate::backend::AnalysisRequest;
+use crate::error::ScreenTimeError;
+use crate::models::TokenUsage;
+use crate::response_cache::{self, CacheRequest};
+use crate::siliconflow::{self, AnalysisResult, RequestOptions};
+
+/// Ollama 原生 /api/chat 请求
+#[derive(Serialize, Debug)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    /// 关闭流式输出，一次性返回完整结果
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<OllamaOptions>,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaMessage {
+    role: String,
+    content: String,
+    /// base64 编码的图片（不带 data: 前缀）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaResponse {
+    message: Option<OllamaResponseMessage>,
+    /// 输入 token 数
+    prompt_eval_count: Option<u32>,
+    /// 输出 token 数
+    eval_count: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaResponseMessage {
+    content: String,
+    /// 开启思考的模型单独返回的推理过程
+    #[serde(default)]
+    thinking: Option<String>,
+}
+
+impl OllamaOptions {
+    /// max_tokens / temperature 映射到 Ollama 的 options，均未设置时不携带
+    fn from_request_options(options: &RequestOptions) -> Option<Self> {
+        if options.max_tokens.is_none() && options.temperature.is_none() {
+            return None;
+        }
+        Some(Self {
+            num_predict: options.max_tokens,
+            temperature: options.temperature,
+        })
+    }
+}
+
+/// 发送最小的纯文本请求（num_predict = 1），用于验证 Ollama 地址和模型是否可用
+pub async fn ping(
+    api_url: &str,
+    model: &str,
+    timeout_secs: u64,
+    connect_timeout_secs: u64,
+    proxy: Option<&reqwest::Proxy>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = siliconflow::http_client(
+        Duration::from_secs(timeout_secs),
+        Duration::from_secs(connect_timeout_secs),
+        proxy,
+    )?;
+    let request_body = serde_json::json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": "ping" }],
+        "stream": false,
+        "options": { "num_predict": 1 },
+    });
+
+    let response = client.post(api_url).json(&request_body).send().await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API请求失败: {} - {}", status, error_text).into());
+    }
+    Ok(())
+}
+
+/// 与 siliconflow::analyze_screenshot_with_prompt 参数一致，按 Ollama 原生格式请求（不使用 api_key）
+/// Ollama 的消息内容是单个字符串，提示词、系统上下文和活动历史按顺序拼接
+pub async fn analyze_screenshot_with_prompt(
+    request: &AnalysisRequest<'_>,
+    options: &RequestOptions,
+) -> Result<AnalysisResult, ScreenTimeError> {
+    let cache_request = CacheRequest {
+        backend: "ollama",
+        api_url: request.api_url,
+        model: request.model,
+        image_paths: request.image_paths,
+        prompt: request.prompt,
+        extra_context: request.extra_context,
+        activity_history: request.activity_history,
+    };
+    response_cache::cached(options, cache_request, || async move {
+        let _permit = options.acquire_analysis_slot().await;
+        request_analysis(request, options).await
+    })
+    .await
+}
+
+/// 发送分析请求（不经过缓存）
+async fn request_analysis(
+    request: &AnalysisRequest<'_>,
+    options: &RequestOptions,
+) -> Result<AnalysisResult, ScreenTimeError> {
+    let AnalysisRequest {
+        api_url,
+        model,
+        image_paths,
+        prompt,
+        extra_context,
+        activity_history,
+        timeout_secs,
+        ..
+    } = *request;
+    let start_time = std::time::Instant::now();
+
+    let client =
+        siliconflow::http_client(Duration::from_secs(timeout_secs), options.connect_timeout, options.proxy.as_ref())?;
+
+    // Ollama 按内容识别图片格式，压缩后的 JPEG 无需额外标注
+    let mut images = Vec::with_capacity(image_paths.len());
+    for image_path in image_paths {
+        images.push(siliconflow::encode_image_for_upload(image_path, options.max_upload_bytes).await?.0);
+    }
+
+    let mut content = prompt.to_string();
+    if let Some(ctx) = extra_context {
+        content.push_str(&format!("\n\n以下是当前系统上下文，请结合截图一起分析：\n{}", ctx));
+    }
+    if let Some(history) = activity_history {
+        content.push_str(&format!(
+            "\n\n{}以下是用户最近的活动历史，仅供参考。请独立分析当前截图，当前行为可能与历史活动相关，也可能完全无关。",
+            history
+        ));
+    }
+
+    let request_body = OllamaRequest {
+        model: model.to_string(),
+        messages: vec![OllamaMessage {
+            role: "user".to_string(),
+            content,
+            images,
+        }],
+        stream: false,
+        options: OllamaOptions::from_request_options(options),
+    };
+
+    // 客户端限速：与上次调用保持最小间隔
+    siliconflow::wait_for_min_interval(options.min_interval).await;
+
+    let response = client.post(api_url).json(&request_body).send().await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(siliconflow::parse_retry_after);
+        let error_text = response.text().await?;
+        return Err(siliconflow::response_error(status, retry_after, error_text));
+    }
+
+    let response_text = response.text().await?;
+
+    match serde_json::from_str::<OllamaResponse>(&response_text) {
+        Ok(response) => {
+            // 思考模型的推理过程从描述中分离，单独保存
+            let (description, reasoning) = match &response.message {
+                Some(message) => siliconflow::split_reasoning(&message.content, message.thinking.as_deref()),
+                None => siliconflow::split_reasoning("", None),
+            };
+
+            let token_usage = if response.prompt_eval_count.is_some() || response.eval_count.is_some() {
+                Some(TokenUsage {
+                    prompt_tokens: response.prompt_eval_count,
+                    completion_tokens: response.eval_count,
+                    total_tokens: Some(
+                        response.prompt_eval_count.unwrap_or(0) + response.eval_count.unwrap_or(0),
+                    ),
+                })
+            } else {
+                None
+            };
+
+            Ok(AnalysisResult {
+                description,
+                token_usage,
+                processing_time: start_time.elapsed(),
+                model: Some(model.to_string()),
+                reasoning,
+            })
+        }
+        Err(e) => {
+            eprintln!("解析Ollama响应时出错: {}", e);
+            eprintln!("原始响应: {}", response_text);
+            Err(ScreenTimeError::other("解析API响应失败"))
+        }
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::config::Config;
 use crate::logger;
+use crate::verbosity::text_println;
 use chrono::{Duration, Local};
 use std::error::Error;
 use std::time::Duration as StdDuration;
@@ -58,7 +59,7 @@ pub async fn run_reporter_loop(config: Config) {
     let interval_minutes = config.openclaw_report_interval_minutes;
     let interval_duration = tokio::time::Duration::from_secs(interval_minutes * 60);
 
-    println!(
+    text_println!(
         "📤 OpenClaw agent 已启用：每 {} 分钟向 {} 提交摘要并由智能体总结",
         interval_minutes,
         url
@@ -83,7 +84,7 @@ pub async fn run_reporter_loop(config: Config) {
         if let Err(e) = send_agent(&url, &token, &text).await {
             eprintln!("⚠️ OpenClaw agent 上报失败: {}", e);
         } else {
-            println!("📤 OpenClaw agent 已提交，本周期 {} 条记录，由智能体做总结", logs.len());
+            text_println!("📤 OpenClaw agent 已提交，本周期 {} 条记录，由智能体做总结", logs.len());
         }
     }
 }
@@ -1,5 +1,8 @@
 use std::process::Command;
 use std::error::Error;
+use std::io::IsTerminal;
+use crate::error::ScreenTimeError;
+use crate::verbosity::{info_println, text_println};
 
 
 
@@ -22,6 +25,18 @@ impl PermissionStatus {
     pub fn has_missing_permissions(&self) -> bool {
         !self.all_granted()
     }
+
+    /// 缺少的权限名称，如 "屏幕录制、辅助功能"
+    fn missing_names(&self) -> String {
+        let mut missing = Vec::new();
+        if !self.screen_recording {
+            missing.push("屏幕录制");
+        }
+        if !self.accessibility {
+            missing.push("辅助功能");
+        }
+        missing.join("、")
+    }
 }
 
 /// 检查屏幕录制权限
@@ -137,14 +152,14 @@ pub fn check_accessibility_permission() -> bool {
 
 /// 检查所有必需的权限
 pub fn check_all_permissions() -> PermissionStatus {
-    println!("正在检查系统权限...");
+    info_println!("正在检查系统权限...");
     
     let screen_recording = check_screen_recording_permission();
     let accessibility = check_accessibility_permission();
     
-    println!("权限检查结果:");
-    println!("  - 屏幕录制权限: {}", if screen_recording { "✅ 已授权" } else { "❌ 未授权" });
-    println!("  - 辅助功能权限: {}", if accessibility { "✅ 已授权" } else { "❌ 未授权" });
+    info_println!("权限检查结果:");
+    info_println!("  - 屏幕录制权限: {}", if screen_recording { "✅ 已授权" } else { "❌ 未授权" });
+    info_println!("  - 辅助功能权限: {}", if accessibility { "✅ 已授权" } else { "❌ 未授权" });
     
     PermissionStatus {
         screen_recording,
@@ -191,7 +206,7 @@ pub fn open_permission_settings(permission_type: &str) -> Result<(), Box<dyn Err
     
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
     {
-        println!("当前系统无需打开权限设置");
+        info_println!("当前系统无需打开权限设置");
         Ok(())
     }
 }
@@ -199,112 +214,128 @@ pub fn open_permission_settings(permission_type: &str) -> Result<(), Box<dyn Err
 /// 显示权限请求提示并引导用户
 pub fn prompt_for_permissions(status: &PermissionStatus) -> Result<(), Box<dyn Error + Send + Sync>> {
     if status.all_granted() {
-        println!("✅ 所有权限已授权，可以正常使用！");
+        info_println!("✅ 所有权限已授权，可以正常使用！");
         return Ok(());
     }
     
-    println!("\n⚠️  缺少必要权限，程序需要以下权限才能正常工作：");
+    text_println!("\n⚠️  缺少必要权限，程序需要以下权限才能正常工作：");
     
     if !status.screen_recording {
-        println!("\n📱 屏幕录制权限:");
-        println!("   - 用途：截取屏幕截图进行分析");
+        text_println!("\n📱 屏幕录制权限:");
+        text_println!("   - 用途：截取屏幕截图进行分析");
         
         if cfg!(target_os = "macos") {
-            println!("   - 操作：请在弹出的系统偏好设置中，找到 'OpenRecall' 并勾选");
-            println!("   - 提示：可能需要输入管理员密码");
-            println!("\n正在打开屏幕录制权限设置...");
+            text_println!("   - 操作：请在弹出的系统偏好设置中，找到 'OpenRecall' 并勾选");
+            text_println!("   - 提示：可能需要输入管理员密码");
+            text_println!("\n正在打开屏幕录制权限设置...");
             if let Err(e) = open_permission_settings("screen_recording") {
                 eprintln!("无法自动打开设置页面: {}", e);
-                println!("请手动打开：系统偏好设置 -> 安全性与隐私 -> 隐私 -> 屏幕录制");
+                text_println!("请手动打开：系统偏好设置 -> 安全性与隐私 -> 隐私 -> 屏幕录制");
             }
         } else if cfg!(target_os = "windows") {
-            println!("   - 操作：请在 Windows 设置中允许应用访问屏幕内容");
-            println!("   - 提示：可能需要管理员权限");
-            println!("\n正在打开 Windows 隐私设置...");
+            text_println!("   - 操作：请在 Windows 设置中允许应用访问屏幕内容");
+            text_println!("   - 提示：可能需要管理员权限");
+            text_println!("\n正在打开 Windows 隐私设置...");
             if let Err(e) = open_permission_settings("screen_recording") {
                 eprintln!("无法自动打开设置页面: {}", e);
-                println!("请手动打开：设置 -> 隐私 -> 应用权限 -> 屏幕录制");
+                text_println!("请手动打开：设置 -> 隐私 -> 应用权限 -> 屏幕录制");
             }
         }
     }
     
     if !status.accessibility {
-        println!("\n🔍 辅助功能权限:");
-        println!("   - 用途：获取当前活跃窗口和应用程序信息");
-        println!("   - 注意：这有助于AI更准确地分析您的使用情况");
+        text_println!("\n🔍 辅助功能权限:");
+        text_println!("   - 用途：获取当前活跃窗口和应用程序信息");
+        text_println!("   - 注意：这有助于AI更准确地分析您的使用情况");
         
         if cfg!(target_os = "macos") {
-            println!("   - 操作：请在弹出的系统偏好设置中，找到 'OpenRecall' 并勾选");
-            println!("\n正在打开辅助功能权限设置...");
+            text_println!("   - 操作：请在弹出的系统偏好设置中，找到 'OpenRecall' 并勾选");
+            text_println!("\n正在打开辅助功能权限设置...");
             if let Err(e) = open_permission_settings("accessibility") {
                 eprintln!("无法自动打开设置页面: {}", e);
-                println!("请手动打开：系统偏好设置 -> 安全性与隐私 -> 隐私 -> 辅助功能");
+                text_println!("请手动打开：系统偏好设置 -> 安全性与隐私 -> 隐私 -> 辅助功能");
             }
         } else if cfg!(target_os = "windows") {
-            println!("   - 操作：程序将尝试使用 PowerShell 或系统命令获取窗口信息");
-            println!("\n正在打开 Windows 辅助功能设置...");
+            text_println!("   - 操作：程序将尝试使用 PowerShell 或系统命令获取窗口信息");
+            text_println!("\n正在打开 Windows 辅助功能设置...");
             if let Err(e) = open_permission_settings("accessibility") {
                 eprintln!("无法自动打开设置页面: {}", e);
-                println!("如需更多权限，请手动打开：设置 -> 轻松使用 -> 其他选项");
+                text_println!("如需更多权限，请手动打开：设置 -> 轻松使用 -> 其他选项");
             }
         }
     }
     
     if cfg!(target_os = "macos") {
-        println!("\n📋 macOS 授权步骤:");
-        println!("1. 在弹出的系统偏好设置窗口中");
-        println!("2. 点击左下角的锁图标解锁（需要管理员密码）");
-        println!("3. 找到 'OpenRecall' 或 'openrecall' 应用");
-        println!("4. 勾选对应的复选框");
-        println!("5. 重新启动本程序");
+        text_println!("\n📋 macOS 授权步骤:");
+        text_println!("1. 在弹出的系统偏好设置窗口中");
+        text_println!("2. 点击左下角的锁图标解锁（需要管理员密码）");
+        text_println!("3. 找到 'OpenRecall' 或 'openrecall' 应用");
+        text_println!("4. 勾选对应的复选框");
+        text_println!("5. 回到终端按回车重新检查");
     } else if cfg!(target_os = "windows") {
-        println!("\n📋 Windows 授权步骤:");
-        println!("1. 在弹出的 Windows 设置窗口中");
-        println!("2. 找到相关的隐私设置选项");
-        println!("3. 允许桌面应用访问相应功能");
-        println!("4. 如需要，以管理员身份运行程序");
-        println!("5. 重新启动本程序");
+        text_println!("\n📋 Windows 授权步骤:");
+        text_println!("1. 在弹出的 Windows 设置窗口中");
+        text_println!("2. 找到相关的隐私设置选项");
+        text_println!("3. 允许桌面应用访问相应功能");
+        text_println!("4. 如需要，以管理员身份运行程序");
+        text_println!("5. 回到终端按回车重新检查");
     } else {
-        println!("\n📋 授权步骤:");
-        println!("1. 根据您的操作系统设置相应权限");
-        println!("2. 重新启动本程序");
+        text_println!("\n📋 授权步骤:");
+        text_println!("1. 根据您的操作系统设置相应权限");
+        text_println!("2. 回到终端按回车重新检查");
     }
     
-    println!("\n⚠️  注意：授权后请重新启动程序以使权限生效");
+    text_println!("\n⚠️  注意：部分系统授权后需重新启动程序权限才会生效，多次检查仍未通过时请输入 'q' 退出后重新启动");
     
     Ok(())
 }
 
-/// 等待用户授权后重新检查权限
-pub fn wait_for_permissions() -> Result<PermissionStatus, Box<dyn Error + Send + Sync>> {
-    println!("\n按回车键重新检查权限，或输入 'q' 退出程序...");
-    
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    
-    if input.trim().to_lowercase() == "q" {
-        println!("程序已退出");
-        std::process::exit(0);
-    }
-    
-    let status = check_all_permissions();
-    
-    if status.has_missing_permissions() {
-        println!("\n仍有权限未授权，请按照上述步骤完成授权后重新启动程序");
-        std::process::exit(1);
+/// 缺少权限且无法交互授权时的错误
+fn missing_permission_error(status: &PermissionStatus) -> Box<dyn Error + Send + Sync> {
+    Box::new(ScreenTimeError::Permission(format!(
+        "缺少{}权限，请在终端中运行 openrecall 按提示完成授权",
+        status.missing_names()
+    )))
+}
+
+/// 等待用户授权后重新检查权限：仍未授权时重新显示引导并继续等待，直到授权通过或用户输入 'q' 退出；
+/// 标准输入已关闭时返回错误，不再空转
+pub fn wait_for_permissions(status: PermissionStatus) -> Result<PermissionStatus, Box<dyn Error + Send + Sync>> {
+    let mut status = status;
+    loop {
+        text_println!("\n按回车键重新检查权限，或输入 'q' 退出程序...");
+
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            return Err(missing_permission_error(&status));
+        }
+
+        if input.trim().to_lowercase() == "q" {
+            text_println!("程序已退出");
+            std::process::exit(0);
+        }
+
+        status = check_all_permissions();
+        if !status.has_missing_permissions() {
+            return Ok(status);
+        }
+
+        text_println!("\n仍缺少{}权限，请按照以下步骤完成授权", status.missing_names());
+        prompt_for_permissions(&status)?;
     }
-    
-    Ok(status)
 }
 
-/// 完整的权限检查和请求流程
-pub async fn ensure_permissions() -> Result<PermissionStatus, Box<dyn Error + Send + Sync>> {
+/// 完整的权限检查和请求流程；interactive 为 false 或标准输入不是终端（如 MCP 服务后台拉起独立服务）时
+/// 不等待输入，缺少权限直接返回错误
+pub async fn ensure_permissions(interactive: bool) -> Result<PermissionStatus, Box<dyn Error + Send + Sync>> {
     let status = check_all_permissions();
-    
-    if status.has_missing_permissions() {
-        prompt_for_permissions(&status)?;
-        wait_for_permissions()
-    } else {
-        Ok(status)
+    if !status.has_missing_permissions() {
+        return Ok(status);
+    }
+
+    if !interactive || !std::io::stdin().is_terminal() {
+        return Err(missing_permission_error(&status));
     }
+    prompt_for_permissions(&status)?;
+    wait_for_permissions(status)
 }
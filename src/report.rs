@@ -0,0 +1,331 @@
+use crate::config::Config;
+use crate::logger;
+use crate::models::ActivityLog;
+use crate::timezone::LogTimezone;
+use crate::verbosity::{self, text_println};
+use chrono::{Duration, NaiveDate};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+
+/// 时间线中单条描述的最大显示字符数
+const TIMELINE_SUMMARY_MAX_CHARS: usize = 60;
+
+/// 报表中展示的应用/分类数量上限
+const TOP_N: usize = 10;
+
+/// 生成活动报表（--report），输出到 stdout 或 --report-out 指定的文件；--json 时 stdout 输出包含报表 Markdown 或文件路径的 JSON
+pub fn run_report(config: &Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let days: u32 = match config.report_period.as_str() {
+        "week" => 7,
+        _ => 1,
+    };
+
+    let logs = logger::load_recent_daily_logs(config, days)?;
+    let today = config.timezone.today();
+    let start_date = today - Duration::days(days as i64 - 1);
+    let markdown = render_report(&logs, start_date, today, config.interval, config.timezone);
+
+    match &config.report_out {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    std::fs::create_dir_all(parent)?;
+                }
+            }
+            std::fs::write(path, &markdown)?;
+            text_println!("📄 报表已保存到: {}", path.display());
+        }
+        None if !verbosity::json_output() => print!("{}", markdown),
+        None => {}
+    }
+    if verbosity::json_output() {
+        verbosity::print_json(&serde_json::json!({
+            "command": "report",
+            "start_date": start_date,
+            "end_date": today,
+            "path": config.report_out,
+            "markdown": config.report_out.is_none().then_some(&markdown),
+        }))?;
+    }
+
+    Ok(())
+}
+
+/// 将日志聚合为 Markdown 报表，每条日志按一个截屏间隔估算时长，日期与时刻按 tz 划分和显示
+fn render_report(
+    logs: &[ActivityLog],
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    interval_secs: u64,
+    tz: LogTimezone,
+) -> String {
+    let mut md = if start_date == end_date {
+        format!("# 活动日报 {}\n\n", end_date.format("%Y-%m-%d"))
+    } else {
+        format!(
+            "# 活动周报 {} ~ {}\n\n",
+            start_date.format("%Y-%m-%d"),
+            end_date.format("%Y-%m-%d")
+        )
+    };
+
+    if logs.is_empty() {
+        md.push_str("## 暂无数据\n\n该时间段内没有活动记录，请确认截屏服务是否在运行。\n");
+        return md;
+    }
+
+    // 概览
+    let active_hours: BTreeSet<String> = logs
+        .iter()
+        .map(|log| tz.format(&log.timestamp, "%Y-%m-%d %H"))
+        .collect();
+    // 压缩过的日志一条代表多次截屏，按原始条数计
+    let total: usize = logs.iter().map(|log| log.entry_count() as usize).sum();
+    md.push_str("## 概览\n\n");
+    md.push_str(&format!("- 截屏次数: {}\n", total));
+    md.push_str(&format!("- 活跃小时数: {}\n", active_hours.len()));
+    md.push_str(&format!(
+        "- 估算活跃时长: {}\n",
+        format_duration(total as u64 * interval_secs)
+    ));
+    let failed = logs.iter().filter(|log| log.analysis_failed).count();
+    if failed > 0 {
+        md.push_str(&format!(
+            "- 分析失败: {} 次（{:.1}%）\n",
+            failed,
+            failed as f64 * 100.0 / total as f64
+        ));
+    }
+    let costs: Vec<f64> = logs.iter().filter_map(|log| log.estimated_cost).collect();
+    if !costs.is_empty() {
+        md.push_str(&format!(
+            "- 估算模型费用: {:.4}（{} 次分析）\n",
+            costs.iter().sum::<f64>(),
+            costs.len()
+        ));
+    }
+    md.push('\n');
+
+    // 应用使用时长
+    md.push_str("## 应用使用时长 Top\n\n");
+    md.push_str("| 应用 | 时长 | 截屏次数 |\n|------|------|----------|\n");
+    for (app, count) in top_counts(logs.iter().map(|log| (app_name(log), log.entry_count()))) {
+        md.push_str(&format!(
+            "| {} | {} | {} |\n",
+            app,
+            format_duration(count as u64 * interval_secs),
+            count
+        ));
+    }
+    md.push('\n');
+
+    // 分类统计
+    md.push_str("## 分类统计\n\n");
+    md.push_str("| 类型 | 时长 | 占比 |\n|------|------|------|\n");
+    for (category, count) in top_counts(logs.iter().map(|log| (category(log), log.entry_count()))) {
+        md.push_str(&format!(
+            "| {} | {} | {:.1}% |\n",
+            category,
+            format_duration(count as u64 * interval_secs),
+            count as f64 * 100.0 / total as f64
+        ));
+    }
+    md.push('\n');
+
+    // 时间线：按天分组，同一应用的连续记录合并为一段
+    md.push_str("## 时间线\n\n");
+    let mut by_day: BTreeMap<NaiveDate, Vec<&ActivityLog>> = BTreeMap::new();
+    for log in logs {
+        by_day.entry(tz.date_of(&log.timestamp)).or_default().push(log);
+    }
+
+    let mut date = start_date;
+    while date <= end_date {
+        if start_date != end_date {
+            md.push_str(&format!("### {}\n\n", date.format("%Y-%m-%d")));
+        }
+        match by_day.get(&date) {
+            Some(day_logs) => {
+                for line in timeline_segments(day_logs, interval_secs, tz) {
+                    md.push_str(&line);
+                    md.push('\n');
+                }
+                md.push('\n');
+            }
+            None => md.push_str("暂无数据\n\n"),
+        }
+        date += Duration::days(1);
+    }
+
+    md
+}
+
+/// 合并连续的同应用记录，间隔超过两个截屏周期视为中断
+fn timeline_segments(logs: &[&ActivityLog], interval_secs: u64, tz: LogTimezone) -> Vec<String> {
+    let max_gap = (interval_secs * 2).max(1) as i64;
+    let mut lines = Vec::new();
+    let mut i = 0;
+
+    while i < logs.len() {
+        let first = logs[i];
+        let app = app_name(first);
+        let mut last = first;
+        let mut j = i + 1;
+        while j < logs.len()
+            && app_name(logs[j]) == app
+            && (logs[j].timestamp - last.end_timestamp()).num_seconds() <= max_gap
+        {
+            last = logs[j];
+            j += 1;
+        }
+
+        let end = last.end_timestamp() + Duration::seconds(interval_secs as i64);
+        lines.push(format!(
+            "- {} – {} **{}** {}",
+            tz.format(&first.timestamp, "%H:%M"),
+            tz.format(&end, "%H:%M"),
+            app,
+            summarize(&first.description)
+        ));
+        i = j;
+    }
+
+    lines
+}
+
+/// 按出现次数（含压缩合并的条数）降序取前 TOP_N 项
+fn top_counts<'a>(items: impl Iterator<Item = (&'a str, u32)>) -> Vec<(&'a str, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (item, weight) in items {
+        *counts.entry(item).or_insert(0) += weight as usize;
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts.truncate(TOP_N);
+    counts
+}
+
+/// 按前台应用估算使用时长（毫秒）：每条记录（含压缩合并的条数）计一个截屏间隔，按时长降序
+pub fn app_usage_from_logs<'a>(logs: impl IntoIterator<Item = &'a ActivityLog>, interval_secs: u64) -> Vec<(String, u64)> {
+    let mut usage: HashMap<&str, u64> = HashMap::new();
+    for log in logs {
+        *usage.entry(app_name(log)).or_insert(0) += log.entry_count() as u64 * interval_secs * 1000;
+    }
+    let mut usage: Vec<(String, u64)> = usage.into_iter().map(|(app, ms)| (app.to_string(), ms)).collect();
+    usage.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    usage
+}
+
+fn app_name(log: &ActivityLog) -> &str {
+    log.context
+        .as_ref()
+        .and_then(|ctx| ctx.active_app.as_deref())
+        .filter(|app| !app.is_empty())
+        .unwrap_or("未知软件")
+}
+
+/// 优先使用结构化分类，旧日志没有该字段时从描述开头的【类型】中解析；分析失败的记录单独归类
+fn category(log: &ActivityLog) -> &str {
+    if log.analysis_failed {
+        return "分析失败";
+    }
+    if let Some(category) = log
+        .classification
+        .as_ref()
+        .and_then(|c| c.category.as_deref())
+    {
+        return category;
+    }
+    log.description
+        .trim_start()
+        .strip_prefix('【')
+        .and_then(|rest| rest.split_once('】'))
+        .map(|(category, _)| category.trim())
+        .filter(|category| !category.is_empty())
+        .unwrap_or("未分类")
+}
+
+/// 取描述首行并截断，避免时间线过长
+fn summarize(description: &str) -> String {
+    let line = description.trim().lines().next().unwrap_or("");
+    if line.chars().count() > TIMELINE_SUMMARY_MAX_CHARS {
+        format!("{}…", line.chars().take(TIMELINE_SUMMARY_MAX_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    if hours > 0 {
+        format!("{}小时{}分", hours, minutes)
+    } else {
+        format!("{}分", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(time: &str, app: &str, description: &str) -> ActivityLog {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": format!("2025-03-0{}Z", time),
+            "description": description,
+            "context": { "active_app": app, "window_title": null, "system_info": null, "timestamp": format!("2025-03-0{}Z", time) },
+        }))
+        .unwrap()
+    }
+
+    fn utc() -> LogTimezone {
+        LogTimezone::parse("UTC").unwrap()
+    }
+
+    #[test]
+    fn test_report_aggregates_by_app_and_category() {
+        let mut merged = log("1T09:02:00", "VSCode", "【工作】【VSCode】【调试】");
+        merged.merged_count = Some(2);
+        let mut failed = log("1T10:00:00", "Safari", "");
+        failed.analysis_failed = true;
+        let logs = [
+            log("1T09:00:00", "VSCode", "【工作】【VSCode】【写代码】"),
+            log("1T09:01:00", "VSCode", "【工作】【VSCode】【写代码】"),
+            merged,
+            log("1T09:30:00", "Safari", "【学习】【Safari】【看文档】"),
+            failed,
+        ];
+
+        let apps = top_counts(logs.iter().map(|log| (app_name(log), log.entry_count())));
+        assert_eq!(apps, [("VSCode", 4), ("Safari", 2)]);
+        let categories = top_counts(logs.iter().map(|log| (category(log), log.entry_count())));
+        assert_eq!(categories, [("工作", 4), ("分析失败", 1), ("学习", 1)]);
+        assert_eq!(
+            app_usage_from_logs(&logs, 60),
+            [("VSCode".to_string(), 240_000), ("Safari".to_string(), 120_000)]
+        );
+
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let md = render_report(&logs, date, date, 60, utc());
+        assert!(md.starts_with("# 活动日报 2025-03-01"));
+        assert!(md.contains("- 截屏次数: 6\n"));
+        assert!(md.contains("- 活跃小时数: 2\n"));
+        assert!(md.contains("| VSCode | 4分 | 4 |"));
+        assert!(md.contains("| 工作 | 4分 | 66.7% |"));
+        assert!(md.contains("- 09:00 – 09:03 **VSCode** 【工作】【VSCode】【写代码】"));
+    }
+
+    #[test]
+    fn test_report_without_logs() {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let md = render_report(&[], date, date, 60, utc());
+        assert!(md.contains("## 暂无数据"));
+        assert!(!md.contains("## 概览"));
+
+        // 周报中没有记录的日期单独标注
+        let logs = [log("3T09:00:00", "VSCode", "【工作】【VSCode】【写代码】")];
+        let md = render_report(&logs, date, date + Duration::days(2), 60, utc());
+        assert!(md.contains("### 2025-03-01\n\n暂无数据\n"));
+        assert!(md.contains("### 2025-03-03\n\n- 09:00"));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::config::Config;
+use crate::error::ScreenTimeError;
+use crate::models::TokenUsage;
+use crate::siliconflow::{AnalysisResult, RequestOptions};
+use crate::verbosity::{info_println, verbose_println};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 两次清理过期缓存之间的最小间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 上一次清理过期缓存的时间
+static LAST_PRUNE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// 分析结果缓存（--cache-dir）：每条结果保存为 <键>.json，超过 --cache-ttl-hours 的条目视为失效
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    /// None 表示永不过期
+    ttl: Option<chrono::Duration>,
+}
+
+/// 缓存文件内容
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    created_at: DateTime<Local>,
+    description: String,
+    token_usage: Option<TokenUsage>,
+    model: Option<String>,
+    #[serde(default)]
+    reasoning: Option<String>,
+}
+
+/// 一次分析请求中决定模型输出的输入，全部参与缓存键的计算
+pub struct CacheRequest<'a> {
+    /// 接口协议（siliconflow / ollama）
+    pub backend: &'a str,
+    pub api_url: &'a str,
+    pub model: &'a str,
+    pub image_paths: &'a [&'a str],
+    /// 完整提示词
+    pub prompt: &'a str,
+    pub extra_context: Option<&'a str>,
+    pub activity_history: Option<&'a str>,
+}
+
+impl ResponseCache {
+    /// 设置了 --cache-dir 时启用
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let dir = config.cache_dir.clone()?;
+        let ttl = Some(config.cache_ttl_hours)
+            .filter(|hours| *hours > 0)
+            .map(|hours| chrono::Duration::hours(hours as i64));
+        Some(Self { dir, ttl })
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn is_expired(&self, created_at: DateTime<Local>) -> bool {
+        self.ttl.is_some_and(|ttl| Local::now() - created_at > ttl)
+    }
+
+    /// 读取未过期的缓存结果；过期或损坏的条目顺便删除。
+    /// 命中时没有实际调用接口，token 用量为 None，不重复计入费用统计
+    async fn get(&self, key: &str) -> Option<AnalysisResult> {
+        let path = self.entry_path(key);
+        let content = tokio::fs::read_to_string(&path).await.ok()?;
+        let cached = match serde_json::from_str::<CachedResponse>(&content) {
+            Ok(cached) if !self.is_expired(cached.created_at) => cached,
+            _ => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return None;
+            }
+        };
+        Some(AnalysisResult {
+            description: cached.description,
+            token_usage: None,
+            processing_time: Duration::ZERO,
+            model: cached.model,
+            reasoning: cached.reasoning,
+        })
+    }
+
+    /// 保存分析结果；写入失败只打印警告，不影响分析流程
+    async fn put(&self, key: &str, result: &AnalysisResult) {
+        let cached = CachedResponse {
+            created_at: Local::now(),
+            description: result.description.clone(),
+            token_usage: result.token_usage.clone(),
+            model: result.model.clone(),
+            reasoning: result.reasoning.clone(),
+        };
+        let written = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let content = serde_json::to_string_pretty(&cached).map_err(std::io::Error::other)?;
+            tokio::fs::write(self.entry_path(key), content).await
+        }
+        .await;
+        if let Err(e) = written {
+            eprintln!("⚠️ 写入分析缓存失败: {}", e);
+        }
+        self.prune_if_due().await;
+    }
+
+    /// 定期删除过期条目，避免只写不读的缓存（如截屏循环）无限增长
+    async fn prune_if_due(&self) {
+        if self.ttl.is_none() {
+            return;
+        }
+        {
+            let mut last_prune = LAST_PRUNE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if last_prune.is_some_and(|last| last.elapsed() < PRUNE_INTERVAL) {
+                return;
+            }
+            *last_prune = Some(Instant::now());
+        }
+
+        let Ok(mut entries) = tokio::fs::read_dir(&self.dir).await else {
+            return;
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let expired = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => serde_json::from_str::<CachedResponse>(&content)
+                    .ok()
+                    .is_none_or(|cached| self.is_expired(cached.created_at)),
+                Err(_) => false,
+            };
+            if expired && tokio::fs::remove_file(&path).await.is_ok() {
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            verbose_println!("🧹 已清理 {} 条过期的分析缓存", removed);
+        }
+    }
+}
+
+/// 计算缓存键：截图内容、完整提示词（含系统上下文与活动历史）、接口、模型及影响输出的请求参数的 SHA-256；
+/// 读取截图失败时返回 None（不使用缓存，由实际请求报告错误）
+async fn cache_key(request: &CacheRequest<'_>, options: &RequestOptions) -> Option<String> {
+    let mut hasher = Sha256::new();
+    // 每段前写入长度，避免不同字段拼接后产生相同的字节序列
+    let mut update = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+    update(request.backend.as_bytes());
+    update(request.api_url.as_bytes());
+    update(request.model.as_bytes());
+    update(request.prompt.as_bytes());
+    update(format!("{:?}", request.extra_context).as_bytes());
+    update(format!("{:?}", request.activity_history).as_bytes());
+    update(
+        format!(
+            "{:?}|{:?}|{:?}|{:?}",
+            options.max_tokens, options.temperature, options.image_detail, options.max_upload_bytes
+        )
+        .as_bytes(),
+    );
+    for image_path in request.image_paths {
+        update(&tokio::fs::read(image_path).await.ok()?);
+    }
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// 启用缓存时先查缓存，命中则不请求接口；未命中时调用 analyze 并缓存成功的结果
+pub async fn cached<F, Fut>(
+    options: &RequestOptions,
+    request: CacheRequest<'_>,
+    analyze: F,
+) -> Result<AnalysisResult, ScreenTimeError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<AnalysisResult, ScreenTimeError>>,
+{
+    let Some(cache) = &options.cache else {
+        return analyze().await;
+    };
+    let key = cache_key(&request, options).await;
+    if let Some(key) = &key {
+        if let Some(result) = cache.get(key).await {
+            info_println!("🗃️ 命中分析缓存，跳过接口请求");
+            return Ok(result);
+        }
+    }
+
+    let result = analyze().await?;
+    if let Some(key) = &key {
+        cache.put(key, &result).await;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_key_covers_image_and_full_prompt() {
+        let image = std::env::temp_dir().join(format!("openrecall_cache_key_{}.png", std::process::id()));
+        std::fs::write(&image, b"image-a").unwrap();
+        let image_path = image.to_string_lossy().into_owned();
+        let image_paths = [image_path.as_str()];
+        let options = RequestOptions::default();
+        let request = |prompt| CacheRequest {
+            backend: "siliconflow",
+            api_url: "http://127.0.0.1:1234/v1/chat/completions",
+            model: "default",
+            image_paths: &image_paths,
+            prompt,
+            extra_context: None,
+            activity_history: None,
+        };
+
+        let key = cache_key(&request("描述截图"), &options).await.unwrap();
+        assert_eq!(cache_key(&request("描述截图"), &options).await.unwrap(), key);
+        assert_ne!(cache_key(&request("描述截图。"), &options).await.unwrap(), key);
+
+        std::fs::write(&image, b"image-b").unwrap();
+        assert_ne!(cache_key(&request("描述截图"), &options).await.unwrap(), key);
+        std::fs::remove_file(&image).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+use crate::timezone::LogTimezone;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime};
+
+const DAY_NAMES: [&str; 7] = [
+    "monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday",
+];
+
+/// 一天的分钟数，时间段结束时刻允许写成 24:00
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// 工作时间表（--schedule），时间按 --timezone 解释
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkSchedule {
+    ranges: Vec<ScheduleRange>,
+}
+
+/// 某些星期几的一个时间段；结束早于开始表示跨越午夜，归属开始的那一天
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScheduleRange {
+    /// 周一到周日
+    days: [bool; 7],
+    /// 距当天 00:00 的分钟数
+    start: u32,
+    /// 距开始那天 00:00 的分钟数，跨午夜时大于一天
+    end: u32,
+}
+
+impl WorkSchedule {
+    /// 解析 "Mon-Fri 09:00-12:00 13:00-18:00; Sat 10:00-14:00"：多组用 ; 或 , 分隔，
+    /// 每组为可选的星期（Mon、Mon-Fri、Fri-Mon、Daily）加一个或多个时间段；
+    /// 未写星期的组沿用前一组的星期，第一组未写时表示每天
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut ranges = Vec::new();
+        let mut days = [true; 7];
+
+        for group in value.split([';', ',']).map(str::trim).filter(|g| !g.is_empty()) {
+            let mut tokens = group.split_whitespace().peekable();
+            if let Some(parsed) = tokens.peek().and_then(|token| parse_days(token)) {
+                days = parsed?;
+                tokens.next();
+            }
+            let mut has_time = false;
+            for token in tokens {
+                let (start, end) = parse_time_range(token)?;
+                ranges.push(ScheduleRange { days, start, end });
+                has_time = true;
+            }
+            if !has_time {
+                return Err(format!("工作时间 \"{}\" 缺少时间段（如 09:00-18:00）", group));
+            }
+        }
+
+        if ranges.is_empty() {
+            return Err("工作时间为空（示例: Mon-Fri 09:00-18:00）".to_string());
+        }
+        Ok(Self { ranges })
+    }
+
+    /// 给定时间点是否在工作时间内
+    pub fn is_active(&self, now: &DateTime<Local>, tz: &LogTimezone) -> bool {
+        let today = tz.date_of(now);
+        // 前一天开始的跨午夜时间段可能覆盖今天
+        [today - Duration::days(1), today].into_iter().any(|date| {
+            self.intervals_on(date, tz)
+                .any(|(start, end)| start <= *now && *now < end)
+        })
+    }
+
+    /// 此后最近的一个开始或结束时刻
+    pub fn next_boundary(&self, now: &DateTime<Local>, tz: &LogTimezone) -> Option<DateTime<Local>> {
+        let today = tz.date_of(now);
+        (-1..=7)
+            .flat_map(|offset| self.intervals_on(today + Duration::days(offset), tz))
+            .flat_map(|(start, end)| [start, end])
+            .filter(|boundary| boundary > now)
+            .min()
+    }
+
+    /// 某天开始的所有时间段
+    fn intervals_on<'a>(
+        &'a self,
+        date: NaiveDate,
+        tz: &'a LogTimezone,
+    ) -> impl Iterator<Item = (DateTime<Local>, DateTime<Local>)> + 'a {
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        self.ranges
+            .iter()
+            .filter(move |range| range.days[weekday])
+            .map(move |range| (at_minute(tz, date, range.start), at_minute(tz, date, range.end)))
+    }
+}
+
+/// 某天 00:00 之后第 minutes 分钟（可超过一天）在该时区下的时间点
+fn at_minute(tz: &LogTimezone, date: NaiveDate, minutes: u32) -> DateTime<Local> {
+    let date = date + Duration::days((minutes / MINUTES_PER_DAY) as i64);
+    let minutes = minutes % MINUTES_PER_DAY;
+    let time = NaiveTime::from_hms_opt(minutes / 60, minutes % 60, 0).unwrap_or(NaiveTime::MIN);
+    tz.localize(date.and_time(time))
+}
+
+/// 星期部分；不是星期（例如时间段）时返回 None
+fn parse_days(token: &str) -> Option<Result<[bool; 7], String>> {
+    let lower = token.to_lowercase();
+    if matches!(lower.as_str(), "daily" | "everyday" | "*") {
+        return Some(Ok([true; 7]));
+    }
+    if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (first, last) = lower.split_once('-').unwrap_or((&lower, &lower));
+    let (Some(first), Some(last)) = (day_index(first), day_index(last)) else {
+        return Some(Err(format!("无法识别的星期 \"{}\"（如 Mon、Mon-Fri、Daily）", token)));
+    };
+    // 允许 Fri-Mon 这样跨周末的写法
+    let mut days = [false; 7];
+    let mut day = first;
+    loop {
+        days[day] = true;
+        if day == last {
+            break;
+        }
+        day = (day + 1) % 7;
+    }
+    Some(Ok(days))
+}
+
+/// 接受 Mon / Tues / Monday 等不少于三个字母的前缀
+fn day_index(name: &str) -> Option<usize> {
+    if name.len() < 3 {
+        return None;
+    }
+    DAY_NAMES.iter().position(|day| day.starts_with(name))
+}
+
+/// "09:00-18:00"，结束早于开始表示跨午夜（如 22:00-02:00）
+fn parse_time_range(token: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("无效的时间段 \"{}\"（应为 HH:MM-HH:MM，如 09:00-18:00）", token);
+    let (start, end) = token.split_once('-').ok_or_else(invalid)?;
+    let start = parse_minutes(start).filter(|&m| m < MINUTES_PER_DAY).ok_or_else(invalid)?;
+    let end = parse_minutes(end).ok_or_else(invalid)?;
+    if start == end {
+        return Err(format!("时间段 \"{}\" 的开始与结束相同", token));
+    }
+    Ok(if end < start { (start, end + MINUTES_PER_DAY) } else { (start, end) })
+}
+
+/// "HH:MM"，允许 24:00
+fn parse_minutes(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if minutes >= 60 || hours > 24 || (hours == 24 && minutes != 0) {
+        return None;
+    }
+    Some(hours * 60 + minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule() {
+        let tz = LogTimezone::parse("+08:00").unwrap();
+        let at = |value: &str| tz.parse_datetime(value).unwrap();
+        let schedule = WorkSchedule::parse("Mon-Fri 09:00-12:00, 13:00-18:00; Sat 22:00-02:00").unwrap();
+
+        // 2024-05-06 是周一
+        assert!(!schedule.is_active(&at("2024-05-06 08:59:59"), &tz));
+        assert!(schedule.is_active(&at("2024-05-06 09:00:00"), &tz));
+        assert!(!schedule.is_active(&at("2024-05-06 12:30:00"), &tz));
+        assert!(schedule.is_active(&at("2024-05-10 17:59:00"), &tz));
+        assert!(!schedule.is_active(&at("2024-05-10 18:00:00"), &tz));
+        // 周六晚上开始的时间段延续到周日凌晨
+        assert!(schedule.is_active(&at("2024-05-12 01:30:00"), &tz));
+        assert!(!schedule.is_active(&at("2024-05-12 10:00:00"), &tz));
+
+        assert_eq!(schedule.next_boundary(&at("2024-05-06 10:00:00"), &tz), Some(at("2024-05-06 12:00:00")));
+        assert_eq!(schedule.next_boundary(&at("2024-05-10 19:00:00"), &tz), Some(at("2024-05-11 22:00:00")));
+        assert_eq!(schedule.next_boundary(&at("2024-05-12 01:00:00"), &tz), Some(at("2024-05-12 02:00:00")));
+
+        let weekend = WorkSchedule::parse("Fri-Mon 00:00-24:00").unwrap();
+        assert!(weekend.is_active(&at("2024-05-05 23:59:00"), &tz));
+        assert!(!weekend.is_active(&at("2024-05-07 12:00:00"), &tz));
+        assert!(WorkSchedule::parse("09:00-18:00").unwrap().is_active(&at("2024-05-07 12:00:00"), &tz));
+
+        assert!(WorkSchedule::parse("").is_err());
+        assert!(WorkSchedule::parse("Mon-Fri").is_err());
+        assert!(WorkSchedule::parse("Someday 09:00-18:00").is_err());
+        assert!(WorkSchedule::parse("Mon 09:00-09:00").is_err());
+        assert!(WorkSchedule::parse("Mon 9-18").is_err());
+        assert!(WorkSchedule::parse("Mon 09:00-24:30").is_err());
+    }
+}
@@ -1,41 +1,96 @@
-use image::{ImageFormat, DynamicImage, GenericImageView};
+use image::codecs::jpeg::JpegEncoder;
+use image::{ImageFormat, DynamicImage, GenericImage, GenericImageView, Rgba};
 use screenshots::Screen;
 use std::error::Error;
 use std::fs::File;
+use std::path::{Path, PathBuf};
+use crate::config::Config;
 use crate::context::{WindowBounds, ActiveWindowInfo};
+use crate::verbosity::{info_println, verbose_println};
+
+/// 上传超限时依次尝试的 JPEG 质量
+const UPLOAD_JPEG_QUALITIES: [u8; 3] = [85, 70, 55];
+
+/// 每轮缩小尺寸的比例
+const UPLOAD_SCALE_STEP: f64 = 0.75;
+
+/// 缩小尺寸的宽度下限，再小模型难以辨认画面文字
+const UPLOAD_MIN_WIDTH: u32 = 480;
+
+/// 窗口覆盖显示器面积达到该比例即视为全屏（留出菜单栏、任务栏等的余量）
+const FULL_SCREEN_COVERAGE: f64 = 0.95;
+
+/// 截图处理参数：缩放约束与灰度开关
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageOptions {
+    /// 最大宽度（None 表示不按宽度限制）
+    pub target_width: Option<u32>,
+    /// 最大总像素数（None 表示不按像素数限制）
+    pub max_pixels: Option<u64>,
+    pub grayscale: bool,
+}
+
+impl ImageOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            target_width: Some(config.image_target_width).filter(|w| *w > 0),
+            max_pixels: Some(config.image_max_pixels).filter(|p| *p > 0),
+            grayscale: config.image_grayscale && !config.no_image_grayscale,
+        }
+    }
+}
 
 /// 处理图片：根据参数进行灰度转换和缩放
-pub fn process_image_for_analysis(
-    image: DynamicImage, 
-    target_width: Option<u32>, 
-    grayscale: bool
-) -> DynamicImage {
+pub fn process_image_for_analysis(image: DynamicImage, options: &ImageOptions) -> DynamicImage {
     let mut processed_image = image;
     
     // 转换为灰度图（如果需要）
-    if grayscale {
+    if options.grayscale {
         processed_image = processed_image.grayscale();
     }
     
-    // 缩放处理（如果需要）
-    if let Some(width) = target_width {
-        if width > 0 {
-            let (current_width, current_height) = processed_image.dimensions();
-            
-            if current_width > width {
-                // 计算新的高度，保持宽高比
-                let scale_factor = width as f32 / current_width as f32;
-                let new_height = (current_height as f32 * scale_factor) as u32;
-                
-                // 缩放到目标尺寸
-                processed_image = processed_image.resize(width, new_height, image::imageops::FilterType::Lanczos3);
-            }
-        }
+    // 缩放处理（如果需要），保持宽高比
+    let (current_width, current_height) = processed_image.dimensions();
+    if let Some((new_width, new_height)) =
+        scaled_dimensions(current_width, current_height, options.target_width, options.max_pixels)
+    {
+        processed_image = processed_image.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3);
     }
     
     processed_image
 }
 
+/// 计算缩放后的尺寸：宽度上限与像素数上限同时生效，取缩得更小的一个；从不放大，无需缩放时返回 None
+fn scaled_dimensions(
+    width: u32,
+    height: u32,
+    target_width: Option<u32>,
+    max_pixels: Option<u64>,
+) -> Option<(u32, u32)> {
+    let mut scale_factor = 1.0_f64;
+
+    if let Some(target_width) = target_width.filter(|w| *w > 0) {
+        if width > target_width {
+            scale_factor = scale_factor.min(target_width as f64 / width as f64);
+        }
+    }
+
+    if let Some(max_pixels) = max_pixels.filter(|p| *p > 0) {
+        let pixels = width as u64 * height as u64;
+        if pixels > max_pixels {
+            scale_factor = scale_factor.min((max_pixels as f64 / pixels as f64).sqrt());
+        }
+    }
+
+    if scale_factor >= 1.0 {
+        return None;
+    }
+
+    let new_width = ((width as f64 * scale_factor).floor() as u32).max(1);
+    let new_height = ((height as f64 * scale_factor).floor() as u32).max(1);
+    Some((new_width, new_height))
+}
+
 // 保留用于向后兼容
 #[allow(dead_code)]
 fn capture_screenshot(file_path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -49,80 +104,789 @@ pub fn capture_screenshot_with_options(
     target_width: Option<u32>, 
     grayscale: bool
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    capture_screenshot_smart(file_path, target_width, grayscale, None)
+    let options = ImageOptions {
+        target_width,
+        max_pixels: None,
+        grayscale,
+    };
+    capture_screenshot_smart(file_path, &options, None, DisplaySelector::Auto, false, &[]).map(|_| ())
 }
 
-/// 智能截图：根据活跃窗口信息选择最佳屏幕
+/// 截图目标显示器
+#[derive(Debug, Clone, Copy)]
+pub enum DisplaySelector<'a> {
+    /// 按活跃窗口所在位置自动选择
+    Auto,
+    /// 按系统显示器列表中的序号（从 0 开始）
+    Index(usize),
+    /// 按显示器 ID 或 "primary"
+    Name(&'a str),
+}
+
+/// 智能截图：根据活跃窗口信息选择最佳屏幕，display 非 Auto 时固定截取指定显示器
+/// crop_to_window 为 true 时仅保留前台窗口区域，缺少窗口位置时回退整屏
+/// redact_keywords 非空时在保存前遮盖包含这些关键词的文字区域
+/// 返回处理后的图片，便于调用方做进一步处理（如去重哈希）
 pub fn capture_screenshot_smart(
     file_path: &str, 
-    target_width: Option<u32>, 
-    grayscale: bool,
-    active_window: Option<&ActiveWindowInfo>
-) -> Result<(), Box<dyn Error + Send + Sync>> {
+    options: &ImageOptions,
+    active_window: Option<&ActiveWindowInfo>,
+    display: DisplaySelector,
+    crop_to_window: bool,
+    redact_keywords: &[String],
+) -> Result<DynamicImage, Box<dyn Error + Send + Sync>> {
     let screens = Screen::all()?;
     if screens.is_empty() {
         return Err("未找到屏幕".into());
     }
 
+    verbose_println!("🖥️ 检测到 {} 个显示器: {}", screens.len(), describe_screens(&screens));
+
+    // 显示器矩形换算到窗口坐标系，供选屏和裁剪共用
+    let window_bounds = active_window.and_then(|w| w.bounds.as_ref());
+    let reported: Vec<DisplayRect> = screens.iter().map(DisplayRect::of).collect();
+    let rects = match window_bounds {
+        Some(bounds) => normalize_display_rects(&reported, bounds),
+        None => reported,
+    };
+
     // 选择要截图的屏幕
-    let target_screen = select_best_screen(&screens, active_window);
+    let target_index = select_target_screen(&screens, &rects, window_bounds, display)?;
+    let target_screen = &screens[target_index];
     
+    let target = target_screen.display_info;
+    verbose_println!(
+        "🎯 截图显示器: id={} {}x{} at {},{}（缩放 {}）",
+        target.id,
+        target.width,
+        target.height,
+        target.x,
+        target.y,
+        target.scale_factor
+    );
+
     // 截取屏幕
     let image = target_screen.capture()?;
     
     // 将screenshots::Image转换为DynamicImage
-    let dynamic_image = DynamicImage::ImageRgba8(image);
+    let mut dynamic_image = DynamicImage::ImageRgba8(image);
+
+    // 裁剪到前台窗口区域（如果需要）
+    if crop_to_window {
+        let cropped = window_bounds.and_then(|bounds| crop_to_window_bounds(&dynamic_image, &rects[target_index], bounds));
+        match cropped {
+            Some(cropped) => dynamic_image = cropped,
+            None => info_println!("⚠️ 无法获取有效的前台窗口区域，回退为整屏截图"),
+        }
+    }
     
+    process_and_save(dynamic_image, file_path, options, redact_keywords)
+}
+
+/// 按 display 选择截图的显示器，返回其在 screens 中的序号；rects 需已换算到窗口坐标系
+fn select_target_screen(
+    screens: &[Screen],
+    rects: &[DisplayRect],
+    window_bounds: Option<&WindowBounds>,
+    display: DisplaySelector,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    match display {
+        DisplaySelector::Auto => {
+            let target = select_best_screen(screens, rects, window_bounds);
+            Ok(screens.iter().position(|screen| std::ptr::eq(screen, target)).unwrap_or(0))
+        }
+        DisplaySelector::Index(index) if index < screens.len() => Ok(index),
+        DisplaySelector::Index(index) => Err(format!(
+            "显示器序号 {} 超出范围（共 {} 个显示器）。可用显示器: {}",
+            index,
+            screens.len(),
+            describe_screens(screens)
+        )
+        .into()),
+        DisplaySelector::Name(name) => find_screen_by_name(screens, name)
+            .and_then(|target| screens.iter().position(|screen| std::ptr::eq(screen, target)))
+            .ok_or_else(|| format!("未找到显示器 \"{}\"。可用显示器: {}", name, describe_screens(screens)).into()),
+    }
+}
+
+/// 录屏区域：与截图相同规则选出的显示器，以及需要裁剪到的前台窗口区域
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordingArea {
+    /// 系统显示器列表中的序号
+    pub screen_index: usize,
+    /// 显示器左上角坐标与尺寸（display-info 上报的值）
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// 窗口区域在显示器画面中的比例位置，不裁剪时为 None
+    pub crop: Option<CropFraction>,
+}
+
+/// 以显示器画面宽高为 1 的矩形：录屏的像素尺寸与截图不一定相同，按比例裁剪
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropFraction {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// 按截图的选屏规则（--display-index / --display-name 或前台窗口所在显示器）确定录屏区域，
+/// crop_to_window 为 true 时附带前台窗口区域，缺少窗口位置时录制整屏
+pub fn recording_area(
+    active_window: Option<&ActiveWindowInfo>,
+    display: DisplaySelector,
+    crop_to_window: bool,
+) -> Result<RecordingArea, Box<dyn Error + Send + Sync>> {
+    let screens = Screen::all()?;
+    if screens.is_empty() {
+        return Err("未找到屏幕".into());
+    }
+
+    let window_bounds = active_window.and_then(|w| w.bounds.as_ref());
+    let reported: Vec<DisplayRect> = screens.iter().map(DisplayRect::of).collect();
+    let rects = match window_bounds {
+        Some(bounds) => normalize_display_rects(&reported, bounds),
+        None => reported,
+    };
+    let screen_index = select_target_screen(&screens, &rects, window_bounds, display)?;
+    let crop = window_bounds
+        .filter(|_| crop_to_window)
+        .and_then(|bounds| window_fraction(&rects[screen_index], bounds));
+
+    let target = screens[screen_index].display_info;
+    Ok(RecordingArea {
+        screen_index,
+        x: target.x,
+        y: target.y,
+        width: target.width,
+        height: target.height,
+        crop,
+    })
+}
+
+/// 窗口是否铺满某个显示器（全屏播放等）：覆盖显示器面积的比例不低于 FULL_SCREEN_COVERAGE；
+/// 获取不到显示器信息时返回 false
+pub fn window_fills_display(bounds: &WindowBounds) -> bool {
+    let Ok(screens) = Screen::all() else {
+        return false;
+    };
+    let reported: Vec<DisplayRect> = screens.iter().map(DisplayRect::of).collect();
+    normalize_display_rects(&reported, bounds)
+        .iter()
+        .any(|rect| rect.coverage_ratio(bounds) >= FULL_SCREEN_COVERAGE)
+}
+
+/// 截图后处理：灰度/缩放、遮盖敏感文字，再保存为 PNG
+fn process_and_save(
+    image: DynamicImage,
+    file_path: &str,
+    options: &ImageOptions,
+    redact_keywords: &[String],
+) -> Result<DynamicImage, Box<dyn Error + Send + Sync>> {
     // 处理图片：根据参数进行灰度转换和缩放
-    let processed_image = process_image_for_analysis(dynamic_image, target_width, grayscale);
+    let mut processed_image = process_image_for_analysis(image, options);
+
+    // 遮盖敏感文字：必须在保存（及后续 base64 上传）之前完成
+    redact_sensitive_text(&mut processed_image, redact_keywords);
     
     // 保存处理后的图片
     let file = File::create(file_path)?;
     processed_image.write_to(&mut std::io::BufWriter::new(file), ImageFormat::Png)?;
     
+    Ok(processed_image)
+}
+
+/// 截图入口：Linux Wayland 会话下通过 XDG 桌面门户截图（门户不可用或失败时回退到 capture_screenshot_smart），
+/// 其他情况直接使用 capture_screenshot_smart
+pub async fn capture_screenshot_auto(
+    file_path: &str,
+    options: &ImageOptions,
+    active_window: Option<&ActiveWindowInfo>,
+    display: DisplaySelector<'_>,
+    crop_to_window: bool,
+    redact_keywords: &[String],
+) -> Result<DynamicImage, Box<dyn Error + Send + Sync>> {
+    #[cfg(target_os = "linux")]
+    if wayland::is_wayland_session() {
+        match wayland::capture().await {
+            Ok(image) => {
+                // 门户返回整个桌面且 Wayland 下拿不到窗口位置，不做选屏和窗口裁剪
+                if crop_to_window || !matches!(display, DisplaySelector::Auto) {
+                    verbose_println!("ℹ️ Wayland 门户截图不支持指定显示器和窗口裁剪，使用整个桌面");
+                }
+                return process_and_save(image, file_path, options, redact_keywords);
+            }
+            Err(e) => info_println!("⚠️ 门户截图失败: {}，回退为直接截屏", e),
+        }
+    }
+
+    capture_screenshot_smart(file_path, options, active_window, display, crop_to_window, redact_keywords)
+}
+
+/// Wayland 会话下的 XDG 桌面门户（org.freedesktop.portal.Screenshot）截图
+/// 门户截图没有可复用的授权令牌：首次请求时桌面弹出授权对话框，授权结果由桌面的权限存储记住，
+/// 之后的非交互请求不再弹窗。这里在进程内缓存授权结果，用户拒绝后在冷却期内不再重复请求，避免每个截屏间隔都弹窗
+#[cfg(target_os = "linux")]
+mod wayland {
+    use ashpd::desktop::screenshot::Screenshot;
+    use ashpd::desktop::ResponseError;
+    use image::DynamicImage;
+    use std::error::Error;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use crate::verbosity::info_println;
+
+    /// 用户拒绝授权后，多久之后再次请求
+    const DENIED_RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+    /// 等待门户响应的上限（包含首次授权对话框的等待时间）
+    const PORTAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+    #[derive(Clone, Copy)]
+    enum PortalPermission {
+        Unknown,
+        Granted,
+        Denied { at: Instant },
+    }
+
+    static PERMISSION: Mutex<PortalPermission> = Mutex::new(PortalPermission::Unknown);
+
+    /// 是否为 Wayland 会话：以 XDG_SESSION_TYPE 为准，未设置时参考 WAYLAND_DISPLAY
+    pub fn is_wayland_session() -> bool {
+        match std::env::var("XDG_SESSION_TYPE") {
+            Ok(session_type) => session_type.trim().eq_ignore_ascii_case("wayland"),
+            Err(_) => std::env::var_os("WAYLAND_DISPLAY").is_some(),
+        }
+    }
+
+    /// 请求一张整个桌面的截图；门户把图片写入文件并返回 URI，读取后删除该文件
+    pub async fn capture() -> Result<DynamicImage, Box<dyn Error + Send + Sync>> {
+        let permission = *PERMISSION.lock().unwrap();
+        match permission {
+            PortalPermission::Denied { at } if at.elapsed() < DENIED_RETRY_AFTER => {
+                let wait = DENIED_RETRY_AFTER - at.elapsed();
+                return Err(format!("截图授权已被拒绝，{} 秒后再次请求", wait.as_secs()).into());
+            }
+            PortalPermission::Granted => {}
+            _ => info_println!("🔐 正在通过桌面门户请求截图权限，请在弹出的对话框中允许 OpenRecall 截图"),
+        }
+
+        let request = async {
+            Screenshot::request()
+                .interactive(false)
+                .modal(false)
+                .send()
+                .await?
+                .response()
+        };
+        let response = match tokio::time::timeout(PORTAL_TIMEOUT, request).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(ashpd::Error::Response(ResponseError::Cancelled))) => {
+                *PERMISSION.lock().unwrap() = PortalPermission::Denied { at: Instant::now() };
+                return Err("用户拒绝了截图授权（可在系统设置的隐私/权限中重新允许）".into());
+            }
+            Ok(Err(e)) => return Err(Box::new(e)),
+            Err(_) => return Err(format!("等待门户响应超时（{} 秒）", PORTAL_TIMEOUT.as_secs()).into()),
+        };
+
+        if !matches!(permission, PortalPermission::Granted) {
+            info_println!("✅ 已获得桌面门户截图授权");
+        }
+        *PERMISSION.lock().unwrap() = PortalPermission::Granted;
+
+        let path = response
+            .uri()
+            .to_file_path()
+            .map_err(|_| format!("门户返回了无法识别的截图地址: {}", response.uri()))?;
+        let image = image::open(&path);
+        // 门户默认把截图保存到图片目录，读取后删除，避免堆积
+        let _ = std::fs::remove_file(&path);
+        Ok(image?)
+    }
+}
+
+/// 遮盖包含敏感关键词的文字行；OCR 不可用或失败时仅告警，不阻断截图
+pub fn redact_sensitive_text(image: &mut DynamicImage, keywords: &[String]) {
+    let keywords: Vec<String> = keywords
+        .iter()
+        .map(|k| k.trim().to_lowercase())
+        .filter(|k| !k.is_empty())
+        .collect();
+    if keywords.is_empty() {
+        return;
+    }
+
+    #[cfg(feature = "tesseract")]
+    {
+        match find_sensitive_text_boxes(image, &keywords) {
+            Ok(boxes) => {
+                for (left, top, width, height) in &boxes {
+                    fill_rect(image, *left, *top, *width, *height);
+                }
+                if !boxes.is_empty() {
+                    info_println!("🙈 已遮盖 {} 处敏感文字区域", boxes.len());
+                }
+            }
+            Err(e) => eprintln!("⚠️ OCR 识别失败，跳过敏感信息遮盖: {}", e),
+        }
+    }
+
+    #[cfg(not(feature = "tesseract"))]
+    {
+        let _ = image;
+        static WARN_ONCE: std::sync::Once = std::sync::Once::new();
+        WARN_ONCE.call_once(|| {
+            eprintln!("⚠️ 已配置 --redact-keywords，但未启用 tesseract 功能，跳过敏感信息遮盖");
+        });
+    }
+}
+
+/// 使用 Tesseract 识别文字，返回包含关键词的文字行区域 (left, top, width, height)
+#[cfg(feature = "tesseract")]
+fn find_sensitive_text_boxes(
+    image: &DynamicImage,
+    keywords: &[String],
+) -> Result<Vec<(u32, u32, u32, u32)>, Box<dyn Error + Send + Sync>> {
+    use std::collections::BTreeMap;
+
+    let gray = image.to_luma8();
+    let (width, height) = gray.dimensions();
+    let mut ocr = tesseract::Tesseract::new(None, Some("eng"))?
+        .set_frame(gray.as_raw(), width as i32, height as i32, 1, width as i32)?
+        .recognize()?;
+    let tsv = ocr.get_tsv_text(0)?;
+
+    // TSV 列：level page block par line word left top width height conf text，level 5 为单词
+    // 按行聚合单词，整行文本命中关键词时遮盖整行，避免多词关键词被拆开漏判
+    let mut lines: BTreeMap<(u32, u32, u32, u32), (String, u32, u32, u32, u32)> = BTreeMap::new();
+    for row in tsv.lines() {
+        let cols: Vec<&str> = row.split('\t').collect();
+        if cols.len() < 12 || cols[0] != "5" {
+            continue;
+        }
+        let nums: Vec<u32> = cols[1..10].iter().filter_map(|c| c.parse().ok()).collect();
+        if nums.len() != 9 || cols[11].trim().is_empty() {
+            continue;
+        }
+        let key = (nums[0], nums[1], nums[2], nums[3]);
+        let (left, top, w, h) = (nums[5], nums[6], nums[7], nums[8]);
+        let entry = lines
+            .entry(key)
+            .or_insert_with(|| (String::new(), left, top, left + w, top + h));
+        if !entry.0.is_empty() {
+            entry.0.push(' ');
+        }
+        entry.0.push_str(&cols[11].to_lowercase());
+        entry.1 = entry.1.min(left);
+        entry.2 = entry.2.min(top);
+        entry.3 = entry.3.max(left + w);
+        entry.4 = entry.4.max(top + h);
+    }
+
+    Ok(lines
+        .into_values()
+        .filter(|(text, ..)| keywords.iter().any(|k| text.contains(k.as_str())))
+        .map(|(_, left, top, right, bottom)| (left, top, right - left, bottom - top))
+        .collect())
+}
+
+/// 用黑色填充矩形区域（自动裁剪到图片范围内）
+#[cfg_attr(not(feature = "tesseract"), allow(dead_code))]
+fn fill_rect(image: &mut DynamicImage, left: u32, top: u32, width: u32, height: u32) {
+    let (image_width, image_height) = image.dimensions();
+    let right = left.saturating_add(width).min(image_width);
+    let bottom = top.saturating_add(height).min(image_height);
+    for y in top.min(bottom)..bottom {
+        for x in left.min(right)..right {
+            image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+        }
+    }
+}
+
+/// 缩略图路径：与截图同目录，screenshot_x.png → screenshot_x_thumb.jpg
+pub fn thumbnail_path(screenshot_path: &Path) -> PathBuf {
+    let stem = screenshot_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("screenshot");
+    screenshot_path.with_file_name(format!("{}_thumb.jpg", stem))
+}
+
+/// 由内存中的截图生成缩略图（宽度不超过 max_width，保持宽高比），保存为 JPEG
+pub fn save_thumbnail(image: &DynamicImage, path: &Path, max_width: u32) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let thumbnail = if image.width() > max_width {
+        image.thumbnail(max_width, image.height())
+    } else {
+        image.clone()
+    };
+    thumbnail.to_rgb8().save_with_format(path, ImageFormat::Jpeg)?;
     Ok(())
 }
 
+/// 数据经 base64 编码后的字节数
+pub fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// 压缩图片使 base64 编码后不超过 max_bytes：先转为 JPEG 逐级降低质量，仍超限则按比例缩小宽度重试，
+/// 返回 JPEG 数据；宽度降到下限仍超限时报错
+pub fn shrink_for_upload(image: &DynamicImage, max_bytes: usize) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut width = image.width();
+    loop {
+        let options = ImageOptions {
+            target_width: Some(width),
+            ..Default::default()
+        };
+        let resized = process_image_for_analysis(image.clone(), &options).to_rgb8();
+        for quality in UPLOAD_JPEG_QUALITIES {
+            let mut data = Vec::new();
+            JpegEncoder::new_with_quality(&mut data, quality).encode_image(&resized)?;
+            if base64_len(data.len()) <= max_bytes {
+                verbose_println!("🗜️ 上传图片压缩为 {}x{} JPEG（质量 {}）", resized.width(), resized.height(), quality);
+                return Ok(data);
+            }
+        }
+        if width <= UPLOAD_MIN_WIDTH {
+            break;
+        }
+        width = ((width as f64 * UPLOAD_SCALE_STEP) as u32).max(UPLOAD_MIN_WIDTH);
+    }
+    Err(format!(
+        "图片无法压缩到上传上限 {} 字节以内（已缩小到宽度 {} 像素），请调大 --max-upload-bytes",
+        max_bytes, width
+    )
+    .into())
+}
+
+/// 计算图片的平均哈希（aHash）：缩放到 8x8 灰度后，以均值为阈值生成 64 位指纹
+pub fn average_hash(image: &DynamicImage) -> u64 {
+    let small = image
+        .resize_exact(8, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<u32> = small.pixels().map(|p| p.0[0] as u32).collect();
+    let mean = pixels.iter().sum::<u32>() / pixels.len().max(1) as u32;
+
+    pixels
+        .iter()
+        .enumerate()
+        .fold(0u64, |hash, (i, &p)| if p > mean { hash | (1 << i) } else { hash })
+}
+
+/// 两个平均哈希的相似度（0.0 ~ 1.0）
+pub fn hash_similarity(a: u64, b: u64) -> f32 {
+    1.0 - (a ^ b).count_ones() as f32 / 64.0
+}
+
+/// 相似度是否达到去重阈值（--dedup-threshold），阈值为 0 表示关闭去重
+pub fn is_near_duplicate(similarity: f32, threshold: f32) -> bool {
+    threshold > 0.0 && similarity >= threshold
+}
+
+/// 显示器在窗口坐标系下的矩形
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DisplayRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    scale_factor: f64,
+    is_primary: bool,
+}
+
+impl DisplayRect {
+    /// display-info 上报的原始矩形
+    fn of(screen: &Screen) -> Self {
+        let display = screen.display_info;
+        Self {
+            x: display.x as f64,
+            y: display.y as f64,
+            width: display.width as f64,
+            height: display.height as f64,
+            scale_factor: if display.scale_factor > 0.0 { display.scale_factor as f64 } else { 1.0 },
+            is_primary: display.is_primary,
+        }
+    }
+
+    /// 把按像素上报的矩形换算为逻辑坐标（点）：尺寸按自身缩放，
+    /// 原点按主屏缩放（系统以主屏为基准排列显示器）
+    fn to_points(self, origin_scale: f64) -> Self {
+        Self {
+            x: self.x / origin_scale,
+            y: self.y / origin_scale,
+            width: self.width / self.scale_factor,
+            height: self.height / self.scale_factor,
+            ..self
+        }
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// 窗口覆盖该显示器面积的比例
+    fn coverage_ratio(&self, bounds: &WindowBounds) -> f64 {
+        let area = self.width * self.height;
+        if area <= 0.0 {
+            return 0.0;
+        }
+        self.overlap_ratio(bounds) * bounds.width as f64 * bounds.height as f64 / area
+    }
+
+    /// 窗口落在该显示器内的面积占窗口面积的比例
+    fn overlap_ratio(&self, bounds: &WindowBounds) -> f64 {
+        let area = bounds.width as f64 * bounds.height as f64;
+        if area <= 0.0 {
+            return 0.0;
+        }
+        let width = (self.x + self.width).min((bounds.x + bounds.width) as f64) - self.x.max(bounds.x as f64);
+        let height = (self.y + self.height).min((bounds.y + bounds.height) as f64) - self.y.max(bounds.y as f64);
+        width.max(0.0) * height.max(0.0) / area
+    }
+}
+
+/// 窗口坐标（macOS 的 AppleScript 为点）与 display-info 的坐标（可能为像素）不一定一致。
+/// 存在缩放不为 1 的显示器时，分别按“已是逻辑坐标”和“按像素上报”两种假设计算窗口
+/// 落在单个显示器内的最大比例：窗口通常完整位于某个显示器内，错误的假设会让它跨越显示器边界。
+/// 像素假设明显更吻合时返回换算后的矩形，否则保持原样
+fn normalize_display_rects(reported: &[DisplayRect], bounds: &WindowBounds) -> Vec<DisplayRect> {
+    if reported.iter().all(|rect| rect.scale_factor == 1.0) {
+        return reported.to_vec();
+    }
+
+    let origin_scale = reported
+        .iter()
+        .find(|rect| rect.is_primary)
+        .or_else(|| reported.first())
+        .map_or(1.0, |rect| rect.scale_factor);
+    let points: Vec<DisplayRect> = reported.iter().map(|rect| rect.to_points(origin_scale)).collect();
+
+    let best_fit = |rects: &[DisplayRect]| rects.iter().map(|rect| rect.overlap_ratio(bounds)).fold(0.0, f64::max);
+    if best_fit(&points) > best_fit(reported) {
+        verbose_println!("🖥️ 显示器坐标按像素上报，已按缩放换算为与窗口一致的逻辑坐标");
+        points
+    } else {
+        reported.to_vec()
+    }
+}
+
+/// 窗口在显示器中的比例位置（限制在显示器范围内），窗口与显示器没有重叠时返回 None
+fn window_fraction(display: &DisplayRect, bounds: &WindowBounds) -> Option<CropFraction> {
+    if bounds.width <= 0 || bounds.height <= 0 {
+        return None;
+    }
+
+    if display.width <= 0.0 || display.height <= 0.0 {
+        return None;
+    }
+
+    let left = ((bounds.x as f64 - display.x) / display.width).clamp(0.0, 1.0);
+    let top = ((bounds.y as f64 - display.y) / display.height).clamp(0.0, 1.0);
+    let right = (((bounds.x + bounds.width) as f64 - display.x) / display.width).clamp(0.0, 1.0);
+    let bottom = (((bounds.y + bounds.height) as f64 - display.y) / display.height).clamp(0.0, 1.0);
+    (right > left && bottom > top).then_some(CropFraction {
+        left,
+        top,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+/// 将整屏截图裁剪为窗口区域，窗口坐标为全局逻辑坐标，需要换算为截图像素坐标
+fn crop_to_window_bounds(image: &DynamicImage, display: &DisplayRect, bounds: &WindowBounds) -> Option<DynamicImage> {
+    let fraction = window_fraction(display, bounds)?;
+    let (image_width, image_height) = image.dimensions();
+
+    let left = (fraction.left * image_width as f64) as u32;
+    let top = (fraction.top * image_height as f64) as u32;
+    let right = (((fraction.left + fraction.width) * image_width as f64) as u32).min(image_width);
+    let bottom = (((fraction.top + fraction.height) * image_height as f64) as u32).min(image_height);
+
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    info_println!("✂️ 裁剪前台窗口区域: {}x{} at {},{}", right - left, bottom - top, left, top);
+    Some(image.crop_imm(left, top, right - left, bottom - top))
+}
+
+/// 按显示器 ID 或 primary 查找屏幕（display-info 0.4 未提供显示器名称）
+fn find_screen_by_name<'a>(screens: &'a [Screen], name: &str) -> Option<&'a Screen> {
+    let name = name.trim();
+    if name.eq_ignore_ascii_case("primary") {
+        return screens.iter().find(|screen| screen.display_info.is_primary);
+    }
+    screens
+        .iter()
+        .find(|screen| screen.display_info.id.to_string() == name)
+}
+
+/// 列出可用显示器，用于错误提示和 --verbose 输出
+fn describe_screens(screens: &[Screen]) -> String {
+    screens
+        .iter()
+        .enumerate()
+        .map(|(index, screen)| {
+            let display = screen.display_info;
+            format!(
+                "[{}] id={} {}x{} at {},{}{}",
+                index,
+                display.id,
+                display.width,
+                display.height,
+                display.x,
+                display.y,
+                if display.is_primary { " (主屏)" } else { "" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 /// 选择最佳屏幕进行截图
-fn select_best_screen<'a>(screens: &'a [Screen], active_window: Option<&ActiveWindowInfo>) -> &'a Screen {
+fn select_best_screen<'a>(screens: &'a [Screen], rects: &[DisplayRect], window_bounds: Option<&WindowBounds>) -> &'a Screen {
     // 如果只有一个屏幕，直接返回
     if screens.len() == 1 {
+        verbose_println!("🖥️ 只有一个显示器，直接使用");
         return &screens[0];
     }
     
-    // 如果有活跃窗口信息且包含位置信息，寻找包含该窗口的屏幕
-    if let Some(window) = active_window {
-        if let Some(bounds) = &window.bounds {
-            if let Some(screen) = find_screen_containing_window(screens, bounds) {
-                println!("📍 选择包含活跃窗口的屏幕 (窗口位置: {}x{} at {},{}) ", 
-                    bounds.width, bounds.height, bounds.x, bounds.y);
-                return screen;
-            }
+    // 如果有活跃窗口位置信息，寻找包含该窗口的屏幕
+    if let Some(bounds) = window_bounds {
+        if let Some(index) = find_screen_containing_window(rects, bounds) {
+            info_println!("📍 选择包含活跃窗口的屏幕 (窗口位置: {}x{} at {},{}) ", 
+                bounds.width, bounds.height, bounds.x, bounds.y);
+            return &screens[index];
         }
     }
     
     // 如果无法确定活跃窗口所在屏幕，选择主屏幕（通常是第一个）
-    println!("🖥️ 使用主屏幕进行截图");
+    match window_bounds {
+        Some(bounds) => verbose_println!(
+            "🖥️ 活跃窗口中心 ({}, {}) 不在任何显示器内",
+            bounds.x + bounds.width / 2,
+            bounds.y + bounds.height / 2
+        ),
+        None => verbose_println!("🖥️ 未获取到活跃窗口位置"),
+    }
+    info_println!("🖥️ 使用主屏幕进行截图");
     &screens[0]
 }
 
-/// 查找包含指定窗口的屏幕
-fn find_screen_containing_window<'a>(screens: &'a [Screen], window_bounds: &WindowBounds) -> Option<&'a Screen> {
-    // 计算窗口中心点
-    let window_center_x = window_bounds.x + window_bounds.width / 2;
-    let window_center_y = window_bounds.y + window_bounds.height / 2;
-    
-    for screen in screens {
-        let display = screen.display_info;
-        
-        // 检查窗口中心点是否在这个屏幕内
-        if window_center_x >= display.x 
-            && window_center_x < display.x + display.width as i32
-            && window_center_y >= display.y 
-            && window_center_y < display.y + display.height as i32 {
-            return Some(screen);
-        }
+/// 查找包含指定窗口中心点的屏幕，rects 需已换算到窗口坐标系
+fn find_screen_containing_window(rects: &[DisplayRect], window_bounds: &WindowBounds) -> Option<usize> {
+    let window_center_x = window_bounds.x as f64 + window_bounds.width as f64 / 2.0;
+    let window_center_y = window_bounds.y as f64 + window_bounds.height as f64 / 2.0;
+
+    rects.iter().position(|rect| rect.contains(window_center_x, window_center_y))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 左右（horizontal）或上下分为明暗两半的测试图
+    fn split_image(horizontal: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(320, 240, |x, y| {
+            let bright = if horizontal { x < 160 } else { y < 120 };
+            image::Rgb(if bright { [230, 230, 230] } else { [20, 20, 20] })
+        }))
     }
-    
-    None
-}
\ No newline at end of file
+
+    #[test]
+    fn test_average_hash_identical_and_different_images() {
+        let image = split_image(true);
+        assert_eq!(hash_similarity(average_hash(&image), average_hash(&image.clone())), 1.0);
+
+        // 明暗分布完全不同的画面：一半的位不同
+        let similarity = hash_similarity(average_hash(&image), average_hash(&split_image(false)));
+        assert!(similarity <= 0.5, "similarity = {}", similarity);
+        assert!(!is_near_duplicate(similarity, 0.9));
+
+        // 明暗反转：所有位都不同
+        let mut inverted = image.clone();
+        inverted.invert();
+        assert_eq!(hash_similarity(average_hash(&image), average_hash(&inverted)), 0.0);
+    }
+
+    #[test]
+    fn test_dedup_threshold_boundary() {
+        // 3 位不同：相似度恰为 1 - 3/64
+        let similarity = hash_similarity(0, 0b111);
+        assert_eq!(similarity, 1.0 - 3.0 / 64.0);
+        assert!(is_near_duplicate(similarity, similarity));
+        assert!(!is_near_duplicate(similarity, f32::from_bits(similarity.to_bits() + 1)));
+        assert!(is_near_duplicate(similarity, 0.95));
+        assert!(!is_near_duplicate(hash_similarity(0, 0b1111), 0.95));
+
+        // 阈值 0 关闭去重，即使画面完全相同
+        assert!(!is_near_duplicate(1.0, 0.0));
+        assert!(is_near_duplicate(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_scaled_dimensions_constraints() {
+        // 从不放大
+        assert_eq!(scaled_dimensions(800, 600, Some(1440), Some(10_000_000)), None);
+        assert_eq!(scaled_dimensions(800, 600, None, None), None);
+
+        // 仅宽度约束
+        assert_eq!(scaled_dimensions(2880, 1800, Some(1440), None), Some((1440, 900)));
+
+        // 超宽屏：宽度约束后仍超过像素上限，由像素约束决定
+        let (w, h) = scaled_dimensions(5120, 1440, Some(1440), Some(200_000)).unwrap();
+        assert!(w as u64 * h as u64 <= 200_000);
+        assert!(w < 1440);
+        assert!(((w as f64 / h as f64) - 5120.0 / 1440.0).abs() < 0.05);
+
+        // 像素约束更宽松时由宽度约束决定
+        assert_eq!(scaled_dimensions(2880, 1800, Some(1440), Some(10_000_000)), Some((1440, 900)));
+    }
+
+    #[test]
+    fn test_shrink_for_upload() {
+        // 伪随机噪点图，JPEG 压缩率低，迫使缩小尺寸
+        let mut seed = 1u32;
+        let noise = image::RgbImage::from_fn(1600, 1000, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        let image = DynamicImage::ImageRgb8(noise);
+
+        let data = shrink_for_upload(&image, 300_000).unwrap();
+        assert!(base64_len(data.len()) <= 300_000);
+        let decoded = image::load_from_memory_with_format(&data, ImageFormat::Jpeg).unwrap();
+        assert!(decoded.width() < 1600 && decoded.width() >= UPLOAD_MIN_WIDTH);
+
+        // 缩到下限仍无法满足时报错
+        assert!(shrink_for_upload(&image, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_find_screen_with_retina_scaling() {
+        let display = |x: f64, width: f64, height: f64, scale_factor: f64, is_primary: bool| DisplayRect {
+            x, y: 0.0, width, height, scale_factor, is_primary,
+        };
+        // 窗口（点）铺满右侧 1920x1080 外接屏：主屏为 1440x900 点的 2x Retina
+        let window = WindowBounds { x: 1440, y: 25, width: 1920, height: 1055 };
+
+        // display-info 按像素上报：未换算时窗口中心 (2400, 552) 会落在 2880 像素宽的主屏内
+        let pixels = [display(0.0, 2880.0, 1800.0, 2.0, true), display(2880.0, 1920.0, 1080.0, 1.0, false)];
+        assert_eq!(find_screen_containing_window(&pixels, &window), Some(0));
+        let rects = normalize_display_rects(&pixels, &window);
+        assert_eq!(rects[0], display(0.0, 1440.0, 900.0, 2.0, true));
+        assert_eq!(rects[1], display(1440.0, 1920.0, 1080.0, 1.0, false));
+        assert_eq!(find_screen_containing_window(&rects, &window), Some(1));
+
+        // 已按点上报时保持原样
+        let points = [display(0.0, 1440.0, 900.0, 2.0, true), display(1440.0, 1920.0, 1080.0, 1.0, false)];
+        assert_eq!(normalize_display_rects(&points, &window), points.to_vec());
+        assert_eq!(find_screen_containing_window(&points, &window), Some(1));
+
+        // 主屏上的窗口两种假设都完整包含时不换算
+        let on_primary = WindowBounds { x: 100, y: 100, width: 600, height: 400 };
+        assert_eq!(normalize_display_rects(&pixels, &on_primary), pixels.to_vec());
+        assert_eq!(find_screen_containing_window(&pixels, &on_primary), Some(0));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::models::ActivityLog;
+use crate::timezone::LogTimezone;
+
+/// 检索结果中匹配片段的标记
+const HIGHLIGHT_MARK: &str = "**";
+
+/// 按关键词检索活动日志（MCP search_logs 与 --search-logs 共用）：描述或窗口标题包含 query，
+/// 且前台应用包含 app（均不区分大小写）；结果保持日志原有的时间顺序
+pub fn search_logs<'a>(logs: &'a [ActivityLog], query: &str, app: Option<&str>) -> Vec<&'a ActivityLog> {
+    let query = query.to_lowercase();
+    let app = app.map(str::to_lowercase).filter(|app| !app.is_empty());
+    logs.iter()
+        .filter(|log| {
+            let active_app = log.context.as_ref().and_then(|ctx| ctx.active_app.as_deref());
+            app.as_ref()
+                .is_none_or(|app| active_app.is_some_and(|name| name.to_lowercase().contains(app)))
+        })
+        .filter(|log| {
+            log.description.to_lowercase().contains(&query)
+                || window_title(log).is_some_and(|title| title.to_lowercase().contains(&query))
+        })
+        .collect()
+}
+
+/// 与 read_logs 相同的 "时间 | 描述" 行，匹配片段用 ** 标出；描述中没有匹配（只有窗口标题匹配）时附上标题
+pub fn format_matches(logs: &[&ActivityLog], query: &str, tz: LogTimezone) -> String {
+    let mut out = String::new();
+    for log in logs {
+        let description = highlight(&log.description, query);
+        out.push_str(&tz.format(&log.timestamp, "%Y-%m-%d %H:%M:%S"));
+        out.push_str(" | ");
+        out.push_str(&description);
+        if description == log.description {
+            if let Some(title) = window_title(log) {
+                out.push_str(&format!(" | 窗口: {}", highlight(title, query)));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn window_title(log: &ActivityLog) -> Option<&str> {
+    log.context.as_ref().and_then(|ctx| ctx.window_title.as_deref())
+}
+
+/// 用 ** 包住 text 中所有与 query 不区分大小写相同的片段
+pub fn highlight(text: &str, query: &str) -> String {
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return text.to_string();
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        // 逐字符小写后比较，保证切片位置落在原文的字符边界上
+        let mut end = i;
+        let mut matched = 0;
+        while matched < needle.len() && end < chars.len() {
+            let lower: Vec<char> = chars[end].1.to_lowercase().collect();
+            if !needle[matched..].starts_with(&lower) {
+                break;
+            }
+            matched += lower.len();
+            end += 1;
+        }
+        if matched < needle.len() {
+            i += 1;
+            continue;
+        }
+        let start_byte = chars[i].0;
+        let end_byte = chars.get(end).map_or(text.len(), |(byte, _)| *byte);
+        out.push_str(&text[copied..start_byte]);
+        out.push_str(HIGHLIGHT_MARK);
+        out.push_str(&text[start_byte..end_byte]);
+        out.push_str(HIGHLIGHT_MARK);
+        copied = end_byte;
+        i = end;
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_is_case_insensitive() {
+        assert_eq!(highlight("Editing main.rs in VS Code", "code"), "Editing main.rs in VS **Code**");
+        assert_eq!(highlight("【开发】【Cursor】写代码，代码评审", "代码"), "【开发】【Cursor】写**代码**，**代码**评审");
+        assert_eq!(highlight("no match", "xyz"), "no match");
+    }
+}
@@ -0,0 +1,187 @@
+use crate::capture;
+use crate::config::Config;
+use crate::error;
+use crate::models::{ActivityClassification, TokenUsage};
+use crate::verbosity::{self, text_println};
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// 合成截图上渲染的文字，用于判断模型是否真正看到了图片
+const SELFTEST_TEXT: &str = "OPENRECALL SELF TEST";
+
+/// 随图片一起发给模型的上下文
+const SELFTEST_CONTEXT: &str = "OpenRecall 端到端自检：这是一张合成的测试截图，不是真实屏幕";
+
+const IMAGE_WIDTH: u32 = 1280;
+const IMAGE_HEIGHT: u32 = 800;
+
+/// 5×7 点阵字形，每行低 5 位从左到右表示像素，只包含 SELFTEST_TEXT 用到的字母
+const GLYPHS: [(char, [u8; 7]); 11] = [
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('N', [0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+];
+
+/// 自检结果（--json 时输出）
+#[derive(Serialize)]
+struct SelftestReport {
+    command: &'static str,
+    ok: bool,
+    latency_ms: u64,
+    model: Option<String>,
+    description: Option<String>,
+    /// 描述能否解析出【类型】【软件】【主要工作摘要】
+    classification: Option<ActivityClassification>,
+    /// 描述中是否提到了图片上的测试文字
+    text_recognized: bool,
+    token_usage: Option<TokenUsage>,
+    error: Option<String>,
+}
+
+/// 端到端自检（--selftest）：生成一张带文字的合成截图，经截图处理、编码后发给配置的模型接口，
+/// 在 --api-timeout-secs 内拿到非空描述即通过；不依赖屏幕内容与截屏权限。返回是否通过
+pub async fn run_selftest(config: &Config) -> bool {
+    text_println!("🧪 OpenRecall 端到端自检：合成截图 → 图片处理与编码 → 模型接口 → 结果解析\n");
+
+    let mut report = SelftestReport {
+        command: "selftest",
+        ok: false,
+        latency_ms: 0,
+        model: None,
+        description: None,
+        classification: None,
+        text_recognized: false,
+        token_usage: None,
+        error: None,
+    };
+    if let Err(e) = run(config, &mut report).await {
+        report.error = Some(e);
+    }
+    print_report(&report);
+    report.ok
+}
+
+async fn run(config: &Config, report: &mut SelftestReport) -> Result<(), String> {
+    if config.no_analysis {
+        return Err("--no-analysis 已跳过 AI 分析，无法自检模型接口".to_string());
+    }
+    // 自检必须真正请求接口，不能命中 --cache-dir 中的缓存回复
+    let config = &Config {
+        cache_dir: None,
+        ..config.clone()
+    };
+    config.ensure_api_key().map_err(|e| e.to_string())?;
+
+    let image_path = std::env::temp_dir().join(format!("openrecall_selftest_{}.png", std::process::id()));
+    render_test_image()
+        .save(&image_path)
+        .map_err(|e| format!("生成合成截图失败: {}", e))?;
+    text_println!("🖼️ 已生成合成截图: {}", image_path.display());
+    text_println!("🔌 正在请求 {}（模型 {}）...", config.api_url, config.model);
+
+    let timeout = Duration::from_secs(config.api_timeout.max(1));
+    let started = Instant::now();
+    let image_path_str = image_path.to_string_lossy();
+    let analyzed = tokio::time::timeout(
+        timeout,
+        capture::analyze_image_file(config, &image_path_str, Some(SELFTEST_CONTEXT)),
+    )
+    .await;
+    report.latency_ms = started.elapsed().as_millis() as u64;
+    let _ = std::fs::remove_file(&image_path);
+
+    let result = match analyzed {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => return Err(format!("分析失败: {}", error::user_message(&*e))),
+        Err(_) => return Err(format!("超过 {} 秒未返回结果（--api-timeout-secs）", timeout.as_secs())),
+    };
+    let description = result.description.trim().to_string();
+    report.model = result.model;
+    report.token_usage = result.token_usage;
+    report.classification = ActivityClassification::parse(&description);
+    report.text_recognized = description
+        .to_uppercase()
+        .contains(SELFTEST_TEXT.split(' ').next().unwrap_or_default());
+    report.ok = !description.is_empty();
+    report.description = Some(description);
+    if !report.ok {
+        return Err("模型返回了空描述".to_string());
+    }
+    Ok(())
+}
+
+fn print_report(report: &SelftestReport) {
+    if verbosity::json_output() {
+        if let Err(e) = verbosity::print_json(report) {
+            eprintln!("❌ 输出自检结果失败: {}", e);
+        }
+        return;
+    }
+
+    text_println!();
+    if let Some(description) = &report.description {
+        text_println!("📝 描述: {}", description);
+        text_println!(
+            "  {} 结构化分类: {}",
+            if report.classification.is_some() { "✅" } else { "⚠️" },
+            if report.classification.is_some() { "已解析【类型】【软件】【主要工作摘要】" } else { "描述不符合【类型】【软件】【主要工作摘要】格式，日志中将没有分类" }
+        );
+        text_println!(
+            "  {} 图片内容: {}",
+            if report.text_recognized { "✅" } else { "⚠️" },
+            if report.text_recognized { "描述提到了图片上的测试文字" } else { "描述没有提到图片上的测试文字，请确认模型支持图片输入" }
+        );
+    }
+    match &report.error {
+        None => text_println!(
+            "✅ 自检通过：耗时 {} ms · 模型 {}",
+            report.latency_ms,
+            report.model.as_deref().unwrap_or("未知")
+        ),
+        Some(error) => eprintln!("❌ 自检失败（耗时 {} ms）: {}", report.latency_ms, error),
+    }
+}
+
+/// 绘制合成截图：浅色桌面、带标题栏的窗口，窗口中央是放大的点阵文字
+fn render_test_image() -> RgbImage {
+    let mut image = RgbImage::from_pixel(IMAGE_WIDTH, IMAGE_HEIGHT, Rgb([236, 239, 244]));
+    fill_rect(&mut image, 80, 60, IMAGE_WIDTH - 160, IMAGE_HEIGHT - 120, Rgb([255, 255, 255]));
+    fill_rect(&mut image, 80, 60, IMAGE_WIDTH - 160, 48, Rgb([52, 73, 94]));
+
+    let scale = 8;
+    let advance = 6 * scale;
+    let text_width = SELFTEST_TEXT.chars().count() as u32 * advance;
+    let x0 = (IMAGE_WIDTH - text_width) / 2;
+    let y0 = (IMAGE_HEIGHT - 7 * scale) / 2;
+    for (index, ch) in SELFTEST_TEXT.chars().enumerate() {
+        let Some((_, rows)) = GLYPHS.iter().find(|(glyph, _)| *glyph == ch) else {
+            continue;
+        };
+        let x = x0 + index as u32 * advance;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..5 {
+                if bits & (0b10000 >> col) != 0 {
+                    fill_rect(&mut image, x + col * scale, y0 + row as u32 * scale, scale, scale, Rgb([20, 20, 20]));
+                }
+            }
+        }
+    }
+    image
+}
+
+fn fill_rect(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
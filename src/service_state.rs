@@ -1,8 +1,9 @@
 use crate::models::{CaptureServiceState, CaptureServiceStatus};
 use crate::config::Config;
-use chrono::Local;
+use crate::error::ScreenTimeError;
+use crate::verbosity::text_println;
+use chrono::{DateTime, Local};
 use std::path::Path;
-use std::error::Error;
 use tokio::sync::{RwLock};
 use std::sync::Arc;
 use serde_json;
@@ -15,7 +16,7 @@ pub struct ServiceStateManager {
 
 impl ServiceStateManager {
     /// 创建新的状态管理器
-    pub async fn new(config: &Config) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    pub async fn new(config: &Config) -> Result<Self, ScreenTimeError> {
         let state_file_path = config.get_state_path();
         
         // 确保状态文件目录存在
@@ -35,7 +36,7 @@ impl ServiceStateManager {
     async fn load_state(
         state_file_path: &Path, 
         config: &Config
-    ) -> Result<CaptureServiceState, Box<dyn Error + Send + Sync>> {
+    ) -> Result<CaptureServiceState, ScreenTimeError> {
         if state_file_path.exists() {
             match tokio::fs::read_to_string(state_file_path).await {
                 Ok(content) => {
@@ -44,12 +45,13 @@ impl ServiceStateManager {
                             // 检查配置是否有变更
                             let current_hash = config.get_config_hash();
                             if state.config_hash != current_hash {
-                                println!("检测到配置变更，重置服务状态");
+                                text_println!("检测到配置变更，重置服务状态");
                                 state.config_hash = current_hash;
                                 // 如果配置变更，停止服务
-                                if matches!(state.status, CaptureServiceStatus::Running) {
+                                if !matches!(state.status, CaptureServiceStatus::Stopped) {
                                     state.status = CaptureServiceStatus::Stopped;
                                     state.last_stop_time = Some(Local::now());
+                                    state.paused_until = None;
                                 }
                             }
                             return Ok(state);
@@ -72,7 +74,7 @@ impl ServiceStateManager {
     }
     
     /// 保存状态到文件
-    pub async fn save_state(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    pub async fn save_state(&self) -> Result<(), ScreenTimeError> {
         let state = self.state.read().await;
         let content = serde_json::to_string_pretty(&*state)?;
         tokio::fs::write(&self.state_file_path, content).await?;
@@ -85,7 +87,7 @@ impl ServiceStateManager {
     }
     
     /// 启动服务
-    pub async fn start_service(&self) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    pub async fn start_service(&self) -> Result<bool, ScreenTimeError> {
         let mut state = self.state.write().await;
         match state.status {
             CaptureServiceStatus::Running => {
@@ -94,6 +96,9 @@ impl ServiceStateManager {
             _ => {
                 state.status = CaptureServiceStatus::Running;
                 state.last_start_time = Some(Local::now());
+                state.paused_until = None;
+                state.outside_schedule = false;
+                state.manual_stop_until = None;
                 drop(state);
                 self.save_state().await?;
                 Ok(true)
@@ -102,7 +107,7 @@ impl ServiceStateManager {
     }
     
     /// 停止服务
-    pub async fn stop_service(&self) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    pub async fn stop_service(&self) -> Result<bool, ScreenTimeError> {
         let mut state = self.state.write().await;
         match state.status {
             CaptureServiceStatus::Stopped => {
@@ -111,6 +116,7 @@ impl ServiceStateManager {
             _ => {
                 state.status = CaptureServiceStatus::Stopped;
                 state.last_stop_time = Some(Local::now());
+                state.paused_until = None;
                 drop(state);
                 self.save_state().await?;
                 Ok(true)
@@ -118,12 +124,85 @@ impl ServiceStateManager {
         }
     }
     
+    /// 暂停服务，返回 false 表示服务未运行无法暂停
+    pub async fn pause_service(
+        &self,
+        paused_until: Option<DateTime<Local>>,
+    ) -> Result<bool, ScreenTimeError> {
+        let mut state = self.state.write().await;
+        match state.status {
+            CaptureServiceStatus::Stopped | CaptureServiceStatus::PermissionLost => Ok(false),
+            _ => {
+                state.status = CaptureServiceStatus::Paused;
+                state.last_stop_time = Some(Local::now());
+                state.paused_until = paused_until;
+                drop(state);
+                self.save_state().await?;
+                Ok(true)
+            }
+        }
+    }
+    
+    /// 标记是否因不在工作时间（--schedule）而停止，供状态查询展示
+    pub async fn set_outside_schedule(&self, outside: bool) -> Result<(), ScreenTimeError> {
+        let mut state = self.state.write().await;
+        if state.outside_schedule == outside {
+            return Ok(());
+        }
+        state.outside_schedule = outside;
+        drop(state);
+        self.save_state().await
+    }
 
+    /// 记录工作时间内的手动停止，until 为下一个工作时间边界；None 表示清除
+    pub async fn set_manual_stop_until(
+        &self,
+        until: Option<DateTime<Local>>,
+    ) -> Result<(), ScreenTimeError> {
+        let mut state = self.state.write().await;
+        if state.manual_stop_until == until {
+            return Ok(());
+        }
+        state.manual_stop_until = until;
+        drop(state);
+        self.save_state().await
+    }
+
+    /// 记录数据目录可用空间不足（Some 为剩余 MB）或已恢复（None），供状态查询展示
+    pub async fn set_low_disk(&self, free_mb: Option<u64>) -> Result<(), ScreenTimeError> {
+        let mut state = self.state.write().await;
+        // 可用空间每次截屏都会变化，只在进入 / 退出低空间状态时写入状态文件
+        let changed = state.low_disk_free_mb.is_some() != free_mb.is_some();
+        state.low_disk_free_mb = free_mb;
+        drop(state);
+        if !changed {
+            return Ok(());
+        }
+        self.save_state().await
+    }
+
+    /// 截屏权限丢失：停止截屏并记录为 PermissionLost，等待用户重新授权
+    pub async fn mark_permission_lost(&self) -> Result<(), ScreenTimeError> {
+        let mut state = self.state.write().await;
+        state.status = CaptureServiceStatus::PermissionLost;
+        state.last_stop_time = Some(Local::now());
+        state.paused_until = None;
+        drop(state);
+        self.save_state().await
+    }
     
-    /// 更新截屏计数
-    pub async fn increment_capture_count(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    /// 更新截屏计数，并累加本次分析的估算费用；sampled_out 表示该截图未被抽中分析
+    pub async fn increment_capture_count(
+        &self,
+        estimated_cost: Option<f64>,
+        sampled_out: bool,
+    ) -> Result<(), ScreenTimeError> {
         let mut state = self.state.write().await;
         state.total_captures += 1;
+        if sampled_out {
+            state.sampled_out_captures += 1;
+        }
+        state.total_estimated_cost += estimated_cost.unwrap_or(0.0);
         state.last_capture_time = Some(Local::now());
         drop(state);
         self.save_state().await?;
@@ -2,12 +2,25 @@ use base64::{Engine as _, engine::general_purpose};
 use reqwest;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::time::{Duration, Instant};
+use crate::backend::AnalysisRequest;
+use crate::analysis_slots::{AnalysisPermit, AnalysisSlots};
+use crate::config::Config;
+use crate::error::ScreenTimeError;
+use crate::frame_buffer;
 use crate::models::TokenUsage;
+use crate::response_cache::{self, CacheRequest, ResponseCache};
+use crate::screenshot;
+use crate::verbosity::info_println;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct SiliconFlowRequest {
     model: String,
     messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -29,6 +42,8 @@ struct Content {
 #[derive(Serialize, Deserialize, Debug)]
 struct ImageUrl {
     url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -51,40 +66,294 @@ struct Choice {
 
 #[derive(Serialize, Deserialize, Debug)]
 struct MessageResponse {
-    content: String,
+    /// 只返回推理过程（如被 max_tokens 截断）时可能为 null
+    #[serde(default)]
+    content: Option<String>,
+    /// 部分接口单独返回的推理过程（SiliconFlow / DeepSeek 为 reasoning_content，OpenRouter 等为 reasoning）
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
 }
 
-/// 分析结果，包含描述、token使用信息和计算耗时
+/// OpenAI 兼容接口的可选请求参数，未设置的字段不会出现在请求体中
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub image_detail: Option<String>,
+    /// 两次 API 调用之间的最小间隔
+    pub min_interval: Duration,
+    /// 建立连接的超时时间
+    pub connect_timeout: Duration,
+    /// 上传图片 base64 编码后的最大字节数（None 表示不限制）
+    pub max_upload_bytes: Option<usize>,
+    /// 分析结果缓存（--cache-dir），None 表示不缓存
+    pub cache: Option<ResponseCache>,
+    /// 跨进程的分析并发名额（--max-concurrent-analyses），None 表示不限制
+    pub analysis_slots: Option<AnalysisSlots>,
+    /// 请求经过的代理（--proxy），启动时已校验
+    pub proxy: Option<reqwest::Proxy>,
+}
+
+impl RequestOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_tokens: config.max_tokens,
+            temperature: config.temperature,
+            image_detail: config
+                .image_detail
+                .as_ref()
+                .map(|d| d.trim().to_lowercase())
+                .filter(|d| !d.is_empty()),
+            min_interval: Duration::from_millis(config.api_min_interval_ms),
+            connect_timeout: Duration::from_secs(config.api_connect_timeout),
+            max_upload_bytes: Some(config.max_upload_bytes as usize).filter(|b| *b > 0),
+            cache: ResponseCache::from_config(config),
+            analysis_slots: AnalysisSlots::from_config(config),
+            proxy: config.api_proxy().ok().flatten(),
+        }
+    }
+
+    /// 调用模型前占用一个并发名额，返回值持有期间生效；未限制时直接返回 None
+    pub async fn acquire_analysis_slot(&self) -> Option<AnalysisPermit> {
+        match &self.analysis_slots {
+            Some(slots) => slots.acquire().await,
+            None => None,
+        }
+    }
+}
+
+/// 读取截图并 base64 编码，返回 (base64 数据, MIME 类型)；
+/// 设置了上传上限且 PNG 超限时转为 JPEG 并逐步压缩，直到符合上限
+pub(crate) async fn encode_image_for_upload(
+    image_path: &str,
+    max_upload_bytes: Option<usize>,
+) -> Result<(String, &'static str), Box<dyn Error + Send + Sync>> {
+    let image_data = tokio::fs::read(image_path).await?;
+    let original_len = screenshot::base64_len(image_data.len());
+    let max_bytes = match max_upload_bytes {
+        Some(max_bytes) if original_len > max_bytes => max_bytes,
+        _ => {
+            frame_buffer::record(image_path, &image_data, "image/png");
+            return Ok((general_purpose::STANDARD.encode(&image_data), "image/png"));
+        }
+    };
+
+    let jpeg = tokio::task::spawn_blocking(move || {
+        let image = image::load_from_memory(&image_data)?;
+        screenshot::shrink_for_upload(&image, max_bytes)
+    })
+    .await??;
+    frame_buffer::record(image_path, &jpeg, "image/jpeg");
+    let base64_image = general_purpose::STANDARD.encode(&jpeg);
+    info_println!(
+        "🗜️ 图片编码后 {} 字节超过上传上限 {} 字节，已压缩为 {} 字节",
+        original_len,
+        max_bytes,
+        base64_image.len()
+    );
+    Ok((base64_image, "image/jpeg"))
+}
+
+/// 按状态码把失败响应转换为 ScreenTimeError::Api：429 或携带 Retry-After 时为限流，
+/// 400 / 413 / 422 为请求被拒绝（批量分析时据此拆分批次）
+pub(crate) fn response_error(
+    status: reqwest::StatusCode,
+    retry_after: Option<Duration>,
+    body: String,
+) -> ScreenTimeError {
+    ScreenTimeError::Api {
+        status: status.as_u16(),
+        retry_after,
+        body,
+    }
+}
+
+lazy_static::lazy_static! {
+    /// 上一次 API 调用的发起时间，用于 --api-min-interval-ms
+    static ref LAST_REQUEST_AT: tokio::sync::Mutex<Option<Instant>> = tokio::sync::Mutex::new(None);
+}
+
+/// 等待到距上次调用至少 min_interval 后再放行；持锁等待以保证并发调用也按顺序间隔
+pub(crate) async fn wait_for_min_interval(min_interval: Duration) {
+    if min_interval.is_zero() {
+        return;
+    }
+    let mut last_request_at = LAST_REQUEST_AT.lock().await;
+    if let Some(last) = *last_request_at {
+        let elapsed = last.elapsed();
+        if elapsed < min_interval {
+            tokio::time::sleep(min_interval - elapsed).await;
+        }
+    }
+    *last_request_at = Some(Instant::now());
+}
+
+/// 解析 Retry-After：支持秒数和 HTTP 日期两种格式
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds().max(0);
+    Some(Duration::from_secs(secs as u64))
+}
+
+/// 分析结果，包含描述、token使用信息、计算耗时和所用模型
 #[derive(Debug)]
 pub struct AnalysisResult {
     pub description: String,
     pub token_usage: Option<TokenUsage>,
     pub processing_time: std::time::Duration,
+    /// 实际生成结果的模型（跳过分析时为 None）
+    pub model: Option<String>,
+    /// 思考模型的推理过程（已从 description 中移除），仅在 --keep-reasoning 时写入日志
+    pub reasoning: Option<String>,
 }
 
-pub async fn analyze_screenshot_with_prompt(
+/// 思考模型输出推理过程的标记
+const THINK_OPEN: &str = "<think>";
+const THINK_CLOSE: &str = "</think>";
+
+/// 回答部分中需要去掉的格式标记（GLM-4.1V-Thinking 用 <answer> 包裹回答，并用 box 标记框出结论）
+const ANSWER_MARKERS: [&str; 4] = ["<answer>", "</answer>", "<|begin_of_box|>", "<|end_of_box|>"];
+
+/// 没有可用回答时的描述
+const EMPTY_DESCRIPTION: &str = "无法分析截图内容";
+
+/// 从模型回复中分离推理过程：去掉 <think>...</think> 段（聊天模板已写入开头的 <think> 时回复中只有 </think>，
+/// 之前的内容都是推理；未闭合的 <think> 视为被截断的推理），并去掉回答的格式标记。
+/// provider_reasoning 为接口单独返回的推理字段。返回 (描述, 推理过程)
+pub fn split_reasoning(content: &str, provider_reasoning: Option<&str>) -> (String, Option<String>) {
+    let mut reasoning: Vec<&str> = provider_reasoning.into_iter().collect();
+    let mut rest = content;
+    if let Some(close) = rest.find(THINK_CLOSE) {
+        if !rest[..close].contains(THINK_OPEN) {
+            reasoning.push(&rest[..close]);
+            rest = &rest[close + THINK_CLOSE.len()..];
+        }
+    }
+
+    let mut answer = String::new();
+    while let Some(open) = rest.find(THINK_OPEN) {
+        answer.push_str(&rest[..open]);
+        let thought = &rest[open + THINK_OPEN.len()..];
+        match thought.find(THINK_CLOSE) {
+            Some(close) => {
+                reasoning.push(&thought[..close]);
+                rest = &thought[close + THINK_CLOSE.len()..];
+            }
+            None => {
+                reasoning.push(thought);
+                rest = "";
+            }
+        }
+    }
+    answer.push_str(rest);
+    for marker in ANSWER_MARKERS {
+        answer = answer.replace(marker, "");
+    }
+
+    let reasoning: Vec<&str> = reasoning.into_iter().map(str::trim).filter(|r| !r.is_empty()).collect();
+    let answer = answer.trim();
+    (
+        if answer.is_empty() { EMPTY_DESCRIPTION.to_string() } else { answer.to_string() },
+        (!reasoning.is_empty()).then(|| reasoning.join("\n\n")),
+    )
+}
+
+/// 创建请求模型接口的 HTTP 客户端：总超时覆盖整个请求，连接超时让不可达的地址尽快失败，配置了代理时经代理发送
+pub(crate) fn http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+    proxy: Option<&reqwest::Proxy>,
+) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().timeout(timeout).connect_timeout(connect_timeout);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.clone());
+    }
+    builder.build()
+}
+
+/// 发送最小的纯文本请求（max_tokens = 1），用于验证 API 地址、密钥和模型是否可用
+pub async fn ping(
     api_key: &str,
     api_url: &str,
     model: &str,
-    image_path: &str,
-    prompt: &str,
-    extra_context: Option<&str>, // 系统上下文
-    activity_history: Option<&str>, // 新增：用户活动历史
-    timeout_secs: u64, // 新增：超时时间参数
-) -> Result<AnalysisResult, Box<dyn Error + Send + Sync>> {
+    timeout_secs: u64,
+    connect_timeout_secs: u64,
+    proxy: Option<&reqwest::Proxy>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = http_client(
+        Duration::from_secs(timeout_secs),
+        Duration::from_secs(connect_timeout_secs),
+        proxy,
+    )?;
+    let request_body = serde_json::json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": "ping" }],
+        "max_tokens": 1,
+    });
+
+    let response = client
+        .post(api_url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API请求失败: {} - {}", status, error_text).into());
+    }
+    Ok(())
+}
+
+/// 分析一张或多张截图：多张时按顺序作为多个 image_url 放在同一条消息中；
+/// 启用 --cache-dir 时相同输入直接返回缓存结果
+pub async fn analyze_screenshot_with_prompt(
+    request: &AnalysisRequest<'_>,
+    options: &RequestOptions,
+) -> Result<AnalysisResult, ScreenTimeError> {
+    let cache_request = CacheRequest {
+        backend: "siliconflow",
+        api_url: request.api_url,
+        model: request.model,
+        image_paths: request.image_paths,
+        prompt: request.prompt,
+        extra_context: request.extra_context,
+        activity_history: request.activity_history,
+    };
+    response_cache::cached(options, cache_request, || async move {
+        let _permit = options.acquire_analysis_slot().await;
+        request_analysis(request, options).await
+    })
+    .await
+}
+
+/// 发送分析请求（不经过缓存）
+async fn request_analysis(
+    request: &AnalysisRequest<'_>,
+    options: &RequestOptions,
+) -> Result<AnalysisResult, ScreenTimeError> {
+    let AnalysisRequest {
+        api_key,
+        api_url,
+        model,
+        image_paths,
+        prompt,
+        extra_context,
+        activity_history,
+        timeout_secs,
+        ..
+    } = *request;
     let start_time = std::time::Instant::now();
     
-    // 创建带有自定义超时的HTTP客户端
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(timeout_secs))
-        .build()?;
+    let client = http_client(Duration::from_secs(timeout_secs), options.connect_timeout, options.proxy.as_ref())?;
     let url = api_url;
     
-    // 读取图片文件并编码为base64
-    let image_data = tokio::fs::read(image_path).await?;
-    let base64_image = general_purpose::STANDARD.encode(&image_data);
-    let image_url = format!("data:image/png;base64,{}", base64_image);
-    
     // 构建请求体
     let mut contents = vec![
         Content {
@@ -110,11 +379,18 @@ pub async fn analyze_screenshot_with_prompt(
         });
     }
 
-    contents.push(Content {
-        content_type: "image_url".to_string(),
-        text: None,
-        image_url: Some(ImageUrl { url: image_url }),
-    });
+    // 读取图片文件并编码为base64（超过上传上限时先压缩）
+    for image_path in image_paths {
+        let (base64_image, mime_type) = encode_image_for_upload(image_path, options.max_upload_bytes).await?;
+        contents.push(Content {
+            content_type: "image_url".to_string(),
+            text: None,
+            image_url: Some(ImageUrl {
+                url: format!("data:{};base64,{}", mime_type, base64_image),
+                detail: options.image_detail.clone(),
+            }),
+        });
+    }
 
     let request_body = SiliconFlowRequest {
         model: model.to_string(),
@@ -122,8 +398,13 @@ pub async fn analyze_screenshot_with_prompt(
             role: "user".to_string(),
             content: contents,
         }],
+        max_tokens: options.max_tokens,
+        temperature: options.temperature,
     };
     
+    // 客户端限速：与上次调用保持最小间隔
+    wait_for_min_interval(options.min_interval).await;
+
     // 发送请求
     let response = client
         .post(url)
@@ -136,8 +417,13 @@ pub async fn analyze_screenshot_with_prompt(
     // 检查响应状态
     if !response.status().is_success() {
         let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
         let error_text = response.text().await?;
-        return Err(format!("API请求失败: {} - {}", status, error_text).into());
+        return Err(response_error(status, retry_after, error_text));
     }
     
     let response_text = response.text().await?;
@@ -147,16 +433,12 @@ pub async fn analyze_screenshot_with_prompt(
     
     match siliconflow_response {
         Ok(response) => {
-            // 提取描述文本
-            let description = if let Some(choices) = response.choices {
-                if let Some(choice) = choices.first() {
-                    choice.message.content.clone()
-                } else {
-                    "无法分析截图内容".to_string()
-                }
-            } else {
-                "无法分析截图内容".to_string()
-            };
+            // 提取描述文本，思考模型的推理过程单独保存
+            let message = response.choices.and_then(|choices| choices.into_iter().next()).map(|choice| choice.message);
+            let (description, reasoning) = split_reasoning(
+                message.as_ref().and_then(|m| m.content.as_deref()).unwrap_or_default(),
+                message.as_ref().and_then(|m| m.reasoning_content.as_deref()),
+            );
 
             // 提取token使用信息
             let token_usage = response.usage.map(|usage| TokenUsage {
@@ -170,12 +452,40 @@ pub async fn analyze_screenshot_with_prompt(
                 description,
                 token_usage,
                 processing_time,
+                model: Some(model.to_string()),
+                reasoning,
             })
         },
         Err(e) => {
             eprintln!("解析API响应时出错: {}", e);
             eprintln!("原始响应: {}", response_text);
-            Err("解析API响应失败".into())
+            Err(ScreenTimeError::other("解析API响应失败"))
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_reasoning() {
+        let (description, reasoning) = split_reasoning(
+            "<think>用户在写代码。</think><answer><|begin_of_box|>【编程】【Code】【编写解析器】<|end_of_box|></answer>",
+            None,
+        );
+        assert_eq!(description, "【编程】【Code】【编写解析器】");
+        assert_eq!(reasoning.as_deref(), Some("用户在写代码。"));
+
+        // 模板已写入 <think>，回复中只有闭合标记
+        let (description, reasoning) = split_reasoning("先看窗口标题。\n</think>\n【学习】【Chrome】【阅读文档】", None);
+        assert_eq!(description, "【学习】【Chrome】【阅读文档】");
+        assert_eq!(reasoning.as_deref(), Some("先看窗口标题。"));
+
+        let (description, reasoning) = split_reasoning("【编程】【Code】【运行测试】", Some("接口单独返回的推理"));
+        assert_eq!(description, "【编程】【Code】【运行测试】");
+        assert_eq!(reasoning.as_deref(), Some("接口单独返回的推理"));
+
+        // 推理被 max_tokens 截断，没有回答
+        assert_eq!(split_reasoning("<think>还没想完", None).0, EMPTY_DESCRIPTION);
+    }
+}
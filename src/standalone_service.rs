@@ -3,8 +3,16 @@ use crate::openclaw;
 use crate::clipboard::{self, ClipboardManager};
 use crate::service_state::ServiceStateManager;
 use crate::capture;
-use crate::models::{CaptureServiceStatus, ServiceCommand, ServiceResponse};
+use crate::compress;
+use crate::error::ScreenTimeError;
+use crate::logger;
+use crate::offline_queue;
+use crate::events;
+use crate::verbosity::info_println;
+use crate::models::{CaptureEvent, CaptureServiceStatus, ServiceCommand, ServiceRequest, ServiceResponse};
+use crate::window_tracker::WINDOW_TRACKER;
 use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{broadcast, Mutex};
 
@@ -12,17 +20,58 @@ use tokio::sync::{broadcast, Mutex};
 use tokio::net::{UnixListener, UnixStream};
 #[cfg(windows)]
 use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json;
 
+/// 控制 socket 协议版本，帧格式变更时递增
+const CONTROL_PROTOCOL_VERSION: u32 = 3;
+
+/// 单帧最大长度（字节），防止异常长度导致内存暴涨
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// 窗口追踪历史定期保存间隔（秒）
+const WINDOW_TRACKER_SAVE_INTERVAL_SECS: u64 = 300;
+
+/// 工作时间表最长复查间隔（秒），系统休眠或时钟调整后也能及时跟上边界
+const SCHEDULE_RECHECK_INTERVAL_SECS: u64 = 60;
+
 /// 独立截屏服务
 pub struct StandaloneService {
     config: Config,
     state_manager: Arc<ServiceStateManager>,
     shutdown_tx: broadcast::Sender<()>,
-    capture_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    capture_handle: Arc<Mutex<Option<capture::CaptureTask>>>,
+    clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    clipboard_manager: Arc<Mutex<ClipboardManager>>,
+    resume_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+/// 控制 socket 处理命令所需的共享状态，每个连接持有一份克隆
+#[derive(Clone)]
+struct ControlContext {
+    state_manager: Arc<ServiceStateManager>,
+    config: Config,
+    capture_handle: Arc<Mutex<Option<capture::CaptureTask>>>,
     clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
     clipboard_manager: Arc<Mutex<ClipboardManager>>,
+    resume_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+impl ControlContext {
+    /// 执行一条控制命令
+    async fn handle_command(&self, command: ServiceCommand) -> ServiceResponse {
+        StandaloneService::handle_command(
+            command,
+            &self.state_manager,
+            &self.config,
+            &self.capture_handle,
+            &self.clipboard_handle,
+            &self.clipboard_manager,
+            &self.resume_handle,
+        )
+        .await
+    }
 }
 
 impl StandaloneService {
@@ -39,36 +88,122 @@ impl StandaloneService {
             capture_handle: Arc::new(Mutex::new(None)),
             clipboard_handle: Arc::new(Mutex::new(None)),
             clipboard_manager,
+            resume_handle: Arc::new(Mutex::new(None)),
         })
     }
     
     /// 启动服务（包括恢复之前的状态）
     pub async fn start(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        println!("🚀 启动独立截屏服务...");
+        info_println!("🚀 启动独立截屏服务...");
+
+        // 单实例锁：持有到 start 返回，覆盖截屏循环与日志写入
+        let _instance_lock = InstanceLock::acquire(self.config.get_lock_path())?;
+
+        // 恢复窗口追踪历史，并定期落盘
+        let tracker_path = self.config.get_window_tracker_path();
+        WINDOW_TRACKER.load(&tracker_path).await;
+        {
+            let tracker_path = tracker_path.clone();
+            tokio::spawn(async move {
+                let mut timer = tokio::time::interval(tokio::time::Duration::from_secs(
+                    WINDOW_TRACKER_SAVE_INTERVAL_SECS,
+                ));
+                timer.tick().await;
+                loop {
+                    timer.tick().await;
+                    if let Err(e) = WINDOW_TRACKER.save(&tracker_path).await {
+                        eprintln!("保存窗口追踪历史失败: {}", e);
+                    }
+                }
+            });
+        }
         
-        // 检查之前的状态并自动恢复
+        // 检查之前的状态并自动恢复；配置了 --schedule 且当前不在工作时间内时保持停止
+        let now = chrono::Local::now();
+        let outside_schedule = self
+            .config
+            .schedule
+            .as_ref()
+            .is_some_and(|schedule| !schedule.is_active(&now, &self.config.timezone));
         let current_state = self.state_manager.get_state().await;
-        match current_state.status {
-            CaptureServiceStatus::Running => {
-                println!("🔄 检测到之前服务正在运行，自动恢复截屏...");
-                self.start_capture_loop().await?;
+        let manual_stop_until = current_state.manual_stop_until.filter(|until| now < *until);
+        if outside_schedule {
+            if matches!(current_state.status, CaptureServiceStatus::Running | CaptureServiceStatus::Paused) {
+                self.state_manager.stop_service().await?;
             }
-            CaptureServiceStatus::Stopped => {
-                println!("⏹️ 服务处于停止状态");
+            self.state_manager.set_outside_schedule(true).await?;
+            let next_start = self
+                .config
+                .schedule
+                .as_ref()
+                .and_then(|schedule| schedule.next_boundary(&now, &self.config.timezone))
+                .map(|time| format!("，将于 {} 开始", self.config.timezone.format(&time, "%Y-%m-%d %H:%M")))
+                .unwrap_or_default();
+            info_println!("🕘 当前不在工作时间内，截屏保持停止{}", next_start);
+        } else {
+            match current_state.status {
+                CaptureServiceStatus::Running => {
+                    info_println!("🔄 检测到之前服务正在运行，自动恢复截屏...");
+                    self.start_capture_loop().await?;
+                }
+                CaptureServiceStatus::Stopped => match manual_stop_until {
+                    Some(until) if self.config.schedule.is_some() => info_println!(
+                        "⏹️ 工作时间内已手动停止截屏，{} 前保持停止",
+                        self.config.timezone.format(&until, "%Y-%m-%d %H:%M")
+                    ),
+                    _ => info_println!("⏹️ 服务处于停止状态"),
+                },
+                CaptureServiceStatus::Paused => {
+                    // 暂停期间重启：按剩余时间继续等待自动恢复，已过期则立即恢复
+                    match current_state.paused_until {
+                        Some(until) => {
+                            let remaining = (until - chrono::Local::now()).num_seconds().max(0) as u64;
+                            info_println!("⏸️ 服务处于暂停状态，将在 {} 秒后自动恢复", remaining);
+                            Self::schedule_auto_resume(
+                                remaining,
+                                &self.state_manager,
+                                &self.config,
+                                &self.capture_handle,
+                                &self.resume_handle,
+                            )
+                            .await;
+                        }
+                        None => info_println!("⏸️ 服务处于暂停状态，等待手动恢复"),
+                    }
+                }
+                CaptureServiceStatus::PermissionLost => {
+                    // 重新授权通常需要重启进程才生效，因此启动时权限已恢复则直接继续截屏
+                    if crate::permissions::check_screen_recording_permission() {
+                        info_println!("🔄 屏幕录制权限已恢复，自动恢复截屏...");
+                        self.state_manager.start_service().await?;
+                        self.start_capture_loop().await?;
+                    } else {
+                        info_println!("❌ 上次运行时屏幕录制权限丢失，且当前仍未授权，截屏保持停止");
+                        info_println!("👉 macOS: 系统设置 → 隐私与安全性 → 屏幕录制，勾选终端或 OpenRecall 后重启服务");
+                    }
+                }
             }
         }
 
-        if self.config.start_capture_on_launch
+        // 工作时间内启动时按时间表开始截屏，与 --start-capture-on-launch 效果相同；
+        // 工作时间内手动停止过则保持停止到下一个边界
+        if (self.config.start_capture_on_launch
+            || (self.config.schedule.is_some() && manual_stop_until.is_none()))
+            && !outside_schedule
             && matches!(current_state.status, CaptureServiceStatus::Stopped)
         {
-            println!("⚡ 检测到 --start-capture-on-launch，强制开启截屏服务...");
+            if self.config.start_capture_on_launch {
+                info_println!("⚡ 检测到 --start-capture-on-launch，强制开启截屏服务...");
+            } else {
+                info_println!("🕘 当前在工作时间内，开启截屏服务...");
+            }
             match self.state_manager.start_service().await {
                 Ok(true) => {
                     self.start_capture_loop().await?;
-                    println!("✅ 启动时已强制开启截屏服务");
+                    info_println!("✅ 启动时已强制开启截屏服务");
                 }
                 Ok(false) => {
-                    println!("ℹ️ 截屏服务已在运行状态");
+                    info_println!("ℹ️ 截屏服务已在运行状态");
                 }
                 Err(e) => {
                     eprintln!("⚠️ 启动时强制开启截屏失败: {}", e);
@@ -81,6 +216,9 @@ impl StandaloneService {
         }
         
         // 启动控制socket服务器
+        if self.config.control_token().is_none() {
+            eprintln!("⚠️ 未设置 --control-token（SERVICE_CONTROL_TOKEN），本机任意进程都可以通过控制 socket 启停截屏服务，多用户环境建议设置");
+        }
         #[cfg(unix)]
         {
             let socket_path = self.config.get_socket_path();
@@ -94,25 +232,11 @@ impl StandaloneService {
             }
             
             let listener = UnixListener::bind(&socket_path)?;
-            println!("🔌 控制socket启动: {:?}", socket_path);
-            
-            let state_manager = self.state_manager.clone();
-            let config = self.config.clone();
-            let shutdown_tx = self.shutdown_tx.clone();
-            let capture_handle = self.capture_handle.clone();
-            let clipboard_handle = self.clipboard_handle.clone();
-            let clipboard_manager = self.clipboard_manager.clone();
+            info_println!("🔌 控制socket启动: {:?}", socket_path);
             
+            let context = self.control_context();
             tokio::spawn(async move {
-                Self::handle_unix_socket_connections(
-                    listener, 
-                    state_manager, 
-                    config, 
-                    shutdown_tx,
-                    capture_handle,
-                    clipboard_handle,
-                    clipboard_manager,
-                ).await;
+                Self::handle_unix_socket_connections(listener, context).await;
             });
         }
         
@@ -120,25 +244,46 @@ impl StandaloneService {
         {
             let port = self.config.get_control_port();
             let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
-            println!("🔌 控制TCP socket启动: 127.0.0.1:{}", port);
+            info_println!("🔌 控制TCP socket启动: 127.0.0.1:{}", port);
             
+            let context = self.control_context();
+            tokio::spawn(async move {
+                Self::handle_tcp_socket_connections(listener, context).await;
+            });
+        }
+
+        // 控制 socket 就绪后再注册暂停快捷键，快捷键通过 socket 发送启停命令
+        crate::hotkey::spawn_pause_hotkey(&self.config);
+
+        // 配置了工作时间表时，在开始 / 结束时刻自动启停截屏
+        if self.config.schedule.is_some() {
             let state_manager = self.state_manager.clone();
             let config = self.config.clone();
-            let shutdown_tx = self.shutdown_tx.clone();
             let capture_handle = self.capture_handle.clone();
             let clipboard_handle = self.clipboard_handle.clone();
             let clipboard_manager = self.clipboard_manager.clone();
-            
+            let resume_handle = self.resume_handle.clone();
             tokio::spawn(async move {
-                Self::handle_tcp_socket_connections(
-                    listener, 
-                    state_manager, 
-                    config, 
-                    shutdown_tx,
+                Self::run_schedule_loop(
+                    config,
+                    state_manager,
                     capture_handle,
                     clipboard_handle,
                     clipboard_manager,
-                ).await;
+                    resume_handle,
+                )
+                .await;
+            });
+        }
+
+        // 配置了截图保留天数或压缩时，启动每日清理任务
+        if self.config.screenshot_retention_days > 0
+            || self.config.compress
+            || self.config.compress_screenshots_after_days > 0
+        {
+            let config = self.config.clone();
+            tokio::spawn(async move {
+                run_retention_loop(config).await;
             });
         }
 
@@ -150,11 +295,18 @@ impl StandaloneService {
             });
         }
         
-        println!("✅ 独立截屏服务启动完成！");
+        info_println!("✅ 独立截屏服务启动完成！");
         
         // 等待关闭信号
         let mut shutdown_rx = self.shutdown_tx.subscribe();
         shutdown_rx.recv().await.ok();
+
+        // 退出前让截屏循环收尾，批量与排队中的截图写入日志
+        Self::stop_capture_task(&self.capture_handle).await;
+
+        if let Err(e) = WINDOW_TRACKER.save(&tracker_path).await {
+            eprintln!("保存窗口追踪历史失败: {}", e);
+        }
         
         // 清理socket文件（仅Unix系统）
         #[cfg(unix)]
@@ -166,28 +318,30 @@ impl StandaloneService {
         Ok(())
     }
     
+    /// 控制 socket 连接共享的服务状态
+    fn control_context(&self) -> ControlContext {
+        ControlContext {
+            state_manager: self.state_manager.clone(),
+            config: self.config.clone(),
+            capture_handle: self.capture_handle.clone(),
+            clipboard_handle: self.clipboard_handle.clone(),
+            clipboard_manager: self.clipboard_manager.clone(),
+            resume_handle: self.resume_handle.clone(),
+        }
+    }
+
     /// 处理Unix socket连接
     #[cfg(unix)]
     async fn handle_unix_socket_connections(
         listener: UnixListener,
-        state_manager: Arc<ServiceStateManager>,
-        config: Config,
-        _shutdown_tx: broadcast::Sender<()>,
-        capture_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_manager: Arc<Mutex<ClipboardManager>>,
+        context: ControlContext,
     ) {
         loop {
             match listener.accept().await {
                 Ok((stream, _addr)) => {
-                    let state_manager = state_manager.clone();
-                    let config = config.clone();
-                    let capture_handle = capture_handle.clone();
-                    let clipboard_handle = clipboard_handle.clone();
-                    let clipboard_manager = clipboard_manager.clone();
-                    
+                    let context = context.clone();
                     tokio::spawn(async move {
-                        Self::handle_unix_stream(stream, state_manager, config, capture_handle, clipboard_handle, clipboard_manager).await;
+                        Self::handle_unix_stream(stream, context).await;
                     });
                 }
                 Err(e) => {
@@ -202,25 +356,15 @@ impl StandaloneService {
     #[cfg(windows)]
     async fn handle_tcp_socket_connections(
         listener: TcpListener,
-        state_manager: Arc<ServiceStateManager>,
-        config: Config,
-        _shutdown_tx: broadcast::Sender<()>,
-        capture_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_manager: Arc<Mutex<ClipboardManager>>,
+        context: ControlContext,
     ) {
         loop {
             match listener.accept().await {
                 Ok((stream, addr)) => {
-                    println!("接受TCP连接: {}", addr);
-                    let state_manager = state_manager.clone();
-                    let config = config.clone();
-                    let capture_handle = capture_handle.clone();
-                    let clipboard_handle = clipboard_handle.clone();
-                    let clipboard_manager = clipboard_manager.clone();
-                    
+                    info_println!("接受TCP连接: {}", addr);
+                    let context = context.clone();
                     tokio::spawn(async move {
-                        Self::handle_tcp_stream(stream, state_manager, config, capture_handle, clipboard_handle, clipboard_manager).await;
+                        Self::handle_tcp_stream(stream, context).await;
                     });
                 }
                 Err(e) => {
@@ -235,39 +379,31 @@ impl StandaloneService {
     #[cfg(unix)]
     async fn handle_unix_stream(
         mut stream: UnixStream,
-        state_manager: Arc<ServiceStateManager>,
-        config: Config,
-        capture_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_manager: Arc<Mutex<ClipboardManager>>,
+        context: ControlContext,
     ) {
-        let mut buffer = Vec::new();
-        let mut temp_buffer = [0; 1024];
-        
-        loop {
-            match stream.read(&mut temp_buffer).await {
-                Ok(0) => break, // 连接关闭
-                Ok(n) => {
-                    buffer.extend_from_slice(&temp_buffer[..n]);
-                    
-                    // 尝试解析JSON命令
-                    if let Ok(command) = serde_json::from_slice::<ServiceCommand>(&buffer) {
-                        let response = Self::handle_command(command, &state_manager, &config, &capture_handle, &clipboard_handle, &clipboard_manager).await;
-                        
-                        if let Ok(response_json) = serde_json::to_string(&response) {
-                            if let Err(e) = stream.write_all(response_json.as_bytes()).await {
-                                eprintln!("写入Unix socket响应失败: {}", e);
-                                break;
-                            }
-                        }
-                        break;
-                    }
-                }
-                Err(e) => {
-                    eprintln!("读取Unix socket失败: {}", e);
-                    break;
+        let response = match read_frame::<_, ServiceRequest>(&mut stream).await {
+            Ok(request) if !control_token_matches(&context.config, request.token.as_deref()) => {
+                Self::unauthorized_response(&request)
+            }
+            Ok(ServiceRequest { command: ServiceCommand::Subscribe, .. }) => {
+                Self::stream_capture_events(&mut stream).await;
+                return;
+            }
+            Ok(request) => context.handle_command(request.command).await,
+            Err(e) => {
+                eprintln!("读取Unix socket命令失败: {}", e);
+                ServiceResponse {
+                    success: false,
+                    message: format!("无效的控制命令: {}", e),
+                    state: None,
+                    clipboard_status: None,
+                    metrics: None,
                 }
             }
+        };
+
+        if let Err(e) = write_frame(&mut stream, &response).await {
+            eprintln!("写入Unix socket响应失败: {}", e);
         }
     }
     
@@ -275,53 +411,90 @@ impl StandaloneService {
     #[cfg(windows)]
     async fn handle_tcp_stream(
         mut stream: TcpStream,
-        state_manager: Arc<ServiceStateManager>,
-        config: Config,
-        capture_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
-        clipboard_manager: Arc<Mutex<ClipboardManager>>,
+        context: ControlContext,
     ) {
-        let mut buffer = Vec::new();
-        let mut temp_buffer = [0; 1024];
-        
+        let response = match read_frame::<_, ServiceRequest>(&mut stream).await {
+            Ok(request) if !control_token_matches(&context.config, request.token.as_deref()) => {
+                Self::unauthorized_response(&request)
+            }
+            Ok(ServiceRequest { command: ServiceCommand::Subscribe, .. }) => {
+                Self::stream_capture_events(&mut stream).await;
+                return;
+            }
+            Ok(request) => context.handle_command(request.command).await,
+            Err(e) => {
+                eprintln!("读取TCP socket命令失败: {}", e);
+                ServiceResponse {
+                    success: false,
+                    message: format!("无效的控制命令: {}", e),
+                    state: None,
+                    clipboard_status: None,
+                    metrics: None,
+                }
+            }
+        };
+
+        if let Err(e) = write_frame(&mut stream, &response).await {
+            eprintln!("写入TCP socket响应失败: {}", e);
+        }
+    }
+    
+    /// 控制令牌不匹配时的拒绝响应
+    fn unauthorized_response(request: &ServiceRequest) -> ServiceResponse {
+        eprintln!("⚠️ 拒绝控制令牌不匹配的命令: {:?}", request.command);
+        ServiceResponse {
+            success: false,
+            message: "控制令牌无效：请使用与独立服务相同的 --control-token / SERVICE_CONTROL_TOKEN".to_string(),
+            state: None,
+            clipboard_status: None,
+            metrics: None,
+        }
+    }
+
+    /// 推送截屏进度事件，直到对端断开
+    async fn stream_capture_events<S>(stream: &mut S)
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let mut receiver = events::subscribe();
+        let ack = ServiceResponse {
+            success: true,
+            message: "已订阅截屏进度事件".to_string(),
+            state: None,
+            clipboard_status: None,
+            metrics: None,
+        };
+        if write_frame(stream, &ack).await.is_err() {
+            return;
+        }
+
         loop {
-            match stream.read(&mut temp_buffer).await {
-                Ok(0) => break, // 连接关闭
-                Ok(n) => {
-                    buffer.extend_from_slice(&temp_buffer[..n]);
-                    
-                    // 尝试解析JSON命令
-                    if let Ok(command) = serde_json::from_slice::<ServiceCommand>(&buffer) {
-                        let response = Self::handle_command(command, &state_manager, &config, &capture_handle, &clipboard_handle, &clipboard_manager).await;
-                        
-                        if let Ok(response_json) = serde_json::to_string(&response) {
-                            if let Err(e) = stream.write_all(response_json.as_bytes()).await {
-                                eprintln!("写入TCP socket响应失败: {}", e);
-                                break;
-                            }
-                        }
+            match receiver.recv().await {
+                Ok(event) => {
+                    if write_frame(stream, &event).await.is_err() {
                         break;
                     }
                 }
-                Err(e) => {
-                    eprintln!("读取TCP socket失败: {}", e);
-                    break;
-                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
             }
         }
     }
-    
+
     /// 处理服务命令
     async fn handle_command(
         command: ServiceCommand,
         state_manager: &Arc<ServiceStateManager>,
         config: &Config,
-        capture_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+        capture_handle: &Arc<Mutex<Option<capture::CaptureTask>>>,
         clipboard_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
         clipboard_manager: &Arc<Mutex<ClipboardManager>>,
+        resume_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
     ) -> ServiceResponse {
         match command {
             ServiceCommand::Start => {
+                // 手动启动时取消待执行的自动恢复
+                Self::cancel_auto_resume(resume_handle).await;
                 match state_manager.start_service().await {
                     Ok(true) => {
                         // 启动截屏循环
@@ -332,6 +505,7 @@ impl StandaloneService {
                                 message: format!("启动截屏失败: {}", e),
                                 state: Some(state_manager.get_state().await),
                                 clipboard_status: Some(clipboard_manager.lock().await.status()),
+                                metrics: None,
                             }
                         } else {
                             if config.clipboard_enabled {
@@ -344,6 +518,7 @@ impl StandaloneService {
                                 message: "服务已启动".to_string(),
                                 state: Some(state_manager.get_state().await),
                                 clipboard_status: Some(clipboard_manager.lock().await.status()),
+                                metrics: None,
                             }
                         }
                     }
@@ -352,26 +527,43 @@ impl StandaloneService {
                         message: "服务已在运行".to_string(),
                         state: Some(state_manager.get_state().await),
                         clipboard_status: Some(clipboard_manager.lock().await.status()),
+                        metrics: None,
                     },
                     Err(e) => ServiceResponse {
                         success: false,
                         message: format!("启动失败: {}", e),
                         state: Some(state_manager.get_state().await),
                         clipboard_status: Some(clipboard_manager.lock().await.status()),
+                        metrics: None,
                     }
                 }
             }
             ServiceCommand::Stop => {
+                Self::cancel_auto_resume(resume_handle).await;
                 match state_manager.stop_service().await {
                     Ok(_) => {
                         // 停止截屏循环
                         Self::stop_capture_task(capture_handle).await;
                         Self::stop_clipboard_task(clipboard_handle).await;
+                        if let Err(e) = WINDOW_TRACKER.save(&config.get_window_tracker_path()).await {
+                            eprintln!("保存窗口追踪历史失败: {}", e);
+                        }
+                        // 工作时间内的停止视为手动停止，直到下一个边界前重启也不自动开始
+                        let now = chrono::Local::now();
+                        let manual_stop_until = config
+                            .schedule
+                            .as_ref()
+                            .filter(|schedule| schedule.is_active(&now, &config.timezone))
+                            .and_then(|schedule| schedule.next_boundary(&now, &config.timezone));
+                        if let Err(e) = state_manager.set_manual_stop_until(manual_stop_until).await {
+                            eprintln!("保存服务状态失败: {}", e);
+                        }
                         ServiceResponse {
                             success: true,
                             message: "服务已停止".to_string(),
                             state: Some(state_manager.get_state().await),
                             clipboard_status: Some(clipboard_manager.lock().await.status()),
+                            metrics: None,
                         }
                     }
                     Err(e) => ServiceResponse {
@@ -379,21 +571,132 @@ impl StandaloneService {
                         message: format!("停止失败: {}", e),
                         state: Some(state_manager.get_state().await),
                         clipboard_status: Some(clipboard_manager.lock().await.status()),
+                        metrics: None,
+                    }
+                }
+            }
+            ServiceCommand::Pause { resume_after_secs } => {
+                Self::cancel_auto_resume(resume_handle).await;
+                let paused_until = resume_after_secs
+                    .map(|secs| chrono::Local::now() + chrono::Duration::seconds(secs as i64));
+                match state_manager.pause_service(paused_until).await {
+                    Ok(true) => {
+                        Self::stop_capture_task(capture_handle).await;
+                        if let Some(secs) = resume_after_secs {
+                            Self::schedule_auto_resume(secs, state_manager, config, capture_handle, resume_handle).await;
+                        }
+                        ServiceResponse {
+                            success: true,
+                            message: match resume_after_secs {
+                                Some(secs) => format!("服务已暂停，将在 {} 秒后自动恢复", secs),
+                                None => "服务已暂停".to_string(),
+                            },
+                            state: Some(state_manager.get_state().await),
+                            clipboard_status: Some(clipboard_manager.lock().await.status()),
+                            metrics: None,
+                        }
                     }
+                    Ok(false) => ServiceResponse {
+                        success: false,
+                        message: "服务未运行，无法暂停".to_string(),
+                        state: Some(state_manager.get_state().await),
+                        clipboard_status: Some(clipboard_manager.lock().await.status()),
+                        metrics: None,
+                    },
+                    Err(e) => ServiceResponse {
+                        success: false,
+                        message: format!("暂停失败: {}", e),
+                        state: Some(state_manager.get_state().await),
+                        clipboard_status: Some(clipboard_manager.lock().await.status()),
+                        metrics: None,
+                    },
                 }
             }
 
+            ServiceCommand::Subscribe => ServiceResponse {
+                success: false,
+                message: "订阅需使用 ServiceController::subscribe_events".to_string(),
+                state: None,
+                clipboard_status: None,
+                metrics: None,
+            },
+
             ServiceCommand::Status => ServiceResponse {
                 success: true,
-                message: "状态查询成功".to_string(),
+                message: {
+                    let mut message = "状态查询成功".to_string();
+                    if config.analysis_sample_rate < 1.0 {
+                        message.push_str(&format!("\n分析采样率: {:.0}%", config.analysis_sample_rate * 100.0));
+                    }
+                    let offline_pending = offline_queue::len(config);
+                    if offline_pending > 0 {
+                        message.push_str(&format!("\n离线待补分析: {} 张截图", offline_pending));
+                    }
+                    message
+                },
                 state: Some(state_manager.get_state().await),
                 clipboard_status: Some(clipboard_manager.lock().await.status()),
+                metrics: Some(crate::metrics::snapshot()),
+            },
+            ServiceCommand::Metrics => ServiceResponse {
+                success: true,
+                message: "指标查询成功".to_string(),
+                state: Some(state_manager.get_state().await),
+                clipboard_status: None,
+                metrics: Some(crate::metrics::snapshot()),
             },
+            ServiceCommand::CaptureNow => {
+                // 暂停期间用户明确不希望被记录，权限丢失时截屏必然失败
+                let status = state_manager.get_state().await.status;
+                let result = match status {
+                    CaptureServiceStatus::Paused => Err(ScreenTimeError::other("服务已暂停，恢复后再截屏")),
+                    CaptureServiceStatus::PermissionLost => {
+                        Err(ScreenTimeError::Permission("屏幕录制权限已丢失".to_string()))
+                    }
+                    CaptureServiceStatus::Running | CaptureServiceStatus::Stopped => {
+                        capture::capture_now(config, state_manager).await
+                    }
+                };
+                match result {
+                    Ok(log) => ServiceResponse {
+                        success: true,
+                        message: format!("截屏完成（{}）\n{}", log.timestamp.format("%Y-%m-%d %H:%M:%S"), log.description),
+                        state: Some(state_manager.get_state().await),
+                        clipboard_status: None,
+                        metrics: None,
+                    },
+                    Err(e) => ServiceResponse {
+                        success: false,
+                        message: e.user_message(),
+                        state: Some(state_manager.get_state().await),
+                        clipboard_status: None,
+                        metrics: None,
+                    },
+                }
+            }
+            ServiceCommand::DumpFrames => {
+                let (success, message) = match crate::frame_buffer::dump(config, "manual") {
+                    Ok(Some((dir, count))) => (true, format!("已导出最近 {} 帧上传图片到 {}", count, dir.display())),
+                    Ok(None) if config.keep_last_frames == 0 => {
+                        (false, "未开启最近截图缓冲，请使用 --keep-last-frames N 启动服务".to_string())
+                    }
+                    Ok(None) => (true, "最近截图缓冲为空，尚无上传给模型的截图".to_string()),
+                    Err(e) => (false, format!("导出最近截图失败: {}", e)),
+                };
+                ServiceResponse {
+                    success,
+                    message,
+                    state: None,
+                    clipboard_status: None,
+                    metrics: None,
+                }
+            }
             ServiceCommand::ClipboardStatus => ServiceResponse {
                 success: true,
                 message: "剪贴板状态查询成功".to_string(),
                 state: Some(state_manager.get_state().await),
                 clipboard_status: Some(clipboard_manager.lock().await.status()),
+                metrics: None,
             },
             ServiceCommand::ClipboardList { limit } => {
                 let guard = clipboard_manager.lock().await;
@@ -414,6 +717,7 @@ impl StandaloneService {
                     message,
                     state: Some(state_manager.get_state().await),
                     clipboard_status: Some(guard.status()),
+                    metrics: None,
                 }
             }
             ServiceCommand::ClipboardSave { id, target_dir } => {
@@ -427,18 +731,21 @@ impl StandaloneService {
                         message: format!("已保存到 {}", path.to_string_lossy()),
                         state: Some(state_manager.get_state().await),
                         clipboard_status: Some(guard.status()),
+                        metrics: None,
                     },
                     Ok(None) => ServiceResponse {
                         success: false,
                         message: "未找到对应剪贴板记录".to_string(),
                         state: Some(state_manager.get_state().await),
                         clipboard_status: Some(guard.status()),
+                        metrics: None,
                     },
                     Err(e) => ServiceResponse {
                         success: false,
                         message: format!("保存失败: {}", e),
                         state: Some(state_manager.get_state().await),
                         clipboard_status: Some(guard.status()),
+                        metrics: None,
                     },
                 }
             }
@@ -450,6 +757,7 @@ impl StandaloneService {
                     message: format!("自动保存已{}", if enabled { "开启" } else { "关闭" }),
                     state: Some(state_manager.get_state().await),
                     clipboard_status: Some(guard.status()),
+                    metrics: None,
                 }
             }
         }
@@ -459,36 +767,128 @@ impl StandaloneService {
     async fn start_capture_task(
         state_manager: &Arc<ServiceStateManager>,
         config: &Config,
-        capture_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>
+        capture_handle: &Arc<Mutex<Option<capture::CaptureTask>>>
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let mut handle_guard = capture_handle.lock().await;
         
-        // 如果已有任务在运行，先停止
-        if let Some(handle) = handle_guard.take() {
-            handle.abort();
+        // 如果已有任务在运行，先停止并等待收尾
+        if let Some(task) = handle_guard.take() {
+            task.stop().await;
         }
         
-        let state_manager_clone = state_manager.clone();
-        let config_clone = config.clone();
-        
+        *handle_guard = Some(capture::CaptureTask::spawn(config.clone(), state_manager.clone()));
+        Ok(())
+    }
+    
+    /// 在指定秒数后自动恢复截屏
+    async fn schedule_auto_resume(
+        resume_after_secs: u64,
+        state_manager: &Arc<ServiceStateManager>,
+        config: &Config,
+        capture_handle: &Arc<Mutex<Option<capture::CaptureTask>>>,
+        resume_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    ) {
+        let state_manager = state_manager.clone();
+        let config = config.clone();
+        let capture_handle = capture_handle.clone();
+
         let handle = tokio::spawn(async move {
-            if let Err(e) = capture::run_capture_loop_with_state(config_clone, state_manager_clone).await {
-                eprintln!("截屏循环出错: {}", e);
+            tokio::time::sleep(tokio::time::Duration::from_secs(resume_after_secs)).await;
+            match state_manager.start_service().await {
+                Ok(true) => {
+                    if let Err(e) = Self::start_capture_task(&state_manager, &config, &capture_handle).await {
+                        eprintln!("自动恢复截屏失败: {}", e);
+                        let _ = state_manager.stop_service().await;
+                    } else {
+                        info_println!("▶️ 暂停结束，已自动恢复截屏");
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("自动恢复截屏失败: {}", e),
             }
         });
-        
-        *handle_guard = Some(handle);
-        Ok(())
+
+        *resume_handle.lock().await = Some(handle);
     }
-    
-    /// 停止截屏任务
-    async fn stop_capture_task(capture_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>) {
-        let mut handle_guard = capture_handle.lock().await;
-        if let Some(handle) = handle_guard.take() {
+
+    /// 工作时间表任务（--schedule）：进入工作时间时执行 Start，离开时执行 Stop。
+    /// 只在进出工作时间的边界动作，期间的手动启停不会被覆盖
+    async fn run_schedule_loop(
+        config: Config,
+        state_manager: Arc<ServiceStateManager>,
+        capture_handle: Arc<Mutex<Option<capture::CaptureTask>>>,
+        clipboard_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+        clipboard_manager: Arc<Mutex<ClipboardManager>>,
+        resume_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    ) {
+        let Some(schedule) = config.schedule.clone() else {
+            return;
+        };
+        let tz = config.timezone;
+        let recheck = std::time::Duration::from_secs(SCHEDULE_RECHECK_INTERVAL_SECS);
+        let mut active = schedule.is_active(&chrono::Local::now(), &tz);
+
+        loop {
+            let now = chrono::Local::now();
+            let wait = schedule
+                .next_boundary(&now, &tz)
+                .and_then(|boundary| (boundary - now).to_std().ok())
+                .map_or(recheck, |wait| wait.min(recheck));
+            tokio::time::sleep(wait).await;
+
+            let now_active = schedule.is_active(&chrono::Local::now(), &tz);
+            if now_active == active {
+                continue;
+            }
+            active = now_active;
+
+            // 到达边界后手动停止不再生效
+            if let Err(e) = state_manager.set_manual_stop_until(None).await {
+                eprintln!("保存服务状态失败: {}", e);
+            }
+            let command = if active { ServiceCommand::Start } else { ServiceCommand::Stop };
+            let response = Self::handle_command(
+                command,
+                &state_manager,
+                &config,
+                &capture_handle,
+                &clipboard_handle,
+                &clipboard_manager,
+                &resume_handle,
+            )
+            .await;
+            if !active {
+                if let Err(e) = state_manager.set_outside_schedule(true).await {
+                    eprintln!("保存服务状态失败: {}", e);
+                }
+            }
+            if response.success {
+                info_println!(
+                    "🕘 {}：{}",
+                    if active { "进入工作时间" } else { "工作时间结束" },
+                    response.message
+                );
+            } else {
+                eprintln!("⚠️ 按工作时间表启停截屏失败: {}", response.message);
+            }
+        }
+    }
+
+    /// 取消待执行的自动恢复
+    async fn cancel_auto_resume(resume_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>) {
+        if let Some(handle) = resume_handle.lock().await.take() {
             handle.abort();
         }
     }
 
+    /// 停止截屏任务：通知截屏循环退出，等待剩余截图分析完并写入日志
+    async fn stop_capture_task(capture_handle: &Arc<Mutex<Option<capture::CaptureTask>>>) {
+        let mut handle_guard = capture_handle.lock().await;
+        if let Some(task) = handle_guard.take() {
+            task.stop().await;
+        }
+    }
+
     async fn start_clipboard_task(
         config: &Config,
         clipboard_handle: &Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
@@ -531,12 +931,344 @@ impl StandaloneService {
 
 }
 
+/// 单实例锁：持有 service.lock 的独占文件锁（与 analysis_slots 相同的机制），进程退出（包括崩溃）时由操作系统释放，
+/// 不需要根据 PID 判断锁是否失效；文件内容只记录持有者 PID，用于提示
+struct InstanceLock {
+    file: std::fs::File,
+}
+
+impl InstanceLock {
+    /// 获取锁；已有存活实例持有锁时返回错误
+    fn acquire(path: std::path::PathBuf) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        use std::io::Write;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                let holder = std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u32>().ok())
+                    .map(|pid| format!(" (PID {})", pid))
+                    .unwrap_or_default();
+                return Err(format!("已有独立截屏服务在运行{}，锁文件: {}", holder, path.display()).into());
+            }
+            Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        // 持有锁之后才写入 PID，其他实例读到的空内容不会被当作失效锁
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // 不删除锁文件：删除后其他进程可能锁住已脱离路径的旧文件，与新建的锁文件同时生效；关闭文件即释放锁
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// 过期文件清理与压缩循环：启动时执行一次，之后每天执行一次（先删除过期文件，再压缩）
+async fn run_retention_loop(config: Config) {
+    let retention_days = config.screenshot_retention_days;
+    if retention_days > 0 {
+        info_println!(
+            "🧹 截图保留策略已启用：保留 {} 天{}",
+            retention_days,
+            if config.retention_delete_logs { "（含每日日志）" } else { "" }
+        );
+    }
+    if config.compress || config.compress_screenshots_after_days > 0 {
+        info_println!(
+            "🗜️ 压缩已启用：{}{}",
+            if config.compress { "已结束日期的每日日志 " } else { "" },
+            match config.compress_screenshots_after_days {
+                0 => String::new(),
+                days => format!("超过 {} 天的截图", days),
+            }
+        );
+    }
+
+    let mut timer = tokio::time::interval(tokio::time::Duration::from_secs(24 * 60 * 60));
+    loop {
+        timer.tick().await;
+
+        if retention_days > 0 {
+            let config = config.clone();
+            match tokio::task::spawn_blocking(move || cleanup_expired_files(&config)).await {
+                Ok((files, bytes)) => {
+                    info_println!(
+                        "🧹 过期文件清理完成：删除 {} 个文件，释放 {:.2} MB",
+                        files,
+                        bytes as f64 / 1024.0 / 1024.0
+                    );
+                }
+                Err(e) => eprintln!("⚠️ 过期文件清理失败: {}", e),
+            }
+        }
+
+        if config.compress || config.compress_screenshots_after_days > 0 {
+            let config = config.clone();
+            match tokio::task::spawn_blocking(move || compress_old_files(&config)).await {
+                Ok((files, bytes)) if files > 0 => {
+                    info_println!(
+                        "🗜️ 压缩完成：压缩 {} 个文件，节省 {:.2} MB",
+                        files,
+                        bytes as f64 / 1024.0 / 1024.0
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("⚠️ 文件压缩失败: {}", e),
+            }
+        }
+    }
+}
+
+/// 压缩已结束日期的每日日志（--compress）和超过天数的截图（--compress-screenshots-after-days），
+/// 返回 (文件数, 节省的字节数)
+fn compress_old_files(config: &Config) -> (u64, u64) {
+    let mut files = 0u64;
+    let mut saved = 0u64;
+
+    if config.compress {
+        match logger::compress_daily_logs(config) {
+            Ok((count, bytes)) => {
+                files += count;
+                saved += bytes;
+            }
+            Err(e) => eprintln!("⚠️ 日志压缩失败: {}", e),
+        }
+    }
+
+    let days = config.compress_screenshots_after_days;
+    if days == 0 {
+        return (files, saved);
+    }
+    let Some(cutoff) = std::time::SystemTime::now().checked_sub(std::time::Duration::from_secs(days * 24 * 60 * 60)) else {
+        return (files, saved);
+    };
+    for (path, metadata) in walk_files(&config.get_screenshot_dir()) {
+        // 只压缩截图（.png），缩略图（.jpg）保持原样供缩略图索引使用
+        if path.extension().and_then(|e| e.to_str()) != Some("png") {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
+        if modified >= cutoff {
+            continue;
+        }
+        match compress::gzip_file(&path) {
+            Ok(bytes) => {
+                files += 1;
+                saved += bytes;
+            }
+            Err(e) => eprintln!("⚠️ 压缩截图失败: {} ({})", path.display(), e),
+        }
+    }
+
+    (files, saved)
+}
+
+/// 递归列出目录下的文件：--screenshot-name-template 可以把截图放进按日期等划分的子目录。
+/// 不跟随符号链接，避免清理到截图目录之外
+fn walk_files(dir: &Path) -> Vec<(PathBuf, std::fs::Metadata)> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                if let Ok(metadata) = entry.metadata() {
+                    files.push((entry.path(), metadata));
+                }
+            }
+        }
+    }
+    files
+}
+
+/// 删除 dir 下清理后变空的子目录（不删除 dir 本身）
+fn remove_empty_subdirs(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            let path = entry.path();
+            remove_empty_subdirs(&path);
+            // 非空时删除失败，忽略即可
+            let _ = std::fs::remove_dir(&path);
+        }
+    }
+}
+
+/// 删除超过保留天数的截图（及可选的每日日志），返回 (文件数, 字节数)
+pub fn cleanup_expired_files(config: &Config) -> (u64, u64) {
+    let retention = std::time::Duration::from_secs(config.screenshot_retention_days * 24 * 60 * 60);
+    let cutoff = match std::time::SystemTime::now().checked_sub(retention) {
+        Some(t) => t,
+        None => return (0, 0),
+    };
+
+    let mut removed_files = 0u64;
+    let mut removed_bytes = 0u64;
+
+    let screenshot_dir = config.get_screenshot_dir();
+    for (path, metadata) in walk_files(&screenshot_dir) {
+        // 截图（.png，压缩后为 .png.gz）、缩略图（.jpg）与录屏片段（.mp4）
+        if !matches!(path.extension().and_then(|e| e.to_str()), Some("png") | Some("jpg") | Some("gz") | Some("mp4")) {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
+        if modified < cutoff && std::fs::remove_file(&path).is_ok() {
+            removed_files += 1;
+            removed_bytes += metadata.len();
+        }
+    }
+    if removed_files > 0 {
+        remove_empty_subdirs(&screenshot_dir);
+    }
+
+    if config.retention_delete_logs {
+        // 每日日志按文件名中的日期判断是否过期
+        let cutoff_date = config.timezone.today()
+            - chrono::Duration::days(config.screenshot_retention_days as i64);
+        let log_dirs = [config.get_logs_dir(), config.get_data_dir().join("logs_md")];
+        for dir in log_dirs.iter() {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                // 取第一个 '.' 之前的部分，连同 .json.bak / .json.corrupt 一并清理
+                let date = path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| s.split('.').next())
+                    .and_then(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+                if let Some(date) = date {
+                    if date < cutoff_date {
+                        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                        if std::fs::remove_file(&path).is_ok() {
+                            removed_files += 1;
+                            removed_bytes += size;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    (removed_files, removed_bytes)
+}
+
+/// 校验控制令牌：服务端未设置时放行，否则要求完全一致（按字节恒定时间比较）
+fn control_token_matches(config: &Config, token: Option<&str>) -> bool {
+    let Some(expected) = config.control_token() else {
+        return true;
+    };
+    let Some(token) = token else {
+        return false;
+    };
+    let (expected, token) = (expected.as_bytes(), token.as_bytes());
+    expected.len() == token.len()
+        && expected.iter().zip(token).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// 控制协议帧内容：带版本号的 JSON 负载
+#[derive(Serialize, Deserialize)]
+struct ControlFrame<T> {
+    version: u32,
+    payload: T,
+}
+
+/// 写入一帧：u32 大端长度 + JSON 负载
+async fn write_frame<W, T>(stream: &mut W, payload: &T) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let body = serde_json::to_vec(&ControlFrame { version: CONTROL_PROTOCOL_VERSION, payload })?;
+    if body.len() > MAX_FRAME_LEN as usize {
+        return Err(format!("帧过大: {} 字节", body.len()).into());
+    }
+    stream.write_all(&(body.len() as u32).to_be_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// 读取一帧并校验协议版本
+async fn read_frame<R, T>(stream: &mut R) -> Result<T, Box<dyn Error + Send + Sync>>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf);
+    if len > MAX_FRAME_LEN {
+        return Err(format!("帧长度超出上限: {} 字节", len).into());
+    }
+
+    let mut body = vec![0u8; len as usize];
+    stream.read_exact(&mut body).await?;
+
+    let frame: ControlFrame<T> = serde_json::from_slice(&body)?;
+    if frame.version != CONTROL_PROTOCOL_VERSION {
+        return Err(format!(
+            "控制协议版本不匹配: 对端 {}，本端 {}",
+            frame.version, CONTROL_PROTOCOL_VERSION
+        )
+        .into());
+    }
+    Ok(frame.payload)
+}
+
+#[cfg(unix)]
+type ControlStream = UnixStream;
+#[cfg(windows)]
+type ControlStream = TcpStream;
+
+/// 截屏进度事件订阅（保持一条控制连接）
+pub struct EventSubscription {
+    stream: ControlStream,
+}
+
+impl EventSubscription {
+    /// 等待下一条事件，连接断开时返回错误
+    pub async fn next_event(&mut self) -> Result<CaptureEvent, Box<dyn Error + Send + Sync>> {
+        read_frame::<_, CaptureEvent>(&mut self.stream).await
+    }
+}
+
 /// 服务控制客户端
 pub struct ServiceController {
     #[cfg(unix)]
     socket_path: std::path::PathBuf,
     #[cfg(windows)]
     port: u16,
+    /// 随每条命令发送的控制令牌
+    token: Option<String>,
 }
 
 impl ServiceController {
@@ -545,57 +1277,136 @@ impl ServiceController {
         {
             Self {
                 socket_path: config.get_socket_path(),
+                token: config.control_token().map(str::to_string),
             }
         }
         #[cfg(windows)]
         {
             Self {
                 port: config.get_control_port(),
+                token: config.control_token().map(str::to_string),
             }
         }
     }
     
+    fn request(&self, command: ServiceCommand) -> ServiceRequest {
+        ServiceRequest {
+            command,
+            token: self.token.clone(),
+        }
+    }
+
     /// 发送命令到服务
     pub async fn send_command(&self, command: ServiceCommand) -> Result<ServiceResponse, Box<dyn Error + Send + Sync>> {
-        use tokio::time::{timeout, Duration};
-        
-        // 设置30秒的连接和通信超时
-        let timeout_duration = Duration::from_secs(30);
-        
-        let result = timeout(timeout_duration, async {
+        // 设置30秒的连接和通信超时；停止 / 暂停需等待剩余截图分析完（含重试），与 CaptureNow 一样放宽到5分钟
+        let timeout_secs = match command {
+            ServiceCommand::Stop | ServiceCommand::Pause { .. } => 300,
+            _ => 30,
+        };
+        self.send_command_with_timeout(command, std::time::Duration::from_secs(timeout_secs)).await
+    }
+
+    /// 立即截屏并分析一次，成功时响应消息中包含分析描述
+    pub async fn capture_now(&self) -> Result<ServiceResponse, Box<dyn Error + Send + Sync>> {
+        // 需等待截屏循环中正在进行的截屏以及模型分析（含重试），超时放宽到5分钟
+        self.send_command_with_timeout(ServiceCommand::CaptureNow, std::time::Duration::from_secs(300)).await
+    }
+
+    async fn send_command_with_timeout(
+        &self,
+        command: ServiceCommand,
+        timeout_duration: std::time::Duration,
+    ) -> Result<ServiceResponse, Box<dyn Error + Send + Sync>> {
+        let request = self.request(command);
+
+        let result = tokio::time::timeout(timeout_duration, async {
             #[cfg(unix)]
             {
                 let mut stream = UnixStream::connect(&self.socket_path).await?;
-                
-                let command_str = serde_json::to_string(&command)?;
-                stream.write_all(command_str.as_bytes()).await?;
-                
-                let mut buffer = [0; 4096];
-                let n = stream.read(&mut buffer).await?;
-                let response_str = String::from_utf8_lossy(&buffer[..n]);
-                
-                let response: ServiceResponse = serde_json::from_str(&response_str)?;
-                Ok(response)
+                write_frame(&mut stream, &request).await?;
+                read_frame::<_, ServiceResponse>(&mut stream).await
             }
             #[cfg(windows)]
             {
                 let mut stream = TcpStream::connect(format!("127.0.0.1:{}", self.port)).await?;
-                
-                let command_str = serde_json::to_string(&command)?;
-                stream.write_all(command_str.as_bytes()).await?;
-                
-                let mut buffer = [0; 4096];
-                let n = stream.read(&mut buffer).await?;
-                let response_str = String::from_utf8_lossy(&buffer[..n]);
-                
-                let response: ServiceResponse = serde_json::from_str(&response_str)?;
-                Ok(response)
+                write_frame(&mut stream, &request).await?;
+                read_frame::<_, ServiceResponse>(&mut stream).await
             }
         }).await;
         
         match result {
             Ok(response) => response,
-            Err(_) => Err("操作超时：TCP连接或通信超过30秒".into()),
+            Err(_) => Err(format!("操作超时：TCP连接或通信超过{}秒", timeout_duration.as_secs()).into()),
+        }
+    }
+
+    /// 订阅截屏进度事件
+    pub async fn subscribe_events(&self) -> Result<EventSubscription, Box<dyn Error + Send + Sync>> {
+        #[cfg(unix)]
+        let mut stream = UnixStream::connect(&self.socket_path).await?;
+        #[cfg(windows)]
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", self.port)).await?;
+
+        write_frame(&mut stream, &self.request(ServiceCommand::Subscribe)).await?;
+        let ack = read_frame::<_, ServiceResponse>(&mut stream).await?;
+        if !ack.success {
+            return Err(ack.message.into());
         }
+        Ok(EventSubscription { stream })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_lock_is_exclusive_until_dropped() {
+        let dir = std::env::temp_dir().join(format!("openrecall_instance_lock_{}", std::process::id()));
+        let path = dir.join("service.lock");
+
+        let lock = InstanceLock::acquire(path.clone()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), std::process::id().to_string());
+        assert!(InstanceLock::acquire(path.clone()).is_err());
+        drop(lock);
+        // 空锁文件不代表失效：能否获取只取决于文件锁
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        assert!(InstanceLock::acquire(path.clone()).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cleanup_expired_files_walks_template_subdirs() {
+        use clap::Parser;
+
+        let dir = std::env::temp_dir().join(format!("openrecall_cleanup_{}", std::process::id()));
+        let config = Config::parse_from([
+            "openrecall",
+            "--data-dir",
+            dir.to_str().unwrap(),
+            "--screenshot-retention-days",
+            "7",
+        ]);
+        let screenshots = config.get_screenshot_dir();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(30 * 24 * 60 * 60);
+        let write = |path: PathBuf, modified: Option<std::time::SystemTime>| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let file = std::fs::File::create(&path).unwrap();
+            std::fs::write(&path, b"png").unwrap();
+            if let Some(modified) = modified {
+                file.set_modified(modified).unwrap();
+            }
+            path
+        };
+        let old_nested = write(screenshots.join("2024-01-01").join("a.png"), Some(old));
+        let old_top = write(screenshots.join("b.jpg"), Some(old));
+        let recent_nested = write(screenshots.join("today").join("c.png"), None);
+
+        assert_eq!(cleanup_expired_files(&config), (2, 6));
+        assert!(!old_nested.exists() && !old_top.exists());
+        assert!(recent_nested.exists());
+        // 清空的子目录一并删除
+        assert!(!screenshots.join("2024-01-01").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
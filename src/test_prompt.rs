@@ -1,21 +1,29 @@
+use crate::backend::{analyze_with_backend, AnalysisRequest};
 use crate::siliconflow;
 use crate::logger;
-use crate::models::{ActivityLog, SystemContext};
+use crate::models::{ActivityClassification, ActivityLog, SystemContext};
 use crate::config::Config;
+use crate::capture;
 use crate::context;
+use crate::verbosity::{self, text_println};
+use std::collections::HashSet;
 use std::error::Error;
-use std::fs::File;
-use std::io::BufWriter;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// 追加测试结果失败时的最大尝试次数
+const WRITE_RETRIES: u32 = 3;
 
 pub async fn run_test_prompt(config: Config) -> Result<(), Box<dyn Error + Send + Sync>> {
     let test_prompt = config.test_prompt.as_ref()
         .ok_or("测试prompt不能为空")?;
     
-    println!("🧪 开始测试新prompt...");
-    println!("📝 测试prompt: {}", test_prompt);
-    println!("📊 使用日志目录: {:?}", config.get_logs_dir());
-    println!("💾 测试结果保存到: {:?}", config.test_log_path);
-    println!();
+    text_println!("🧪 开始测试新prompt...");
+    text_println!("📝 测试prompt: {}", test_prompt);
+    text_println!("📊 使用日志目录: {:?}", config.get_logs_dir());
+    text_println!("💾 测试结果保存到: {:?}", config.test_log_path);
+    text_println!();
 
     // 读取最近30天的日志
     let existing_logs = logger::load_recent_daily_logs(&config, 30)?;
@@ -24,24 +32,54 @@ pub async fn run_test_prompt(config: Config) -> Result<(), Box<dyn Error + Send
         return Err("没有找到现有的活动日志，无法进行测试".into());
     }
 
-    println!("📋 找到 {} 条现有记录，开始重新分析...", existing_logs.len());
+    text_println!("📋 找到 {} 条现有记录，开始重新分析...", existing_logs.len());
 
+    let request_options = siliconflow::RequestOptions::from_config(&config);
     let mut processed_count = 0;
     let mut success_count = 0;
     let mut skip_count = 0;
 
-    // 初始化测试日志文件
-    initialize_test_log(&config.test_log_path)?;
-    println!("💾 测试日志文件已初始化: {:?}", config.test_log_path);
+    // 续跑时沿用已有结果，按时间戳跳过已处理的记录
+    let previous_results = if config.resume {
+        load_previous_results(&config.test_log_path)?
+    } else {
+        Vec::new()
+    };
+    let processed_timestamps: HashSet<i64> = previous_results
+        .iter()
+        .map(|log| log.timestamp.timestamp_millis())
+        .collect();
+    let resumed_count = existing_logs
+        .iter()
+        .filter(|log| processed_timestamps.contains(&log.timestamp.timestamp_millis()))
+        .count();
+    if config.resume {
+        text_println!(
+            "♻️  续跑：已有 {} 条结果，其中 {} 条对应本次记录，将跳过",
+            previous_results.len(),
+            resumed_count
+        );
+    }
+
+    // 初始化测试日志文件（续跑时先写回已有结果）
+    initialize_test_log(&config.test_log_path, &previous_results)?;
+    text_println!("💾 逐条结果写入进度文件: {:?}", progress_path(&config.test_log_path));
 
     for (index, original_log) in existing_logs.iter().enumerate() {
+        if processed_timestamps.contains(&original_log.timestamp.timestamp_millis()) {
+            continue;
+        }
         processed_count += 1;
-        println!("🔄 处理第 {}/{} 条记录...", processed_count, existing_logs.len());
+        text_println!(
+            "🔄 处理第 {}/{} 条记录...",
+            processed_count,
+            existing_logs.len() - resumed_count
+        );
 
         // 检查截图文件是否存在
         if let Some(screenshot_path) = &original_log.screenshot_path {
             if !std::path::Path::new(screenshot_path).exists() {
-                println!("⚠️  截图文件不存在: {}，跳过此记录", screenshot_path);
+                text_println!("⚠️  截图文件不存在: {}，跳过此记录", screenshot_path);
                 skip_count += 1;
                 continue;
             }
@@ -50,20 +88,20 @@ pub async fn run_test_prompt(config: Config) -> Result<(), Box<dyn Error + Send
             let history_context = get_history_context_excluding_current(&existing_logs, index, 5)?;
 
             // 使用新的prompt重新分析截图
-            match siliconflow::analyze_screenshot_with_prompt(
-                &config.api_key,
-                &config.api_url,
-                &config.model,
-                screenshot_path,
-                test_prompt,
-                original_log.context.as_ref().map(|ctx| convert_models_to_context(ctx)).as_ref().map(|ctx| context::format_context_as_text(ctx)).as_deref(),
-                Some(&history_context),
-                config.api_timeout,
-            ).await {
+            let extra_context = original_log.context.as_ref().map(|ctx| convert_models_to_context(ctx)).as_ref().map(|ctx| capture::format_context_for_model(&config, ctx));
+            let image_paths = [screenshot_path.as_str()];
+            let request = AnalysisRequest {
+                image_paths: &image_paths,
+                extra_context: extra_context.as_deref(),
+                activity_history: Some(&history_context),
+                ..AnalysisRequest::new(&config, test_prompt)
+            };
+            let result = analyze_with_backend(&request, &request_options).await;
+            match result {
                 Ok(analysis_result) => {
-                    println!("✅ 重新分析完成: {}", analysis_result.description.lines().next().unwrap_or("无描述"));
+                    text_println!("✅ 重新分析完成: {}", analysis_result.description.lines().next().unwrap_or("无描述"));
                     if let Some(ref token_usage) = analysis_result.token_usage {
-                        println!("Token使用情况 - 输入: {:?}, 输出: {:?}, 总计: {:?}，截图时间: {}", 
+                        text_println!("Token使用情况 - 输入: {:?}, 输出: {:?}, 总计: {:?}，截图时间: {}", 
                             token_usage.prompt_tokens, 
                             token_usage.completion_tokens, 
                             token_usage.total_tokens,
@@ -73,16 +111,33 @@ pub async fn run_test_prompt(config: Config) -> Result<(), Box<dyn Error + Send
                     // 创建新的测试日志条目
                     let test_log = ActivityLog {
                         timestamp: original_log.timestamp,
+                        classification: ActivityClassification::parse(&analysis_result.description),
                         description: analysis_result.description,
                         context: original_log.context.clone(),
                         screenshot_path: original_log.screenshot_path.clone(),
                         model: Some(config.model.clone()),
+                        estimated_cost: config.estimate_cost(analysis_result.token_usage.as_ref()),
                         token_usage: analysis_result.token_usage,
+                        shadow_description: None,
+                        thumbnail_path: original_log.thumbnail_path.clone(),
+                        clip_path: original_log.clip_path.clone(),
+                        span_end: original_log.span_end,
+                        merged_count: original_log.merged_count,
+                        analysis_failed: false,
+                        reasoning: analysis_result.reasoning.filter(|_| config.keep_reasoning),
                     };
 
-                    // 立即保存到测试日志文件
-                    append_test_result(&test_log, &config.test_log_path)?;
-                    println!("💾 已保存到测试日志");
+                    // 立即追加到进度文件，写入失败时短暂等待后重试
+                    let mut attempt = 1;
+                    while let Err(e) = append_test_result(&test_log, &config.test_log_path) {
+                        if attempt >= WRITE_RETRIES {
+                            return Err(e);
+                        }
+                        eprintln!("⚠️  写入测试日志失败（第 {}/{} 次）: {}", attempt, WRITE_RETRIES, e);
+                        attempt += 1;
+                        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                    }
+                    text_println!("💾 已保存到测试日志");
                     
                     success_count += 1;
                 },
@@ -93,23 +148,45 @@ pub async fn run_test_prompt(config: Config) -> Result<(), Box<dyn Error + Send
                 }
             }
         } else {
-            println!("⚠️  记录中没有截图路径，跳过此记录");
+            text_println!("⚠️  记录中没有截图路径，跳过此记录");
             skip_count += 1;
         }
     }
 
     // 显示最终统计信息
-    println!("\n🎉 测试完成！");
-    println!("📊 成功重新分析了 {} 条记录", success_count);
-    println!("⚠️  跳过了 {} 条记录", skip_count);
-    println!("💾 结果已保存到: {:?}", config.test_log_path);
+    text_println!("\n🎉 测试完成！");
+    text_println!("📊 成功重新分析了 {} 条记录", success_count);
+    text_println!("⚠️  跳过了 {} 条记录", skip_count);
+    if config.resume {
+        text_println!(
+            "♻️  续跑：沿用已有结果 {} 条，本次新处理 {} 条",
+            resumed_count, success_count
+        );
+    }
+    text_println!("💾 结果已保存到: {:?}", config.test_log_path);
     
-    // 读取最终结果进行对比
+    // 读取逐条写入的结果，整体写入最终结果文件后进行对比
     let final_results = load_test_results(&config.test_log_path)?;
+    finalize_test_log(&final_results, &config.test_log_path)?;
     if !final_results.is_empty() {
         show_comparison_summary(&existing_logs, &final_results)?;
+        if config.test_diff {
+            write_diff_report(&config, test_prompt, &existing_logs, &final_results)?;
+        }
     } else {
-        println!("❌ 没有成功重新分析任何记录");
+        text_println!("❌ 没有成功重新分析任何记录");
+    }
+
+    if verbosity::json_output() {
+        verbosity::print_json(&serde_json::json!({
+            "command": "test-prompt",
+            "analyzed": success_count,
+            "skipped": skip_count,
+            "resumed": resumed_count,
+            "results": final_results.len(),
+            "results_path": config.test_log_path,
+            "diff_report_path": config.test_diff_out.as_ref().filter(|_| config.test_diff && !final_results.is_empty()),
+        }))?;
     }
 
     Ok(())
@@ -156,50 +233,102 @@ fn get_history_context_excluding_current(
     Ok(context)
 }
 
-/// 初始化测试日志文件
-fn initialize_test_log(file_path: &std::path::Path) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let file = File::create(file_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &Vec::<ActivityLog>::new())?;
+/// 逐条写入的进度文件（JSON Lines），与最终结果文件同目录：test_log.json → test_log.partial.jsonl
+fn progress_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("partial.jsonl")
+}
+
+/// 初始化进度文件：清空后写入续跑沿用的已有结果，保证再次中断时进度文件仍包含全部结果
+fn initialize_test_log(file_path: &Path, previous: &[ActivityLog]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut writer = BufWriter::new(File::create(progress_path(file_path))?);
+    for result in previous {
+        serde_json::to_writer(&mut writer, result)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
     Ok(())
 }
 
-/// 追加测试结果到文件
-fn append_test_result(result: &ActivityLog, file_path: &std::path::Path) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // 读取现有结果
-    let mut results = load_test_results(file_path)?;
-    
-    // 添加新结果
-    results.push(result.clone());
-    
-    // 保存更新后的结果
-    let file = File::create(file_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &results)?;
+/// 续跑时读取已有结果：上次完成的最终结果文件加上中断留下的进度文件，按时间戳去重
+fn load_previous_results(file_path: &Path) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
+    let mut results: Vec<ActivityLog> = if file_path.exists() {
+        let content = std::fs::read_to_string(file_path)?;
+        if content.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&content)
+                .map_err(|e| format!("无法解析已有测试结果 {:?}: {}", file_path, e))?
+        }
+    } else {
+        Vec::new()
+    };
+    results.extend(load_test_results(file_path)?);
+
+    let mut seen = HashSet::new();
+    results.retain(|log| seen.insert(log.timestamp.timestamp_millis()));
+    results.sort_by_key(|log| log.timestamp);
+    Ok(results)
+}
+
+/// 追加一条测试结果到进度文件：每条一行，一次写入，中途中断最多丢失最后一行
+fn append_test_result(result: &ActivityLog, file_path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut line = serde_json::to_string(result)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(progress_path(file_path))?;
+    file.write_all(line.as_bytes())?;
+    file.flush()?;
     Ok(())
 }
 
-/// 读取测试结果
-fn load_test_results(file_path: &std::path::Path) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
-    if !file_path.exists() {
+/// 读取进度文件中的测试结果，跳过无法解析的行（例如中断时未写完的最后一行）
+fn load_test_results(file_path: &Path) -> Result<Vec<ActivityLog>, Box<dyn Error + Send + Sync>> {
+    let path = progress_path(file_path);
+    if !path.exists() {
         return Ok(Vec::new());
     }
-    
-    let file = File::open(file_path)?;
-    let results: Vec<ActivityLog> = serde_json::from_reader(file)?;
+
+    let reader = BufReader::new(File::open(&path)?);
+    let mut results = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ActivityLog>(&line) {
+            Ok(result) => results.push(result),
+            Err(e) => eprintln!("⚠️  跳过进度文件第 {} 行（无法解析: {}）", index + 1, e),
+        }
+    }
     Ok(results)
 }
 
+/// 将全部结果以格式化 JSON 数组写入最终结果文件（先写临时文件再重命名，避免写到一半留下损坏文件），
+/// 成功后删除进度文件
+fn finalize_test_log(results: &[ActivityLog], file_path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tmp_path = file_path.with_extension("json.tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer_pretty(&mut writer, results)?;
+        writer.flush()?;
+    }
+    std::fs::rename(&tmp_path, file_path)?;
+    let _ = std::fs::remove_file(progress_path(file_path));
+    Ok(())
+}
+
 /// 显示对比摘要
 fn show_comparison_summary(original: &[ActivityLog], test: &[ActivityLog]) -> Result<(), Box<dyn Error + Send + Sync>> {
-    println!("\n📈 对比摘要:");
-    println!("原始记录数: {}", original.len());
-    println!("测试记录数: {}", test.len());
+    text_println!("\n📈 对比摘要:");
+    text_println!("原始记录数: {}", original.len());
+    text_println!("测试记录数: {}", test.len());
     
     if original.len() == test.len() {
-        println!("✅ 所有记录都成功重新分析");
+        text_println!("✅ 所有记录都成功重新分析");
     } else {
-        println!("⚠️  部分记录重新分析失败");
+        text_println!("⚠️  部分记录重新分析失败");
     }
     
     // 计算描述长度对比
@@ -211,14 +340,101 @@ fn show_comparison_summary(original: &[ActivityLog], test: &[ActivityLog]) -> Re
         .map(|log| log.description.len())
         .sum::<usize>() as f64 / test.len() as f64;
     
-    println!("📏 描述长度对比:");
-    println!("  原始平均长度: {:.1} 字符", original_avg_length);
-    println!("  测试平均长度: {:.1} 字符", test_avg_length);
-    println!("  长度变化: {:.1}%", ((test_avg_length - original_avg_length) / original_avg_length * 100.0));
+    text_println!("📏 描述长度对比:");
+    text_println!("  原始平均长度: {:.1} 字符", original_avg_length);
+    text_println!("  测试平均长度: {:.1} 字符", test_avg_length);
+    text_println!("  长度变化: {:.1}%", ((test_avg_length - original_avg_length) / original_avg_length * 100.0));
     
     Ok(())
 }
 
+/// 输出新旧描述逐条对照报告（--test-diff），按时间戳配对原始记录与测试结果
+fn write_diff_report(
+    config: &Config,
+    test_prompt: &str,
+    original: &[ActivityLog],
+    test: &[ActivityLog],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let pairs: Vec<(&ActivityLog, &ActivityLog)> = test
+        .iter()
+        .filter_map(|new_log| {
+            original
+                .iter()
+                .find(|old| old.timestamp == new_log.timestamp && old.screenshot_path == new_log.screenshot_path)
+                .map(|old| (old, new_log))
+        })
+        .collect();
+
+    let mut category_changes = 0;
+    let mut app_changes = 0;
+    let mut body = String::new();
+    for (old, new) in &pairs {
+        let (old_category, old_app) = extract_tagged_fields(old);
+        let (new_category, new_app) = extract_tagged_fields(new);
+        let category_changed = old_category != new_category;
+        let app_changed = old_app != new_app;
+        if category_changed {
+            category_changes += 1;
+        }
+        if app_changed {
+            app_changes += 1;
+        }
+
+        body.push_str(&format!("## {}\n\n", old.timestamp.format("%Y-%m-%d %H:%M:%S")));
+        body.push_str(&format!("- 截图: {}\n", old.screenshot_path.as_deref().unwrap_or("-")));
+        body.push_str(&format!("- 类型: {}\n", field_change(&old_category, &new_category)));
+        body.push_str(&format!("- 软件: {}\n\n", field_change(&old_app, &new_app)));
+        body.push_str("| 原描述 | 新描述 |\n|--------|--------|\n");
+        body.push_str(&format!(
+            "| {} | {} |\n\n",
+            table_cell(&old.description),
+            table_cell(&new.description)
+        ));
+    }
+
+    let mut report = String::from("# Prompt 对比报告\n\n");
+    report.push_str(&format!("- 新 prompt: {}\n", test_prompt));
+    report.push_str(&format!("- 对照记录: {} 条\n", pairs.len()));
+    report.push_str(&format!("- 类型变化: {} 条\n", category_changes));
+    report.push_str(&format!("- 软件变化: {} 条\n\n", app_changes));
+    report.push_str(&body);
+
+    match &config.test_diff_out {
+        Some(path) => {
+            std::fs::write(path, report)?;
+            text_println!("📄 对照报告已保存到: {:?}", path);
+        }
+        // --json 时 stdout 只留给结果摘要，报告改写到 stderr
+        None if verbosity::json_output() => eprint!("\n{}", report),
+        None => print!("\n{}", report),
+    }
+    Ok(())
+}
+
+/// 取日志中的【类型】【软件】，旧日志没有结构化字段时现场解析描述
+fn extract_tagged_fields(log: &ActivityLog) -> (Option<String>, Option<String>) {
+    log.classification
+        .clone()
+        .or_else(|| ActivityClassification::parse(&log.description))
+        .map(|c| (c.category, c.software))
+        .unwrap_or((None, None))
+}
+
+fn field_change(old: &Option<String>, new: &Option<String>) -> String {
+    let old = old.as_deref().unwrap_or("-");
+    let new = new.as_deref().unwrap_or("-");
+    if old == new {
+        old.to_string()
+    } else {
+        format!("**{} → {}** ⚠️", old, new)
+    }
+}
+
+/// Markdown 表格单元格：换行替换为 <br>，转义竖线
+fn table_cell(text: &str) -> String {
+    text.trim().replace('|', "\\|").replace('\n', "<br>")
+}
+
 /// 将models模块的SystemContext转换为context模块的SystemContext
 fn convert_models_to_context(ctx: &SystemContext) -> context::SystemContext {
     context::SystemContext {
@@ -242,6 +458,8 @@ fn convert_models_to_context(ctx: &SystemContext) -> context::SystemContext {
         }),
         installed_apps: Vec::new(),
         input_activity: None,
-
+        clipboard_text: None,
+        since_last_capture: None,
+        window_info_disabled: false,
     }
 }
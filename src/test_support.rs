@@ -0,0 +1,9 @@
+use crate::config::Config;
+use clap::Parser;
+
+/// 测试用配置：数据目录为临时目录下以 name 和进程号区分的子目录，创建前清空上次残留
+pub fn test_config(name: &str) -> Config {
+    let dir = std::env::temp_dir().join(format!("openrecall_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    Config::parse_from(["openrecall", "--data-dir", dir.to_str().unwrap()])
+}
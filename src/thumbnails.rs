@@ -0,0 +1,172 @@
+use crate::compress;
+use crate::config::Config;
+use crate::logger;
+use crate::verbosity::{self, text_println};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Serialize;
+use std::error::Error;
+use std::path::Path;
+
+/// 缩略图索引中的一条记录
+#[derive(Debug, Serialize)]
+pub struct ThumbnailEntry {
+    pub timestamp: DateTime<Local>,
+    pub thumbnail_path: String,
+    /// 原截图路径（未保留截图时为空）
+    pub screenshot_path: Option<String>,
+    pub category: Option<String>,
+    pub software: Option<String>,
+    /// 描述的第一行
+    pub summary: String,
+}
+
+/// 解析日期参数，未指定时为今天（按 --timezone）
+pub fn resolve_date(config: &Config, date: Option<&str>) -> Result<NaiveDate, Box<dyn Error + Send + Sync>> {
+    match date {
+        Some(d) => NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d")
+            .map_err(|e| format!("日期格式错误（应为 YYYY-MM-DD）: {} ({})", d, e).into()),
+        None => Ok(config.timezone.today()),
+    }
+}
+
+/// 读取某天带缩略图的日志，跳过缩略图文件已不存在的记录，按时间先后排列
+pub fn list_thumbnails(config: &Config, date: NaiveDate) -> Result<Vec<ThumbnailEntry>, Box<dyn Error + Send + Sync>> {
+    let logs = logger::load_daily_activity_logs(config, &date.format("%Y-%m-%d").to_string())?;
+    let mut entries: Vec<ThumbnailEntry> = logs
+        .into_iter()
+        .filter_map(|log| {
+            let thumbnail_path = log.thumbnail_path.filter(|p| Path::new(p).exists())?;
+            let classification = log.classification.unwrap_or_default();
+            Some(ThumbnailEntry {
+                timestamp: log.timestamp,
+                thumbnail_path,
+                screenshot_path: log.screenshot_path.and_then(|p| existing_screenshot(&p)),
+                category: classification.category,
+                software: classification.software,
+                summary: log.description.lines().next().unwrap_or("").trim().to_string(),
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.timestamp);
+    Ok(entries)
+}
+
+/// JSON 格式的缩略图索引
+pub fn render_json(date: NaiveDate, entries: &[ThumbnailEntry]) -> Result<String, Box<dyn Error + Send + Sync>> {
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "date": date.format("%Y-%m-%d").to_string(),
+        "count": entries.len(),
+        "thumbnails": entries,
+    }))?)
+}
+
+/// HTML 缩略图网格：每格显示时间、类型/软件和描述首行，点击打开原截图（未保留时打开缩略图）
+pub fn render_html(date: NaiveDate, entries: &[ThumbnailEntry]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>OpenRecall 截图 {}</title>\n", date.format("%Y-%m-%d")));
+    html.push_str(
+        "<style>\n\
+         body { font-family: -apple-system, \"Segoe UI\", sans-serif; margin: 24px; background: #f5f5f5; }\n\
+         .grid { display: flex; flex-wrap: wrap; gap: 12px; }\n\
+         .item { width: 320px; background: #fff; border-radius: 6px; padding: 8px; box-shadow: 0 1px 3px rgba(0,0,0,.1); }\n\
+         .item img { width: 100%; border-radius: 4px; }\n\
+         .time { font-weight: bold; }\n\
+         .tag { color: #666; font-size: 12px; }\n\
+         .summary { font-size: 13px; margin-top: 4px; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    html.push_str(&format!(
+        "<h1>{} · {} 张截图</h1>\n<div class=\"grid\">\n",
+        date.format("%Y-%m-%d"),
+        entries.len()
+    ));
+    for entry in entries {
+        // 已压缩的截图（.png.gz）浏览器无法直接打开，链接到缩略图
+        let target = entry
+            .screenshot_path
+            .as_deref()
+            .filter(|p| !p.ends_with(".gz"))
+            .unwrap_or(&entry.thumbnail_path);
+        let tags: Vec<&str> = [entry.category.as_deref(), entry.software.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        html.push_str(&format!(
+            "<div class=\"item\">\n<a href=\"{}\"><img src=\"{}\" loading=\"lazy\"></a>\n\
+             <div class=\"time\">{}</div>\n<div class=\"tag\">{}</div>\n<div class=\"summary\">{}</div>\n</div>\n",
+            escape_html(&file_url(target)),
+            escape_html(&file_url(&entry.thumbnail_path)),
+            entry.timestamp.format("%H:%M:%S"),
+            escape_html(&tags.join(" · ")),
+            escape_html(&entry.summary)
+        ));
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+/// 输出缩略图索引（--contact-sheet），写入 stdout 或 --contact-sheet-out 指定的文件；
+/// --json 时输出到 stdout 的索引使用 JSON 格式，写入文件时 stdout 输出文件路径
+pub fn run_contact_sheet(config: &Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let date = resolve_date(config, config.contact_sheet_date.as_deref())?;
+    let entries = list_thumbnails(config, date)?;
+    let format = if verbosity::json_output() && config.contact_sheet_out.is_none() {
+        "json"
+    } else {
+        config.contact_sheet_format.as_str()
+    };
+    let output = match format {
+        "json" => render_json(date, &entries)?,
+        _ => render_html(date, &entries),
+    };
+
+    match &config.contact_sheet_out {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    std::fs::create_dir_all(parent)?;
+                }
+            }
+            std::fs::write(path, output)?;
+            text_println!("🖼️ 缩略图索引（{} 张）已保存到: {}", entries.len(), path.display());
+            if verbosity::json_output() {
+                verbosity::print_json(&serde_json::json!({
+                    "command": "contact-sheet",
+                    "date": date,
+                    "count": entries.len(),
+                    "path": path,
+                }))?;
+            }
+        }
+        None => print!("{}", output),
+    }
+    Ok(())
+}
+
+/// 本地路径转 file:// URL（Windows 反斜杠转为正斜杠）
+fn file_url(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 截图仍存在时返回其路径，已被 --compress-screenshots-after-days 压缩时返回 .png.gz 路径
+fn existing_screenshot(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    if path.exists() {
+        return Some(path.display().to_string());
+    }
+    let gz = compress::gz_path(path);
+    gz.exists().then(|| gz.display().to_string())
+}
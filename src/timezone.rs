@@ -0,0 +1,154 @@
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+/// 日志日期边界与查询时间所用的时区（--timezone）。
+/// 日志时间戳本身始终带 UTC 偏移保存（RFC 3339），这里只决定"哪一天"以及不带偏移的查询时间如何解释
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogTimezone {
+    /// 跟随系统时区（默认）
+    #[default]
+    Local,
+    /// IANA 时区名，如 Asia/Shanghai、America/New_York、UTC
+    Named(Tz),
+    /// 固定偏移，如 +08:00，不受夏令时影响
+    Fixed(FixedOffset),
+}
+
+impl LogTimezone {
+    /// 解析 --timezone：local、UTC、IANA 时区名或 +08:00 形式的固定偏移
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        if value.starts_with('+') || value.starts_with('-') {
+            return value
+                .parse::<FixedOffset>()
+                .map(Self::Fixed)
+                .map_err(|_| format!("无效的时区偏移 \"{}\"（应为 +08:00 形式）", value));
+        }
+        if value.eq_ignore_ascii_case("utc") {
+            return Ok(Self::Named(Tz::UTC));
+        }
+        value
+            .parse::<Tz>()
+            .map(Self::Named)
+            .map_err(|_| format!("无法识别的时区 \"{}\"（可用 local、IANA 时区名如 Asia/Shanghai，或 +08:00）", value))
+    }
+
+    /// 时间点在该时区下所属的日期，用于按天分文件与按天统计
+    pub fn date_of(&self, time: &DateTime<Local>) -> NaiveDate {
+        match self {
+            Self::Local => time.date_naive(),
+            Self::Named(tz) => time.with_timezone(tz).date_naive(),
+            Self::Fixed(offset) => time.with_timezone(offset).date_naive(),
+        }
+    }
+
+    /// 该时区下的今天
+    pub fn today(&self) -> NaiveDate {
+        self.date_of(&Local::now())
+    }
+
+    /// 按该时区格式化时间点
+    pub fn format(&self, time: &DateTime<Local>, fmt: &str) -> String {
+        match self {
+            Self::Local => time.format(fmt).to_string(),
+            Self::Named(tz) => time.with_timezone(tz).format(fmt).to_string(),
+            Self::Fixed(offset) => time.with_timezone(offset).format(fmt).to_string(),
+        }
+    }
+
+    /// 把该时区下的本地时间解释为时间点：夏令时回拨重复的时间取较早的一次，
+    /// 夏令时跳过的时间（如 02:30）按跳变前的偏移顺延（得到 03:30）
+    pub fn localize(&self, naive: NaiveDateTime) -> DateTime<Local> {
+        match self {
+            Self::Local => resolve_local(&Local, naive),
+            Self::Named(tz) => resolve_local(tz, naive),
+            Self::Fixed(offset) => resolve_local(offset, naive),
+        }
+    }
+
+    /// 某天在该时区下的 00:00
+    pub fn start_of_day(&self, date: NaiveDate) -> DateTime<Local> {
+        self.localize(date.and_time(NaiveTime::MIN))
+    }
+
+    /// 解析查询时间：带偏移的时间（2024-05-01T10:00:00+08:00、...Z）按偏移解释，
+    /// 不带偏移的 "YYYY-MM-DD HH:MM:SS" / "YYYY-MM-DDTHH:MM:SS" 按该时区解释，只给日期时取当天 00:00
+    pub fn parse_datetime(&self, value: &str) -> Result<DateTime<Local>, String> {
+        let value = value.trim();
+        let with_offset = DateTime::parse_from_rfc3339(value)
+            .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%:z"))
+            .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S %:z"));
+        if let Ok(time) = with_offset {
+            return Ok(time.with_timezone(&Local));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        {
+            return Ok(self.localize(naive));
+        }
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|date| self.start_of_day(date))
+            .map_err(|_| {
+                format!(
+                    "时间格式错误（应为 YYYY-MM-DD HH:MM:SS、YYYY-MM-DD 或带偏移的 RFC 3339）: {}",
+                    value
+                )
+            })
+    }
+}
+
+fn resolve_local<T: TimeZone>(tz: &T, naive: NaiveDateTime) -> DateTime<Local> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.with_timezone(&Local),
+        LocalResult::None => {
+            // 跳变前一天的偏移即跳变前的偏移（时区跳变间隔远大于一天）
+            let before = tz
+                .offset_from_local_datetime(&(naive - Duration::days(1)))
+                .earliest()
+                .map(|offset| offset.fix())
+                .unwrap_or_else(|| Utc.fix());
+            let utc = naive - Duration::seconds(before.local_minus_utc() as i64);
+            Utc.from_utc_datetime(&utc).with_timezone(&Local)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime_with_timezone() {
+        let tz = LogTimezone::parse("America/New_York").unwrap();
+        assert_eq!(LogTimezone::parse("local").unwrap(), LogTimezone::Local);
+        assert!(matches!(LogTimezone::parse("+08:00").unwrap(), LogTimezone::Fixed(_)));
+        assert!(LogTimezone::parse("Mars/Olympus").is_err());
+
+        // 显式偏移优先于 --timezone
+        let explicit = tz.parse_datetime("2024-05-01T10:00:00+08:00").unwrap();
+        assert_eq!(explicit.with_timezone(&Utc).to_rfc3339(), "2024-05-01T02:00:00+00:00");
+        assert_eq!(tz.parse_datetime("2024-05-01 02:00:00Z").unwrap(), explicit);
+
+        // 不带偏移时按 --timezone 解释，日期边界也按该时区
+        let naive = tz.parse_datetime("2024-05-01 22:00:00").unwrap();
+        assert_eq!(naive.with_timezone(&Utc).to_rfc3339(), "2024-05-02T02:00:00+00:00");
+        assert_eq!(tz.date_of(&naive), NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+
+        // 夏令时跳过的 02:30 顺延到 03:30（EDT），回拨重复的 01:30 取较早的一次（EDT）
+        let gap = tz.parse_datetime("2024-03-10 02:30:00").unwrap();
+        assert_eq!(gap.with_timezone(&Utc).to_rfc3339(), "2024-03-10T07:30:00+00:00");
+        assert_eq!(tz.format(&gap, "%H:%M"), "03:30");
+        let overlap = tz.parse_datetime("2024-11-03 01:30:00").unwrap();
+        assert_eq!(overlap.with_timezone(&Utc).to_rfc3339(), "2024-11-03T05:30:00+00:00");
+
+        let day = tz.parse_datetime("2024-05-01").unwrap();
+        assert_eq!(day, tz.start_of_day(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()));
+        assert!(tz.parse_datetime("05/01/2024").is_err());
+    }
+}
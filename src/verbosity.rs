@@ -0,0 +1,132 @@
+use crate::config::Config;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// 终端输出详细程度：-q 只输出错误和最终结果，-v 额外输出每次重试、选屏等细节
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet = 0,
+    Normal = 1,
+    Verbose = 2,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+impl Verbosity {
+    pub fn from_config(config: &Config) -> Self {
+        if config.quiet {
+            Verbosity::Quiet
+        } else if config.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    /// 未设置 RUST_LOG 时 tracing 的默认过滤级别
+    pub fn tracing_filter(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "error",
+            Verbosity::Normal => "info",
+            Verbosity::Verbose => "debug",
+        }
+    }
+}
+
+/// 设置全局输出级别，启动时根据 -q / -v 调用一次
+pub fn set(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn get() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        2 => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    }
+}
+
+/// 当前级别是否输出 level 级别的内容
+pub fn enabled(level: Verbosity) -> bool {
+    get() >= level
+}
+
+/// 设置 --json 输出模式，启动时调用一次
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// 是否处于 --json 模式：stdout 只输出 JSON，提示文字改写到 stderr
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// 以单行 JSON 输出到 stdout（--json 模式下的命令结果）
+pub fn print_json<T: Serialize>(value: &T) -> Result<(), serde_json::Error> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// 输出不换行的输入提示并立即刷新，--json 时输出到 stderr
+pub fn print_prompt(prompt: &str) -> std::io::Result<()> {
+    if json_output() {
+        eprint!("{}", prompt);
+        std::io::stderr().flush()
+    } else {
+        print!("{}", prompt);
+        std::io::stdout().flush()
+    }
+}
+
+/// 输出一行面向人的提示：按 level 决定是否打印到终端（平时为 stdout，--json 时为 stderr），
+/// 并始终记录为带 console 字段的 tracing 事件（详细输出为 debug 级别），诊断日志中可以看到完整的终端输出。
+/// 终端的 tracing 输出层会过滤这些事件，避免重复打印
+pub fn console_line(level: Verbosity, message: &str) {
+    if level == Verbosity::Verbose {
+        tracing::debug!(console = true, "{}", message);
+    } else {
+        tracing::info!(console = true, "{}", message);
+    }
+    if enabled(level) {
+        if json_output() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
+
+/// 面向人的提示文字：平时输出到 stdout，--json 时输出到 stderr，避免混入 JSON 结果
+macro_rules! text_println {
+    () => {
+        $crate::verbosity::console_line($crate::verbosity::Verbosity::Quiet, "")
+    };
+    ($($arg:tt)*) => {
+        $crate::verbosity::console_line($crate::verbosity::Verbosity::Quiet, &format!($($arg)*))
+    };
+}
+
+/// 常规进度输出，--quiet 时隐藏
+macro_rules! info_println {
+    () => {
+        $crate::verbosity::console_line($crate::verbosity::Verbosity::Normal, "")
+    };
+    ($($arg:tt)*) => {
+        $crate::verbosity::console_line($crate::verbosity::Verbosity::Normal, &format!($($arg)*))
+    };
+}
+
+/// 细节输出（每次重试、选屏过程等），仅 --verbose 时显示
+macro_rules! verbose_println {
+    () => {
+        $crate::verbosity::console_line($crate::verbosity::Verbosity::Verbose, "")
+    };
+    ($($arg:tt)*) => {
+        $crate::verbosity::console_line($crate::verbosity::Verbosity::Verbose, &format!($($arg)*))
+    };
+}
+
+pub(crate) use {info_println, text_println, verbose_println};
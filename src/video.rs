@@ -0,0 +1,254 @@
+use crate::config::Config;
+use crate::screenshot::{CropFraction, RecordingArea};
+use std::path::{Path, PathBuf};
+
+/// 录屏帧率：画面变化靠代表帧体现，低帧率即可，文件也更小
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+const CLIP_FPS: u32 = 5;
+
+/// ffmpeg 在录制时长之外允许的额外时间（启动设备、编码收尾、抽帧）
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+const FFMPEG_GRACE_SECS: u64 = 30;
+
+/// 一段录屏（--capture-mode video）：片段文件与从中抽取的代表帧，Drop 时删除代表帧
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+pub struct VideoClip {
+    pub clip_path: PathBuf,
+    /// 按时间先后排列、已按截图规则缩放和遮盖的代表帧
+    pub frame_paths: Vec<String>,
+    frame_dir: PathBuf,
+}
+
+impl Drop for VideoClip {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.frame_dir);
+    }
+}
+
+/// 录制 --clip-secs 秒的 area 区域到 clip_path，并均匀抽取 --clip-frames 帧；
+/// 未启用 video 特性、找不到 ffmpeg 或录制失败时返回原因，由调用方退回静态截图
+pub async fn record_clip(config: &Config, area: &RecordingArea, clip_path: &Path) -> Result<VideoClip, String> {
+    #[cfg(feature = "video")]
+    {
+        record_and_extract(config, area, clip_path).await
+    }
+
+    #[cfg(not(feature = "video"))]
+    {
+        let _ = (config, area, clip_path);
+        Err("未启用 video 功能（需以 --features video 编译）".to_string())
+    }
+}
+
+#[cfg(feature = "video")]
+async fn record_and_extract(config: &Config, area: &RecordingArea, clip_path: &Path) -> Result<VideoClip, String> {
+    use crate::screenshot;
+
+    run_ffmpeg(config, &recording_args(area, config.clip_secs, clip_path)).await?;
+
+    // 按片段时长均匀取帧：fps=N/时长，最多 N 帧
+    let frame_dir = clip_path.with_extension("frames");
+    std::fs::create_dir_all(&frame_dir).map_err(|e| format!("创建抽帧目录失败: {}", e))?;
+    let mut clip = VideoClip {
+        clip_path: clip_path.to_path_buf(),
+        frame_paths: Vec::new(),
+        frame_dir,
+    };
+    let args = [
+        "-i".to_string(),
+        clip_path.to_string_lossy().into_owned(),
+        "-vf".to_string(),
+        format!("fps={}/{}", config.clip_frames, config.clip_secs),
+        "-frames:v".to_string(),
+        config.clip_frames.to_string(),
+        clip.frame_dir.join("frame_%02d.png").to_string_lossy().into_owned(),
+    ];
+    run_ffmpeg(config, &args).await?;
+
+    let mut frames: Vec<PathBuf> = std::fs::read_dir(&clip.frame_dir)
+        .map_err(|e| format!("读取抽帧目录失败: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    frames.sort();
+    if frames.is_empty() {
+        return Err("录屏片段中没有可用的帧".to_string());
+    }
+
+    // 代表帧与静态截图一样缩放、灰度化并遮盖敏感文字后再上传
+    let image_options = screenshot::ImageOptions::from_config(config);
+    let redact_keywords = config.redact_keywords.clone();
+    let processed = tokio::task::spawn_blocking(move || -> Result<Vec<PathBuf>, String> {
+        for frame in &frames {
+            let image = image::open(frame).map_err(|e| format!("读取代表帧失败: {}", e))?;
+            let mut processed = screenshot::process_image_for_analysis(image, &image_options);
+            screenshot::redact_sensitive_text(&mut processed, &redact_keywords);
+            processed
+                .save_with_format(frame, image::ImageFormat::Png)
+                .map_err(|e| format!("保存代表帧失败: {}", e))?;
+        }
+        Ok(frames)
+    })
+    .await
+    .map_err(|e| format!("处理代表帧任务异常: {}", e))??;
+
+    clip.frame_paths = processed.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+    Ok(clip)
+}
+
+/// 录制一段屏幕的 ffmpeg 参数：录制 area 所在的显示器，需要时裁剪到窗口区域
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+fn recording_args(area: &RecordingArea, secs: u64, clip_path: &Path) -> Vec<String> {
+    let mut args = screen_input_args(&CLIP_FPS.to_string(), area);
+    if let Some(crop) = &area.crop {
+        args.extend(["-vf".to_string(), crop_filter(crop)]);
+    }
+    let secs = secs.to_string();
+    args.extend(["-t", secs.as_str(), "-c:v", "libx264", "-preset", "ultrafast", "-pix_fmt", "yuv420p"].map(String::from));
+    args.push(clip_path.to_string_lossy().into_owned());
+    args
+}
+
+/// 各平台 ffmpeg 录制指定显示器的输入参数
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+fn screen_input_args(fps: &str, area: &RecordingArea) -> Vec<String> {
+    // avfoundation 按屏幕序号选择设备
+    #[cfg(target_os = "macos")]
+    let args = vec![
+        "-f".to_string(),
+        "avfoundation".to_string(),
+        "-capture_cursor".to_string(),
+        "1".to_string(),
+        "-framerate".to_string(),
+        fps.to_string(),
+        "-i".to_string(),
+        format!("Capture screen {}:none", area.screen_index),
+    ];
+    // gdigrab 按虚拟桌面坐标截取显示器所在的矩形
+    #[cfg(windows)]
+    let args = vec![
+        "-f".to_string(),
+        "gdigrab".to_string(),
+        "-framerate".to_string(),
+        fps.to_string(),
+        "-offset_x".to_string(),
+        area.x.to_string(),
+        "-offset_y".to_string(),
+        area.y.to_string(),
+        "-video_size".to_string(),
+        format!("{}x{}", area.width, area.height),
+        "-i".to_string(),
+        "desktop".to_string(),
+    ];
+    // x11grab 以 DISPLAY+x,y 指定显示器左上角
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let args = {
+        let display = std::env::var("DISPLAY").unwrap_or_else(|_| ":0".to_string());
+        vec![
+            "-f".to_string(),
+            "x11grab".to_string(),
+            "-framerate".to_string(),
+            fps.to_string(),
+            "-video_size".to_string(),
+            format!("{}x{}", area.width, area.height),
+            "-i".to_string(),
+            format!("{}+{},{}", display, area.x, area.y),
+        ]
+    };
+
+    args
+}
+
+/// 按比例裁剪画面的 crop 滤镜：录屏像素尺寸由 ffmpeg 决定，宽高取偶数以满足 yuv420p 编码
+#[cfg_attr(not(feature = "video"), allow(dead_code))]
+fn crop_filter(crop: &CropFraction) -> String {
+    format!(
+        "crop=trunc(iw*{:.4}/2)*2:trunc(ih*{:.4}/2)*2:trunc(iw*{:.4}):trunc(ih*{:.4})",
+        crop.width, crop.height, crop.left, crop.top
+    )
+}
+
+/// 运行 ffmpeg，超时后终止；失败时返回 ffmpeg 的错误输出
+#[cfg(feature = "video")]
+async fn run_ffmpeg(config: &Config, args: &[String]) -> Result<(), String> {
+    use std::process::Stdio;
+    use std::time::Duration;
+
+    let mut command = tokio::process::Command::new(&config.ffmpeg_path);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let timeout = Duration::from_secs(config.clip_secs + FFMPEG_GRACE_SECS);
+    let output = match tokio::time::timeout(timeout, command.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "找不到 ffmpeg（{}），请安装 ffmpeg 或通过 --ffmpeg-path 指定",
+                config.ffmpeg_path
+            ))
+        }
+        Ok(Err(e)) => return Err(format!("启动 ffmpeg 失败: {}", e)),
+        Err(_) => return Err(format!("ffmpeg 超过 {} 秒未完成", timeout.as_secs())),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let detail = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("无错误输出");
+        return Err(format!("ffmpeg 退出（{}）: {}", output.status, detail.trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(crop: Option<CropFraction>) -> RecordingArea {
+        RecordingArea {
+            screen_index: 1,
+            x: 1920,
+            y: 0,
+            width: 2560,
+            height: 1440,
+            crop,
+        }
+    }
+
+    #[test]
+    fn test_recording_args_target_display_and_crop() {
+        let clip_path = Path::new("/tmp/clip.mp4");
+        let args = recording_args(&area(None), 10, clip_path);
+        assert!(!args.contains(&"-vf".to_string()));
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/clip.mp4"));
+        let secs = args.iter().position(|arg| arg == "-t").map(|i| args[i + 1].as_str());
+        assert_eq!(secs, Some("10"));
+
+        let input = args.iter().position(|arg| arg == "-i").map(|i| args[i + 1].as_str()).unwrap();
+        #[cfg(target_os = "macos")]
+        assert_eq!(input, "Capture screen 1:none");
+        #[cfg(windows)]
+        assert_eq!(input, "desktop");
+        #[cfg(all(unix, not(target_os = "macos")))]
+        assert!(input.ends_with("+1920,0"));
+        #[cfg(not(target_os = "macos"))]
+        assert!(args.windows(2).any(|pair| pair[0] == "-video_size" && pair[1] == "2560x1440"));
+
+        let crop = CropFraction {
+            left: 0.25,
+            top: 0.1,
+            width: 0.5,
+            height: 0.8,
+        };
+        let args = recording_args(&area(Some(crop)), 10, clip_path);
+        let filter = args.iter().position(|arg| arg == "-vf").map(|i| args[i + 1].as_str());
+        assert_eq!(
+            filter,
+            Some("crop=trunc(iw*0.5000/2)*2:trunc(ih*0.8000/2)*2:trunc(iw*0.2500):trunc(ih*0.1000)")
+        );
+    }
+}
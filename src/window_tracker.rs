@@ -1,8 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH, Duration, Instant};
-use std::sync::{Arc, Mutex};
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{broadcast, RwLock};
+use crate::app_aliases;
+use crate::config::Config;
+use crate::verbosity::info_println;
 
 // 窗口切换事件
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -19,45 +25,299 @@ pub struct WindowSwitchEvent {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WindowSession {
     pub app_name: Option<String>,
+    /// 稳定的应用标识（macOS bundle id / 可执行文件路径），用于区分同名应用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
     pub window_title: Option<String>,
     pub start_time: u64,
+    /// 结束时间：开始时间加上按单调时钟计算的时长，墙上时钟跳变不影响
     pub end_time: Option<u64>,
     pub duration_ms: u64,
+    /// 会话开始时追踪器单调时钟的读数（毫秒），只用于计算时长，不持久化（从文件恢复的会话为 None）
+    #[serde(skip)]
+    start_mono_ms: Option<u64>,
 }
 
 // 窗口统计信息 - 简化版本
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WindowSwitchStats {
     pub total_switches: u32,
-    pub most_used_apps: Vec<(String, u64)>, // (app_name, total_duration_ms)
+    pub most_used_apps: Vec<(String, u64)>, // (展示名称, total_duration_ms)
     pub current_session_duration_ms: u64,
     pub last_switch_time: Option<u64>,
 }
 
+// 专注时段：连续停留在相关应用上、没有长时间中断的一段时间
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FocusSession {
+    pub start_time: u64, // Unix timestamp in milliseconds
+    pub end_time: u64,
+    pub duration_ms: u64,
+    /// 时段内使用时间最长的应用
+    pub dominant_app: String,
+    /// 时段内各应用的使用时长（毫秒），按时长降序
+    pub apps: Vec<(String, u64)>,
+    /// 时段内的窗口切换次数
+    pub switch_count: usize,
+}
+
+/// 会话落在查询范围内的部分：(应用, 开始, 结束)
+type SessionSpan<'a> = (&'a str, u64, u64);
+
+// 持久化快照（跨重启恢复历史统计）
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WindowTrackerSnapshot {
+    pub switch_history: VecDeque<WindowSwitchEvent>,
+    pub session_history: VecDeque<WindowSession>,
+    /// 按应用标识（取不到时按名称）累计的使用时长
+    pub app_usage_stats: HashMap<String, u64>,
+    /// 应用标识 → 应用名称，用于展示
+    #[serde(default)]
+    pub app_names: HashMap<String, String>,
+}
+
+/// 会话的统计键：优先使用应用标识，取不到时退回应用名称
+fn session_app_key(app_name: &Option<String>, app_id: &Option<String>) -> Option<String> {
+    app_id.clone().or_else(|| app_name.clone())
+}
+
+/// 将按统计键累计的时长转换为展示名称并按时长降序：
+/// 不同标识对应同一名称时附带标识以示区分，旧数据（以名称为键）并入同名应用
+fn label_usage(
+    usage: impl IntoIterator<Item = (String, u64)>,
+    app_names: &HashMap<String, String>,
+) -> Vec<(String, u64)> {
+    let mut labeled: HashMap<String, u64> = HashMap::new();
+    for (key, duration) in usage {
+        let label = match app_names.get(&key) {
+            Some(name) if app_names.iter().any(|(other, n)| n == name && *other != key) => {
+                format!("{} ({})", name, key)
+            }
+            Some(name) => name.clone(),
+            None => key,
+        };
+        *labeled.entry(label).or_insert(0) += duration;
+    }
+    let mut labeled: Vec<(String, u64)> = labeled.into_iter().collect();
+    labeled.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    labeled
+}
+
+impl WindowTrackerSnapshot {
+    /// 从 JSON 文件读取快照（不修改全局追踪器），文件缺失或损坏时返回 None
+    pub async fn read_from(path: &Path) -> Option<Self> {
+        let content = tokio::fs::read_to_string(path).await.ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// 会话历史是否完整覆盖 start_ms 之后的时间：历史有条数上限，最早的会话晚于 start_ms 时更早的部分已被丢弃
+    pub fn covers_since(&self, start_ms: u64) -> bool {
+        self.session_history.front().is_some_and(|session| session.start_time <= start_ms)
+    }
+
+    /// 时间范围 [start_ms, end_ms) 内的窗口切换事件，按时间先后排列，只保留最近 limit 条
+    pub fn switches_between(&self, start_ms: u64, end_ms: u64, limit: usize) -> Vec<WindowSwitchEvent> {
+        let mut switches: Vec<WindowSwitchEvent> = self
+            .switch_history
+            .iter()
+            .rev()
+            .filter(|event| event.timestamp >= start_ms && event.timestamp < end_ms)
+            .take(limit)
+            .cloned()
+            .collect();
+        switches.reverse();
+        switches
+    }
+
+    /// 统计时间范围 [start_ms, end_ms) 内各应用的使用时长（毫秒），按时长降序
+    /// 跨越边界的会话只计入落在范围内的部分，未结束的会话计算到当前时间
+    pub fn app_usage_between(&self, start_ms: u64, end_ms: u64) -> Vec<(String, u64)> {
+        let now = get_current_timestamp();
+        let mut usage: HashMap<String, u64> = HashMap::new();
+        for session in &self.session_history {
+            let app = match session_app_key(&session.app_name, &session.app_id) {
+                Some(app) => app,
+                None => continue,
+            };
+            let session_end = session.end_time.unwrap_or(now);
+            let overlap_start = session.start_time.max(start_ms);
+            let overlap_end = session_end.min(end_ms);
+            if overlap_end > overlap_start {
+                *usage.entry(app).or_insert(0) += overlap_end - overlap_start;
+            }
+        }
+        label_usage(usage, &self.app_names)
+    }
+
+    /// 累计使用时长最多的 limit 个应用（展示名称）
+    pub fn most_used_apps(&self, limit: usize) -> Vec<(String, u64)> {
+        let mut usage = label_usage(
+            self.app_usage_stats.iter().map(|(key, duration)| (key.clone(), *duration)),
+            &self.app_names,
+        );
+        usage.truncate(limit);
+        usage
+    }
+
+    /// 将时间范围 [start_ms, end_ms) 内的会话归并为专注时段：
+    /// 切到本时段未用过的应用（离开）累计达到 max_gap_ms，或两段会话之间的未追踪间隔达到 max_gap_ms 时，
+    /// 当前时段结束；短暂离开后回到本时段用过的应用则并入。只返回时长不少于 min_duration_ms 的时段
+    pub fn focus_sessions_between(
+        &self,
+        start_ms: u64,
+        end_ms: u64,
+        max_gap_ms: u64,
+        min_duration_ms: u64,
+    ) -> Vec<FocusSession> {
+        let now = get_current_timestamp();
+        let spans = self.session_history.iter().filter_map(|session| {
+            let app = session.app_name.as_deref()?;
+            let span_start = session.start_time.max(start_ms);
+            let span_end = session.end_time.unwrap_or(now).min(end_ms);
+            (span_end > span_start).then_some((app, span_start, span_end))
+        });
+
+        let mut focus_sessions = Vec::new();
+        let mut current: Vec<SessionSpan> = Vec::new();
+        let mut excursion: Vec<SessionSpan> = Vec::new();
+        for span in spans {
+            let last_end = excursion.last().or(current.last()).map(|s| s.2);
+            if last_end.is_some_and(|end| span.1.saturating_sub(end) >= max_gap_ms) {
+                // 长时间未追踪（进程退出、休眠等）视为中断
+                focus_sessions.extend(build_focus_session(&current, min_duration_ms));
+                current = std::mem::take(&mut excursion);
+            }
+
+            if current.is_empty() || current.iter().any(|s| s.0 == span.0) {
+                current.append(&mut excursion);
+                current.push(span);
+            } else {
+                excursion.push(span);
+                let away_ms = excursion.last().map_or(0, |s| s.2) - excursion[0].1;
+                if away_ms >= max_gap_ms {
+                    focus_sessions.extend(build_focus_session(&current, min_duration_ms));
+                    current = std::mem::take(&mut excursion);
+                }
+            }
+        }
+        focus_sessions.extend(build_focus_session(&current, min_duration_ms));
+        focus_sessions.extend(build_focus_session(&excursion, min_duration_ms));
+        focus_sessions
+    }
+}
+
+/// 由连续的会话片段生成专注时段，时长不足 min_duration_ms 时返回 None
+fn build_focus_session(spans: &[SessionSpan], min_duration_ms: u64) -> Option<FocusSession> {
+    let (first, last) = (spans.first()?, spans.last()?);
+    let duration_ms = last.2.saturating_sub(first.1);
+    if duration_ms < min_duration_ms {
+        return None;
+    }
+
+    let mut usage: HashMap<&str, u64> = HashMap::new();
+    for (app, start, end) in spans {
+        *usage.entry(app).or_insert(0) += end - start;
+    }
+    let mut apps: Vec<(String, u64)> = usage
+        .into_iter()
+        .map(|(app, ms)| (app.to_string(), ms))
+        .collect();
+    apps.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    Some(FocusSession {
+        start_time: first.1,
+        end_time: last.2,
+        duration_ms,
+        dominant_app: apps[0].0.clone(),
+        apps,
+        switch_count: spans.len() - 1,
+    })
+}
+
+/// 追踪器内部的时间点：wall_ms 为墙上时钟（Unix 毫秒，用于展示），mono_ms 为单调时钟（用于计算时长）
+#[derive(Debug, Clone, Copy)]
+struct TrackerTime {
+    wall_ms: u64,
+    mono_ms: u64,
+}
+
 // 增强的窗口信息
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EnhancedWindowInfo {
     pub app_name: Option<String>,
     pub window_title: Option<String>,
+    /// 稳定的应用标识：macOS 为 bundle identifier（非 .app 进程为可执行文件路径），Windows 为可执行文件完整路径
+    #[serde(default)]
+    pub app_id: Option<String>,
     pub bounds: Option<crate::context::WindowBounds>,
     pub timestamp: u64,
     pub process_id: Option<u32>,
     pub is_focus_changed: bool, // 是否是焦点变化
 }
 
+/// 窗口追踪器的缓存与历史容量配置
+#[derive(Debug, Clone)]
+pub struct WindowTrackerOptions {
+    /// 窗口信息缓存时长（毫秒）
+    pub cache_ms: u64,
+    /// 窗口切换历史保留条数
+    pub switch_history_size: usize,
+    /// 窗口会话历史保留条数
+    pub session_history_size: usize,
+    /// 窗口查询子进程（osascript）的超时时间（毫秒）
+    pub query_timeout_ms: u64,
+    /// 短于该时长（毫秒）的会话并回之前的会话，0 表示关闭
+    pub min_session_ms: u64,
+    /// 两次观测前台窗口的间隔达到该时长（毫秒）时视为系统休眠，间隔不计入任何应用，0 表示关闭
+    pub sleep_gap_ms: u64,
+}
+
+impl Default for WindowTrackerOptions {
+    fn default() -> Self {
+        Self {
+            cache_ms: 500,
+            switch_history_size: 100,
+            session_history_size: 50,
+            query_timeout_ms: 2000,
+            min_session_ms: 0,
+            sleep_gap_ms: 600_000,
+        }
+    }
+}
+
+impl WindowTrackerOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            cache_ms: config.window_cache_ms,
+            switch_history_size: config.switch_history_size.max(1),
+            session_history_size: config.session_history_size.max(1),
+            query_timeout_ms: config.window_query_timeout_ms.max(1),
+            min_session_ms: config.min_session_ms,
+            // 只有截屏时才查询前台窗口时，正常的截屏间隔不能被误判为休眠
+            sleep_gap_ms: match config.sleep_gap_secs {
+                0 => 0,
+                secs => secs.max(config.interval.saturating_mul(2)).saturating_mul(1000),
+            },
+        }
+    }
+}
+
 // 窗口追踪器
 pub struct WindowTracker {
     // 当前窗口信息
     current_window: Arc<RwLock<Option<EnhancedWindowInfo>>>,
     
-    // 窗口切换历史 (最近100个事件)
+    // 窗口切换历史 (最近 switch_history_size 个事件)
     switch_history: Arc<Mutex<VecDeque<WindowSwitchEvent>>>,
     
     // 窗口会话历史
     session_history: Arc<Mutex<VecDeque<WindowSession>>>,
     
-    // 应用使用时间统计
+    // 应用使用时间统计（按应用标识，取不到时按名称）
     app_usage_stats: Arc<Mutex<HashMap<String, u64>>>,
+
+    // 应用标识 → 应用名称
+    app_names: Arc<Mutex<HashMap<String, String>>>,
     
     // 统计信息
     stats: Arc<Mutex<WindowSwitchStats>>,
@@ -66,21 +326,49 @@ pub struct WindowTracker {
     last_query_time: Arc<Mutex<Instant>>,
     cached_info: Arc<Mutex<Option<EnhancedWindowInfo>>>,
     cache_duration: Duration,
+
+    // 窗口查询子进程超时（仅 macOS 的 AppleScript 回退方案使用）
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    query_timeout: Duration,
+
+    // 历史容量上限
+    switch_history_size: usize,
+    session_history_size: usize,
+
+    // 最短会话时长（毫秒），更短的会话并回之前的会话
+    min_session_ms: u64,
+
+    // 单调时钟起点：会话时长一律按单调时钟计算，墙上时钟只用于展示
+    mono_origin: Instant,
+
+    // 上一次观测到前台窗口时的单调时钟读数，以及判定为休眠的观测间隔
+    last_observed_mono: Mutex<Option<u64>>,
+    sleep_gap_ms: u64,
+
+    // 累计切换次数（不受历史容量限制）
+    switch_count: AtomicU64,
+
+    // 前台应用切换通知（仅应用变化，不含同一应用内的标题变化）
+    app_switch_tx: broadcast::Sender<WindowSwitchEvent>,
 }
 
+/// 应用切换通知缓冲区大小，订阅方处理过慢时丢弃最旧的事件
+const APP_SWITCH_CHANNEL_CAPACITY: usize = 16;
+
 impl Default for WindowTracker {
     fn default() -> Self {
-        Self::new()
+        Self::new(WindowTrackerOptions::default())
     }
 }
 
 impl WindowTracker {
-    pub fn new() -> Self {
+    pub fn new(options: WindowTrackerOptions) -> Self {
         Self {
             current_window: Arc::new(RwLock::new(None)),
-            switch_history: Arc::new(Mutex::new(VecDeque::with_capacity(100))),
-            session_history: Arc::new(Mutex::new(VecDeque::with_capacity(50))),
+            switch_history: Arc::new(Mutex::new(VecDeque::with_capacity(options.switch_history_size))),
+            session_history: Arc::new(Mutex::new(VecDeque::with_capacity(options.session_history_size))),
             app_usage_stats: Arc::new(Mutex::new(HashMap::new())),
+            app_names: Arc::new(Mutex::new(HashMap::new())),
             stats: Arc::new(Mutex::new(WindowSwitchStats {
                 total_switches: 0,
                 most_used_apps: Vec::new(),
@@ -89,7 +377,16 @@ impl WindowTracker {
             })),
             last_query_time: Arc::new(Mutex::new(Instant::now() - Duration::from_secs(10))),
             cached_info: Arc::new(Mutex::new(None)),
-            cache_duration: Duration::from_millis(500), // 500ms缓存
+            cache_duration: Duration::from_millis(options.cache_ms),
+            query_timeout: Duration::from_millis(options.query_timeout_ms),
+            switch_history_size: options.switch_history_size,
+            session_history_size: options.session_history_size,
+            min_session_ms: options.min_session_ms,
+            mono_origin: Instant::now(),
+            last_observed_mono: Mutex::new(None),
+            sleep_gap_ms: options.sleep_gap_ms,
+            switch_count: AtomicU64::new(0),
+            app_switch_tx: broadcast::channel(APP_SWITCH_CHANNEL_CAPACITY).0,
         }
     }
     
@@ -116,14 +413,47 @@ impl WindowTracker {
         
         // 如果窗口发生变化，记录切换事件
         if let Some(ref new_window) = new_info {
-            self.handle_window_change(new_window.clone()).await;
+            self.observe_window(new_window.clone(), self.now()).await;
         }
         
         new_info
     }
+
+    /// 当前时间：墙上时钟用于展示，单调时钟用于计算时长
+    fn now(&self) -> TrackerTime {
+        TrackerTime {
+            wall_ms: get_current_timestamp(),
+            mono_ms: self.mono_origin.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// 记录一次前台窗口观测：与上次观测间隔过长时先按休眠处理，再检测窗口变化
+    async fn observe_window(&self, window: EnhancedWindowInfo, now: TrackerTime) {
+        let last_observed = self.last_observed_mono.lock().unwrap().replace(now.mono_ms);
+        if let Some(last_observed) = last_observed {
+            if self.sleep_gap_ms > 0 && now.mono_ms.saturating_sub(last_observed) >= self.sleep_gap_ms {
+                self.split_session_at_gap(last_observed, now).await;
+            }
+        }
+        self.handle_window_change(window, now).await;
+    }
+
+    /// 休眠（两次观测间隔达到 sleep_gap_ms）：当前会话在上次观测时结束，并为同一窗口重新开始会话，
+    /// 休眠时段不计入任何应用。macOS / Linux 的单调时钟在休眠时暂停，本身就不会计入；
+    /// Windows 的单调时钟包含休眠时间，依靠这里处理
+    async fn split_session_at_gap(&self, last_observed_mono: u64, now: TrackerTime) {
+        let Some(current) = self.current_window.read().await.clone() else {
+            return;
+        };
+        self.end_session(current.clone(), last_observed_mono).await;
+        self.start_session(current, now).await;
+        if let Some(current) = self.current_window.write().await.as_mut() {
+            current.timestamp = now.wall_ms;
+        }
+    }
     
     /// 处理窗口变化
-    async fn handle_window_change(&self, new_window: EnhancedWindowInfo) {
+    async fn handle_window_change(&self, new_window: EnhancedWindowInfo, now: TrackerTime) {
         let current = self.current_window.read().await;
         let is_different = match &*current {
             Some(old) => {
@@ -142,15 +472,33 @@ impl WindowTracker {
             drop(current_write);
             
             // 记录切换事件
-            self.record_switch_event(old_window, new_window).await;
+            self.record_switch_event(old_window, new_window, now).await;
         }
     }
     
     /// 记录窗口切换事件
-    async fn record_switch_event(&self, old_window: Option<EnhancedWindowInfo>, new_window: EnhancedWindowInfo) {
-        let now = get_current_timestamp();
-        let duration = if let Some(ref old) = old_window {
-            now.saturating_sub(old.timestamp)
+    async fn record_switch_event(
+        &self,
+        mut old_window: Option<EnhancedWindowInfo>,
+        new_window: EnhancedWindowInfo,
+        now: TrackerTime,
+    ) {
+        // 上一个窗口停留不足 --min-session-ms：并回之前的会话，切入它的那次切换也不再计入
+        if let Some(previous) = self.absorb_short_session(now) {
+            if previous.app_name == new_window.app_name && previous.window_title == new_window.window_title {
+                // 又切回了原来的窗口（如 Alt-Tab 一闪而过），原会话继续
+                if let Some(current) = self.current_window.write().await.as_mut() {
+                    current.timestamp = previous.timestamp;
+                }
+                self.update_stats().await;
+                return;
+            }
+            old_window = Some(previous);
+        }
+
+        // 上一个窗口的停留时长按单调时钟计算，墙上时钟回拨时不会变成 0
+        let duration = if old_window.is_some() {
+            self.open_session_duration(now)
         } else {
             0
         };
@@ -161,22 +509,29 @@ impl WindowTracker {
             to_app: new_window.app_name.clone(),
             from_title: old_window.as_ref().and_then(|w| w.window_title.clone()),
             to_title: new_window.window_title.clone(),
-            timestamp: now,
+            timestamp: now.wall_ms,
             duration_ms: duration,
         };
         
+        self.switch_count.fetch_add(1, Ordering::Relaxed);
+
+        // 前台应用变化时通知订阅方（首次检测到窗口不算切换）；没有订阅者时直接丢弃
+        if old_window.is_some() && switch_event.from_app != switch_event.to_app {
+            let _ = self.app_switch_tx.send(switch_event.clone());
+        }
+
         // 添加到历史记录
         {
             let mut history = self.switch_history.lock().unwrap();
             history.push_back(switch_event);
-            if history.len() > 100 {
+            if history.len() > self.switch_history_size {
                 history.pop_front();
             }
         }
         
         // 结束旧会话，开始新会话
         if let Some(old) = old_window {
-            self.end_session(old, now).await;
+            self.end_session(old, now.mono_ms).await;
         }
         self.start_session(new_window, now).await;
         
@@ -184,36 +539,109 @@ impl WindowTracker {
         self.update_stats().await;
     }
     
+    /// 当前会话短于 min_session_ms 且紧接在上一个会话之后时，丢弃它并重新打开上一个会话，
+    /// 返回上一个会话的窗口信息（timestamp 为会话开始时间）
+    fn absorb_short_session(&self, now: TrackerTime) -> Option<EnhancedWindowInfo> {
+        if self.min_session_ms == 0 {
+            return None;
+        }
+        // 加锁顺序与 update_stats 一致
+        let mut history = self.switch_history.lock().unwrap();
+        let mut sessions = self.session_history.lock().unwrap();
+        let short = sessions.back()?;
+        let short_start_mono = short.start_mono_ms?;
+        if short.end_time.is_some() || now.mono_ms.saturating_sub(short_start_mono) >= self.min_session_ms {
+            return None;
+        }
+        let previous = sessions.get(sessions.len().checked_sub(2)?)?;
+        if previous.end_time.is_none() || previous.start_mono_ms? + previous.duration_ms != short_start_mono {
+            return None;
+        }
+
+        let short = sessions.pop_back()?;
+        if history
+            .back()
+            .is_some_and(|event| event.to_app == short.app_name && event.to_title == short.window_title)
+        {
+            history.pop_back();
+        }
+        let previous = sessions.back_mut()?;
+        // 上一个会话结束时已计入使用时长，重新打开时先扣除，再次结束时按完整时长累加
+        if let Some(app_key) = session_app_key(&previous.app_name, &previous.app_id) {
+            if let Some(total) = self.app_usage_stats.lock().unwrap().get_mut(&app_key) {
+                *total = total.saturating_sub(previous.duration_ms);
+            }
+        }
+        previous.end_time = None;
+        previous.duration_ms = 0;
+
+        Some(EnhancedWindowInfo {
+            app_name: previous.app_name.clone(),
+            window_title: previous.window_title.clone(),
+            app_id: previous.app_id.clone(),
+            bounds: None,
+            timestamp: previous.start_time,
+            process_id: None,
+            is_focus_changed: false,
+        })
+    }
+
+    /// 当前未结束会话已持续的时长（按单调时钟）
+    fn open_session_duration(&self, now: TrackerTime) -> u64 {
+        self.session_history
+            .lock()
+            .unwrap()
+            .back()
+            .filter(|session| session.end_time.is_none())
+            .and_then(|session| session.start_mono_ms)
+            .map_or(0, |start| now.mono_ms.saturating_sub(start))
+    }
+
     /// 开始新会话
-    async fn start_session(&self, window: EnhancedWindowInfo, start_time: u64) {
-        let session = WindowSession {
+    async fn start_session(&self, window: EnhancedWindowInfo, now: TrackerTime) {
+        if let (Some(app_id), Some(app_name)) = (&window.app_id, &window.app_name) {
+            self.app_names
+                .lock()
+                .unwrap()
+                .insert(app_id.clone(), app_name.clone());
+        }
+
+        let mut sessions = self.session_history.lock().unwrap();
+        // 墙上时钟回拨时不早于上一个会话的结束时间，避免会话在时间线上重叠
+        let start_time = sessions
+            .back()
+            .and_then(|session| session.end_time)
+            .map_or(now.wall_ms, |end| now.wall_ms.max(end));
+        sessions.push_back(WindowSession {
             app_name: window.app_name,
+            app_id: window.app_id,
             window_title: window.window_title,
             start_time,
             end_time: None,
             duration_ms: 0,
-        };
-        
-        let mut sessions = self.session_history.lock().unwrap();
-        sessions.push_back(session);
-        if sessions.len() > 50 {
+            start_mono_ms: Some(now.mono_ms),
+        });
+        if sessions.len() > self.session_history_size {
             sessions.pop_front();
         }
     }
     
-    /// 结束会话
-    async fn end_session(&self, old_window: EnhancedWindowInfo, end_time: u64) {
+    /// 结束会话，end_mono_ms 为结束时的单调时钟读数
+    async fn end_session(&self, old_window: EnhancedWindowInfo, end_mono_ms: u64) {
         let mut sessions = self.session_history.lock().unwrap();
         if let Some(last_session) = sessions.back_mut() {
-            if last_session.app_name == old_window.app_name && 
+            if last_session.end_time.is_none() &&
+               last_session.app_name == old_window.app_name && 
                last_session.window_title == old_window.window_title {
-                last_session.end_time = Some(end_time);
-                last_session.duration_ms = end_time.saturating_sub(last_session.start_time);
+                last_session.duration_ms = last_session
+                    .start_mono_ms
+                    .map_or(0, |start| end_mono_ms.saturating_sub(start));
+                last_session.end_time = Some(last_session.start_time + last_session.duration_ms);
                 
                 // 更新应用使用统计
-                if let Some(ref app_name) = last_session.app_name {
+                if let Some(app_key) = session_app_key(&last_session.app_name, &last_session.app_id) {
                     let mut stats = self.app_usage_stats.lock().unwrap();
-                    *stats.entry(app_name.clone()).or_insert(0) += last_session.duration_ms;
+                    *stats.entry(app_key).or_insert(0) += last_session.duration_ms;
                 }
             }
         }
@@ -225,22 +653,22 @@ impl WindowTracker {
         let history = self.switch_history.lock().unwrap();
         let sessions = self.session_history.lock().unwrap();
         let app_stats = self.app_usage_stats.lock().unwrap();
+        let app_names = self.app_names.lock().unwrap();
         
         stats.total_switches = history.len() as u32;
         
         // 最常用应用
-        let mut app_usage: Vec<(String, u64)> = app_stats.iter()
-            .map(|(name, duration)| (name.clone(), *duration))
-            .collect();
-        app_usage.sort_by(|a, b| b.1.cmp(&a.1));
+        let mut app_usage = label_usage(
+            app_stats.iter().map(|(key, duration)| (key.clone(), *duration)),
+            &app_names,
+        );
         app_usage.truncate(5);
         stats.most_used_apps = app_usage;
         
         // 当前会话时长
         if let Some(last_session) = sessions.back() {
-            if last_session.end_time.is_none() {
-                stats.current_session_duration_ms = 
-                    get_current_timestamp().saturating_sub(last_session.start_time);
+            if let (None, Some(start)) = (last_session.end_time, last_session.start_mono_ms) {
+                stats.current_session_duration_ms = self.now().mono_ms.saturating_sub(start);
             }
         }
         
@@ -253,6 +681,16 @@ impl WindowTracker {
         self.stats.lock().unwrap().clone()
     }
     
+    /// 订阅前台应用切换事件（同一应用内切换窗口标题不会通知）
+    pub fn subscribe_app_switches(&self) -> broadcast::Receiver<WindowSwitchEvent> {
+        self.app_switch_tx.subscribe()
+    }
+
+    /// 进程启动以来的累计窗口切换次数
+    pub fn total_switch_count(&self) -> u64 {
+        self.switch_count.load(Ordering::Relaxed)
+    }
+
     /// 获取切换历史
     pub async fn get_switch_history(&self, limit: Option<usize>) -> Vec<WindowSwitchEvent> {
         let history = self.switch_history.lock().unwrap();
@@ -264,8 +702,75 @@ impl WindowTracker {
             .collect()
     }
     
-    /// 跨平台获取窗口信息
+    /// 获取当前内存中的历史数据快照
+    pub fn snapshot(&self) -> WindowTrackerSnapshot {
+        WindowTrackerSnapshot {
+            switch_history: self.switch_history.lock().unwrap().clone(),
+            session_history: self.session_history.lock().unwrap().clone(),
+            app_usage_stats: self.app_usage_stats.lock().unwrap().clone(),
+            app_names: self.app_names.lock().unwrap().clone(),
+        }
+    }
+
+    /// 将切换历史、会话历史和应用使用统计保存到 JSON 文件
+    pub async fn save(&self, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let snapshot = self.snapshot();
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // 先写临时文件再重命名，保存中途退出时不会留下半个文件
+        let content = serde_json::to_string_pretty(&snapshot)?;
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, content).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+
+    /// 从 JSON 文件恢复历史数据；文件缺失或损坏时从空状态开始
+    pub async fn load(&self, path: &Path) {
+        let snapshot = match tokio::fs::read_to_string(path).await {
+            Ok(content) => match serde_json::from_str::<WindowTrackerSnapshot>(&content) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    eprintln!("解析窗口追踪历史失败: {}, 从空状态开始", e);
+                    return;
+                }
+            },
+            Err(_) => return,
+        };
+
+        {
+            let mut history = self.switch_history.lock().unwrap();
+            *history = snapshot.switch_history;
+            while history.len() > self.switch_history_size {
+                history.pop_front();
+            }
+        }
+        {
+            let mut sessions = self.session_history.lock().unwrap();
+            *sessions = snapshot.session_history;
+            // 上次运行未结束的会话无法得知真实结束时间，直接丢弃
+            if sessions.back().map(|s| s.end_time.is_none()).unwrap_or(false) {
+                sessions.pop_back();
+            }
+            while sessions.len() > self.session_history_size {
+                sessions.pop_front();
+            }
+        }
+        *self.app_usage_stats.lock().unwrap() = snapshot.app_usage_stats;
+        *self.app_names.lock().unwrap() = snapshot.app_names;
+
+        self.update_stats().await;
+        info_println!("🔄 已恢复窗口追踪历史: {:?}", path);
+    }
+
+    /// 跨平台获取窗口信息，应用名称按 --app-aliases 规范化后再参与会话与使用时长统计
     async fn fetch_window_info(&self) -> Option<EnhancedWindowInfo> {
+        self.fetch_platform_window_info().await.map(normalize_window_app_name)
+    }
+
+    async fn fetch_platform_window_info(&self) -> Option<EnhancedWindowInfo> {
         #[cfg(target_os = "macos")]
         {
             self.fetch_macos_window_info().await
@@ -282,16 +787,31 @@ impl WindowTracker {
         }
     }
     
-    /// macOS 窗口信息获取（优化版 AppleScript）
+    /// macOS 窗口信息获取：优先使用 Core Graphics / Accessibility 原生 API，
+    /// 取不到时回退到 AppleScript
     #[cfg(target_os = "macos")]
     async fn fetch_macos_window_info(&self) -> Option<EnhancedWindowInfo> {
-        use std::process::Command;
+        if let Some(info) = macos_native::frontmost_window_info() {
+            return Some(info);
+        }
+        self.fetch_macos_window_info_applescript().await
+    }
+
+    /// macOS 窗口信息获取（AppleScript 回退方案，每次调用会启动 osascript 进程）
+    #[cfg(target_os = "macos")]
+    async fn fetch_macos_window_info_applescript(&self) -> Option<EnhancedWindowInfo> {
+        use tokio::process::Command;
         
         let script = r#"
             tell application "System Events"
                 set frontApp to first process whose frontmost is true
                 set appName to name of frontApp
                 set processId to unix id of frontApp
+                try
+                    set bundleId to bundle identifier of frontApp
+                on error
+                    set bundleId to ""
+                end try
                 try
                     set windowTitle to title of front window of frontApp
                 on error
@@ -300,23 +820,16 @@ impl WindowTracker {
                 try
                     set windowPos to position of front window of frontApp
                     set windowSize to size of front window of frontApp
-                    return appName & "|" & windowTitle & "|" & processId & "|" & (item 1 of windowPos as string) & "," & (item 2 of windowPos as string) & "|" & (item 1 of windowSize as string) & "," & (item 2 of windowSize as string)
+                    return appName & "|" & windowTitle & "|" & processId & "|" & (item 1 of windowPos as string) & "," & (item 2 of windowPos as string) & "|" & (item 1 of windowSize as string) & "," & (item 2 of windowSize as string) & "|" & bundleId
                 on error
-                    return appName & "|" & windowTitle & "|" & processId & "||"
+                    return appName & "|" & windowTitle & "|" & processId & "|||" & bundleId
                 end try
             end tell
         "#;
         
-        let output = Command::new("/usr/bin/osascript")
-            .args(["-e", script])
-            .output()
-            .ok()?;
-        
-        if !output.status.success() {
-            return None;
-        }
-        
-        let output_str = String::from_utf8(output.stdout).ok()?;
+        let mut command = Command::new("/usr/bin/osascript");
+        command.args(["-e", script]);
+        let output_str = run_window_query(command, self.query_timeout).await?;
         let parts: Vec<&str> = output_str.trim().split('|').collect();
         
         if parts.len() < 5 {
@@ -342,10 +855,19 @@ impl WindowTracker {
         } else {
             None
         };
+
+        // 第 6 段为 bundle identifier，取不到时用可执行文件路径
+        let app_id = parts
+            .get(5)
+            .map(|id| id.trim())
+            .filter(|id| !id.is_empty() && *id != "missing value")
+            .map(str::to_string)
+            .or_else(|| process_id.and_then(|pid| macos_native::app_identifier(pid as i32)));
         
         Some(EnhancedWindowInfo {
             app_name,
             window_title,
+            app_id,
             bounds,
             timestamp: get_current_timestamp(),
             process_id,
@@ -362,6 +884,7 @@ impl WindowTracker {
         use winapi::um::winuser::{GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId, GetWindowRect};
         use winapi::um::processthreadsapi::OpenProcess;
         use winapi::um::psapi::GetModuleBaseNameW;
+        use winapi::um::winbase::QueryFullProcessImageNameW;
         use winapi::um::handleapi::CloseHandle;
         use winapi::um::winnt::PROCESS_QUERY_INFORMATION;
         use winapi::shared::windef::RECT;
@@ -386,7 +909,7 @@ impl WindowTracker {
             let mut process_id = 0;
             GetWindowThreadProcessId(hwnd, &mut process_id);
             
-            let app_name = if process_id != 0 {
+            let (app_name, app_id) = if process_id != 0 {
                 let process_handle = OpenProcess(PROCESS_QUERY_INFORMATION, 0, process_id);
                 if !process_handle.is_null() {
                     let mut app_name_buf = [0u16; 512];
@@ -396,19 +919,29 @@ impl WindowTracker {
                         app_name_buf.as_mut_ptr(),
                         app_name_buf.len() as u32,
                     );
+
+                    // 可执行文件完整路径，用于区分同名进程
+                    let mut path_buf = [0u16; 1024];
+                    let mut path_len = path_buf.len() as u32;
+                    let app_id = if QueryFullProcessImageNameW(process_handle, 0, path_buf.as_mut_ptr(), &mut path_len) != 0 {
+                        Some(OsString::from_wide(&path_buf[..path_len as usize]).to_string_lossy().into_owned())
+                    } else {
+                        None
+                    };
                     CloseHandle(process_handle);
                     
-                    if name_len > 0 {
+                    let app_name = if name_len > 0 {
                         let name_slice = &app_name_buf[..name_len as usize];
                         Some(OsString::from_wide(name_slice).to_string_lossy().into_owned())
                     } else {
                         None
-                    }
+                    };
+                    (app_name, app_id)
                 } else {
-                    None
+                    (None, None)
                 }
             } else {
-                None
+                (None, None)
             };
             
             // 获取窗口位置和大小
@@ -433,6 +966,7 @@ impl WindowTracker {
             Some(EnhancedWindowInfo {
                 app_name,
                 window_title,
+                app_id,
                 bounds,
                 timestamp: get_current_timestamp(),
                 process_id: Some(process_id),
@@ -466,7 +1000,513 @@ fn parse_window_bounds(pos_str: &str, size_str: &str) -> Option<crate::context::
     }
 }
 
-// 全局窗口追踪器实例
+/// 当前平台是否支持按标题查找窗口（--capture-window-title）
+pub fn window_lookup_supported() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+/// 按标题查找屏幕上可见（未最小化）的窗口，不要求在前台：标题包含 pattern 即匹配（不区分大小写），
+/// 多个匹配时取最靠前的一个；不支持的平台返回 None
+pub fn find_window_by_title(pattern: &str) -> Option<EnhancedWindowInfo> {
+    let pattern = pattern.trim().to_lowercase();
+    if pattern.is_empty() {
+        return None;
+    }
+
+    #[cfg(target_os = "macos")]
+    let window = macos_native::find_window_by_title(&pattern);
+
+    #[cfg(target_os = "windows")]
+    let window = find_windows_window_by_title(&pattern);
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let window = None;
+
+    window.map(normalize_window_app_name)
+}
+
+fn normalize_window_app_name(mut window: EnhancedWindowInfo) -> EnhancedWindowInfo {
+    window.app_name = app_aliases::normalize_app_name(window.app_name);
+    window
+}
+
+/// Windows：EnumWindows 按 Z 序从上到下遍历顶层窗口
+#[cfg(target_os = "windows")]
+fn find_windows_window_by_title(pattern: &str) -> Option<EnhancedWindowInfo> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use winapi::shared::minwindef::{BOOL, FALSE, LPARAM, TRUE};
+    use winapi::shared::windef::{HWND, RECT};
+    use winapi::um::winuser::{
+        EnumWindows, GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindowVisible,
+    };
+
+    struct Search<'a> {
+        pattern: &'a str,
+        found: Option<EnhancedWindowInfo>,
+    }
+
+    unsafe extern "system" fn visit(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam as *mut Search);
+        if IsWindowVisible(hwnd) == 0 || IsIconic(hwnd) != 0 {
+            return TRUE;
+        }
+
+        let mut title_buf = [0u16; 512];
+        let title_len = GetWindowTextW(hwnd, title_buf.as_mut_ptr(), title_buf.len() as i32);
+        if title_len <= 0 {
+            return TRUE;
+        }
+        let title = OsString::from_wide(&title_buf[..title_len as usize]).to_string_lossy().into_owned();
+        if !title.to_lowercase().contains(search.pattern) {
+            return TRUE;
+        }
+
+        let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+        if GetWindowRect(hwnd, &mut rect) == 0 {
+            return TRUE;
+        }
+        let mut process_id = 0;
+        GetWindowThreadProcessId(hwnd, &mut process_id);
+
+        search.found = Some(EnhancedWindowInfo {
+            app_name: None,
+            window_title: Some(title),
+            app_id: None,
+            bounds: Some(crate::context::WindowBounds {
+                x: rect.left,
+                y: rect.top,
+                width: rect.right - rect.left,
+                height: rect.bottom - rect.top,
+            }),
+            timestamp: get_current_timestamp(),
+            process_id: (process_id != 0).then_some(process_id),
+            is_focus_changed: false,
+        });
+        FALSE
+    }
+
+    let mut search = Search { pattern, found: None };
+    unsafe {
+        EnumWindows(Some(visit), &mut search as *mut Search as LPARAM);
+    }
+    search.found
+}
+
+/// macOS 原生窗口查询：CGWindowListCopyWindowInfo 获取前台窗口，
+/// 标题为空（未授予屏幕录制权限）时通过 AXUIElement 读取焦点窗口标题
+#[cfg(target_os = "macos")]
+mod macos_native {
+    use super::{get_current_timestamp, EnhancedWindowInfo};
+    use core_foundation::base::{CFType, CFTypeRef, TCFType};
+    use core_foundation::bundle::CFBundle;
+    use core_foundation::dictionary::CFDictionary;
+    use core_foundation::number::CFNumber;
+    use core_foundation::string::{CFString, CFStringRef};
+    use core_foundation::url::CFURL;
+    use core_graphics::window::{
+        copy_window_info, kCGNullWindowID, kCGWindowBounds, kCGWindowLayer,
+        kCGWindowListExcludeDesktopElements, kCGWindowListOptionOnScreenOnly, kCGWindowName,
+        kCGWindowOwnerName, kCGWindowOwnerPID,
+    };
+    use std::collections::HashMap;
+    use std::ffi::c_void;
+    use std::path::PathBuf;
+    use std::sync::{Mutex, OnceLock};
+
+    type AXUIElementRef = *const c_void;
+    type AXError = i32;
+    const AX_ERROR_SUCCESS: AXError = 0;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXUIElementCreateApplication(pid: i32) -> AXUIElementRef;
+        fn AXUIElementCopyAttributeValue(
+            element: AXUIElementRef,
+            attribute: CFStringRef,
+            value: *mut CFTypeRef,
+        ) -> AXError;
+    }
+
+    // libproc（系统库，默认链接）
+    extern "C" {
+        fn proc_pidpath(pid: i32, buffer: *mut c_void, buffersize: u32) -> i32;
+    }
+    const PROC_PIDPATHINFO_MAXSIZE: usize = 4096;
+
+    /// 应用标识：.app 包内的进程返回 bundle identifier，否则返回可执行文件路径。
+    /// 每次按 PID 查询可执行文件路径（PID 会被新进程复用），按路径缓存读取 bundle 的结果，缓存大小不超过用过的程序数
+    pub fn app_identifier(pid: i32) -> Option<String> {
+        static CACHE: OnceLock<Mutex<HashMap<PathBuf, String>>> = OnceLock::new();
+
+        let mut buffer = vec![0u8; PROC_PIDPATHINFO_MAXSIZE];
+        let len = unsafe { proc_pidpath(pid, buffer.as_mut_ptr() as *mut c_void, buffer.len() as u32) };
+        if len <= 0 {
+            return None;
+        }
+        let exe_path = PathBuf::from(String::from_utf8_lossy(&buffer[..len as usize]).into_owned());
+        let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+        if let Some(id) = cache.lock().unwrap_or_else(|p| p.into_inner()).get(&exe_path) {
+            return Some(id.clone());
+        }
+
+        let bundle_id = exe_path
+            .ancestors()
+            .find(|path| path.extension().is_some_and(|ext| ext == "app"))
+            .and_then(|app_path| CFURL::from_path(app_path, true))
+            .and_then(CFBundle::new)
+            .and_then(|bundle| {
+                bundle
+                    .info_dictionary()
+                    .find(&CFString::from_static_string("CFBundleIdentifier"))
+                    .and_then(|value| value.downcast::<CFString>())
+                    .map(|id| id.to_string())
+            });
+        let id = bundle_id.unwrap_or_else(|| exe_path.to_string_lossy().into_owned());
+        cache.lock().unwrap_or_else(|p| p.into_inner()).insert(exe_path, id.clone());
+        Some(id)
+    }
+
+    /// 获取前台窗口信息，未找到普通层级窗口时返回 None
+    pub fn frontmost_window_info() -> Option<EnhancedWindowInfo> {
+        let windows = copy_window_info(
+            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+            kCGNullWindowID,
+        )?;
+
+        // 窗口列表按从前到后排序，第一个 layer 为 0 的窗口即前台应用的窗口
+        for item in windows.iter() {
+            let dict: CFDictionary = unsafe { CFDictionary::wrap_under_get_rule(*item as _) };
+            let layer = unsafe { number_value(&dict, kCGWindowLayer) };
+            if layer != Some(0) {
+                continue;
+            }
+
+            let app_name = unsafe { string_value(&dict, kCGWindowOwnerName) };
+            let process_id = unsafe { number_value(&dict, kCGWindowOwnerPID) }.map(|pid| pid as u32);
+            let bounds = unsafe { dict_value(&dict, kCGWindowBounds) }
+                .and_then(|v| v.downcast::<CFDictionary>())
+                .and_then(|b| parse_bounds(&b));
+
+            let window_title = unsafe { string_value(&dict, kCGWindowName) }
+                .or_else(|| process_id.and_then(|pid| focused_window_title(pid as i32)));
+
+            return Some(EnhancedWindowInfo {
+                app_name,
+                window_title,
+                app_id: process_id.and_then(|pid| app_identifier(pid as i32)),
+                bounds,
+                timestamp: get_current_timestamp(),
+                process_id,
+                is_focus_changed: true,
+            });
+        }
+
+        None
+    }
+
+    /// 按标题查找屏幕上的普通层级窗口（列表按从前到后排序），pattern 已转为小写
+    pub fn find_window_by_title(pattern: &str) -> Option<EnhancedWindowInfo> {
+        let windows = copy_window_info(
+            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+            kCGNullWindowID,
+        )?;
+
+        for item in windows.iter() {
+            let dict: CFDictionary = unsafe { CFDictionary::wrap_under_get_rule(*item as _) };
+            if unsafe { number_value(&dict, kCGWindowLayer) } != Some(0) {
+                continue;
+            }
+            // 读取其他应用的窗口标题需要屏幕录制权限，截屏本身已要求该权限
+            let Some(window_title) = (unsafe { string_value(&dict, kCGWindowName) }) else {
+                continue;
+            };
+            if !window_title.to_lowercase().contains(pattern) {
+                continue;
+            }
+
+            let process_id = unsafe { number_value(&dict, kCGWindowOwnerPID) }.map(|pid| pid as u32);
+            return Some(EnhancedWindowInfo {
+                app_name: unsafe { string_value(&dict, kCGWindowOwnerName) },
+                window_title: Some(window_title),
+                app_id: process_id.and_then(|pid| app_identifier(pid as i32)),
+                bounds: unsafe { dict_value(&dict, kCGWindowBounds) }
+                    .and_then(|v| v.downcast::<CFDictionary>())
+                    .and_then(|b| parse_bounds(&b)),
+                timestamp: get_current_timestamp(),
+                process_id,
+                is_focus_changed: false,
+            });
+        }
+
+        None
+    }
+
+    /// 通过 Accessibility API 读取应用焦点窗口标题（需要辅助功能权限）
+    fn focused_window_title(pid: i32) -> Option<String> {
+        unsafe {
+            let app = AXUIElementCreateApplication(pid);
+            if app.is_null() {
+                return None;
+            }
+            let app = CFType::wrap_under_create_rule(app);
+
+            let window = copy_attribute(app.as_CFTypeRef(), "AXFocusedWindow")?;
+            copy_attribute(window.as_CFTypeRef(), "AXTitle")?
+                .downcast::<CFString>()
+                .map(|title| title.to_string())
+                .filter(|title| !title.is_empty())
+        }
+    }
+
+    unsafe fn copy_attribute(element: AXUIElementRef, attribute: &'static str) -> Option<CFType> {
+        let attribute = CFString::from_static_string(attribute);
+        let mut value: CFTypeRef = std::ptr::null();
+        let err = AXUIElementCopyAttributeValue(element, attribute.as_concrete_TypeRef(), &mut value);
+        if err != AX_ERROR_SUCCESS || value.is_null() {
+            return None;
+        }
+        Some(CFType::wrap_under_create_rule(value))
+    }
+
+    unsafe fn dict_value(dict: &CFDictionary, key: CFStringRef) -> Option<CFType> {
+        dict.find(key as *const c_void)
+            .map(|value| CFType::wrap_under_get_rule(*value as CFTypeRef))
+    }
+
+    unsafe fn string_value(dict: &CFDictionary, key: CFStringRef) -> Option<String> {
+        dict_value(dict, key)?
+            .downcast::<CFString>()
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    unsafe fn number_value(dict: &CFDictionary, key: CFStringRef) -> Option<i64> {
+        dict_value(dict, key)?.downcast::<CFNumber>()?.to_i64()
+    }
+
+    /// 解析 kCGWindowBounds 字典（X / Y / Width / Height）
+    fn parse_bounds(bounds: &CFDictionary) -> Option<crate::context::WindowBounds> {
+        let get = |key: &'static str| -> Option<i32> {
+            let key = CFString::from_static_string(key);
+            unsafe { dict_value(bounds, key.as_concrete_TypeRef()) }?
+                .downcast::<CFNumber>()?
+                .to_f64()
+                .map(|v| v.round() as i32)
+        };
+
+        Some(crate::context::WindowBounds {
+            x: get("X")?,
+            y: get("Y")?,
+            width: get("Width")?,
+            height: get("Height")?,
+        })
+    }
+}
+
+static WINDOW_TRACKER_OPTIONS: OnceLock<WindowTrackerOptions> = OnceLock::new();
+
+/// 设置全局窗口追踪器的配置，需在首次访问 WINDOW_TRACKER 之前调用；
+/// 追踪器已初始化或重复设置时返回 false
+pub fn configure_window_tracker(options: WindowTrackerOptions) -> bool {
+    WINDOW_TRACKER_OPTIONS.set(options).is_ok()
+}
+
+/// 窗口查询子进程的超时时间（--window-query-timeout-ms）
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn window_query_timeout() -> Duration {
+    let options = WINDOW_TRACKER_OPTIONS.get_or_init(WindowTrackerOptions::default);
+    Duration::from_millis(options.query_timeout_ms)
+}
+
+/// 执行窗口查询子进程（如 osascript）并返回标准输出；超时后杀掉子进程返回 None，
+/// 避免 System Events 卡住时拖住窗口追踪和截屏循环。退出码非 0 时同样返回 None
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub async fn run_window_query(mut command: tokio::process::Command, timeout: Duration) -> Option<String> {
+    command
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true);
+    let child = command.spawn().ok()?;
+    // 超时后 wait_with_output 的 future 被丢弃，子进程随之被杀掉
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(_) => None,
+        Err(_) => {
+            eprintln!("⚠️ 窗口查询超过 {} 毫秒未返回，已终止子进程", timeout.as_millis());
+            None
+        }
+    }
+}
+
+// 全局窗口追踪器实例，首次访问时按 configure_window_tracker 的配置创建
 lazy_static::lazy_static! {
-    pub static ref WINDOW_TRACKER: WindowTracker = WindowTracker::new();
-} 
\ No newline at end of file
+    pub static ref WINDOW_TRACKER: WindowTracker = WindowTracker::new(
+        WINDOW_TRACKER_OPTIONS.get_or_init(WindowTrackerOptions::default).clone()
+    );
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_name: &str) -> EnhancedWindowInfo {
+        EnhancedWindowInfo {
+            app_name: Some(app_name.to_string()),
+            window_title: None,
+            app_id: None,
+            bounds: None,
+            timestamp: get_current_timestamp(),
+            process_id: None,
+            is_focus_changed: true,
+        }
+    }
+
+    fn session(app_name: &str, start_secs: u64, end_secs: u64) -> WindowSession {
+        WindowSession {
+            app_name: Some(app_name.to_string()),
+            app_id: None,
+            window_title: None,
+            start_time: start_secs * 1000,
+            end_time: Some(end_secs * 1000),
+            duration_ms: (end_secs - start_secs) * 1000,
+            start_mono_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_focus_sessions_between() {
+        let snapshot = WindowTrackerSnapshot {
+            session_history: vec![
+                session("Code", 0, 600),
+                // 短暂离开后回到 Code：并入同一时段
+                session("Slack", 600, 660),
+                session("Code", 660, 1200),
+                // 10 分钟未追踪：时段中断，之后的片段都不足最短时长
+                session("Code", 1800, 2000),
+                // 切到新应用超过 5 分钟：Code 时段结束
+                session("Mail", 2000, 2500),
+            ]
+            .into(),
+            ..WindowTrackerSnapshot::default()
+        };
+
+        let focus = snapshot.focus_sessions_between(0, 10_000_000, 300_000, 600_000);
+        assert_eq!(focus.len(), 1);
+        assert_eq!((focus[0].start_time, focus[0].end_time), (0, 1_200_000));
+        assert_eq!(focus[0].dominant_app, "Code");
+        assert_eq!(
+            focus[0].apps,
+            vec![("Code".to_string(), 1_140_000), ("Slack".to_string(), 60_000)]
+        );
+        assert_eq!(focus[0].switch_count, 2);
+
+        // 跨越查询起点的会话只计入范围内的部分
+        let clipped = snapshot.focus_sessions_between(300_000, 10_000_000, 300_000, 600_000);
+        assert_eq!(clipped.len(), 1);
+        assert_eq!((clipped[0].start_time, clipped[0].duration_ms), (300_000, 900_000));
+        assert!(snapshot.focus_sessions_between(300_000, 10_000_000, 300_000, 1_000_000).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_short_sessions_merge_into_previous() {
+        let tracker = WindowTracker::new(WindowTrackerOptions {
+            min_session_ms: 60_000,
+            ..WindowTrackerOptions::default()
+        });
+
+        // A → B 一闪而过 → 回到 A：B 并回 A，两次切换都不计入
+        tracker.observe_window(window("Code"), tracker.now()).await;
+        tracker.observe_window(window("Finder"), tracker.now()).await;
+        tracker.observe_window(window("Code"), tracker.now()).await;
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.switch_history.len(), 1);
+        assert_eq!(snapshot.session_history.len(), 1);
+        assert!(snapshot.session_history[0].end_time.is_none());
+
+        // A → B 一闪而过 → C：记为一次 A → C
+        tracker.observe_window(window("Finder"), tracker.now()).await;
+        tracker.observe_window(window("Safari"), tracker.now()).await;
+        let snapshot = tracker.snapshot();
+        let last = snapshot.switch_history.back().unwrap();
+        assert_eq!((last.from_app.as_deref(), last.to_app.as_deref()), (Some("Code"), Some("Safari")));
+        assert_eq!(snapshot.switch_history.len(), 2);
+        let apps: Vec<_> = snapshot.session_history.iter().map(|s| s.app_name.as_deref()).collect();
+        assert_eq!(apps, vec![Some("Code"), Some("Safari")]);
+        assert_eq!(tracker.get_stats().await.total_switches, 2);
+    }
+
+    #[tokio::test]
+    async fn test_durations_survive_clock_jumps_and_sleep() {
+        let tracker = WindowTracker::new(WindowTrackerOptions {
+            sleep_gap_ms: 600_000,
+            ..WindowTrackerOptions::default()
+        });
+        let at = |wall_ms, mono_ms| TrackerTime { wall_ms, mono_ms };
+
+        tracker.observe_window(window("Code"), at(1_000_000, 0)).await;
+        tracker.observe_window(window("Finder"), at(1_010_000, 10_000)).await;
+        // 墙上时钟回拨 110 秒：Finder 的停留时长仍按单调时钟计为 10 秒
+        tracker.observe_window(window("Safari"), at(900_000, 20_000)).await;
+
+        let snapshot = tracker.snapshot();
+        let durations: Vec<u64> = snapshot.switch_history.iter().map(|e| e.duration_ms).collect();
+        assert_eq!(durations, vec![0, 10_000, 10_000]);
+        assert_eq!(snapshot.app_usage_stats["Finder"], 10_000);
+        // 回拨后开始的会话不早于上一个会话的结束时间
+        assert_eq!(snapshot.session_history[1].end_time, Some(1_020_000));
+        assert_eq!(snapshot.session_history[2].start_time, 1_020_000);
+
+        // 两次观测间隔 1 小时（休眠）：休眠时段不计入 Safari
+        tracker.observe_window(window("Safari"), at(910_000, 30_000)).await;
+        tracker.observe_window(window("Safari"), at(4_510_000, 3_630_000)).await;
+        tracker.observe_window(window("Code"), at(4_520_000, 3_640_000)).await;
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.app_usage_stats["Safari"], 20_000);
+        assert_eq!(snapshot.switch_history.back().unwrap().duration_ms, 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_roundtrip() {
+        let tracker = WindowTracker::new(WindowTrackerOptions::default());
+        let at = |wall_ms, mono_ms| TrackerTime { wall_ms, mono_ms };
+        tracker.observe_window(window("Code"), at(1_000_000, 0)).await;
+        tracker.observe_window(window("Finder"), at(1_060_000, 60_000)).await;
+        tracker.observe_window(window("Code"), at(1_120_000, 120_000)).await;
+
+        let dir = std::env::temp_dir().join(format!("openrecall_tracker_roundtrip_{}", std::process::id()));
+        let path = dir.join("window_tracker.json");
+        tracker.save(&path).await.unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let restored = WindowTracker::new(WindowTrackerOptions::default());
+        restored.load(&path).await;
+        let (saved, loaded) = (tracker.snapshot(), restored.snapshot());
+        assert_eq!(loaded.switch_history.len(), saved.switch_history.len());
+        assert_eq!(loaded.app_usage_stats, saved.app_usage_stats);
+        // 未结束的会话（当前的 Code）恢复时丢弃
+        let apps: Vec<_> = loaded.session_history.iter().map(|s| s.app_name.as_deref()).collect();
+        assert_eq!(apps, vec![Some("Code"), Some("Finder")]);
+        assert_eq!(loaded.session_history[1].end_time, Some(1_120_000));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_window_query_timeout_kills_slow_subprocess() {
+        let mut slow = tokio::process::Command::new("sh");
+        slow.args(["-c", "sleep 5; echo late"]);
+        let started = Instant::now();
+        assert_eq!(run_window_query(slow, Duration::from_millis(200)).await, None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let mut fast = tokio::process::Command::new("sh");
+        fast.args(["-c", "echo Finder"]);
+        assert_eq!(
+            run_window_query(fast, Duration::from_secs(5)).await.as_deref().map(str::trim),
+            Some("Finder")
+        );
+    }
+}